@@ -35,7 +35,11 @@ fn main() -> anyhow::Result<ExitCode> {
     if std::env::var("RA_RUSTC_WRAPPER").is_ok() {
         rustc_wrapper::main().map_err(Into::into)
     } else {
-        actual_main()
+        let res = actual_main();
+        if let Err(e) = rust_analyzer::tracing::otel::shutdown() {
+            eprintln!("Failed to flush OpenTelemetry spans: {e:#}");
+        }
+        res
     }
 }
 
@@ -47,7 +51,7 @@ fn actual_main() -> anyhow::Result<ExitCode> {
         wait_for_debugger();
     }
 
-    if let Err(e) = setup_logging(flags.log_file.clone()) {
+    if let Err(e) = setup_logging(flags.log_file.clone(), flags.otlp_endpoint.clone()) {
         eprintln!("Failed to setup logging: {e:#}");
     }
 
@@ -87,6 +91,27 @@ fn actual_main() -> anyhow::Result<ExitCode> {
         }
         flags::RustAnalyzerCmd::Scip(cmd) => cmd.run()?,
         flags::RustAnalyzerCmd::FunctionAnalyzer(cmd) => cmd.run()?,
+        flags::RustAnalyzerCmd::Graph(cmd) => match cmd.subcommand {
+            flags::GraphCmd::Serve(cmd) => cmd.run()?,
+            flags::GraphCmd::Wiki(cmd) => cmd.run()?,
+            flags::GraphCmd::Plugins(cmd) => cmd.run()?,
+            flags::GraphCmd::IdlDiff(cmd) => cmd.run()?,
+        },
+        flags::RustAnalyzerCmd::Metrics(cmd) => cmd.run()?,
+        flags::RustAnalyzerCmd::Clones(cmd) => cmd.run()?,
+        flags::RustAnalyzerCmd::Strings(cmd) => cmd.run()?,
+        flags::RustAnalyzerCmd::Lint(cmd) => cmd.run()?,
+        flags::RustAnalyzerCmd::DynUsage(cmd) => cmd.run()?,
+        flags::RustAnalyzerCmd::BuildInventory(cmd) => cmd.run()?,
+        flags::RustAnalyzerCmd::DepsCallers(cmd) => cmd.run()?,
+        flags::RustAnalyzerCmd::Features(cmd) => cmd.run()?,
+        flags::RustAnalyzerCmd::Export(cmd) => match cmd.subcommand {
+            flags::ExportCmd::Bundle(cmd) => cmd.run()?,
+        },
+        flags::RustAnalyzerCmd::CallHierarchy(cmd) => match cmd.subcommand {
+            flags::CallHierarchyCmd::Tree(cmd) => cmd.run()?,
+            flags::CallHierarchyCmd::Diff(cmd) => cmd.run()?,
+        },
         flags::RustAnalyzerCmd::SourceFinder(cmd) => cmd.run()?,
         flags::RustAnalyzerCmd::RunTests(cmd) => cmd.run()?,
         flags::RustAnalyzerCmd::RustcTests(cmd) => cmd.run()?,
@@ -117,7 +142,10 @@ fn wait_for_debugger() {
     }
 }
 
-fn setup_logging(log_file_flag: Option<PathBuf>) -> anyhow::Result<()> {
+fn setup_logging(
+    log_file_flag: Option<PathBuf>,
+    otlp_endpoint_flag: Option<String>,
+) -> anyhow::Result<()> {
     if cfg!(windows) {
         // This is required so that windows finds our pdb that is placed right beside the exe.
         // By default it doesn't look at the folder the exe resides in, only in the current working
@@ -168,6 +196,7 @@ fn setup_logging(log_file_flag: Option<PathBuf>) -> anyhow::Result<()> {
         chalk_filter: env::var("CHALK_DEBUG").ok(),
         profile_filter: env::var("RA_PROFILE").ok(),
         json_profile_filter: std::env::var("RA_PROFILE_JSON").ok(),
+        otlp_endpoint: env::var("RA_OTLP_ENDPOINT").ok().or(otlp_endpoint_flag),
     }
     .init()?;
 
@@ -2,21 +2,86 @@
 
 #![allow(clippy::print_stdout, clippy::print_stderr)]
 
+mod account_constraints;
+mod analysis_progress;
 mod analysis_stats;
+mod anchor_checks;
+mod anchor_lang;
+mod audit_report;
+mod authorities;
+mod batch;
+mod budget;
+mod build_inventory;
+mod call_cache;
+mod call_csv;
+mod call_diff;
+mod call_dot;
+mod call_edges;
+mod call_graphml;
+mod call_kind;
+mod call_metrics;
+mod call_ndjson;
+mod call_paths;
+mod call_traversal;
+mod call_tree;
+mod clones;
+mod code_graph;
+mod constraint_expr;
+mod cpi_calls;
+mod custom_rules;
+mod dead_code;
+mod deps_callers;
 mod function_analyzer;
 mod diagnostics;
+mod discriminators;
+mod dispatch;
+mod dyn_usage;
+mod entry_points;
+mod error_codes;
+mod events;
+mod export_bundle;
+mod exporters;
+mod external_calls;
+mod feature_unification;
+mod findings;
 pub mod flags;
+mod governance;
+mod graph_filter;
+mod graph_plugins;
+mod graph_serve;
+mod graph_tui;
+mod graph_watch;
+mod graph_wiki;
 mod highlight;
+mod idl_diff;
+mod instantiations;
+mod lamport_flows;
+mod lint;
 mod lsif;
+mod low_memory;
+mod macro_edges;
+mod metrics;
+mod module_graph;
+mod native_programs;
 mod parse;
+mod pdas;
 mod prime_caches;
+mod programs;
+mod prune;
+mod redact;
 mod run_tests;
 mod rustc_tests;
 mod scip;
 mod source_finder;
 mod ssr;
+mod state_access;
+mod state_accounts;
+mod strings;
 mod symbols;
+mod sysvars;
+mod token_operations;
 mod unresolved_references;
+mod zero_copy;
 
 mod progress_report;
 
@@ -0,0 +1,183 @@
+//! The constraints of the `#[account(...)]` attributes of Anchor account struct fields.
+//!
+//! Constraints are read from the token tree of the attribute, which doesn't parse as an
+//! expression: each top-level comma separated item is a key path like `mut`, `has_one` or
+//! `token::mint`, optionally followed by `= value` and a custom error after `@`.
+//!
+//! The namespaced families (`token::`, `mint::`, `associated_token::`, `realloc::`) keep their
+//! namespace in the kind, so `mint::authority = payer` is a constraint of the mint account, not a
+//! field named `authority`.
+
+use std::fmt;
+
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use syntax::{
+    AstNode, NodeOrToken, SyntaxKind, SyntaxToken,
+    ast::{self, HasAttrs},
+};
+
+use crate::cli::{
+    code_graph::AccountField, constraint_expr::ConstraintExpr, error_codes::ErrorVariant,
+};
+
+/// One constraint, like `has_one = owner @ ErrorCode::NotOwner`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct Constraint {
+    /// The key of the constraint with its namespace, like `init`, `seeds` or `token::mint`.
+    pub(super) kind: String,
+    /// The expression after `=`, none for flags like `mut` or a bare `bump`.
+    pub(super) value: Option<String>,
+    /// The parsed `value` of a `constraint = ...`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) expr: Option<ConstraintExpr>,
+    /// The type of the field of the same struct `value` names, like `Account<'info, Mint>` for
+    /// `associated_token::mint = mint`.
+    pub(super) field_type: Option<String>,
+    /// The error raised when the constraint fails, given after `@`.
+    pub(super) error: Option<String>,
+    /// The code of `error` when it's a variant of an `#[error_code]` enum of the project.
+    pub(super) error_code: Option<u32>,
+    /// The variant `error` names, when it's one of an `#[error_code]` enum of the project.
+    pub(super) error_variant: Option<ErrorVariant>,
+}
+
+impl Constraint {
+    /// The namespace of the constraint, like `token` for `token::mint`, none for plain keys.
+    pub(super) fn namespace(&self) -> Option<&str> {
+        self.kind.split_once("::").map(|(namespace, _)| namespace)
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.kind)?;
+        if let Some(value) = &self.value {
+            write!(f, " = {value}")?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " @ {error}")?;
+        }
+        Ok(())
+    }
+}
+
+type TokenOrTree = NodeOrToken<ast::TokenTree, SyntaxToken>;
+
+/// The constraints of all `#[account(...)]` attributes of `item`, in order.
+pub(super) fn account_constraints(item: &impl HasAttrs) -> Vec<Constraint> {
+    item.attrs()
+        .filter_map(|attr| attr.as_simple_call())
+        .filter(|(name, _)| name == "account")
+        .flat_map(|(_, tt)| {
+            let children: Vec<TokenOrTree> = tt.token_trees_and_tokens().collect();
+            // Skip the delimiters of the tree itself.
+            let inner = children.get(1..children.len().saturating_sub(1)).unwrap_or_default();
+            inner
+                .split(|it| it.as_token().is_some_and(|t| t.kind() == SyntaxKind::COMMA))
+                .filter_map(parse_constraint)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Resolves the constraints of `fields` naming another field of the struct, like
+/// `associated_token::authority = owner`, to the type of that field.
+pub(super) fn link_field_types(fields: &mut [AccountField]) {
+    let types: FxHashMap<String, String> =
+        fields.iter().map(|it| (it.name.clone(), it.ty.clone())).collect();
+    for constraint in fields.iter_mut().flat_map(|it| &mut it.constraints) {
+        constraint.field_type = constraint.value.as_ref().and_then(|it| types.get(it)).cloned();
+    }
+}
+
+/// Reads the constraint made of `item`, the tokens between two top-level commas.
+fn parse_constraint(item: &[TokenOrTree]) -> Option<Constraint> {
+    let is_token = |it: &TokenOrTree, kind| it.as_token().is_some_and(|t| t.kind() == kind);
+    let (rest, error) = match item.iter().position(|it| is_token(it, SyntaxKind::AT)) {
+        Some(at) => (&item[..at], Some(&item[at + 1..])),
+        None => (item, None),
+    };
+    let (key, value) = match rest.iter().position(|it| is_token(it, SyntaxKind::EQ)) {
+        Some(eq) => (&rest[..eq], Some(&rest[eq + 1..])),
+        None => (rest, None),
+    };
+    let kind: String = text(key).split_whitespace().collect();
+    if kind.is_empty() {
+        return None;
+    }
+    let value = value.map(text).filter(|it| !it.is_empty());
+    let expr = value.as_deref().filter(|_| kind == "constraint").and_then(ConstraintExpr::parse);
+    Some(Constraint {
+        kind,
+        value,
+        expr,
+        field_type: None,
+        error: error.map(text).filter(|it| !it.is_empty()),
+        error_code: None,
+        error_variant: None,
+    })
+}
+
+/// The source of `tokens`, with runs of whitespace collapsed to a single space.
+fn text(tokens: &[TokenOrTree]) -> String {
+    let mut text = String::new();
+    for token in tokens {
+        match token {
+            NodeOrToken::Token(token) => text.push_str(token.text()),
+            NodeOrToken::Node(node) => text.push_str(&node.syntax().text().to_string()),
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use syntax::{Edition, SourceFile};
+
+    use super::*;
+
+    fn constraints(attr: &str) -> Vec<Constraint> {
+        let text = format!("struct S {{ {attr} pub field: u8 }}");
+        let file = SourceFile::parse(&text, Edition::CURRENT).tree();
+        let field = file.syntax().descendants().find_map(ast::RecordField::cast).unwrap();
+        account_constraints(&field)
+    }
+
+    #[test]
+    fn splits_values_and_errors() {
+        let cases = [
+            ("#[account(mut)]", "mut", None, None),
+            (
+                "#[account(has_one = owner @ ErrorCode::NotOwner)]",
+                "has_one",
+                Some("owner"),
+                Some("ErrorCode::NotOwner"),
+            ),
+            (
+                "#[account(constraint = pool.authority == admin.key() @ ErrorCode::Unauthorized)]",
+                "constraint",
+                Some("pool.authority == admin.key()"),
+                Some("ErrorCode::Unauthorized"),
+            ),
+            ("#[account(constraint = a >= b)]", "constraint", Some("a >= b"), None),
+            ("#[account(token::mint = mint)]", "token::mint", Some("mint"), None),
+        ];
+        for (attr, kind, value, error) in cases {
+            let [constraint] = &constraints(attr)[..] else { panic!("{attr}") };
+            assert_eq!(constraint.kind, kind, "{attr}");
+            assert_eq!(constraint.value.as_deref(), value, "{attr}");
+            assert_eq!(constraint.error.as_deref(), error, "{attr}");
+        }
+    }
+
+    #[test]
+    fn parses_constraint_expressions() {
+        let attr =
+            "#[account(mut, constraint = !pool.paused && pool.authority == admin.key() @ E::X)]";
+        let constraints = constraints(attr);
+        assert_eq!(constraints[0].expr, None);
+        let expr = constraints[1].expr.as_ref().unwrap();
+        assert_eq!(expr.to_string(), "!pool.paused && (pool.authority == admin.key())");
+    }
+}
@@ -0,0 +1,90 @@
+//! Progress of the slow steps of the analysis commands, printed to stderr as
+//! `analyzed 1200/5400 functions` lines or, for tools driving the commands, as JSON events.
+//!
+//! Reports are throttled to one a second, except for the last one of a step.
+
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::cli::flags::ProgressFormat;
+
+const INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    Message { message: &'a str },
+    Progress { unit: &'static str, done: usize, total: usize },
+}
+
+/// Counts the items a step went through, out of the items known so far.
+pub(super) struct Progress {
+    format: ProgressFormat,
+    unit: &'static str,
+    done: AtomicUsize,
+    total: AtomicUsize,
+    reported: Mutex<Instant>,
+    last_message: Mutex<String>,
+}
+
+impl Progress {
+    pub(super) fn new(format: ProgressFormat, unit: &'static str) -> Progress {
+        Progress {
+            format,
+            unit,
+            done: AtomicUsize::new(0),
+            total: AtomicUsize::new(0),
+            reported: Mutex::new(Instant::now()),
+            last_message: Mutex::default(),
+        }
+    }
+
+    /// Reports what the workspace loading is busy with, like running `cargo metadata`. Repeats of
+    /// the previous message are left out.
+    pub(super) fn message(&self, message: &str) {
+        let mut last_message = self.last_message.lock().unwrap();
+        if *last_message == message {
+            return;
+        }
+        message.clone_into(&mut last_message);
+        match self.format {
+            ProgressFormat::Text => eprintln!("{message}"),
+            ProgressFormat::Json => emit(&Event::Message { message }),
+            ProgressFormat::None => (),
+        }
+    }
+
+    /// Adds `count` items to the work of the step.
+    pub(super) fn extend(&self, count: usize) {
+        self.total.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Counts one more item as done.
+    pub(super) fn advance(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let total = self.total.load(Ordering::Relaxed);
+        let mut reported = self.reported.lock().unwrap();
+        if done < total && reported.elapsed() < INTERVAL {
+            return;
+        }
+        *reported = Instant::now();
+        match self.format {
+            ProgressFormat::Text => eprintln!("analyzed {done}/{total} {}", self.unit),
+            ProgressFormat::Json => emit(&Event::Progress { unit: self.unit, done, total }),
+            ProgressFormat::None => (),
+        }
+    }
+}
+
+fn emit(event: &Event<'_>) {
+    if let Ok(line) = serde_json::to_string(event) {
+        eprintln!("{line}");
+    }
+}
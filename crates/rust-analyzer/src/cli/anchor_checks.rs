@@ -0,0 +1,1704 @@
+//! Checks of Anchor programs against the code graph, reported as findings.
+//!
+//! - `insufficient-space`: an `init` constraint whose `space` is smaller than the serialized size
+//!   of the account it creates. Anchor fails to write the account at the end of the instruction,
+//!   or the account can't grow to the size its fields reach.
+//! - `missing-signer`: an authority-like field, named `authority`, `admin` or `owner` or paying
+//!   for account creation or transfers, that is an `AccountInfo` or `UncheckedAccount` without a
+//!   `signer` constraint. Anyone can pass the key of the authority without its signature. Payers
+//!   are errors, names only warnings.
+//! - `unchecked-account`: an `AccountInfo` or `UncheckedAccount` field whose owner or address is
+//!   never checked: no `owner`, `address`, `seeds` or `signer` constraint, no `has_one` pointing
+//!   at it and no comparison of its key or owner, in a `constraint` or in the functions the
+//!   instructions using the struct reach. Any account can be passed in its place.
+//! - `non-canonical-bump`: a `seeds` constraint whose `bump` isn't the canonical one Anchor finds
+//!   itself. `bump = account.bump` is canonical when the stored bump is set from `ctx.bumps` (or
+//!   `find_program_address`) somewhere, any other value is a bump the caller chooses.
+//! - `realloc-without-zero`: a `realloc` constraint without `realloc::zero = true` on an account
+//!   whose size varies, because its `realloc` size isn't constant or differs between structs.
+//!   Growing the account back after a shrink exposes the bytes the shrink left behind.
+//! - `unsupported-constraint`: a constraint the anchor-lang version of the project doesn't accept
+//!   yet, or only with a feature it doesn't enable, like `init_if_needed`.
+//! - `unnecessary-mut`: a `mut` field no instruction using the struct writes, neither in the
+//!   functions it reaches, through a CPI, nor as the `payer` or `close` target of another field.
+//!   Writable accounts widen what a caller can make the instruction do.
+//! - `write-without-mut`: a field without `mut` that a function reached by an instruction using
+//!   the struct assigns to or calls a mutating method like `load_mut` on. Anchor doesn't persist
+//!   the change, or the runtime rejects it.
+//! - `unguarded-init-if-needed`: an `init_if_needed` account whose initialization nothing guards.
+//!   No `constraint` of the field and no function the instructions using the struct reach checks
+//!   a flag of the account, a `bool` field like `initialized`, or compares one of its fields with
+//!   a default value. Calling the instruction again reinitializes the account.
+//! - `discriminator-collision`: two accounts, or two events, of a program whose discriminators are
+//!   equal or one a prefix of the other. Types of the same name in different modules hash the
+//!   same, and explicit `discriminator` bytes can shadow each other. Data of one type deserializes
+//!   as the other.
+//! - `unchecked-arithmetic`: a `+`, `-` or `*` of integers, or its compound assignment, in a
+//!   function an instruction reaches, rather than a `checked_*` or `saturating_*` call. It wraps
+//!   silently unless the release profile of the workspace sets `overflow-checks = true`, and
+//!   aborts the instruction when it does, so findings are only informational then. Constant
+//!   expressions are left out, the compiler evaluates them.
+//! - `truncating-cast`: an `as` cast of an integer to a narrower integer type, of a float to a
+//!   narrower float type or to an integer, in a function of the program, unless it's a constant.
+//!   Values that don't fit are silently truncated, or saturated for floats. Casts taking part in
+//!   arithmetic, or of values named like amounts or reserves, are warnings, the others only
+//!   informational.
+//! - `borrow-across-cpi`: a borrow of the data of an account, from `try_borrow_mut_data`,
+//!   `data.borrow_mut()`, `load_mut` and the like, bound to a local still alive at a CPI of the
+//!   same function. The runtime borrows the accounts it passes to the CPI and fails with
+//!   `AccountBorrowFailed`, and the callee can change data the local aliases. Shared borrows only
+//!   fail when the CPI takes the account writable, so they are informational.
+//! - `zero-copy-padding`: padding in the layout of a zero-copy struct, before a field or at its
+//!   end. bytemuck's `Pod` derive rejects it, and `space` computed from the field sizes misses it.
+
+use std::fs;
+
+use hir::{ModuleDef, PathResolution, Semantics, SemanticsScope};
+use ide::{Analysis, LineIndex, RootDatabase};
+use ide_db::base_db::salsa;
+use rustc_hash::{FxHashMap, FxHashSet};
+use syntax::{
+    AstNode, Edition, SyntaxKind, SyntaxNode, T,
+    ast::{self, ArithOp, BinaryOp, CmpOp, HasArgList, HasGenericArgs, HasName},
+    match_ast,
+};
+use triomphe::Arc;
+
+use crate::cli::{
+    account_constraints::account_constraints,
+    anchor_lang::AnchorLang,
+    code_graph::{
+        AccountField, AccountStruct, CodeGraph, LoadedProject, body_descendants, crate_of, derives,
+        module_path, parse_expr, project_files, qualify, resolve_struct, wrapped_account,
+    },
+    cpi_calls::{CpiKind, account_name},
+    findings::{self, Finding, Related, Severity},
+    function_analyzer::convert_to_relative_path,
+    state_accounts::{DISCRIMINATOR_SIZE, StateAccount},
+    zero_copy::ZeroCopyType,
+};
+
+/// Runs every check over the account structs of the project.
+pub(super) fn check(
+    project: &LoadedProject,
+    analysis: &Analysis,
+    graph: &CodeGraph,
+) -> Vec<Finding> {
+    let _p = tracing::info_span!("anchor_checks").entered();
+    let sema = Semantics::new(&project.db);
+    let mut findings = Vec::new();
+    let bodies = function_bodies(&sema, project, analysis, graph);
+    let compared = compared_accounts(&bodies);
+    let conditions = guard_conditions(&bodies);
+    let writes = account_writes(&bodies);
+    let bump_writes = bump_writes(&sema, project);
+    let anchor = AnchorLang::detect(&project.db);
+    let reach: FxHashMap<String, FxHashSet<usize>> =
+        graph.instruction_reach().into_iter().collect();
+    let mut structs = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let relative_path = convert_to_relative_path(&file_path, &project.project_root);
+        structs.extend(
+            file.syntax()
+                .descendants()
+                .filter_map(ast::Struct::cast)
+                .filter(|it| derives(it, "Accounts"))
+                .map(|it| (relative_path.clone(), line_index.clone(), it)),
+        );
+    }
+
+    // The sizes every account type is reallocated to, none when they aren't constant.
+    let mut realloc_sizes: FxHashMap<String, Vec<Option<u64>>> = FxHashMap::default();
+    for (_, _, strukt) in &structs {
+        let Some(scope) = sema.scope(strukt.syntax()) else { continue };
+        let space = Space {
+            sema: &sema,
+            scope,
+            accounts: &graph.state_accounts,
+            zero_copy: &graph.zero_copy_types,
+        };
+        for field in record_fields(strukt) {
+            let Some(ty) = field.ty() else { continue };
+            if let Some((account, size)) = space.realloc(&field, &ty) {
+                realloc_sizes.entry(account).or_default().push(size);
+            }
+        }
+    }
+
+    for (relative_path, line_index, strukt) in &structs {
+        let (Some(name), Some(scope)) = (strukt.name(), sema.scope(strukt.syntax())) else {
+            continue;
+        };
+        let space = Space {
+            sema: &sema,
+            scope,
+            accounts: &graph.state_accounts,
+            zero_copy: &graph.zero_copy_types,
+        };
+        let account_struct = sema.to_def(strukt).and_then(|def| {
+            let path = qualify(&module_path(sema.db, def.module(sema.db)), name.text().as_str());
+            graph.account_structs.iter().find(|it| it.path() == path)
+        });
+        let payers = account_struct.map(|it| payers(graph, it)).unwrap_or_default();
+        let checked =
+            account_struct.map(|it| checked_accounts(it, &reach, &compared)).unwrap_or_default();
+        let written = account_struct.map(|it| written_accounts(graph, it, &reach, &writes));
+        for field in record_fields(strukt) {
+            let (Some(field_name), Some(ty)) = (field.name(), field.ty()) else { continue };
+            let mut report = |rule: &str, severity: Severity, message: String| {
+                findings.push(Finding {
+                    plugin: "anchor".to_owned(),
+                    rule: rule.to_owned(),
+                    message: format!("`{}.{}` {message}", name.text(), field_name.text()),
+                    severity,
+                    file: Some(relative_path.clone()),
+                    line: Some(
+                        line_index.line_col(field_name.syntax().text_range().start()).line + 1,
+                    ),
+                    related: Vec::new(),
+                })
+            };
+            if let Some(message) = space.check(&field, &ty) {
+                report("insufficient-space", Severity::Error, message);
+            }
+            let paid = payers.get(field_name.text().as_str()).copied();
+            if let Some((severity, message)) = missing_signer(&field, &ty, paid) {
+                report("missing-signer", severity, message);
+            }
+            if let Some(message) = unchecked_account(&field, &ty, &checked) {
+                report("unchecked-account", Severity::Warning, message);
+            }
+            let account_fields = account_struct.map_or(&[][..], |it| &it.fields);
+            if let Some((severity, message)) =
+                non_canonical_bump(&field, account_fields, &bump_writes)
+            {
+                report("non-canonical-bump", severity, message);
+            }
+            if let Some(message) = space.realloc_without_zero(&field, &ty, &realloc_sizes) {
+                report("realloc-without-zero", Severity::Warning, message);
+            }
+            if let Some((rule, severity, message)) =
+                written.as_ref().and_then(|it| mut_mismatch(graph, &field, &ty, it))
+            {
+                report(rule, severity, message);
+            }
+            if let Some(message) =
+                unguarded_init_if_needed(&field, &ty, &space, account_struct, &reach, &conditions)
+            {
+                report("unguarded-init-if-needed", Severity::Warning, message);
+            }
+            if let Some(anchor) = &anchor {
+                for constraint in account_constraints(&field) {
+                    if let Some(reason) = anchor.unsupported(&constraint.kind) {
+                        let message = format!("uses `{}`, but {reason}", constraint.kind);
+                        report("unsupported-constraint", Severity::Error, message);
+                    }
+                }
+            }
+        }
+    }
+    let overflow_checks = overflow_checks(project);
+    findings.extend(unchecked_arithmetic(&sema, graph, &bodies, &reach, overflow_checks));
+    findings.extend(truncating_casts(&sema, graph, &bodies));
+    findings.extend(borrows_across_cpis(graph, &bodies));
+    findings.extend(findings::check_graph("anchor", graph));
+    findings
+}
+
+/// The padding in the layouts of the zero-copy types.
+pub(super) fn zero_copy_padding(graph: &CodeGraph) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for ty in &graph.zero_copy_types {
+        let mut report = |message: String| {
+            findings.push(Finding {
+                plugin: "anchor".to_owned(),
+                rule: "zero-copy-padding".to_owned(),
+                message,
+                severity: Severity::Warning,
+                file: Some(ty.file.clone()),
+                line: Some(ty.line),
+                related: Vec::new(),
+            })
+        };
+        let repr = &ty.layout.repr;
+        for field in &ty.layout.fields {
+            if let Some(padding @ 1..) = field.padding {
+                report(format!(
+                    "`{}.{}` follows {padding} bytes of padding in the `#[repr({repr})]` layout",
+                    ty.name, field.name
+                ));
+            }
+        }
+        if let Some(padding @ 1..) = ty.layout.trailing_padding {
+            report(format!(
+                "`{}` ends with {padding} bytes of padding in the `#[repr({repr})]` layout",
+                ty.name
+            ));
+        }
+    }
+    findings
+}
+
+/// The accounts and events sharing their discriminator, or a prefix of it, with another one of the
+/// same program.
+pub(super) fn discriminator_collisions(graph: &CodeGraph) -> Vec<Finding> {
+    let accounts = graph.state_accounts.iter().map(|it| {
+        ("account", &it.module, &it.name, it.discriminator.as_deref(), &it.file, it.line)
+    });
+    let events = graph
+        .events
+        .iter()
+        .map(|it| ("event", &it.module, &it.name, it.discriminator.as_deref(), &it.file, it.line));
+    let types: Vec<_> = accounts.chain(events).collect();
+    let mut findings = Vec::new();
+    for (i, &(kind, module, name, discriminator, file, line)) in types.iter().enumerate() {
+        let Some(discriminator) = discriminator else { continue };
+        let earlier = types[..i].iter().find(|(other_kind, other_module, _, other, ..)| {
+            *other_kind == kind
+                && crate_of(other_module) == crate_of(module)
+                && other.is_some_and(|it| {
+                    it.starts_with(discriminator) || discriminator.starts_with(it)
+                })
+        });
+        let Some(&(_, other_module, other_name, _, other_file, other_line)) = earlier else {
+            continue;
+        };
+        findings.push(Finding {
+            plugin: "anchor".to_owned(),
+            rule: "discriminator-collision".to_owned(),
+            message: format!(
+                "{kind} `{}` has the discriminator `{discriminator}`, which collides with `{}`",
+                qualify(module, name),
+                qualify(other_module, other_name)
+            ),
+            severity: Severity::Error,
+            file: Some(file.clone()),
+            line: Some(line),
+            related: vec![Related {
+                file: other_file.clone(),
+                line: other_line,
+                message: format!("the {kind} it collides with"),
+            }],
+        });
+    }
+    findings
+}
+
+/// The fields of an account struct with named fields.
+fn record_fields(strukt: &ast::Struct) -> impl Iterator<Item = ast::RecordField> {
+    let fields = match strukt.field_list() {
+        Some(ast::FieldList::RecordFieldList(fields)) => Some(fields),
+        _ => None,
+    };
+    fields.into_iter().flat_map(|it| it.fields())
+}
+
+/// What the fields of `strukt` pay for, by field name: the accounts created with them as
+/// `payer`, and the lamports moved from them by the unsigned system transfers of the
+/// instructions using the struct.
+fn payers<'a>(graph: &'a CodeGraph, strukt: &'a AccountStruct) -> FxHashMap<&'a str, &'static str> {
+    let mut payers = FxHashMap::default();
+    for constraint in strukt.fields.iter().flat_map(|it| &it.constraints) {
+        if matches!(constraint.kind.as_str(), "payer" | "realloc::payer")
+            && let Some(value) = &constraint.value
+        {
+            payers.insert(value.as_str(), "account creation");
+        }
+    }
+    // Transfers signed by the program move lamports of its PDAs, which sign through the seeds.
+    let cpis = graph.cpi_calls.iter().filter(|cpi| {
+        cpi.signer_seeds.is_none()
+            && cpi.instructions.iter().any(|it| strukt.instructions.contains(it))
+    });
+    for cpi in cpis {
+        let transfer = cpi.target.as_deref().is_some_and(|it| it.ends_with("transfer"));
+        let system = cpi.program.as_deref() == Some("system_program");
+        let source = match cpi.kind {
+            CpiKind::CpiContext if transfer => {
+                // Token transfers are authorized by their authority, not by the token account.
+                let role = if system { "from" } else { "authority" };
+                cpi.accounts.iter().find(|it| it.role.as_deref() == Some(role))
+            }
+            CpiKind::Invoke if transfer && system => cpi.accounts.first(),
+            _ => None,
+        };
+        if let Some(source) = source {
+            payers.entry(source.account.as_str()).or_insert("transfers");
+        }
+    }
+    payers
+}
+
+/// The severity and message of a missing signer finding for `field`, if it's an authority or
+/// pays for `paid` without having to sign.
+fn missing_signer(
+    field: &ast::RecordField,
+    ty: &ast::Type,
+    paid: Option<&str>,
+) -> Option<(Severity, String)> {
+    let ty = unboxed_type_name(ty)?;
+    if !matches!(ty.as_str(), "AccountInfo" | "UncheckedAccount")
+        || account_constraints(field).iter().any(|it| it.kind == "signer")
+    {
+        return None;
+    }
+    if let Some(paid) = paid {
+        return Some((
+            Severity::Error,
+            format!("pays for {paid} but is an `{ty}` that doesn't have to sign"),
+        ));
+    }
+    let name = field.name()?.text().to_string();
+    let authority = name.split('_').any(|it| matches!(it, "authority" | "admin" | "owner"));
+    authority.then(|| {
+        (Severity::Warning, format!("is an authority but an `{ty}` that doesn't have to sign"))
+    })
+}
+
+/// The message of an unchecked account finding for `field`, if it's an `AccountInfo` or
+/// `UncheckedAccount` that neither its constraints nor the `checked` accounts validate.
+fn unchecked_account(
+    field: &ast::RecordField,
+    ty: &ast::Type,
+    checked: &FxHashSet<String>,
+) -> Option<String> {
+    let ty = unboxed_type_name(ty)?;
+    if !matches!(ty.as_str(), "AccountInfo" | "UncheckedAccount")
+        || checked.contains(field.name()?.text().as_str())
+        || account_constraints(field)
+            .iter()
+            .any(|it| matches!(it.kind.as_str(), "owner" | "address" | "seeds" | "signer"))
+    {
+        return None;
+    }
+    Some(format!("is an `{ty}` whose owner and address are never checked"))
+}
+
+/// The accounts the instructions using a struct write.
+struct Written {
+    /// Written directly, with the first function doing it.
+    direct: FxHashMap<String, usize>,
+    /// Written in any way: directly, borrowed mutably, passed to a CPI, or paying for or receiving
+    /// the lamports of another field.
+    any: FxHashSet<String>,
+    /// Whether any instruction uses the struct, without which nothing is known.
+    used: bool,
+}
+
+fn written_accounts(
+    graph: &CodeGraph,
+    strukt: &AccountStruct,
+    reach: &FxHashMap<String, FxHashSet<usize>>,
+    writes: &FxHashMap<usize, Writes>,
+) -> Written {
+    let mut written = Written {
+        direct: FxHashMap::default(),
+        any: FxHashSet::default(),
+        used: !strukt.instructions.is_empty(),
+    };
+    let mut functions: Vec<usize> =
+        strukt.instructions.iter().filter_map(|it| reach.get(it)).flatten().copied().collect();
+    functions.sort_unstable();
+    functions.dedup();
+    for function in functions {
+        let Some(writes) = writes.get(&function) else { continue };
+        for name in &writes.direct {
+            written.direct.entry(name.clone()).or_insert(function);
+        }
+        written.any.extend(writes.direct.iter().chain(&writes.borrowed).cloned());
+    }
+    let cpis = graph
+        .cpi_calls
+        .iter()
+        .filter(|cpi| cpi.instructions.iter().any(|it| strukt.instructions.contains(it)));
+    for account in cpis.flat_map(|it| &it.accounts) {
+        written.any.extend(
+            account.account.split(|c: char| !c.is_alphanumeric() && c != '_').map(str::to_owned),
+        );
+    }
+    for constraint in strukt.fields.iter().flat_map(|it| &it.constraints) {
+        if matches!(constraint.kind.as_str(), "payer" | "close" | "realloc::payer")
+            && let Some(value) = &constraint.value
+        {
+            written.any.insert(value.clone());
+        }
+    }
+    written
+}
+
+/// The rule, severity and message of a `mut` constraint that doesn't match the writes of
+/// `field`, if it doesn't.
+fn mut_mismatch(
+    graph: &CodeGraph,
+    field: &ast::RecordField,
+    ty: &ast::Type,
+    written: &Written,
+) -> Option<(&'static str, Severity, String)> {
+    let name = field.name()?.text().to_string();
+    let constraints = account_constraints(field);
+    let writable =
+        |kind: &str| matches!(kind, "init" | "init_if_needed" | "zero" | "close" | "realloc");
+    if !written.used || constraints.iter().any(|it| writable(&it.kind)) {
+        return None;
+    }
+    let is_mut = constraints.iter().any(|it| it.kind == "mut");
+    if is_mut && !written.any.contains(&name) {
+        return Some((
+            "unnecessary-mut",
+            Severity::Warning,
+            "is `mut`, but no instruction using the struct writes it".to_owned(),
+        ));
+    }
+    let function = *written.direct.get(&name)?;
+    let ty = unboxed_type_name(ty)?;
+    if is_mut || matches!(ty.as_str(), "Program" | "Interface" | "Sysvar") {
+        return None;
+    }
+    Some((
+        "write-without-mut",
+        Severity::Error,
+        format!("isn't `mut`, but `{}` writes it", graph.function_path(function)),
+    ))
+}
+
+/// The fields of `strukt` validated outside of their own constraints: the targets of `has_one`,
+/// and the accounts whose key or owner a `constraint` or a function reached by the instructions
+/// using the struct compares.
+fn checked_accounts(
+    strukt: &AccountStruct,
+    reach: &FxHashMap<String, FxHashSet<usize>>,
+    compared: &FxHashMap<usize, FxHashSet<String>>,
+) -> FxHashSet<String> {
+    let mut checked = FxHashSet::default();
+    for constraint in strukt.fields.iter().flat_map(|it| &it.constraints) {
+        if constraint.kind == "has_one"
+            && let Some(value) = &constraint.value
+        {
+            checked.insert(value.clone());
+        }
+        let parts = constraint.expr.iter().flat_map(|it| it.walk());
+        checked.extend(parts.filter_map(|it| {
+            let owner = it.field_read().filter(|(_, field)| *field == "owner");
+            Some(it.key_of().or(owner.map(|(account, _)| account))?.to_owned())
+        }));
+    }
+    let functions = strukt.instructions.iter().filter_map(|it| reach.get(it)).flatten();
+    checked.extend(functions.filter_map(|it| compared.get(it)).flatten().cloned());
+    checked
+}
+
+/// The integer additions, subtractions and multiplications of the functions instructions reach.
+fn unchecked_arithmetic(
+    sema: &Semantics<'_, RootDatabase>,
+    graph: &CodeGraph,
+    bodies: &[Body],
+    reach: &FxHashMap<String, FxHashSet<usize>>,
+    overflow_checks: bool,
+) -> Vec<Finding> {
+    let reached: FxHashSet<usize> = reach.values().flatten().copied().collect();
+    let (severity, outcome) = match overflow_checks {
+        true => (Severity::Info, "aborts the instruction on overflow"),
+        false => {
+            (Severity::Warning, "wraps silently without `overflow-checks` in the release profile")
+        }
+    };
+    let mut findings = Vec::new();
+    for body in bodies.iter().filter(|it| reached.contains(&it.function)) {
+        for expr in body_descendants(body.block.syntax()).filter_map(ast::BinExpr::cast) {
+            let verb = match expr.op_kind() {
+                Some(BinaryOp::ArithOp(op) | BinaryOp::Assignment { op: Some(op) }) => match op {
+                    ArithOp::Add => "adds",
+                    ArithOp::Sub => "subtracts",
+                    ArithOp::Mul => "multiplies",
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let (Some(lhs), Some(rhs)) = (expr.lhs(), expr.rhs()) else { continue };
+            if is_constant(sema, &lhs) && is_constant(sema, &rhs) {
+                continue;
+            }
+            // Types can only be inspected with the database attached to the thread.
+            let types = salsa::attach(sema.db, || [&lhs, &rhs].map(|it| integer_type(sema, it)));
+            let [Some(lhs_ty), Some(rhs_ty)] = types else { continue };
+            findings.push(Finding {
+                plugin: "anchor".to_owned(),
+                rule: "unchecked-arithmetic".to_owned(),
+                message: format!(
+                    "`{expr}` {verb} `{lhs_ty}` and `{rhs_ty}` in `{}`, which {outcome}",
+                    graph.function_path(body.function)
+                ),
+                severity,
+                file: Some(body.file.clone()),
+                line: Some(body.line_index.line_col(expr.syntax().text_range().start()).line + 1),
+                related: Vec::new(),
+            });
+        }
+    }
+    findings
+}
+
+/// The `as` casts of the functions of the graph losing bits of their value.
+fn truncating_casts(
+    sema: &Semantics<'_, RootDatabase>,
+    graph: &CodeGraph,
+    bodies: &[Body],
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for body in bodies {
+        for cast in body_descendants(body.block.syntax()).filter_map(ast::CastExpr::cast) {
+            let (Some(expr), Some(target)) = (cast.expr(), cast.ty()) else { continue };
+            if is_constant(sema, &expr) {
+                continue;
+            }
+            // Types can only be inspected with the database attached to the thread.
+            let types = salsa::attach(sema.db, || {
+                let from = sema.type_of_expr(&expr)?.original.as_builtin()?;
+                let to = sema.resolve_type(&target)?.as_builtin()?;
+                Some((from, to))
+            });
+            let Some((from, to)) = types else { continue };
+            if !truncates(from.name().as_str(), to.name().as_str()) {
+                continue;
+            }
+            let in_arithmetic = cast
+                .syntax()
+                .ancestors()
+                .skip(1)
+                .map_while(ast::Expr::cast)
+                .filter_map(|it| match it {
+                    ast::Expr::BinExpr(it) => it.op_kind(),
+                    _ => None,
+                })
+                .any(|it| matches!(it, BinaryOp::ArithOp(_)));
+            let text = expr.syntax().text().to_string();
+            let amount = ["amount", "reserve", "lamports", "balance", "supply"]
+                .iter()
+                .any(|it| text.contains(it));
+            let severity = match in_arithmetic || amount {
+                true => Severity::Warning,
+                false => Severity::Info,
+            };
+            findings.push(Finding {
+                plugin: "anchor".to_owned(),
+                rule: "truncating-cast".to_owned(),
+                message: format!(
+                    "`{cast}` casts `{}` to `{}` in `{}`, dropping what doesn't fit",
+                    from.name().as_str(),
+                    to.name().as_str(),
+                    graph.function_path(body.function)
+                ),
+                severity,
+                file: Some(body.file.clone()),
+                line: Some(body.line_index.line_col(cast.syntax().text_range().start()).line + 1),
+                related: Vec::new(),
+            });
+        }
+    }
+    findings
+}
+
+/// Borrows of account data bound to a local that is still alive at a CPI made after it: before the
+/// end of the block of the `let` and before a `drop` of the local.
+fn borrows_across_cpis(graph: &CodeGraph, bodies: &[Body]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for body in bodies {
+        let function = graph.function_path(body.function);
+        // The functions taking the contexts of the CPIs of the function, like `token::transfer`.
+        let consumers: FxHashSet<&str> = graph
+            .cpi_calls
+            .iter()
+            .filter(|it| it.function.as_ref() == Some(&function))
+            .filter_map(|it| it.target.as_deref())
+            .collect();
+        let cpis: Vec<ast::CallExpr> = body_descendants(body.block.syntax())
+            .filter_map(ast::CallExpr::cast)
+            .filter(|call| {
+                let Some(ast::Expr::PathExpr(callee)) = call.expr() else { return false };
+                let Some(path) = callee.path() else { return false };
+                let name = path.segment().and_then(|it| it.name_ref());
+                name.is_some_and(|it| {
+                    matches!(
+                        it.text().as_str(),
+                        "invoke" | "invoke_signed" | "invoke_unchecked" | "invoke_signed_unchecked"
+                    )
+                }) || consumers.contains(path.syntax().text().to_string().as_str())
+            })
+            .collect();
+        if cpis.is_empty() {
+            continue;
+        }
+        for let_ in body_descendants(body.block.syntax()).filter_map(ast::LetStmt::cast) {
+            let Some(ast::Pat::IdentPat(binding)) = let_.pat() else { continue };
+            let (Some(binding), Some(init)) = (binding.name(), let_.initializer()) else {
+                continue;
+            };
+            let Some((account, mutable)) = data_borrow(init) else { continue };
+            let Some(scope) = let_.syntax().parent() else { continue };
+            let start = let_.syntax().text_range().end();
+            let dropped = scope
+                .descendants()
+                .filter_map(ast::CallExpr::cast)
+                .filter(|it| it.syntax().text_range().start() >= start)
+                .find(|call| {
+                    let callee = call.expr().map(|it| it.syntax().text().to_string());
+                    let arg = call.arg_list().and_then(|it| it.args().next());
+                    matches!(callee.as_deref(), Some("drop" | "std::mem::drop" | "core::mem::drop"))
+                        && arg.is_some_and(|it| it.syntax().text() == binding.text().as_str())
+                });
+            let end =
+                dropped.map_or(scope.text_range().end(), |it| it.syntax().text_range().start());
+            let Some(cpi) = cpis.iter().find(|it| {
+                let at = it.syntax().text_range().start();
+                start <= at && at < end
+            }) else {
+                continue;
+            };
+            let line_of =
+                |node: &SyntaxNode| body.line_index.line_col(node.text_range().start()).line + 1;
+            let (kind, taken, severity) = match mutable {
+                true => ("a mutable", "the account", Severity::Warning),
+                false => ("a shared", "the account writable", Severity::Info),
+            };
+            findings.push(Finding {
+                plugin: "anchor".to_owned(),
+                rule: "borrow-across-cpi".to_owned(),
+                message: format!(
+                    "`{}` holds {kind} borrow of the data of `{account}` across a CPI in \
+                     `{function}`, the CPI fails if it takes {taken}",
+                    binding.text()
+                ),
+                severity,
+                file: Some(body.file.clone()),
+                line: Some(line_of(let_.syntax())),
+                related: vec![Related {
+                    file: body.file.clone(),
+                    line: line_of(cpi.syntax()),
+                    message: "the CPI".to_owned(),
+                }],
+            });
+        }
+    }
+    findings
+}
+
+/// The account whose data `expr` borrows and whether mutably, like `vault` and true for
+/// `vault.try_borrow_mut_data()?`.
+fn data_borrow(mut expr: ast::Expr) -> Option<(String, bool)> {
+    loop {
+        expr = match expr {
+            ast::Expr::RefExpr(it) => it.expr()?,
+            ast::Expr::ParenExpr(it) => it.expr()?,
+            ast::Expr::TryExpr(it) => it.expr()?,
+            ast::Expr::MethodCallExpr(call) => {
+                let (method, receiver) = (call.name_ref()?, call.receiver()?);
+                match method.text().as_str() {
+                    "unwrap" | "expect" => receiver,
+                    "try_borrow_mut_data" | "load_mut" | "load_init" => {
+                        return Some((account_name(&receiver), true));
+                    }
+                    "try_borrow_data" => return Some((account_name(&receiver), false)),
+                    "borrow_mut" | "try_borrow_mut" | "borrow" | "try_borrow" => {
+                        let ast::Expr::FieldExpr(field) = receiver else { return None };
+                        if field.name_ref().is_none_or(|it| it.text() != "data") {
+                            return None;
+                        }
+                        let mutable = method.text().ends_with("mut");
+                        return Some((account_name(&field.expr()?), mutable));
+                    }
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+    }
+}
+
+/// Whether an `as` cast between the numeric types named `from` and `to` can lose bits of the
+/// value: an integer or a float cast to a narrower type of its kind, or a float to an integer.
+fn truncates(from: &str, to: &str) -> bool {
+    match (width(from), width(to)) {
+        (Some((true, _)), Some((false, _))) => true,
+        (Some((from_float, from)), Some((to_float, to))) => from_float == to_float && to < from,
+        _ => false,
+    }
+}
+
+/// Whether a numeric type is a float and its width, taking `usize` to be 64 bits as on Solana.
+fn width(ty: &str) -> Option<(bool, u32)> {
+    let bits = match ty {
+        "u8" | "i8" => 8,
+        "u16" | "i16" | "f16" => 16,
+        "u32" | "i32" | "f32" => 32,
+        "u64" | "i64" | "usize" | "isize" | "f64" => 64,
+        "u128" | "i128" | "f128" => 128,
+        _ => return None,
+    };
+    Some((ty.starts_with('f'), bits))
+}
+
+/// The integer type of `expr`, like `u64`.
+fn integer_type(sema: &Semantics<'_, RootDatabase>, expr: &ast::Expr) -> Option<String> {
+    let ty = sema.type_of_expr(expr)?.original.as_builtin()?;
+    (ty.is_int() || ty.is_uint()).then(|| ty.name().as_str().to_owned())
+}
+
+/// Whether `expr` is made of literals and constants only.
+fn is_constant(sema: &Semantics<'_, RootDatabase>, expr: &ast::Expr) -> bool {
+    match expr {
+        ast::Expr::Literal(_) => true,
+        ast::Expr::ParenExpr(it) => it.expr().is_some_and(|it| is_constant(sema, &it)),
+        ast::Expr::BinExpr(it) => {
+            [it.lhs(), it.rhs()].into_iter().all(|it| it.is_some_and(|it| is_constant(sema, &it)))
+        }
+        ast::Expr::PathExpr(it) => {
+            it.path().and_then(|it| sema.resolve_path(&it)).is_some_and(|it| {
+                matches!(it, PathResolution::Def(ModuleDef::Const(_) | ModuleDef::Static(_)))
+            })
+        }
+        _ => false,
+    }
+}
+
+/// Whether the release profile of the workspace sets `overflow-checks = true`.
+fn overflow_checks(project: &LoadedProject) -> bool {
+    let Ok(text) = fs::read_to_string(project.project_root.join("Cargo.toml")) else {
+        return false;
+    };
+    let Ok(manifest) = toml::from_str::<toml::Table>(&text) else { return false };
+    let release = manifest.get("profile").and_then(|it| it.get("release"));
+    release.and_then(|it| it.get("overflow-checks")).and_then(|it| it.as_bool()).unwrap_or(false)
+}
+
+/// The body of a function of the graph, with the file declaring it.
+struct Body {
+    function: usize,
+    file: String,
+    line_index: Arc<LineIndex>,
+    block: ast::BlockExpr,
+}
+
+/// The bodies of the functions of the graph declared in project files.
+fn function_bodies(
+    sema: &Semantics<'_, RootDatabase>,
+    project: &LoadedProject,
+    analysis: &Analysis,
+    graph: &CodeGraph,
+) -> Vec<Body> {
+    let mut bodies = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let relative_path = convert_to_relative_path(&file_path, &project.project_root);
+        for function in file.syntax().descendants().filter_map(ast::Fn::cast) {
+            let (Some(name), Some(block)) = (function.name(), function.body()) else { continue };
+            let line = line_index.line_col(name.syntax().text_range().start()).line + 1;
+            if let Some(function) = graph.function_at(&relative_path, name.text().as_str(), line) {
+                let (file, line_index) = (relative_path.clone(), line_index.clone());
+                bodies.push(Body { function, file, line_index, block });
+            }
+        }
+    }
+    bodies
+}
+
+/// The accounts whose key or owner every function compares, in `==`/`!=` expressions or in
+/// `require!`-like assertions, by function.
+fn compared_accounts(bodies: &[Body]) -> FxHashMap<usize, FxHashSet<String>> {
+    let mut compared: FxHashMap<usize, FxHashSet<String>> = FxHashMap::default();
+    for Body { function: id, block: body, .. } in bodies {
+        for node in body_descendants(body.syntax()) {
+            let comparison = match_ast! {
+                match node {
+                    ast::BinExpr(it) => {
+                        matches!(it.op_kind(), Some(BinaryOp::CmpOp(CmpOp::Eq { .. })))
+                    },
+                    ast::MacroCall(it) => is_assertion(&it),
+                    _ => false,
+                }
+            };
+            if comparison {
+                compared.entry(*id).or_default().extend(key_reads(&node));
+            }
+        }
+    }
+    compared
+}
+
+fn is_assertion(call: &ast::MacroCall) -> bool {
+    call.path().and_then(|it| it.segment()?.name_ref()).is_some_and(|it| {
+        matches!(
+            it.text().as_str(),
+            "require"
+                | "require_eq"
+                | "require_neq"
+                | "require_keys_eq"
+                | "require_keys_neq"
+                | "assert"
+                | "assert_eq"
+                | "assert_ne"
+        )
+    })
+}
+
+/// The description of an `init_if_needed` account field nothing guards against reinitialization,
+/// if it's one.
+fn unguarded_init_if_needed(
+    field: &ast::RecordField,
+    ty: &ast::Type,
+    space: &Space<'_, '_>,
+    strukt: Option<&AccountStruct>,
+    reach: &FxHashMap<String, FxHashSet<usize>>,
+    conditions: &FxHashMap<usize, Vec<Condition>>,
+) -> Option<String> {
+    let constraints = account_constraints(field);
+    if !constraints.iter().any(|it| it.kind == "init_if_needed") {
+        return None;
+    }
+    let account = space.account(&wrapped_account(ty)?)?;
+    let mut checks = constraints
+        .iter()
+        .filter(|it| it.kind == "constraint")
+        .filter_map(|it| parse_expr(it.value.as_deref()?))
+        .map(|it| Condition::of(it.syntax()));
+    if checks.any(|it| it.guards(account)) {
+        return None;
+    }
+    let functions = strukt?.instructions.iter().filter_map(|it| reach.get(it)).flatten();
+    if functions.filter_map(|it| conditions.get(it)).flatten().any(|it| it.guards(account)) {
+        return None;
+    }
+    Some(match account.fields.iter().find(|it| it.ty == "bool") {
+        Some(flag) => format!(
+            "is `init_if_needed`, but nothing checks `{}.{}` before the instruction writes it \
+             again",
+            account.name, flag.name
+        ),
+        None => format!(
+            "is `init_if_needed`, but nothing checks whether the `{}` was initialized already",
+            account.name
+        ),
+    })
+}
+
+/// A condition of an `if`, a `while` or a `require!`-like assertion.
+struct Condition {
+    /// The names of the fields it reads, like `initialized` in `!global.initialized`.
+    fields: FxHashSet<String>,
+    /// Whether it mentions a default value, like `pool.authority != Pubkey::default()`.
+    default: bool,
+}
+
+impl Condition {
+    fn of(node: &SyntaxNode) -> Condition {
+        let tokens: Vec<_> = node
+            .descendants_with_tokens()
+            .filter_map(|it| it.into_token())
+            .filter(|it| !it.kind().is_trivia())
+            .collect();
+        let fields = tokens
+            .windows(2)
+            .enumerate()
+            .filter(|(i, it)| {
+                it[0].kind() == T![.]
+                    && it[1].kind() == SyntaxKind::IDENT
+                    && tokens.get(i + 2).is_none_or(|it| it.kind() != T!['('])
+            })
+            .map(|(_, it)| it[1].text().to_owned())
+            .collect();
+        let default = tokens.iter().any(|it| it.text() == "default");
+        Condition { fields, default }
+    }
+
+    /// Whether the condition tells an initialized `account` apart from a new one, reading one of
+    /// its flags or comparing one of its fields with a default value.
+    fn guards(&self, account: &StateAccount) -> bool {
+        account
+            .fields
+            .iter()
+            .any(|field| self.fields.contains(&field.name) && (self.default || field.ty == "bool"))
+    }
+}
+
+/// The conditions of every function, by function.
+fn guard_conditions(bodies: &[Body]) -> FxHashMap<usize, Vec<Condition>> {
+    let mut conditions: FxHashMap<usize, Vec<Condition>> = FxHashMap::default();
+    for Body { function: id, block: body, .. } in bodies {
+        for node in body_descendants(body.syntax()) {
+            let condition = match_ast! {
+                match node {
+                    ast::IfExpr(it) => it.condition().map(|it| it.syntax().clone()),
+                    ast::WhileExpr(it) => it.condition().map(|it| it.syntax().clone()),
+                    ast::MacroCall(it) => is_assertion(&it).then(|| it.syntax().clone()),
+                    _ => None,
+                }
+            };
+            if let Some(condition) = condition {
+                conditions.entry(*id).or_default().push(Condition::of(&condition));
+            }
+        }
+    }
+    conditions
+}
+
+/// The names of the accounts every function writes, by function.
+#[derive(Default)]
+struct Writes {
+    /// Assigned to, or with a mutating method like `load_mut` or `close` called on them.
+    direct: FxHashSet<String>,
+    /// Borrowed mutably, which may write them.
+    borrowed: FxHashSet<String>,
+}
+
+/// The accounts every function writes, by function. Writes are told by name, the fields and
+/// bindings a write goes through, like `global` in `ctx.accounts.global.fee_bps = fee`.
+fn account_writes(bodies: &[Body]) -> FxHashMap<usize, Writes> {
+    const MUTATING: &[&str] = &[
+        "borrow_mut",
+        "close",
+        "exit",
+        "load_init",
+        "load_mut",
+        "realloc",
+        "serialize",
+        "set_inner",
+        "try_borrow_mut_data",
+        "try_borrow_mut_lamports",
+        "try_serialize",
+    ];
+    let names = |node: &SyntaxNode| {
+        node.descendants_with_tokens()
+            .filter_map(|it| it.into_token())
+            .filter(|it| it.kind() == SyntaxKind::IDENT)
+            .map(|it| it.text().to_owned())
+            .collect::<Vec<_>>()
+    };
+    let mut writes: FxHashMap<usize, Writes> = FxHashMap::default();
+    for Body { function: id, block: body, .. } in bodies {
+        let writes = writes.entry(*id).or_default();
+        for node in body_descendants(body.syntax()) {
+            match_ast! {
+                match node {
+                    ast::BinExpr(it) => {
+                        if matches!(it.op_kind(), Some(BinaryOp::Assignment { .. }))
+                            && let Some(lhs) = it.lhs()
+                        {
+                            writes.direct.extend(names(lhs.syntax()));
+                        }
+                    },
+                    ast::MethodCallExpr(it) => {
+                        if it.name_ref().is_some_and(|it| MUTATING.contains(&it.text().as_str()))
+                            && let Some(receiver) = it.receiver()
+                        {
+                            writes.direct.extend(names(receiver.syntax()));
+                        }
+                    },
+                    ast::RefExpr(it) => {
+                        if it.mut_token().is_some() {
+                            writes.borrowed.extend(names(it.syntax()));
+                        }
+                    },
+                    _ => (),
+                }
+            }
+        }
+    }
+    writes
+}
+
+/// The accounts `node` reads the key or owner of, like `vault` in `ctx.accounts.vault.key()`.
+fn key_reads(node: &SyntaxNode) -> Vec<String> {
+    let tokens: Vec<_> = node
+        .descendants_with_tokens()
+        .filter_map(|it| it.into_token())
+        .filter(|it| !it.kind().is_trivia())
+        .collect();
+    tokens
+        .windows(3)
+        .filter(|it| {
+            it[0].kind() == SyntaxKind::IDENT
+                && it[1].kind() == T![.]
+                && matches!(it[2].text(), "key" | "owner")
+        })
+        .map(|it| it[0].text().to_owned())
+        .collect()
+}
+
+/// A write of a bump into a field of an account, like `ctx.accounts.pool.bump = ctx.bumps.pool`.
+struct BumpWrite {
+    /// The struct owning the field, none when it doesn't resolve.
+    account: Option<String>,
+    field: String,
+    /// Whether the value comes from `ctx.bumps` or `find_program_address`.
+    canonical: bool,
+}
+
+/// The severity and message of a non-canonical bump finding for `field`, if its seeds are
+/// checked with a bump that isn't known to be canonical. `fields` are the fields of its struct.
+fn non_canonical_bump(
+    field: &ast::RecordField,
+    fields: &[AccountField],
+    writes: &[BumpWrite],
+) -> Option<(Severity, String)> {
+    let constraints = account_constraints(field);
+    if !constraints.iter().any(|it| it.kind == "seeds") {
+        return None;
+    }
+    let value = constraints.iter().find(|it| it.kind == "bump")?.value.as_deref()?;
+    let Some((account, stored)) = stored_bump(&parse_expr(value)?, fields) else {
+        return Some((
+            Severity::Error,
+            format!("checks its seeds with `bump = {value}`, a bump the caller chooses"),
+        ));
+    };
+    let canonical = writes.iter().any(|it| {
+        it.canonical && it.field == stored && it.account.as_deref().is_none_or(|it| it == account)
+    });
+    (!canonical).then(|| {
+        (
+            Severity::Warning,
+            format!(
+                "checks its seeds with `bump = {value}`, but `{account}.{stored}` is never set \
+                 from `ctx.bumps`"
+            ),
+        )
+    })
+}
+
+/// The account type and field of a bump stored by an earlier instruction, read by a
+/// `bump = account.bump` constraint.
+fn stored_bump(value: &ast::Expr, fields: &[AccountField]) -> Option<(String, String)> {
+    let ast::Expr::FieldExpr(expr) = value else { return None };
+    let ast::Expr::PathExpr(receiver) = expr.expr()? else { return None };
+    let receiver = receiver.path()?.as_single_name_ref()?;
+    let account = fields.iter().find(|it| it.name == receiver.text())?;
+    let ty = account.account_type.as_deref()?;
+    Some((ty.rsplit("::").next().unwrap_or(ty).to_owned(), expr.name_ref()?.to_string()))
+}
+
+/// Every write of a field named like a bump in project files, by assignment or struct literal.
+fn bump_writes(sema: &Semantics<'_, RootDatabase>, project: &LoadedProject) -> Vec<BumpWrite> {
+    let mut writes = Vec::new();
+    for (file_id, _) in project_files(project) {
+        let file = sema.parse_guess_edition(file_id);
+        for node in file.syntax().descendants() {
+            let Some((field, name, value)) = field_write(sema, &node) else { continue };
+            if !name.contains("bump") {
+                continue;
+            }
+            let canonical = value.syntax().descendants_with_tokens().any(|it| {
+                it.as_token()
+                    .is_some_and(|it| matches!(it.text(), "bumps" | "find_program_address"))
+            });
+            let account = field.and_then(|it| match it.parent_def(sema.db) {
+                hir::VariantDef::Struct(it) => Some(it.name(sema.db).as_str().to_owned()),
+                _ => None,
+            });
+            writes.push(BumpWrite { account, field: name, canonical });
+        }
+    }
+    writes
+}
+
+/// The field `node` writes, its name and the value written, if it's a plain assignment to a
+/// field or a field of a struct literal.
+fn field_write(
+    sema: &Semantics<'_, RootDatabase>,
+    node: &SyntaxNode,
+) -> Option<(Option<hir::Field>, String, ast::Expr)> {
+    match_ast! {
+        match node {
+            ast::BinExpr(it) => {
+                if !matches!(it.op_kind()?, BinaryOp::Assignment { op: None }) {
+                    return None;
+                }
+                let ast::Expr::FieldExpr(lhs) = it.lhs()? else { return None };
+                let field = sema.resolve_field(&lhs).and_then(|it| it.left());
+                Some((field, lhs.name_ref()?.to_string(), it.rhs()?))
+            },
+            ast::RecordExprField(it) => {
+                let field = sema.resolve_record_field(&it).map(|(it, ..)| it);
+                Some((field, it.field_name()?.to_string(), it.expr()?))
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The name of the type of an account field, without its `Box`.
+fn unboxed_type_name(ty: &ast::Type) -> Option<String> {
+    let ast::Type::PathType(path) = ty else { return None };
+    let segment = path.path()?.segment()?;
+    let name = segment.name_ref()?.text().to_string();
+    if name != "Box" {
+        return Some(name);
+    }
+    let inner = segment.generic_arg_list()?.generic_args().find_map(|arg| match arg {
+        ast::GenericArg::TypeArg(arg) => arg.ty(),
+        _ => None,
+    })?;
+    unboxed_type_name(&inner)
+}
+
+/// Evaluates `space` expressions in the scope of an account struct.
+struct Space<'a, 'db> {
+    sema: &'a Semantics<'db, RootDatabase>,
+    scope: SemanticsScope<'db>,
+    accounts: &'a [StateAccount],
+    zero_copy: &'a [ZeroCopyType],
+}
+
+impl Space<'_, '_> {
+    /// Describes the under-allocation of the account `field` initializes, if any.
+    fn check(&self, field: &ast::RecordField, ty: &ast::Type) -> Option<String> {
+        let constraints = account_constraints(field);
+        if !constraints.iter().any(|it| matches!(it.kind.as_str(), "init" | "init_if_needed")) {
+            return None;
+        }
+        let value = constraints.iter().find(|it| it.kind == "space")?.value.as_deref()?;
+        let account = self.account(&wrapped_account(ty)?)?;
+        let size = account.size?;
+        let space = self.eval(&parse_expr(value)?)?;
+        if space >= size {
+            return None;
+        }
+        let padding = self
+            .zero_copy
+            .iter()
+            .find(|it| it.module == account.module && it.name == account.name)
+            .and_then(|it| it.layout.padding())
+            .filter(|&it| it > 0);
+        let mut message = format!(
+            "allocates `space = {value}` = {space} bytes, but `{}` takes {size}",
+            account.name
+        );
+        if let Some(padding) = padding {
+            message.push_str(&format!(", {padding} of them padding"));
+        }
+        Some(message)
+    }
+
+    /// The account type `field` reallocates and the size it reallocates it to, none when the size
+    /// isn't constant.
+    fn realloc(&self, field: &ast::RecordField, ty: &ast::Type) -> Option<(String, Option<u64>)> {
+        let constraints = account_constraints(field);
+        let value = constraints.iter().find(|it| it.kind == "realloc")?.value.as_deref()?;
+        let ty = wrapped_account(ty)?;
+        let account = match self.account(&ty) {
+            Some(account) => qualify(&account.module, &account.name),
+            None => ty.syntax().text().to_string(),
+        };
+        Some((account, parse_expr(value).and_then(|it| self.eval(&it))))
+    }
+
+    /// Describes the reallocation of `field` without zeroing, if the account's size varies across
+    /// the `sizes` it's reallocated to.
+    fn realloc_without_zero(
+        &self,
+        field: &ast::RecordField,
+        ty: &ast::Type,
+        sizes: &FxHashMap<String, Vec<Option<u64>>>,
+    ) -> Option<String> {
+        let (account, _) = self.realloc(field, ty)?;
+        let zeroed = account_constraints(field)
+            .iter()
+            .any(|it| it.kind == "realloc::zero" && it.value.as_deref() == Some("true"));
+        let sizes = sizes.get(&account)?;
+        let fixed = sizes.iter().all(|it| it.is_some() && *it == sizes[0]);
+        (!zeroed && !fixed).then(|| {
+            let name = account.rsplit("::").next().unwrap_or(&account);
+            format!(
+                "reallocates `{name}` without `realloc::zero = true`, but its size varies, so \
+                 growing it back after a shrink exposes stale bytes"
+            )
+        })
+    }
+
+    /// The state account `ty` refers to, by name when it can't be resolved.
+    fn account(&self, ty: &ast::Type) -> Option<&StateAccount> {
+        if let Some(path) = resolve_struct(self.sema, ty) {
+            return self.accounts.iter().find(|it| qualify(&it.module, &it.name) == path);
+        }
+        let ast::Type::PathType(ty) = ty else { return None };
+        self.account_named(&ty.path()?)
+    }
+
+    fn account_named(&self, path: &ast::Path) -> Option<&StateAccount> {
+        let name = path.segment()?.name_ref()?;
+        let mut named = self.accounts.iter().filter(|it| it.name == name.text().as_str());
+        let account = named.next()?;
+        named.next().is_none().then_some(account)
+    }
+
+    fn eval(&self, expr: &ast::Expr) -> Option<u64> {
+        match expr {
+            ast::Expr::Literal(literal) => match literal.kind() {
+                ast::LiteralKind::IntNumber(number) => number.value().ok()?.try_into().ok(),
+                _ => None,
+            },
+            ast::Expr::ParenExpr(expr) => self.eval(&expr.expr()?),
+            ast::Expr::CastExpr(expr) => self.eval(&expr.expr()?),
+            ast::Expr::BinExpr(expr) => {
+                let (lhs, rhs) = (self.eval(&expr.lhs()?)?, self.eval(&expr.rhs()?)?);
+                match expr.op_kind()? {
+                    BinaryOp::ArithOp(ArithOp::Add) => lhs.checked_add(rhs),
+                    BinaryOp::ArithOp(ArithOp::Sub) => lhs.checked_sub(rhs),
+                    BinaryOp::ArithOp(ArithOp::Mul) => lhs.checked_mul(rhs),
+                    BinaryOp::ArithOp(ArithOp::Div) => lhs.checked_div(rhs),
+                    _ => None,
+                }
+            }
+            ast::Expr::PathExpr(expr) => self.constant(&expr.path()?),
+            ast::Expr::CallExpr(call) => {
+                let ast::Expr::PathExpr(callee) = call.expr()? else { return None };
+                self.size_of(&callee.path()?.segment()?)
+            }
+            _ => None,
+        }
+    }
+
+    /// The value of the constant `path`. `T::INIT_SPACE` is computed from the fields of `T` when
+    /// the derive isn't expanded.
+    fn constant(&self, path: &ast::Path) -> Option<u64> {
+        let db = self.sema.db;
+        let konst = match self.scope.speculative_resolve(path) {
+            Some(PathResolution::Def(ModuleDef::Const(konst))) => Some(konst),
+            _ => self.associated_const(path),
+        };
+        if let Some(konst) = konst {
+            // Constants are evaluated with the database attached to the thread.
+            let value = salsa::attach(db, || konst.eval(db).ok()?.render_debug(db).ok());
+            // Integers render with their hexadecimal value in parentheses.
+            return value?.split_whitespace().next()?.parse().ok();
+        }
+        if path.segment()?.name_ref()?.text() != "INIT_SPACE" {
+            return None;
+        }
+        let qualifier = path.qualifier()?;
+        let account = match self.scope.speculative_resolve(&qualifier) {
+            Some(PathResolution::Def(ModuleDef::Adt(hir::Adt::Struct(strukt)))) => {
+                let module = module_path(db, strukt.module(db));
+                let name = strukt.name(db).display(db, Edition::CURRENT).to_string();
+                self.accounts.iter().find(|it| it.module == module && it.name == name)?
+            }
+            _ => self.account_named(&qualifier)?,
+        };
+        account.init_space.then_some(account.size?.checked_sub(DISCRIMINATOR_SIZE)?)
+    }
+
+    /// The constant of an inherent impl named by a path like `Global::LEN`, which scopes don't
+    /// resolve.
+    fn associated_const(&self, path: &ast::Path) -> Option<hir::Const> {
+        let Some(PathResolution::Def(ModuleDef::Adt(adt))) =
+            self.scope.speculative_resolve(&path.qualifier()?)
+        else {
+            return None;
+        };
+        let name = path.segment()?.name_ref()?;
+        let db = self.sema.db;
+        salsa::attach(db, || {
+            hir::Impl::all_for_type(db, adt.ty(db))
+                .into_iter()
+                .filter(|it| it.trait_(db).is_none())
+                .flat_map(|it| it.items(db))
+                .find_map(|item| match item {
+                    hir::AssocItem::Const(konst)
+                        if konst.name(db).is_some_and(|it| it.as_str() == name.text()) =>
+                    {
+                        Some(konst)
+                    }
+                    _ => None,
+                })
+        })
+    }
+
+    /// The memory size of `T` for a `size_of::<T>()` call.
+    fn size_of(&self, segment: &ast::PathSegment) -> Option<u64> {
+        if segment.name_ref()?.text() != "size_of" {
+            return None;
+        }
+        let ty = segment.generic_arg_list()?.generic_args().find_map(|arg| match arg {
+            ast::GenericArg::TypeArg(arg) => arg.ty(),
+            _ => None,
+        })?;
+        let ast::Type::PathType(ty) = ty else { return None };
+        let db = self.sema.db;
+        let ty = match self.scope.speculative_resolve(&ty.path()?)? {
+            PathResolution::Def(ModuleDef::Adt(adt)) => adt.ty(db),
+            PathResolution::Def(ModuleDef::BuiltinType(builtin)) => builtin.ty(db),
+            _ => return None,
+        };
+        // Layouts need the database attached to the thread.
+        salsa::attach(db, || ty.layout(db).ok().map(|it| it.size()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ide::AnalysisHost;
+    use syntax::SourceFile;
+    use test_fixture::ChangeFixture;
+
+    use super::*;
+    use crate::cli::state_accounts::StateField;
+
+    /// The bodies of the functions of `text`, numbered in the order they're declared.
+    fn bodies(text: &str) -> Vec<Body> {
+        let file = SourceFile::parse(text, Edition::CURRENT).tree();
+        let line_index = Arc::new(LineIndex::new(text));
+        file.syntax()
+            .descendants()
+            .filter_map(ast::Fn::cast)
+            .filter_map(|it| it.body())
+            .enumerate()
+            .map(|(function, block)| Body {
+                function,
+                file: "src/lib.rs".to_owned(),
+                line_index: line_index.clone(),
+                block,
+            })
+            .collect()
+    }
+
+    /// The fields of the first struct of `text`, by name.
+    fn fields(text: &str) -> FxHashMap<String, ast::RecordField> {
+        let file = SourceFile::parse(text, Edition::CURRENT).tree();
+        let strukt = file.syntax().descendants().find_map(ast::Struct::cast).unwrap();
+        record_fields(&strukt).map(|it| (it.name().unwrap().to_string(), it)).collect()
+    }
+
+    #[test]
+    fn space_covers_the_account() {
+        let mut host = AnalysisHost::default();
+        let change_fixture = ChangeFixture::parse(
+            host.raw_database(),
+            r#"
+//- /lib.rs crate:pool
+const POOL_SPACE: usize = 8 + 32;
+
+pub struct Pool {
+    pub authority: [u8; 32],
+    pub fee: u64,
+}
+
+impl Pool {
+    pub const LEN: usize = 8 + 32;
+}
+
+#[derive(Accounts)]
+pub struct Create<'info> {
+    #[account(init, payer = payer, space = 8 + 32)]
+    pub literal: Account<'info, Pool>,
+    #[account(init, payer = payer, space = 8 + 32 + 8)]
+    pub exact: Account<'info, Pool>,
+    #[account(init_if_needed, payer = payer, space = POOL_SPACE)]
+    pub constant: Account<'info, Pool>,
+    #[account(init, payer = payer, space = Pool::LEN)]
+    pub associated: Account<'info, Pool>,
+    #[account(init, payer = payer, space = Pool::INIT_SPACE)]
+    pub init_space: Account<'info, Pool>,
+    #[account(init, payer = payer, space = (8 + 32) / 2 * 2)]
+    pub arithmetic: Account<'info, Pool>,
+    #[account(init, payer = payer, space = 8 + size_of::<u64>())]
+    pub size_of: Account<'info, Pool>,
+    #[account(init, payer = payer, space = 8 + len)]
+    pub unknown: Account<'info, Pool>,
+    #[account(mut, realloc = 8, realloc::payer = payer, realloc::zero = false)]
+    pub existing: Account<'info, Pool>,
+}
+"#,
+        );
+        host.raw_database_mut().apply_change(change_fixture.change);
+        let account = StateAccount {
+            name: "Pool".to_owned(),
+            file: "src/lib.rs".to_owned(),
+            line: 4,
+            module: "pool".to_owned(),
+            visibility: "pub".to_owned(),
+            zero_copy: false,
+            discriminator: None,
+            init_space: true,
+            fields: Vec::new(),
+            size: Some(48),
+        };
+        let sema = Semantics::new(host.raw_database());
+        let file = sema.parse(change_fixture.files[0]);
+        let strukt = file.syntax().descendants().filter_map(ast::Struct::cast).last().unwrap();
+        let space = Space {
+            sema: &sema,
+            scope: sema.scope(strukt.syntax()).unwrap(),
+            accounts: std::slice::from_ref(&account),
+            zero_copy: &[],
+        };
+        let cases = [
+            ("literal", Some("allocates `space = 8 + 32` = 40 bytes, but `Pool` takes 48")),
+            ("exact", None),
+            ("constant", Some("allocates `space = POOL_SPACE` = 40 bytes, but `Pool` takes 48")),
+            ("associated", Some("allocates `space = Pool::LEN` = 40 bytes, but `Pool` takes 48")),
+            (
+                "init_space",
+                Some("allocates `space = Pool::INIT_SPACE` = 40 bytes, but `Pool` takes 48"),
+            ),
+            (
+                "size_of",
+                Some("allocates `space = 8 + size_of::<u64>()` = 16 bytes, but `Pool` takes 48"),
+            ),
+            (
+                "arithmetic",
+                Some("allocates `space = (8 + 32) / 2 * 2` = 40 bytes, but `Pool` takes 48"),
+            ),
+            ("unknown", None),
+            ("existing", None),
+        ];
+        let fields: FxHashMap<_, _> =
+            record_fields(&strukt).map(|it| (it.name().unwrap().to_string(), it)).collect();
+        for (name, expected) in cases {
+            let field = &fields[name];
+            let finding = space.check(field, &field.ty().unwrap());
+            assert_eq!(finding.as_deref(), expected, "`{name}`");
+        }
+    }
+
+    #[test]
+    fn authorities_and_payers_must_sign() {
+        let fields = fields(
+            r#"
+#[derive(Accounts)]
+pub struct Update<'info> {
+    pub authority: AccountInfo<'info>,
+    pub pool_admin: Box<UncheckedAccount<'info>>,
+    #[account(signer)]
+    pub owner: AccountInfo<'info>,
+    pub signer_authority: Signer<'info>,
+    pub owners: AccountInfo<'info>,
+    pub funder: AccountInfo<'info>,
+}
+"#,
+        );
+        let cases = [
+            (
+                "authority",
+                None,
+                Some((
+                    Severity::Warning,
+                    "is an authority but an `AccountInfo` that doesn't have to sign",
+                )),
+            ),
+            (
+                "pool_admin",
+                None,
+                Some((
+                    Severity::Warning,
+                    "is an authority but an `UncheckedAccount` that doesn't have to sign",
+                )),
+            ),
+            ("owner", None, None),
+            ("owner", Some("account creation"), None),
+            ("signer_authority", None, None),
+            ("owners", None, None),
+            (
+                "funder",
+                Some("transfers"),
+                Some((
+                    Severity::Error,
+                    "pays for transfers but is an `AccountInfo` that doesn't have to sign",
+                )),
+            ),
+            ("funder", None, None),
+        ];
+        for (name, paid, expected) in cases {
+            let field = &fields[name];
+            let finding = missing_signer(field, &field.ty().unwrap(), paid);
+            let expected = expected.map(|(severity, message)| (severity, message.to_owned()));
+            assert_eq!(finding, expected, "`{name}` paying for {paid:?}");
+        }
+    }
+
+    #[test]
+    fn unchecked_accounts_need_a_check() {
+        let fields = fields(
+            r#"
+#[derive(Accounts)]
+pub struct Update<'info> {
+    pub vault: AccountInfo<'info>,
+    pub mint: Box<UncheckedAccount<'info>>,
+    #[account(owner = token_program.key())]
+    pub owned: AccountInfo<'info>,
+    #[account(address = config.treasury)]
+    pub treasury: AccountInfo<'info>,
+    #[account(seeds = [b"escrow"], bump)]
+    pub escrow: UncheckedAccount<'info>,
+    #[account(signer)]
+    pub authority: AccountInfo<'info>,
+    #[account(mut)]
+    pub recipient: AccountInfo<'info>,
+    pub compared: AccountInfo<'info>,
+    pub pool: Account<'info, Pool>,
+}
+"#,
+        );
+        let checked = FxHashSet::from_iter(["compared".to_owned()]);
+        let cases = [
+            ("vault", Some("is an `AccountInfo` whose owner and address are never checked")),
+            ("mint", Some("is an `UncheckedAccount` whose owner and address are never checked")),
+            ("owned", None),
+            ("treasury", None),
+            ("escrow", None),
+            ("authority", None),
+            ("recipient", Some("is an `AccountInfo` whose owner and address are never checked")),
+            ("compared", None),
+            ("pool", None),
+        ];
+        for (name, expected) in cases {
+            let field = &fields[name];
+            let finding = unchecked_account(field, &field.ty().unwrap(), &checked);
+            assert_eq!(finding.as_deref(), expected, "`{name}`");
+        }
+    }
+
+    #[test]
+    fn seeds_need_the_canonical_bump() {
+        let fields = fields(
+            r#"
+#[derive(Accounts)]
+pub struct Update<'info> {
+    #[account(seeds = [b"pool"], bump)]
+    pub found: Account<'info, Pool>,
+    #[account(seeds = [b"pool"], bump = pool.bump)]
+    pub pool: Account<'info, Pool>,
+    #[account(seeds = [b"vault"], bump = pool.vault_bump)]
+    pub vault: AccountInfo<'info>,
+    #[account(seeds = [b"fee"], bump = fee_bump)]
+    pub fee: AccountInfo<'info>,
+    #[account(bump = pool.bump)]
+    pub unseeded: AccountInfo<'info>,
+}
+"#,
+        );
+        let account_fields: Vec<_> = ["found", "pool", "vault", "fee", "unseeded"]
+            .into_iter()
+            .map(|name| AccountField {
+                name: name.to_owned(),
+                line: 0,
+                visibility: "pub".to_owned(),
+                ty: String::new(),
+                account_type: matches!(name, "found" | "pool").then(|| "state::Pool".to_owned()),
+                constraints: Vec::new(),
+            })
+            .collect();
+        let writes = [
+            BumpWrite {
+                account: Some("Pool".to_owned()),
+                field: "bump".to_owned(),
+                canonical: true,
+            },
+            BumpWrite { account: None, field: "vault_bump".to_owned(), canonical: false },
+        ];
+        let cases = [
+            ("found", None),
+            ("pool", None),
+            (
+                "vault",
+                Some((
+                    Severity::Warning,
+                    "checks its seeds with `bump = pool.vault_bump`, but `Pool.vault_bump` is \
+                     never set from `ctx.bumps`",
+                )),
+            ),
+            (
+                "fee",
+                Some((
+                    Severity::Error,
+                    "checks its seeds with `bump = fee_bump`, a bump the caller chooses",
+                )),
+            ),
+            ("unseeded", None),
+        ];
+        for (name, expected) in cases {
+            let finding = non_canonical_bump(&fields[name], &account_fields, &writes);
+            let expected = expected.map(|(severity, message)| (severity, message.to_owned()));
+            assert_eq!(finding, expected, "`{name}`");
+        }
+    }
+
+    #[test]
+    fn conditions_guard_flags_and_defaults() {
+        let field = |name: &str, ty: &str| StateField {
+            name: name.to_owned(),
+            ty: ty.to_owned(),
+            visibility: "pub".to_owned(),
+            size: None,
+            offset: None,
+        };
+        let account = StateAccount {
+            name: "Global".to_owned(),
+            file: "src/lib.rs".to_owned(),
+            line: 1,
+            module: "crate".to_owned(),
+            visibility: "pub".to_owned(),
+            zero_copy: false,
+            discriminator: None,
+            init_space: false,
+            fields: vec![field("authority", "Pubkey"), field("initialized", "bool")],
+            size: None,
+        };
+        let cases = [
+            ("!global.initialized", true),
+            ("global.authority != Pubkey::default()", true),
+            ("global.authority == Pubkey::default()", true),
+            ("global.authority != ctx.accounts.admin.key()", false),
+            ("global.is_initialized()", false),
+            ("other.enabled", false),
+            ("count > 0", false),
+        ];
+        for (condition, expected) in cases {
+            let text = format!("fn f() {{ if {condition} {{}} }}");
+            let file = SourceFile::parse(&text, Edition::CURRENT).tree();
+            let condition_node =
+                file.syntax().descendants().find_map(ast::IfExpr::cast).unwrap().condition();
+            let guards = Condition::of(condition_node.unwrap().syntax()).guards(&account);
+            assert_eq!(guards, expected, "`{condition}`");
+        }
+    }
+
+    #[test]
+    fn nested_functions_keep_their_comparisons_guards_and_writes() {
+        let bodies = bodies(
+            r#"
+fn handler(ctx: Context<Update>) {
+    fn check(pool: &Pool, authority: &Signer) {
+        require_keys_eq!(pool.authority, authority.key());
+        if pool.initialized {}
+        pool.fee = 1;
+    }
+}
+"#,
+        );
+        assert!(!compared_accounts(&bodies).contains_key(&0));
+        assert!(compared_accounts(&bodies)[&1].contains("authority"));
+        assert!(!guard_conditions(&bodies).contains_key(&0));
+        assert!(guard_conditions(&bodies)[&1].iter().any(|it| it.fields.contains("initialized")));
+        assert!(account_writes(&bodies)[&0].direct.is_empty());
+        assert!(account_writes(&bodies)[&1].direct.contains("pool"));
+    }
+
+    #[test]
+    fn narrowing_casts_truncate() {
+        let cases = [
+            ("u64", "u32", true),
+            ("u64", "i64", false),
+            ("i128", "usize", true),
+            ("u8", "u64", false),
+            ("f64", "f32", true),
+            ("f32", "f64", false),
+            ("f32", "u128", true),
+            ("u64", "f32", false),
+            ("bool", "u8", false),
+        ];
+        for (from, to, expected) in cases {
+            assert_eq!(truncates(from, to), expected, "`{from} as {to}`");
+        }
+    }
+}
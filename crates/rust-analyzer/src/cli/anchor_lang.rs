@@ -0,0 +1,83 @@
+//! The anchor-lang dependency of the project, found in the crate graph Cargo metadata loads, and
+//! the constraint grammar of its version.
+//!
+//! Constraints are parsed the same way for every version, since newer ones only add keys. What
+//! changes is which keys a version accepts: a constraint added after the anchor-lang version the
+//! program builds with, or gated behind a feature it doesn't enable, fails to compile.
+
+use hir::Crate;
+use ide::RootDatabase;
+use serde::Serialize;
+
+/// Constraints anchor-lang accepts from some version on, by key or namespace (`realloc::`).
+const ADDED_IN: &[(&str, Version)] = &[
+    ("realloc", (0, 25, 0)),
+    ("realloc::", (0, 25, 0)),
+    ("token::token_program", (0, 29, 0)),
+    ("mint::token_program", (0, 29, 0)),
+    ("associated_token::token_program", (0, 29, 0)),
+    ("extensions::", (0, 30, 0)),
+];
+
+/// Constraints anchor-lang only accepts with one of its features enabled.
+const GATED: &[(&str, &str)] = &[("init_if_needed", "init-if-needed")];
+
+type Version = (u64, u64, u64);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct AnchorLang {
+    pub(super) version: String,
+    /// The enabled Cargo features, like `init-if-needed`.
+    pub(super) features: Vec<String>,
+}
+
+impl AnchorLang {
+    /// The anchor-lang crate of the workspace, the newest one when several versions are loaded.
+    pub(super) fn detect(db: &RootDatabase) -> Option<AnchorLang> {
+        Crate::all(db)
+            .into_iter()
+            .filter(|krate| {
+                krate
+                    .display_name(db)
+                    .is_some_and(|it| it.crate_name().symbol().as_str() == "anchor_lang")
+            })
+            .filter_map(|krate| {
+                let version = krate.version(db)?;
+                let mut features: Vec<String> = krate
+                    .cfg(db)
+                    .get_cfg_values("feature")
+                    .map(|it| it.as_str().to_owned())
+                    .collect();
+                features.sort();
+                Some(AnchorLang { version, features })
+            })
+            .max_by_key(|it| parse_version(&it.version))
+    }
+
+    /// Why this version rejects the constraint `kind`, if it does.
+    pub(super) fn unsupported(&self, kind: &str) -> Option<String> {
+        let matches = |key: &str| match key.strip_suffix("::") {
+            Some(namespace) => kind.split_once("::").is_some_and(|(it, _)| it == namespace),
+            None => kind == key,
+        };
+        if let Some(&(_, (major, minor, patch))) = ADDED_IN.iter().find(|(key, _)| matches(key))
+            && parse_version(&self.version).is_some_and(|it| it < (major, minor, patch))
+        {
+            return Some(format!(
+                "anchor-lang {} only supports it from {major}.{minor}.{patch}",
+                self.version
+            ));
+        }
+        let (_, feature) = GATED.iter().find(|(key, _)| matches(key))?;
+        (!self.features.iter().any(|it| it == feature)).then(|| {
+            format!("anchor-lang {} only supports it with its `{feature}` feature", self.version)
+        })
+    }
+}
+
+/// The numeric part of a semver version, ignoring pre-release and build metadata.
+fn parse_version(version: &str) -> Option<Version> {
+    let core = version.split(['-', '+']).next()?;
+    let mut parts = core.split('.').map(|it| it.parse().ok());
+    Some((parts.next()??, parts.next()??, parts.next().flatten().unwrap_or(0)))
+}
@@ -0,0 +1,349 @@
+//! Renders the code graph and the findings on it as a report to prepare an audit with, a
+//! markdown document or a self-contained HTML page.
+//!
+//! Every instruction gets a section with tables of its accounts and their constraints, the PDAs
+//! among them, the CPIs it reaches and the findings in its code. A finding belongs to an
+//! instruction when it's on a line of its accounts struct or in a function the instruction
+//! reaches, as far as the start lines of the functions tell; the others are listed at the end.
+//! Locations link to `file#Lline`, relative to the project root like every path of the outputs.
+//!
+//! The HTML page embeds the data and the source around every location it shows, so it can be
+//! shared with people not running the tool. It cross-links the instructions and account structs,
+//! draws the PDAs with what their seeds derive from, and charts the findings and constraints.
+
+use std::{collections::BTreeMap, fmt::Write as _};
+
+use anyhow::Result;
+use ide_db::base_db::SourceDatabase;
+use itertools::Itertools;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+
+use crate::cli::{
+    code_graph::{AccountStruct, CodeGraph, Instruction, LoadedProject, project_files},
+    cpi_calls::CpiCall,
+    findings::{Finding, Severity},
+    flags,
+    function_analyzer::convert_to_relative_path,
+    pdas::Pda,
+    programs::AnchorProgram,
+};
+
+const HTML_TEMPLATE: &str = include_str!("audit_report.html");
+
+/// Lines of source shown before and after a location in the HTML report.
+const SNIPPET_CONTEXT: u32 = 3;
+
+#[derive(Serialize)]
+struct HtmlReport<'a> {
+    programs: &'a [AnchorProgram],
+    instructions: Vec<HtmlInstruction<'a>>,
+    account_structs: &'a [AccountStruct],
+    pdas: &'a [Pda],
+    cpi_calls: &'a [CpiCall],
+    findings: &'a [&'a Finding],
+    /// The source around every location of the report, by `file:line`.
+    snippets: BTreeMap<String, Snippet>,
+}
+
+#[derive(Serialize)]
+struct HtmlInstruction<'a> {
+    #[serde(flatten)]
+    instruction: &'a Instruction,
+    /// Indices into the findings of the report.
+    findings: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct Snippet {
+    /// The line number of the first line.
+    start: u32,
+    lines: Vec<String>,
+}
+
+/// Renders `graph` and `findings` as the markdown audit report.
+pub(super) fn markdown(graph: &CodeGraph, findings: &[Finding]) -> String {
+    let _p = tracing::info_span!("markdown_report").entered();
+    let findings = by_severity(findings);
+
+    let mut out = String::from("# Audit report\n\n");
+    let programs = graph.programs.iter().map(|it| format!("`{}`", it.name)).join(", ");
+    if !programs.is_empty() {
+        let _ = writeln!(out, "- Programs: {programs}");
+    }
+    let _ = writeln!(out, "- Instructions: {}", graph.instructions.len());
+    let _ = writeln!(out, "- Account structs: {}", graph.account_structs.len());
+    let _ = writeln!(out, "- CPIs: {}", graph.cpi_calls.len());
+    let counts = [Severity::Error, Severity::Warning, Severity::Info]
+        .map(|severity| {
+            let count = findings.iter().filter(|it| it.severity == severity).count();
+            format!("{count} {}", severity_name(severity))
+        })
+        .join(", ");
+    let _ = writeln!(out, "- Findings: {counts}");
+
+    let mut reported = vec![false; findings.len()];
+    let owned = instruction_findings(graph, &findings);
+    let mut instructions = graph.instructions.iter().zip(owned).collect_vec();
+    instructions.sort_by_key(|(it, _)| it.path());
+    for (instruction, owned) in instructions {
+        let path = instruction.path();
+        let strukt = graph.account_structs.iter().find(|it| it.instructions.contains(&path));
+        for &index in &owned {
+            reported[index] = true;
+        }
+        let owned = owned.into_iter().map(|index| findings[index]).collect_vec();
+        instruction_section(&mut out, graph, instruction, strukt, &owned);
+    }
+
+    let others = (0..findings.len()).filter(|&it| !reported[it]).map(|it| findings[it]);
+    let others = others.collect_vec();
+    if !others.is_empty() {
+        out.push_str("\n## Other findings\n");
+        findings_table(&mut out, &others);
+    }
+    out
+}
+
+/// Renders `graph` and `findings` as the HTML audit report, with the source around their
+/// locations redacted like the outputs.
+pub(super) fn html(
+    project: &LoadedProject,
+    graph: &CodeGraph,
+    findings: &[Finding],
+    redaction: &flags::Redaction,
+) -> Result<String> {
+    let _p = tracing::info_span!("html_report").entered();
+    let findings = by_severity(findings);
+    let owned = instruction_findings(graph, &findings);
+    let report = HtmlReport {
+        programs: &graph.programs,
+        instructions: (graph.instructions.iter().zip(owned))
+            .map(|(instruction, findings)| HtmlInstruction { instruction, findings })
+            .collect(),
+        account_structs: &graph.account_structs,
+        pdas: &graph.pdas,
+        cpi_calls: &graph.cpi_calls,
+        findings: &findings,
+        snippets: snippets(project, graph, &findings, redaction),
+    };
+    // Keeps a `</script>` in the source from ending the script embedding it.
+    let json = serde_json::to_string(&report)?.replace("</", "<\\/");
+    Ok(HTML_TEMPLATE.replace("/*REPORT*/null", &json))
+}
+
+/// `findings` with the most severe first, then by location.
+fn by_severity(findings: &[Finding]) -> Vec<&Finding> {
+    let mut findings = findings.iter().collect_vec();
+    findings.sort_by(|a, b| {
+        b.severity.cmp(&a.severity).then_with(|| (&a.file, a.line).cmp(&(&b.file, b.line)))
+    });
+    findings
+}
+
+/// The indices into `findings` of the findings of every instruction, in the order of
+/// `graph.instructions`.
+fn instruction_findings(graph: &CodeGraph, findings: &[&Finding]) -> Vec<Vec<usize>> {
+    let reach = graph.instruction_reach();
+    graph
+        .instructions
+        .iter()
+        .map(|instruction| {
+            let path = instruction.path();
+            let functions = reach.iter().find(|(it, _)| *it == path).map(|(_, it)| it);
+            let strukt = graph.account_structs.iter().find(|it| it.instructions.contains(&path));
+            (0..findings.len())
+                .filter(|&index| {
+                    let finding = findings[index];
+                    let (Some(file), Some(line)) = (&finding.file, finding.line) else {
+                        return false;
+                    };
+                    let in_struct = strukt.is_some_and(|it| {
+                        it.file == *file
+                            && (it.line == line || it.fields.iter().any(|field| field.line == line))
+                    });
+                    let in_function = functions.is_some_and(|functions| {
+                        graph
+                            .function_enclosing(file, line)
+                            .is_some_and(|it| functions.contains(&it))
+                    });
+                    in_struct || in_function
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The source around the findings and their related places, the handlers of the instructions,
+/// and the whole of every account struct.
+fn snippets(
+    project: &LoadedProject,
+    graph: &CodeGraph,
+    findings: &[&Finding],
+    redaction: &flags::Redaction,
+) -> BTreeMap<String, Snippet> {
+    // The location, first and last line of every snippet, by file.
+    let mut wanted: FxHashMap<&str, Vec<(u32, u32, u32)>> = FxHashMap::default();
+    let mut around = |file, line: u32| {
+        let range = (line, line.saturating_sub(SNIPPET_CONTEXT).max(1), line + SNIPPET_CONTEXT);
+        wanted.entry(file).or_default().push(range);
+    };
+    for finding in findings {
+        if let (Some(file), Some(line)) = (&finding.file, finding.line) {
+            around(file.as_str(), line);
+        }
+        for related in &finding.related {
+            around(&related.file, related.line);
+        }
+    }
+    for instruction in &graph.instructions {
+        around(&instruction.file, instruction.line);
+    }
+    // Last, so a struct shows whole even with a finding on its first line.
+    for strukt in &graph.account_structs {
+        let end = strukt.fields.iter().map(|it| it.line).max().unwrap_or(strukt.line) + 1;
+        wanted.entry(&strukt.file).or_default().push((strukt.line, strukt.line, end));
+    }
+
+    let mut snippets = BTreeMap::new();
+    for (file_id, file_path) in project_files(project) {
+        let relative_path = convert_to_relative_path(&file_path, &project.project_root);
+        let Some(ranges) = wanted.get(relative_path.as_str()) else { continue };
+        let text = redaction.source(project.db.file_text(file_id).text(&project.db));
+        let lines = text.lines().collect_vec();
+        for &(line, start, end) in ranges {
+            let lines = lines
+                [(start as usize - 1).min(lines.len())..(end as usize).min(lines.len())]
+                .iter()
+                .map(|it| (*it).to_owned())
+                .collect();
+            snippets.insert(format!("{relative_path}:{line}"), Snippet { start, lines });
+        }
+    }
+    snippets
+}
+
+fn instruction_section(
+    out: &mut String,
+    graph: &CodeGraph,
+    instruction: &Instruction,
+    strukt: Option<&AccountStruct>,
+    findings: &[&Finding],
+) {
+    let path = instruction.path();
+    let _ = writeln!(out, "\n## `{path}`\n");
+    let _ =
+        writeln!(out, "- Handler: {}", location(Some(&instruction.file), Some(instruction.line)));
+    if !instruction.params.is_empty() {
+        let params =
+            instruction.params.iter().map(|it| format!("`{}: {}`", it.name, it.ty)).join(", ");
+        let _ = writeln!(out, "- Arguments: {params}");
+    }
+    if !instruction.access_control.is_empty() {
+        let guards =
+            instruction.access_control.iter().map(|it| format!("`{}`", it.call)).join(", ");
+        let _ = writeln!(out, "- Access control: {guards}");
+    }
+
+    if let Some(strukt) = strukt {
+        let _ = writeln!(
+            out,
+            "\n### Accounts\n\n`{}`, {}\n\n| Account | Type | Constraints | Location |\n| --- | --- | --- | --- |",
+            strukt.name,
+            location(Some(&strukt.file), Some(strukt.line))
+        );
+        for field in &strukt.fields {
+            let constraints = field.constraints.iter().map(|it| code(&it.to_string())).join("<br>");
+            let _ = writeln!(
+                out,
+                "| `{}` | {} | {constraints} | {} |",
+                field.name,
+                code(&field.ty),
+                location(Some(&strukt.file), Some(field.line))
+            );
+        }
+
+        let pdas = graph.pdas.iter().filter(|it| it.account_struct == strukt.path()).collect_vec();
+        if !pdas.is_empty() {
+            out.push_str(
+                "\n### PDAs\n\n| Account | Seeds | Bump | Program |\n| --- | --- | --- | --- |\n",
+            );
+            for pda in pdas {
+                let seeds = pda.seeds.iter().map(|it| code(it)).join("<br>");
+                let bump = pda.bump.as_deref().map_or_else(|| "canonical".to_owned(), code);
+                let program =
+                    pda.program.as_deref().map_or_else(|| "this program".to_owned(), code);
+                let _ = writeln!(out, "| `{}` | {seeds} | {bump} | {program} |", pda.field);
+            }
+        }
+    }
+
+    let cpis = graph.cpi_calls.iter().filter(|it| it.instructions.contains(&path)).collect_vec();
+    if !cpis.is_empty() {
+        out.push_str(
+            "\n### CPIs\n\n| Call | Program | Accounts | Signer seeds | Location |\n| --- | --- | --- | --- | --- |\n",
+        );
+        for cpi in cpis {
+            let call = cpi.target.as_deref().map_or_else(|| format!("`{:?}`", cpi.kind), code);
+            let program = cpi.program.as_deref().map_or_else(|| "unknown".to_owned(), code);
+            let accounts = cpi.accounts.iter().map(|it| code(&it.account)).join(", ");
+            let seeds = cpi.signer_seeds.as_deref().map_or_else(String::new, code);
+            let _ = writeln!(
+                out,
+                "| {call} | {program} | {accounts} | {seeds} | {} |",
+                location(Some(&cpi.file), Some(cpi.line))
+            );
+        }
+    }
+
+    if !findings.is_empty() {
+        out.push_str("\n### Findings\n");
+        findings_table(out, findings);
+    }
+}
+
+fn findings_table(out: &mut String, findings: &[&Finding]) {
+    out.push_str("\n| Severity | Rule | Message | Location |\n| --- | --- | --- | --- |\n");
+    for finding in findings {
+        let mut message = table_cell(&finding.message);
+        for related in &finding.related {
+            let place = location(Some(&related.file), Some(related.line));
+            let _ = write!(message, "<br>{}: {place}", table_cell(&related.message));
+        }
+        let _ = writeln!(
+            out,
+            "| {} | `{}` | {message} | {} |",
+            severity_name(finding.severity),
+            finding.rule,
+            location(finding.file.as_ref(), finding.line)
+        );
+    }
+}
+
+/// A link to `file:line`, relative to the project root.
+fn location(file: Option<&String>, line: Option<u32>) -> String {
+    match (file, line) {
+        (Some(file), Some(line)) => format!("[{file}:{line}]({file}#L{line})"),
+        (Some(file), None) => format!("[{file}]({file})"),
+        (None, _) => String::new(),
+    }
+}
+
+/// `text` as inline code in a table cell, on a single line.
+fn code(text: &str) -> String {
+    let text = text.split_whitespace().join(" ");
+    format!("`{}`", table_cell(&text))
+}
+
+/// Escapes pipes, which would otherwise end the cell in a table.
+fn table_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Info => "info",
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
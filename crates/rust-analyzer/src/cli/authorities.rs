@@ -0,0 +1,191 @@
+//! Who can execute each instruction: the signers of its accounts struct and the authority their
+//! key is checked against.
+//!
+//! A signer is tied to a field of a state account by `has_one`, by an `address = state.field` or by
+//! a `constraint` comparing both keys, like `global.global_authority` for `admin`. The account
+//! holding that field can itself be pinned by the `has_one` of another account, which makes a
+//! chain from the signer up to the account the instruction trusts. Signers nothing ties to a key
+//! can be anyone.
+
+use serde::Serialize;
+use syntax::{AstNode, Edition, SourceFile, ast};
+
+use crate::cli::{
+    code_graph::{AccountField, AccountStruct, CodeGraph, crate_of, qualify},
+    constraint_expr::ConstraintExpr,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum AuthorityKind {
+    /// The key stored in a field of a state account.
+    StateField,
+    /// A fixed key given to `address`.
+    Address,
+    /// A signer whose key nothing checks.
+    AnySigner,
+    /// Instructions without any signer.
+    Anyone,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct Authority {
+    pub(super) kind: AuthorityKind,
+    /// The state field like `pump::state::Global.global_authority`, or the `address` value.
+    pub(super) name: String,
+    pub(super) grants: Vec<Grant>,
+}
+
+/// An instruction an authority can execute.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct Grant {
+    pub(super) instruction: String,
+    /// The signer field of its accounts struct, none for `Anyone`.
+    pub(super) signer: Option<String>,
+    /// The account holding the authority field and the `has_one` pinning it, from the signer up,
+    /// like `["global", "pool.global"]`.
+    pub(super) chain: Vec<String>,
+}
+
+/// A field `target` of a struct whose key has to equal `field` of the state stored in `account`.
+struct Link<'a> {
+    account: &'a AccountField,
+    field: String,
+    target: String,
+}
+
+/// Groups the instructions of `graph` by the authorities signing them.
+pub(super) fn extract_authorities(graph: &CodeGraph) -> Vec<Authority> {
+    let _p = tracing::info_span!("extract_authorities").entered();
+    let mut authorities: Vec<Authority> = Vec::new();
+    let mut grant = |kind, name: String, grant: Grant| match authorities
+        .iter_mut()
+        .find(|it| it.kind == kind && it.name == name)
+    {
+        Some(authority) => authority.grants.push(grant),
+        None => authorities.push(Authority { kind, name, grants: vec![grant] }),
+    };
+    for instruction in &graph.instructions {
+        let Some(strukt) = graph
+            .account_structs
+            .iter()
+            .find(|it| instruction.accounts_struct.as_ref() == Some(&it.path()))
+        else {
+            continue;
+        };
+        let links = links(strukt);
+        let signers: Vec<_> = strukt.fields.iter().filter(|it| is_signer(it)).collect();
+        if signers.is_empty() {
+            let chain = Vec::new();
+            let to = Grant { instruction: instruction.path(), signer: None, chain };
+            grant(AuthorityKind::Anyone, "anyone".to_owned(), to);
+        }
+        for signer in signers {
+            let to = |chain| Grant {
+                instruction: instruction.path(),
+                signer: Some(signer.name.clone()),
+                chain,
+            };
+            let mut tied = false;
+            for link in links.iter().filter(|it| it.target == signer.name) {
+                let state = link.account.account_type.as_deref().unwrap_or_default();
+                let name = format!("{}.{}", state_path(graph, strukt, state), link.field);
+                grant(AuthorityKind::StateField, name, to(chain(&links, link.account)));
+                tied = true;
+            }
+            let address = signer.constraints.iter().find(|it| it.kind == "address");
+            if let Some(address) = address.and_then(|it| it.value.clone())
+                && !tied
+            {
+                grant(AuthorityKind::Address, address, to(Vec::new()));
+                tied = true;
+            }
+            if !tied {
+                grant(AuthorityKind::AnySigner, "any signer".to_owned(), to(Vec::new()));
+            }
+        }
+    }
+    authorities.sort_by(|a, b| (a.kind, &a.name).cmp(&(b.kind, &b.name)));
+    authorities
+}
+
+/// The keys of fields of `strukt` its constraints tie to fields of its state accounts.
+fn links(strukt: &AccountStruct) -> Vec<Link<'_>> {
+    let account =
+        |name: &str| strukt.fields.iter().find(|it| it.name == name && it.account_type.is_some());
+    let mut links = Vec::new();
+    for field in &strukt.fields {
+        for constraint in &field.constraints {
+            let Some(value) = &constraint.value else { continue };
+            match constraint.kind.as_str() {
+                "has_one" if field.account_type.is_some() => {
+                    links.push(Link { account: field, field: value.clone(), target: value.clone() })
+                }
+                "address" => {
+                    let address = ConstraintExpr::parse(value);
+                    if let Some((holder, state_field)) =
+                        address.as_ref().and_then(|it| it.field_read())
+                        && let Some(account) = account(holder)
+                    {
+                        let target = field.name.clone();
+                        links.push(Link { account, field: state_field.to_owned(), target });
+                    }
+                }
+                "constraint" => {
+                    let Some(expr) = &constraint.expr else { continue };
+                    let equalities =
+                        expr.required_comparisons().into_iter().filter(|it| it.0 == "==");
+                    for (_, lhs, rhs) in equalities {
+                        for (read, key) in [(lhs, rhs), (rhs, lhs)] {
+                            if let Some((holder, state_field)) = read.field_read()
+                                && let Some(account) = account(holder)
+                                && let Some(target) = key.key_of()
+                            {
+                                let (field, target) = (state_field.to_owned(), target.to_owned());
+                                links.push(Link { account, field, target });
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    links
+}
+
+/// The accounts pinning `account` through their `has_one`, starting with `account` itself.
+fn chain(links: &[Link<'_>], account: &AccountField) -> Vec<String> {
+    let mut chain = vec![account.name.clone()];
+    let mut current = account;
+    while let Some(link) = links.iter().find(|it| it.target == current.name) {
+        let step = format!("{}.{}", link.account.name, link.field);
+        if chain.contains(&step) || link.account.name == account.name {
+            break;
+        }
+        chain.push(step);
+        current = link.account;
+    }
+    chain
+}
+
+/// The path of the state account `ty` of a field of `strukt`, preferring one of the same crate.
+pub(super) fn state_path(graph: &CodeGraph, strukt: &AccountStruct, ty: &str) -> String {
+    let name = ty.rsplit("::").next().unwrap_or(ty);
+    let candidates = || graph.state_accounts.iter().filter(|it| it.name == name);
+    candidates()
+        .find(|it| crate_of(&it.module) == crate_of(&strukt.module))
+        .or_else(|| candidates().next())
+        .map_or_else(|| ty.to_owned(), |it| qualify(&it.module, &it.name))
+}
+
+/// Whether the field has to sign, being a `Signer` or constrained with `signer`.
+pub(super) fn is_signer(field: &AccountField) -> bool {
+    let file = SourceFile::parse(&format!("type T = {};", field.ty), Edition::CURRENT).tree();
+    let signer_type = file
+        .syntax()
+        .descendants()
+        .filter_map(ast::PathSegment::cast)
+        .any(|it| it.name_ref().is_some_and(|it| it.text() == "Signer"));
+    signer_type || field.constraints.iter().any(|it| it.kind == "signer")
+}
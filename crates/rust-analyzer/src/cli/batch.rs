@@ -0,0 +1,153 @@
+//! Batch mode of `function-analyzer`: many projects, like all the repositories of an
+//! organization, analyzed in one run.
+//!
+//! The projects share the toolchain of the first one, whose sysroot and proc-macro server are
+//! only looked up once. Each project is written to a file of its own in the `--output` directory,
+//! named after the project, and `projects.json` there maps every project to its file or to the
+//! error that stopped its analysis. A failing project doesn't stop the others.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use load_cargo::ProcMacroServerChoice;
+use project_model::{CargoConfig, ProjectWorkspace, RustLibSource};
+use rustc_hash::FxHashSet;
+use serde::Serialize;
+use vfs::{AbsPath, AbsPathBuf};
+
+use crate::cli::flags::{self, CallFormat};
+
+/// Name of the file mapping the projects to their outputs.
+const INDEX: &str = "projects.json";
+
+/// The sysroot and proc-macro server of the first project analyzed, reused for the others.
+#[derive(Default)]
+pub(super) struct Toolchain {
+    /// The sysroot and its library sources.
+    sysroot: Option<(AbsPathBuf, Option<AbsPathBuf>)>,
+    proc_macro_srv: Option<AbsPathBuf>,
+}
+
+impl Toolchain {
+    /// Points `cargo_config` to the shared sysroot, once there is one.
+    pub(super) fn configure(&self, cargo_config: &mut CargoConfig) {
+        if let Some((sysroot, sysroot_src)) = &self.sysroot {
+            cargo_config.sysroot = Some(RustLibSource::Path(sysroot.clone()));
+            cargo_config.sysroot_src = sysroot_src.clone();
+        }
+    }
+
+    /// Keeps the toolchain of `ws` when it's the first, and has the proc-macro server of the
+    /// sysroot started from the binary found for it rather than searched for again.
+    pub(super) fn share(&mut self, ws: &ProjectWorkspace, server: &mut ProcMacroServerChoice) {
+        if self.sysroot.is_none()
+            && let Some(sysroot) = ws.sysroot.root()
+        {
+            let sysroot_src = ws.sysroot.rust_lib_src_root().map(AbsPath::to_path_buf);
+            self.sysroot = Some((sysroot.to_path_buf(), sysroot_src));
+        }
+        if *server != ProcMacroServerChoice::Sysroot {
+            return;
+        }
+        if self.proc_macro_srv.is_none() {
+            self.proc_macro_srv = ws.find_sysroot_proc_macro_srv().and_then(Result::ok);
+        }
+        if let Some(path) = &self.proc_macro_srv {
+            *server = ProcMacroServerChoice::Explicit(path.clone());
+        }
+    }
+}
+
+/// A line of `projects.json`.
+#[derive(Serialize)]
+struct Entry {
+    project: String,
+    /// The file in the output directory the project was written to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// The projects given as `paths` followed by those listed in `projects_file`.
+pub(super) fn projects(paths: &[PathBuf], projects_file: Option<&Path>) -> Result<Vec<PathBuf>> {
+    let mut projects = paths.to_vec();
+    if let Some(file) = projects_file {
+        let list = fs::read_to_string(file)
+            .with_context(|| format!("failed to read `{}`", file.display()))?;
+        let dir = file.parent().unwrap_or(Path::new(""));
+        projects.extend(
+            list.lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| dir.join(line)),
+        );
+    }
+    if projects.is_empty() {
+        anyhow::bail!("no project to analyze, pass its path or `--projects-file`");
+    }
+    Ok(projects)
+}
+
+/// Analyzes every project of `projects` in turn, writing them to the `--output` directory.
+pub(super) fn run(cmd: &flags::FunctionAnalyzer, projects: &[PathBuf]) -> Result<()> {
+    let Some(dir) = &cmd.output else {
+        anyhow::bail!("several projects are written to an `--output` directory");
+    };
+    if cmd.tui {
+        anyhow::bail!("`--tui` browses a single project");
+    }
+    fs::create_dir_all(dir)?;
+    let extension = match cmd.format.unwrap_or_default() {
+        _ if cmd.metrics => "json",
+        CallFormat::Text => "txt",
+        CallFormat::Json => "json",
+        CallFormat::Dot => "dot",
+        CallFormat::GraphMl => "graphml",
+        CallFormat::Csv => "csv",
+        CallFormat::Ndjson => "ndjson",
+    };
+
+    let mut toolchain = Toolchain::default();
+    let mut names = FxHashSet::default();
+    let mut index = Vec::with_capacity(projects.len());
+    for (position, project) in projects.iter().enumerate() {
+        eprintln!("Project {}/{}: {}", position + 1, projects.len(), project.display());
+        let file = format!("{}.{extension}", unique_name(project, &mut names));
+        let result = cmd.analyze(project, &Some(dir.join(&file)), &mut toolchain);
+        if let Err(error) = &result {
+            eprintln!("Failed to analyze {}: {error:#}", project.display());
+        }
+        index.push(Entry {
+            project: project.display().to_string(),
+            error: result.as_ref().err().map(|error| format!("{error:#}")),
+            output: result.ok().map(|()| file),
+        });
+    }
+    let mut writer = fs::File::create(dir.join(INDEX))?;
+    serde_json::to_writer_pretty(&mut writer, &index)?;
+    writeln!(writer)?;
+
+    let failed = index.iter().filter(|entry| entry.error.is_some()).count();
+    if failed > 0 {
+        anyhow::bail!("{failed} of {} projects failed, see `{INDEX}`", projects.len());
+    }
+    Ok(())
+}
+
+/// The name of the directory of `project`, numbered when another project already has it.
+fn unique_name(project: &Path, names: &mut FxHashSet<String>) -> String {
+    let path = fs::canonicalize(project).unwrap_or_else(|_| project.to_owned());
+    let name = path.file_name().map_or("project".into(), |it| it.to_string_lossy());
+    let mut unique = name.to_string();
+    let mut count = 1;
+    while !names.insert(unique.clone()) {
+        count += 1;
+        unique = format!("{name}-{count}");
+    }
+    unique
+}
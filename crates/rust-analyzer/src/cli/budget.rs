@@ -0,0 +1,141 @@
+//! The `--max-edges` and `--max-nodes` budget, bounding the analysis of giant workspaces.
+//!
+//! Functions are analyzed in their usual order, a file at a time, until a call would go over the
+//! budget. The analysis stops there, so a workspace always truncates to the same graph, and what
+//! was left out is summed up by module with the `--prune` decisions of the output.
+
+use anyhow::Result;
+use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::cli::{
+    call_graphml::crate_name,
+    function_analyzer::{CallRelation, FunctionInfo},
+    prune::{FunctionKey, Keys},
+};
+
+/// Modules listed by the report, the others are only counted.
+const REPORTED_MODULES: usize = 10;
+
+pub(super) struct Budget {
+    max_edges: Option<usize>,
+    max_nodes: Option<usize>,
+    incoming: bool,
+    keys: Keys,
+    edges: FxHashSet<(FunctionKey, FunctionKey)>,
+    nodes: FxHashSet<FunctionKey>,
+    /// The limit the analysis stopped at.
+    exhausted: Option<String>,
+    /// Per module, the calls dropped and the functions left unanalyzed.
+    dropped: FxHashMap<String, (usize, usize)>,
+}
+
+impl Budget {
+    /// The budget of the analysis of `functions`, none when it's unlimited.
+    pub(super) fn new(
+        functions: &[FunctionInfo],
+        max_edges: Option<usize>,
+        max_nodes: Option<usize>,
+        incoming: bool,
+    ) -> Option<Budget> {
+        if max_edges.is_none() && max_nodes.is_none() {
+            return None;
+        }
+        Some(Budget {
+            max_edges,
+            max_nodes,
+            incoming,
+            keys: Keys::new(functions),
+            edges: FxHashSet::default(),
+            nodes: FxHashSet::default(),
+            exhausted: None,
+            dropped: FxHashMap::default(),
+        })
+    }
+
+    /// The calls of `functions` that fit in the budget, analyzed by `calls_of` a file at a time.
+    /// Once the budget is exhausted the remaining functions are skipped.
+    pub(super) fn calls(
+        &mut self,
+        functions: &[FunctionInfo],
+        mut calls_of: impl FnMut(&[FunctionInfo]) -> Result<Vec<CallRelation>>,
+    ) -> Result<Vec<CallRelation>> {
+        let mut kept = Vec::new();
+        for file_functions in functions.chunk_by(|a, b| a.file_path == b.file_path) {
+            if self.exhausted.is_some() {
+                for function in file_functions {
+                    self.dropped.entry(module(function)).or_default().1 += 1;
+                }
+                continue;
+            }
+            for relation in calls_of(file_functions)? {
+                if self.fits(&relation) {
+                    kept.push(relation);
+                } else {
+                    let analyzed = if self.incoming { &relation.callee } else { &relation.caller };
+                    self.dropped.entry(module(analyzed)).or_default().0 += 1;
+                }
+            }
+        }
+        Ok(kept)
+    }
+
+    /// Whether `relation` stays within the budget, counting it in when it does. Further call
+    /// sites of a kept calling pair cost nothing.
+    fn fits(&mut self, relation: &CallRelation) -> bool {
+        let edge = (self.keys.key(&relation.caller), self.keys.key(&relation.callee));
+        if self.edges.contains(&edge) {
+            return true;
+        }
+        if self.exhausted.is_some() {
+            return false;
+        }
+        let new_nodes =
+            [&edge.0, &edge.1].into_iter().unique().filter(|it| !self.nodes.contains(*it)).count();
+        if let Some(max) = self.max_edges.filter(|&max| self.edges.len() >= max) {
+            self.exhausted = Some(format!("{max} edges"));
+            return false;
+        }
+        if let Some(max) = self.max_nodes.filter(|&max| self.nodes.len() + new_nodes > max) {
+            self.exhausted = Some(format!("{max} nodes"));
+            return false;
+        }
+        self.nodes.insert(edge.0.clone());
+        self.nodes.insert(edge.1.clone());
+        self.edges.insert(edge);
+        true
+    }
+
+    /// Describes what the budget left out, for the output metadata. Empty when everything fit.
+    pub(super) fn report(&self) -> Vec<String> {
+        let Some(limit) = &self.exhausted else { return Vec::new() };
+        let (calls, functions) = self
+            .dropped
+            .values()
+            .fold((0, 0), |(calls, functions), &(c, f)| (calls + c, functions + f));
+        let mut report = vec![format!(
+            "stopped at the budget of {limit}: dropped {calls} calls and skipped {functions} functions"
+        )];
+        let modules = self
+            .dropped
+            .iter()
+            .sorted_by(|a, b| (b.1.0 + b.1.1).cmp(&(a.1.0 + a.1.1)).then_with(|| a.0.cmp(b.0)));
+        for (module, (calls, functions)) in modules.clone().take(REPORTED_MODULES) {
+            report.push(format!(
+                "truncated {module}: dropped {calls} calls and skipped {functions} functions"
+            ));
+        }
+        if let Some(others) = modules.len().checked_sub(REPORTED_MODULES).filter(|&it| it > 0) {
+            report.push(format!("truncated {others} more modules"));
+        }
+        report
+    }
+}
+
+/// The module of `function`, or its crate for functions of unknown modules.
+fn module(function: &FunctionInfo) -> String {
+    match function.module.as_str() {
+        "" => crate_name("", &function.file_path).to_owned(),
+        module => module.to_owned(),
+    }
+}
@@ -0,0 +1,218 @@
+//! Lists the code that runs at compile time: every package of the dependency graph with a build
+//! script or a proc-macro library, and the project items invoking those proc-macros.
+
+use std::fs;
+
+use anyhow::Result;
+use hir::{Crate, Macro, PathResolution, Semantics};
+use itertools::Itertools;
+use project_model::{CargoWorkspace, Package, ProjectWorkspaceKind, TargetKind};
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use syntax::{
+    AstNode, SyntaxNode,
+    ast::{self, HasName},
+};
+
+use crate::cli::{
+    code_graph::{LoadOptions, LoadedProject, project_files},
+    flags,
+    function_analyzer::convert_to_relative_path,
+};
+
+impl flags::BuildInventory {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("build_inventory", path = %self.path.display()).entered();
+        eprintln!("Loading workspace...");
+        let project = LoadedProject::load(
+            &self.path,
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+        let ProjectWorkspaceKind::Cargo { cargo, error, .. } = &project.workspace.kind else {
+            anyhow::bail!("the build inventory requires a Cargo workspace");
+        };
+        if let Some(error) = error {
+            eprintln!("warning: dependencies could not be resolved, only listing members: {error}");
+        }
+
+        eprintln!("Collecting build scripts and proc-macros...");
+        let mut packages = compile_time_packages(cargo);
+        for (package, invocation) in macro_invocations(&project, cargo) {
+            if let Some(entry) = packages.iter_mut().find(|it| it.package == package) {
+                entry.inventory.invocations.push(invocation);
+            }
+        }
+        let packages = packages.into_iter().map(|it| it.inventory).collect_vec();
+        eprintln!(
+            "Found {} build scripts and {} proc-macro crates",
+            packages.iter().filter(|it| it.build_script.is_some()).count(),
+            packages.iter().filter(|it| it.proc_macro).count()
+        );
+
+        let json = serde_json::to_string_pretty(&packages)?;
+        match &self.output {
+            Some(path) => fs::write(path, json)?,
+            None => println!("{json}"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PackageInventory {
+    name: String,
+    version: String,
+    /// Whether the package is a member of the analyzed workspace rather than a dependency.
+    member: bool,
+    /// Path of the build script, relative to the package manifest.
+    build_script: Option<String>,
+    proc_macro: bool,
+    /// Packages depending on this one directly.
+    dependents: Vec<String>,
+    /// Project items invoking a proc-macro of this package.
+    invocations: Vec<Invocation>,
+}
+
+#[derive(Debug, Serialize)]
+struct Invocation {
+    #[serde(rename = "macro")]
+    macro_name: String,
+    /// `derive`, `attribute` or `function_like`.
+    kind: &'static str,
+    item: String,
+    file: String,
+    line: u32,
+}
+
+struct CompileTimePackage {
+    package: Package,
+    inventory: PackageInventory,
+}
+
+fn compile_time_packages(cargo: &CargoWorkspace) -> Vec<CompileTimePackage> {
+    let mut dependents: FxHashMap<Package, Vec<String>> = FxHashMap::default();
+    for package in cargo.packages() {
+        for dependency in &cargo[package].dependencies {
+            dependents.entry(dependency.pkg).or_default().push(cargo[package].name.clone());
+        }
+    }
+
+    cargo
+        .packages()
+        .filter_map(|package| {
+            let data = &cargo[package];
+            let targets = || data.targets.iter().map(|&target| &cargo[target]);
+            let build_script = targets().find(|it| it.kind == TargetKind::BuildScript).map(|it| {
+                let dir = data.manifest.parent();
+                it.root
+                    .strip_prefix(dir)
+                    .map_or_else(|| it.root.to_string(), |it| it.as_str().to_owned())
+            });
+            let proc_macro = targets().any(|it| it.kind.is_proc_macro());
+            if build_script.is_none() && !proc_macro {
+                return None;
+            }
+            let inventory = PackageInventory {
+                name: data.name.clone(),
+                version: data.version.to_string(),
+                member: data.is_member,
+                build_script,
+                proc_macro,
+                dependents: dependents
+                    .get(&package)
+                    .map(|it| it.iter().cloned().sorted().dedup().collect())
+                    .unwrap_or_default(),
+                invocations: Vec::new(),
+            };
+            Some(CompileTimePackage { package, inventory })
+        })
+        .sorted_by(|a, b| {
+            (&a.inventory.name, &a.inventory.version)
+                .cmp(&(&b.inventory.name, &b.inventory.version))
+        })
+        .collect()
+}
+
+/// Every proc-macro invocation of the project, with the package defining the macro.
+fn macro_invocations(
+    project: &LoadedProject,
+    cargo: &CargoWorkspace,
+) -> Vec<(Package, Invocation)> {
+    let analysis = project.analysis();
+    let sema = Semantics::new(&project.db);
+    let package_of = |krate: Crate| {
+        let root = project.vfs.file_path(krate.root_file(&project.db));
+        Some(cargo[cargo.target_by_root(root.as_path()?)?].package)
+    };
+
+    let mut invocations = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let relative_path = convert_to_relative_path(&file_path, &project.project_root);
+        let mut push = |mac: Macro, kind, node: &SyntaxNode| {
+            if !mac.is_proc_macro() {
+                return;
+            }
+            let Some(package) = package_of(mac.module(&project.db).krate()) else { return };
+            let item = node
+                .ancestors()
+                .filter_map(ast::Item::cast)
+                .find(|it| !matches!(it, ast::Item::MacroCall(_)));
+            invocations.push((
+                package,
+                Invocation {
+                    macro_name: mac.name(&project.db).as_str().to_owned(),
+                    kind,
+                    item: item.as_ref().map(item_name).unwrap_or_default(),
+                    file: relative_path.clone(),
+                    line: line_index.line_col(node.text_range().start()).line + 1,
+                },
+            ));
+        };
+
+        for node in file.syntax().descendants() {
+            if let Some(attr) = ast::Attr::cast(node.clone()) {
+                if let Some(derives) = sema.resolve_derive_macro(&attr) {
+                    for mac in derives.into_iter().flatten() {
+                        push(mac, "derive", &node);
+                    }
+                } else if let Some(PathResolution::Def(hir::ModuleDef::Macro(mac))) =
+                    attr.path().and_then(|path| sema.resolve_path(&path))
+                {
+                    push(mac, "attribute", &node);
+                }
+            } else if let Some(call) = ast::MacroCall::cast(node.clone())
+                && let Some(mac) = sema.resolve_macro_call(&call)
+            {
+                push(mac, "function_like", &node);
+            }
+        }
+    }
+    invocations
+}
+
+/// The name of an item, `impl Type` for impls.
+fn item_name(item: &ast::Item) -> String {
+    let name = match item {
+        ast::Item::Fn(it) => it.name(),
+        ast::Item::Struct(it) => it.name(),
+        ast::Item::Enum(it) => it.name(),
+        ast::Item::Union(it) => it.name(),
+        ast::Item::Trait(it) => it.name(),
+        ast::Item::Const(it) => it.name(),
+        ast::Item::Static(it) => it.name(),
+        ast::Item::Module(it) => it.name(),
+        ast::Item::TypeAlias(it) => it.name(),
+        ast::Item::Impl(it) => {
+            let ty = it.self_ty().map(|ty| ty.syntax().text().to_string()).unwrap_or_default();
+            return format!("impl {ty}");
+        }
+        _ => None,
+    };
+    name.map(|it| it.text().to_string()).unwrap_or_default()
+}
@@ -0,0 +1,220 @@
+//! On-disk cache of the calls found by `function-analyzer`, so re-running it on an unchanged
+//! workspace skips the call hierarchy queries.
+//!
+//! There's one entry per source file, holding the calls of its functions along with the content
+//! hashes of the files they were computed from: the file itself and the files of the callees. An
+//! entry is dropped as soon as one of these hashes changes. Outgoing calls can also change with
+//! edits elsewhere that only affect name resolution, like a new glob import target, those are
+//! missed and need `--no-cache`. Incoming calls may come from any file, their entries depend on
+//! every file of the project.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use ide::RootDatabase;
+use ide_db::base_db::SourceDatabase;
+use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use vfs::{AbsPathBuf, FileId, Vfs};
+
+use crate::cli::{
+    export_bundle::hash,
+    function_analyzer::{CallRelation, FunctionInfo, is_external_path},
+};
+
+/// Bumped whenever the format of the entries or the analysis producing them changes.
+const CACHE_VERSION: u32 = 4;
+
+/// Dependency standing for every file of the project.
+const WORKSPACE: &str = "*";
+
+#[derive(Default, Serialize, Deserialize)]
+struct Entry {
+    /// Content hashes of the files the calls were computed from, by path.
+    dependencies: BTreeMap<String, String>,
+    functions: Vec<CachedFunction>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedFunction {
+    line: u32,
+    column: u32,
+    relations: Vec<CallRelation>,
+}
+
+pub(super) struct CallCache<'a> {
+    db: &'a RootDatabase,
+    /// Where the entries are kept, `None` with `--no-cache`.
+    dir: Option<PathBuf>,
+    /// Distinguishes the entries of analyses giving different calls for the same file.
+    settings: String,
+    incoming: bool,
+    files: FxHashMap<String, FileId>,
+    project_files: Vec<String>,
+    hashes: FxHashMap<String, String>,
+    hits: usize,
+    misses: usize,
+}
+
+impl<'a> CallCache<'a> {
+    /// The cache of `project_root`, kept in its `target` directory unless `enabled` is false.
+    pub(super) fn new(
+        db: &'a RootDatabase,
+        vfs: &Vfs,
+        project_root: &AbsPathBuf,
+        incoming: bool,
+        exclude_tests: bool,
+        enabled: bool,
+    ) -> Self {
+        let files: FxHashMap<String, FileId> =
+            vfs.iter().map(|(file_id, path)| (path.to_string(), file_id)).collect();
+        let project_files = files
+            .keys()
+            .filter(|path| !is_external_path(path, project_root))
+            .cloned()
+            .sorted()
+            .collect();
+        CallCache {
+            db,
+            dir: enabled
+                .then(|| project_root.join("target").join("function-analyzer-cache").into()),
+            settings: format!(
+                "{CACHE_VERSION}:{}:{incoming}:{exclude_tests}",
+                env!("CARGO_PKG_VERSION")
+            ),
+            incoming,
+            files,
+            project_files,
+            hashes: FxHashMap::default(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// The calls of `functions` in their order, taken from the cache when their files are
+    /// unchanged. The others are computed by `analyze` and stored.
+    pub(super) fn relations(
+        &mut self,
+        functions: &[FunctionInfo],
+        analyze: impl FnOnce(&[FunctionInfo]) -> Result<Vec<CallRelation>>,
+    ) -> Result<Vec<CallRelation>> {
+        let mut entries: FxHashMap<&str, Entry> = FxHashMap::default();
+        let mut calls: Vec<Option<Vec<CallRelation>>> = Vec::with_capacity(functions.len());
+        for function in functions {
+            let file_path = function.file_path.as_str();
+            if !entries.contains_key(file_path) {
+                let entry = self.load(file_path).unwrap_or_default();
+                entries.insert(file_path, entry);
+            }
+            let cached = entries[file_path]
+                .functions
+                .iter()
+                .find(|it| (it.line, it.column) == (function.line, function.column))
+                .map(|it| it.relations.clone());
+            calls.push(cached);
+        }
+        let missing: Vec<FunctionInfo> = functions
+            .iter()
+            .zip(&calls)
+            .filter(|(_, calls)| calls.is_none())
+            .map(|(function, _)| function.clone())
+            .collect();
+        self.hits += functions.len() - missing.len();
+        self.misses += missing.len();
+
+        if !missing.is_empty() {
+            let computed = analyze(&missing)?;
+            let mut changed = FxHashSet::default();
+            for (function, calls) in functions.iter().zip(&mut calls) {
+                if calls.is_some() {
+                    continue;
+                }
+                let relations: Vec<CallRelation> = computed
+                    .iter()
+                    .filter(|relation| {
+                        let analyzed =
+                            if self.incoming { &relation.callee } else { &relation.caller };
+                        (analyzed.line, analyzed.column) == (function.line, function.column)
+                            && analyzed.file_path == function.file_path
+                    })
+                    .cloned()
+                    .collect();
+                let dependencies = if self.incoming {
+                    vec![WORKSPACE.to_owned()]
+                } else {
+                    relations.iter().map(|relation| relation.callee.file_path.clone()).collect()
+                };
+                let file_path = function.file_path.as_str();
+                for path in dependencies.into_iter().chain([file_path.to_owned()]) {
+                    if let Some(hash) = self.hash(&path) {
+                        entries.get_mut(file_path).unwrap().dependencies.insert(path, hash);
+                    }
+                }
+                entries.get_mut(file_path).unwrap().functions.push(CachedFunction {
+                    line: function.line,
+                    column: function.column,
+                    relations: relations.clone(),
+                });
+                changed.insert(file_path);
+                *calls = Some(relations);
+            }
+            for file_path in changed.into_iter().sorted() {
+                self.store(file_path, &entries[file_path])?;
+            }
+        }
+        Ok(calls.into_iter().flatten().flatten().collect())
+    }
+
+    /// How many functions had their calls cached, and how many were analyzed.
+    pub(super) fn stats(&self) -> (usize, usize) {
+        (self.hits, self.misses)
+    }
+
+    /// The entry of `file_path`, unless one of the files it depends on changed.
+    fn load(&mut self, file_path: &str) -> Option<Entry> {
+        let text = fs::read_to_string(self.entry_path(file_path)?).ok()?;
+        let entry: Entry = serde_json::from_str(&text).ok()?;
+        let valid = entry
+            .dependencies
+            .iter()
+            .all(|(path, hash)| self.hash(path).is_some_and(|current| current == *hash));
+        valid.then_some(entry)
+    }
+
+    fn store(&self, file_path: &str, entry: &Entry) -> Result<()> {
+        let Some(path) = self.entry_path(file_path) else { return Ok(()) };
+        let dir = path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+        fs::write(&path, serde_json::to_vec(entry)?)
+            .with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    fn entry_path(&self, file_path: &str) -> Option<PathBuf> {
+        let name = hash(&format!("{}:{file_path}", self.settings));
+        Some(self.dir.as_ref()?.join(format!("{name}.json")))
+    }
+
+    /// The content hash of a file of the workspace, or of every project file for `WORKSPACE`.
+    fn hash(&mut self, path: &str) -> Option<String> {
+        if let Some(hash) = self.hashes.get(path) {
+            return Some(hash.clone());
+        }
+        let hash = if path == WORKSPACE {
+            let mut hashes = Vec::new();
+            for file in self.project_files.clone() {
+                hashes.push(format!("{file}:{}", self.hash(&file)?));
+            }
+            hash(&hashes.join("\n"))
+        } else {
+            let file_id = *self.files.get(path)?;
+            hash(self.db.file_text(file_id).text(self.db))
+        };
+        self.hashes.insert(path.to_owned(), hash.clone());
+        Some(hash)
+    }
+}
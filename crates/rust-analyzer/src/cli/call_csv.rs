@@ -0,0 +1,52 @@
+//! CSV edge list of the call relations found by `function-analyzer`, one row per call site, for
+//! spreadsheets and pandas.
+
+use std::io::Write;
+
+use anyhow::Result;
+use vfs::AbsPathBuf;
+
+use crate::cli::{
+    flags,
+    function_analyzer::{CallRelation, convert_to_relative_path},
+};
+
+pub(super) fn write_csv(
+    writer: &mut dyn Write,
+    relations: &[CallRelation],
+    project_root: &AbsPathBuf,
+    redaction: &flags::Redaction,
+) -> Result<()> {
+    write_row(writer, &["caller", "callee", "caller_file", "callee_file", "line"])?;
+    for relation in relations {
+        let [caller_file, callee_file] = [&relation.caller, &relation.callee]
+            .map(|it| redaction.path(&convert_to_relative_path(&it.file_path, project_root)));
+        write_row(
+            writer,
+            &[
+                &relation.caller.name,
+                &relation.callee.name,
+                &caller_file,
+                &callee_file,
+                &relation.call_site_line.to_string(),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes one record, quoting the fields that need it as RFC 4180 describes.
+pub(super) fn write_row(writer: &mut dyn Write, fields: &[&str]) -> Result<()> {
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            write!(writer, ",")?;
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            write!(writer, "\"{}\"", field.replace('"', "\"\""))?;
+        } else {
+            write!(writer, "{field}")?;
+        }
+    }
+    write!(writer, "\r\n")?;
+    Ok(())
+}
@@ -0,0 +1,209 @@
+//! Compares the call graphs of two versions of a project, so reviewers can see how a change
+//! reshapes the call structure.
+//!
+//! Versions are project directories or git revisions, the latter are extracted to a temporary
+//! directory with `git archive`. Functions are matched by module path and file rather than by
+//! position, moving a function or a call around isn't a change. Methods of the same name in one
+//! module and file, like the `new` of two impls, are merged.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+
+use crate::cli::{
+    code_graph::{CodeGraph, LoadOptions, LoadedProject},
+    export_bundle::hash,
+    flags,
+    graph_watch::Changes,
+};
+
+impl flags::Diff {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("call_hierarchy_diff").entered();
+        let repository = self.path.clone().unwrap_or_else(|| PathBuf::from("."));
+        let base = self.call_graph(&repository, &self.base)?;
+        let head = self.call_graph(&repository, &self.head)?;
+
+        let report = CallGraphDiff {
+            base: self.base.clone(),
+            head: self.head.clone(),
+            functions: changes(&base.functions, &head.functions),
+            calls: changes(&base.calls, &head.calls),
+        };
+        eprintln!(
+            "Functions: {} added, {} removed. Calls: {} added, {} removed",
+            report.functions.added.len(),
+            report.functions.removed.len(),
+            report.calls.added.len(),
+            report.calls.removed.len()
+        );
+
+        let json = serde_json::to_string_pretty(&report)?;
+        match &self.output {
+            Some(path) => fs::write(path, json)?,
+            None => println!("{json}"),
+        }
+        Ok(())
+    }
+
+    /// The call graph of `version`, a directory or a git revision of `repository`.
+    fn call_graph(&self, repository: &Path, version: &str) -> Result<VersionGraph> {
+        let checkout = Checkout::new(repository, version)?;
+        eprintln!("Loading {version}...");
+        let project = LoadedProject::load(
+            checkout.project(),
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+        let graph = CodeGraph::build(&project)?;
+        Ok(VersionGraph::new(&graph))
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct CallGraphDiff {
+    base: String,
+    head: String,
+    functions: Changes<Function, Function>,
+    calls: Changes<Call, Call>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+struct Function {
+    /// `module::name`, or just the name for functions of dependencies.
+    path: String,
+    /// Relative to the project root for project functions.
+    file: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+struct Call {
+    caller: Function,
+    callee: Function,
+}
+
+/// The project functions and the calls of one version, sorted and without duplicates.
+struct VersionGraph {
+    functions: Vec<Function>,
+    calls: Vec<Call>,
+}
+
+impl VersionGraph {
+    fn new(graph: &CodeGraph) -> VersionGraph {
+        let functions: FxHashMap<usize, Function> = graph
+            .functions
+            .iter()
+            .map(|function| {
+                let path = if function.module.is_empty() {
+                    function.name.clone()
+                } else {
+                    format!("{}::{}", function.module, function.name)
+                };
+                (function.id, Function { path, file: function.file.clone() })
+            })
+            .collect();
+        VersionGraph {
+            functions: graph
+                .functions
+                .iter()
+                .filter(|function| !function.external)
+                .map(|function| functions[&function.id].clone())
+                .sorted()
+                .dedup()
+                .collect(),
+            calls: graph
+                .calls
+                .iter()
+                .map(|call| Call {
+                    caller: functions[&call.caller].clone(),
+                    callee: functions[&call.callee].clone(),
+                })
+                .sorted()
+                .dedup()
+                .collect(),
+        }
+    }
+}
+
+/// Compares two sorted lists, items have no identity beyond their value.
+fn changes<T: Clone + Ord>(old: &[T], new: &[T]) -> Changes<T, T> {
+    let missing_from = |items: &[T], other: &[T]| -> Vec<T> {
+        items.iter().filter(|it| other.binary_search(it).is_err()).cloned().collect()
+    };
+    Changes { added: missing_from(new, old), removed: missing_from(old, new), changed: Vec::new() }
+}
+
+/// A version of the project on disk, extracted to a temporary directory for git revisions.
+enum Checkout {
+    Directory(PathBuf),
+    Revision {
+        /// Removed once the version is analyzed.
+        root: PathBuf,
+        project: PathBuf,
+    },
+}
+
+impl Checkout {
+    fn new(repository: &Path, version: &str) -> Result<Checkout> {
+        if Path::new(version).is_dir() {
+            return Ok(Checkout::Directory(PathBuf::from(version)));
+        }
+        let git = |args: &[&str]| -> Result<Vec<u8>> {
+            let output = Command::new("git")
+                .arg("-C")
+                .arg(repository)
+                .args(args)
+                .output()
+                .context("failed to run git")?;
+            if !output.status.success() {
+                anyhow::bail!(
+                    "`{version}` is neither a directory nor a git revision of {}: {}",
+                    repository.display(),
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            Ok(output.stdout)
+        };
+        git(&["rev-parse", "--verify", &format!("{version}^{{commit}}")])?;
+        // The project may be a subdirectory of the repository.
+        let prefix = String::from_utf8(git(&["rev-parse", "--show-prefix"])?)?;
+        let archive = git(&["archive", "--format=tar", version])?;
+
+        let root = std::env::temp_dir().join(format!(
+            "rust-analyzer-call-diff-{}-{}",
+            std::process::id(),
+            &hash(version)[..16]
+        ));
+        let _ = fs::remove_dir_all(&root);
+        tar::Archive::new(archive.as_slice())
+            .unpack(&root)
+            .with_context(|| format!("failed to extract `{version}` to {}", root.display()))?;
+        let project = root.join(prefix.trim());
+        Ok(Checkout::Revision { root, project })
+    }
+
+    fn project(&self) -> &Path {
+        match self {
+            Checkout::Directory(path) => path,
+            Checkout::Revision { project, .. } => project,
+        }
+    }
+}
+
+impl Drop for Checkout {
+    fn drop(&mut self) {
+        if let Checkout::Revision { root, .. } = self {
+            let _ = fs::remove_dir_all(root);
+        }
+    }
+}
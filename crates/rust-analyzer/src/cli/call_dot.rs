@@ -0,0 +1,108 @@
+//! Graphviz export of the call relations found by `function-analyzer`, with one node per
+//! function labeled `file:line:name`, doubly bordered for program entry points, and one edge per
+//! calling pair, dashed when the calls go through dynamic dispatch and dotted when they're
+//! spawned as separate tasks.
+
+use std::io::Write;
+
+use anyhow::Result;
+use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
+use vfs::AbsPathBuf;
+
+use crate::cli::{
+    call_kind::CallKind,
+    dispatch::Dispatch,
+    entry_points::EntryKind,
+    flags,
+    function_analyzer::{CallRelation, FunctionInfo, convert_to_relative_path},
+    prune::{FunctionKey, Keys},
+};
+
+/// Writes `relations` as a `digraph`. With `cluster_modules` the project functions are grouped
+/// into one cluster per module, functions of unknown modules stay outside of them.
+pub(super) fn write_dot(
+    writer: &mut dyn Write,
+    functions: &[FunctionInfo],
+    relations: &[CallRelation],
+    pruned: &[String],
+    roots: &[(FunctionInfo, EntryKind)],
+    cluster_modules: bool,
+    project_root: &AbsPathBuf,
+    redaction: &flags::Redaction,
+) -> Result<()> {
+    // Callees whose definition wasn't found don't know their module, they take it from the
+    // matching project function.
+    let keys = Keys::new(functions);
+    let modules: FxHashMap<FunctionKey, &str> =
+        functions.iter().map(|it| (keys.key(it), it.module.as_str())).collect();
+
+    let roots: FxHashSet<FunctionKey> = roots.iter().map(|(it, _)| keys.key(it)).collect();
+
+    let mut nodes: FxHashMap<FunctionKey, usize> = FxHashMap::default();
+    let mut labels = Vec::new();
+    // Whether every call between the pair goes through dynamic dispatch, and is spawned.
+    let mut edges: FxHashMap<(usize, usize), (bool, bool)> = FxHashMap::default();
+    for relation in relations {
+        let [caller, callee] = [&relation.caller, &relation.callee].map(|function| {
+            *nodes.entry(keys.key(function)).or_insert_with_key(|key| {
+                let file =
+                    redaction.path(&convert_to_relative_path(&function.file_path, project_root));
+                let module = modules.get(key).copied().unwrap_or_default();
+                let root = roots.contains(key);
+                labels.push((format!("{file}:{}:{}", function.line, function.name), module, root));
+                labels.len() - 1
+            })
+        });
+        let dynamic = relation.dispatch == Dispatch::Dynamic;
+        let spawned = relation.call_kind == CallKind::Spawn;
+        let edge = edges.entry((caller, callee)).or_insert((dynamic, spawned));
+        edge.0 &= dynamic;
+        edge.1 &= spawned;
+    }
+
+    for decision in pruned {
+        writeln!(writer, "// Pruned: {decision}")?;
+    }
+    writeln!(writer, "digraph calls {{")?;
+    writeln!(writer, "    node [shape=box];")?;
+    let node = |id: usize| {
+        let border = if labels[id].2 { ", peripheries=2" } else { "" };
+        format!("n{id} [label={}{border}];", quote(&labels[id].0))
+    };
+    if cluster_modules {
+        let by_module = (0..labels.len()).into_group_map_by(|&id| labels[id].1);
+        for (module, ids) in by_module.into_iter().sorted() {
+            if module.is_empty() {
+                for id in ids {
+                    writeln!(writer, "    {}", node(id))?;
+                }
+                continue;
+            }
+            writeln!(writer, "    subgraph {} {{", quote(&format!("cluster_{module}")))?;
+            writeln!(writer, "        label={};", quote(module))?;
+            for id in ids {
+                writeln!(writer, "        {}", node(id))?;
+            }
+            writeln!(writer, "    }}")?;
+        }
+    } else {
+        for id in 0..labels.len() {
+            writeln!(writer, "    {}", node(id))?;
+        }
+    }
+    for ((caller, callee), (dynamic, spawned)) in edges.into_iter().sorted() {
+        let style = match (dynamic, spawned) {
+            (_, true) => " [style=dotted]",
+            (true, false) => " [style=dashed]",
+            (false, false) => "",
+        };
+        writeln!(writer, "    n{caller} -> n{callee}{style};")?;
+    }
+    writeln!(writer, "}}")?;
+    Ok(())
+}
+
+pub(super) fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,112 @@
+//! Grouping of the calls between the same pair of functions into one edge listing every call
+//! site, the default shape of the text and JSON output of `function-analyzer`.
+
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+
+use crate::cli::{
+    call_kind::CallKind,
+    dispatch::Dispatch,
+    external_calls::ExternalCrate,
+    function_analyzer::{CallRelation, FunctionInfo},
+};
+
+/// The calls from one function to another.
+#[derive(Serialize)]
+pub(super) struct CallEdge<'a> {
+    pub(super) caller: &'a FunctionInfo,
+    pub(super) callee: &'a FunctionInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) callee_crate: Option<&'a ExternalCrate>,
+    pub(super) count: usize,
+    pub(super) call_sites: Vec<CallSite<'a>>,
+}
+
+#[derive(Serialize)]
+pub(super) struct CallSite<'a> {
+    pub(super) line: u32,
+    pub(super) column: u32,
+    pub(super) dispatch: Dispatch,
+    pub(super) confidence: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) expanded_from: Option<&'a str>,
+    pub(super) call_kind: CallKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) call_snippet: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) instantiated_with: Option<&'a str>,
+}
+
+impl<'a> CallSite<'a> {
+    pub(super) fn of(relation: &'a CallRelation) -> CallSite<'a> {
+        CallSite {
+            line: relation.call_site_line,
+            column: relation.call_site_column,
+            dispatch: relation.dispatch,
+            confidence: relation.confidence,
+            expanded_from: relation.expanded_from.as_deref(),
+            call_kind: relation.call_kind,
+            call_snippet: relation.call_snippet.as_deref(),
+            instantiated_with: relation.instantiated_with.as_deref(),
+        }
+    }
+
+    /// The ` [kind]`, ` [with T = Type]`, ` [dynamic, confidence]` and ` [expanded from macro]`
+    /// notes following the location of the call in the text output.
+    pub(super) fn notes(&self) -> String {
+        let mut notes = match self.call_kind {
+            CallKind::Function | CallKind::Macro => String::new(),
+            kind => format!(" [{}]", kind.as_str()),
+        };
+        if let Some(instantiation) = self.instantiated_with {
+            notes.push_str(&format!(" [with {instantiation}]"));
+        }
+        if self.dispatch == Dispatch::Dynamic {
+            notes.push_str(&format!(" [dynamic, confidence {:.2}]", self.confidence));
+        }
+        if let Some(name) = self.expanded_from {
+            notes.push_str(&format!(" [expanded from {name}]"));
+        }
+        notes
+    }
+}
+
+/// The ` [crate name version]` note ending the text of a call into a dependency.
+pub(super) fn crate_note(krate: Option<&ExternalCrate>) -> String {
+    match krate {
+        Some(ExternalCrate { name, version: Some(version) }) => {
+            format!(" [crate {name} {version}]")
+        }
+        Some(ExternalCrate { name, version: None }) => format!(" [crate {name}]"),
+        None => String::new(),
+    }
+}
+
+/// Groups `relations` by caller and callee, in the order of their first call. With `flat` every
+/// relation stays an edge of its own.
+pub(super) fn call_edges(relations: &[CallRelation], flat: bool) -> Vec<CallEdge<'_>> {
+    let mut edges: Vec<CallEdge<'_>> = Vec::new();
+    let mut index: FxHashMap<[(&str, u32, u32); 2], usize> = FxHashMap::default();
+    for relation in relations {
+        let [caller, callee] = [&relation.caller, &relation.callee]
+            .map(|it| (it.file_path.as_str(), it.line, it.column));
+        let edge = match index.get(&[caller, callee]) {
+            Some(&edge) if !flat => edge,
+            _ => {
+                edges.push(CallEdge {
+                    caller: &relation.caller,
+                    callee: &relation.callee,
+                    callee_crate: relation.callee_crate.as_ref(),
+                    count: 0,
+                    call_sites: Vec::new(),
+                });
+                index.insert([caller, callee], edges.len() - 1);
+                edges.len() - 1
+            }
+        };
+        let edge = &mut edges[edge];
+        edge.count += 1;
+        edge.call_sites.push(CallSite::of(relation));
+    }
+    edges
+}
@@ -0,0 +1,147 @@
+//! GraphML export of the call relations found by `function-analyzer`, readable by Gephi, yEd
+//! and NetworkX. Nodes carry the name, file, line, crate and module of a function, plus the crate
+//! version of dependencies and the kind of program entry point for roots, edges the location,
+//! dispatch and kind of one call.
+
+use std::io::Write;
+
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use vfs::AbsPathBuf;
+
+use crate::cli::{
+    dispatch::Dispatch,
+    entry_points::EntryKind,
+    external_calls::ExternalCrate,
+    flags,
+    function_analyzer::{CallRelation, FunctionInfo, convert_to_relative_path},
+    prune::{FunctionKey, Keys},
+};
+
+/// Attribute declarations: id, owner, type.
+const KEYS: &[(&str, &str, &str)] = &[
+    ("name", "node", "string"),
+    ("file", "node", "string"),
+    ("line", "node", "int"),
+    ("crate", "node", "string"),
+    ("version", "node", "string"),
+    ("module", "node", "string"),
+    ("root", "node", "string"),
+    ("call_line", "edge", "int"),
+    ("call_column", "edge", "int"),
+    ("dispatch", "edge", "string"),
+    ("confidence", "edge", "double"),
+    ("expanded_from", "edge", "string"),
+    ("call_kind", "edge", "string"),
+];
+
+/// Writes `relations` as a directed GraphML graph with one edge per call, calls between the
+/// same pair of functions giving parallel edges.
+pub(super) fn write_graphml(
+    writer: &mut dyn Write,
+    functions: &[FunctionInfo],
+    relations: &[CallRelation],
+    pruned: &[String],
+    roots: &[(FunctionInfo, EntryKind)],
+    project_root: &AbsPathBuf,
+    redaction: &flags::Redaction,
+) -> Result<()> {
+    // Callees whose definition wasn't found don't know their module, they take it from the
+    // matching project function.
+    let keys = Keys::new(functions);
+    let modules: FxHashMap<FunctionKey, &str> =
+        functions.iter().map(|it| (keys.key(it), it.module.as_str())).collect();
+    let roots: FxHashMap<FunctionKey, EntryKind> =
+        roots.iter().map(|(function, kind)| (keys.key(function), *kind)).collect();
+    let crates: FxHashMap<FunctionKey, &ExternalCrate> = relations
+        .iter()
+        .filter_map(|it| Some((keys.key(&it.callee), it.callee_crate.as_ref()?)))
+        .collect();
+
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    for decision in pruned {
+        writeln!(writer, "<!-- Pruned: {} -->", escape(&decision.replace("--", "- -")))?;
+    }
+    writeln!(writer, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+    for (id, owner, ty) in KEYS {
+        writeln!(writer, r#"  <key id="{id}" for="{owner}" attr.name="{id}" attr.type="{ty}"/>"#)?;
+    }
+    writeln!(writer, r#"  <graph id="calls" edgedefault="directed">"#)?;
+
+    let mut nodes: FxHashMap<FunctionKey, usize> = FxHashMap::default();
+    for function in relations.iter().flat_map(|it| [&it.caller, &it.callee]) {
+        let key = keys.key(function);
+        if nodes.contains_key(&key) {
+            continue;
+        }
+        let id = nodes.len();
+        let file = redaction.path(&convert_to_relative_path(&function.file_path, project_root));
+        let module = modules.get(&key).copied().unwrap_or_default();
+        let root = roots.get(&key).copied();
+        let external = crates.get(&key).copied();
+        nodes.insert(key, id);
+        writeln!(writer, r#"    <node id="n{id}">"#)?;
+        write_data(writer, "name", &function.name)?;
+        write_data(writer, "file", &file)?;
+        write_data(writer, "line", &function.line.to_string())?;
+        match external {
+            Some(krate) => write_data(writer, "crate", &krate.name)?,
+            None => write_data(writer, "crate", crate_name(module, &function.file_path))?,
+        }
+        if let Some(version) = external.and_then(|it| it.version.as_deref()) {
+            write_data(writer, "version", version)?;
+        }
+        write_data(writer, "module", module)?;
+        if let Some(kind) = root {
+            write_data(writer, "root", kind.as_str())?;
+        }
+        writeln!(writer, "    </node>")?;
+    }
+
+    for (id, relation) in relations.iter().enumerate() {
+        let caller = nodes[&keys.key(&relation.caller)];
+        let callee = nodes[&keys.key(&relation.callee)];
+        writeln!(writer, r#"    <edge id="e{id}" source="n{caller}" target="n{callee}">"#)?;
+        write_data(writer, "call_line", &relation.call_site_line.to_string())?;
+        write_data(writer, "call_column", &relation.call_site_column.to_string())?;
+        let dispatch = match relation.dispatch {
+            Dispatch::Static => "static",
+            Dispatch::Dynamic => "dynamic",
+        };
+        write_data(writer, "dispatch", dispatch)?;
+        write_data(writer, "confidence", &relation.confidence.to_string())?;
+        if let Some(mac) = &relation.expanded_from {
+            write_data(writer, "expanded_from", mac)?;
+        }
+        write_data(writer, "call_kind", relation.call_kind.as_str())?;
+        writeln!(writer, "    </edge>")?;
+    }
+    writeln!(writer, "  </graph>")?;
+    writeln!(writer, "</graphml>")?;
+    Ok(())
+}
+
+pub(super) fn write_data(writer: &mut dyn Write, key: &str, value: &str) -> Result<()> {
+    writeln!(writer, r#"      <data key="{key}">{}</data>"#, escape(value))?;
+    Ok(())
+}
+
+/// The crate of a function: the first segment of its module path, or for functions of unknown
+/// modules the package directory of sysroot and registry sources, like `core` or `serde`.
+pub(super) fn crate_name<'a>(module: &'a str, file_path: &'a str) -> &'a str {
+    if !module.is_empty() {
+        return module.split("::").next().unwrap_or(module);
+    }
+    let segments: Vec<&str> = file_path.split('/').collect();
+    let Some(src) = segments.iter().rposition(|it| *it == "src") else { return "" };
+    let Some(&package) = src.checked_sub(1).and_then(|it| segments.get(it)) else { return "" };
+    // Registry packages are unpacked to `name-version`.
+    match package.rsplit_once('-') {
+        Some((name, version)) if version.starts_with(|c: char| c.is_ascii_digit()) => name,
+        _ => package,
+    }
+}
+
+pub(super) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,147 @@
+//! Classification of calls by the syntax at their call site: calls of free functions, of
+//! inherent and trait methods, calls produced by macros, and calls crossing an async boundary,
+//! either spawned as a separate task or awaited.
+
+use hir::{AsAssocItem, Semantics};
+use ide::{LineCol, RootDatabase};
+use ide_db::{LineIndexDatabase, base_db::salsa};
+use serde::{Deserialize, Serialize};
+use syntax::{AstNode, SyntaxNode, ast};
+use vfs::Vfs;
+
+use crate::cli::{
+    function_analyzer::{CallRelation, find_file_id_by_path},
+    macro_edges::resolve_call,
+};
+
+/// How a call is written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum CallKind {
+    /// A call of a free function, or of an associated function without receiver.
+    #[default]
+    Function,
+    /// A call of a method of an inherent `impl`.
+    Method,
+    /// A call of a trait method, through method syntax or a path like `Clone::clone`.
+    TraitMethod,
+    /// A call only existing in the expansion of a macro.
+    Macro,
+    /// A call running as a separate task or thread, made in a closure or async block handed to a
+    /// `spawn` function or handing its future to one.
+    Spawn,
+    /// A call whose future is awaited right away.
+    Await,
+}
+
+impl CallKind {
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            CallKind::Function => "function",
+            CallKind::Method => "method",
+            CallKind::TraitMethod => "trait-method",
+            CallKind::Macro => "macro",
+            CallKind::Spawn => "spawn",
+            CallKind::Await => "await",
+        }
+    }
+}
+
+/// Fills in the `call_kind` field of every relation, once `expanded_from` is known.
+pub(super) fn classify_calls(db: &RootDatabase, vfs: &Vfs, relations: &mut [CallRelation]) {
+    let _p = tracing::info_span!("classify_calls").entered();
+    salsa::attach(db, || {
+        let sema = Semantics::new(db);
+        for relation in relations {
+            relation.call_kind = if relation.expanded_from.is_some() {
+                CallKind::Macro
+            } else {
+                call_at(&sema, vfs, relation)
+                    .map_or(CallKind::Function, |call| classify(&sema, &call))
+            };
+        }
+    })
+}
+
+/// The call expression of `relation`, whose call site is the callee of a call or the name of a
+/// called method.
+pub(super) fn call_at(
+    sema: &Semantics<'_, RootDatabase>,
+    vfs: &Vfs,
+    relation: &CallRelation,
+) -> Option<SyntaxNode> {
+    let file_id = find_file_id_by_path(vfs, &relation.caller.file_path)?;
+    let offset = sema.db.line_index(file_id).offset(LineCol {
+        line: relation.call_site_line.saturating_sub(1),
+        col: relation.call_site_column.saturating_sub(1),
+    })?;
+    let file = sema.parse_guess_edition(file_id);
+    let token = file.syntax().token_at_offset(offset).right_biased()?;
+    token.parent_ancestors().find(|node| ast::CallableExpr::can_cast(node.kind()))
+}
+
+fn classify(sema: &Semantics<'_, RootDatabase>, call: &SyntaxNode) -> CallKind {
+    if is_spawned(call) {
+        return CallKind::Spawn;
+    }
+    if is_awaited(call) {
+        return CallKind::Await;
+    }
+    let db = sema.db;
+    let Some((function, _)) = resolve_call(sema, call) else { return CallKind::Function };
+    let Some(item) = function.as_assoc_item(db) else { return CallKind::Function };
+    if item.container_or_implemented_trait(db).is_some() {
+        CallKind::TraitMethod
+    } else if function.has_self_param(db) {
+        CallKind::Method
+    } else {
+        CallKind::Function
+    }
+}
+
+/// Whether `call` runs as a separate task or thread: it's in a closure or async block passed to
+/// a function like `std::thread::spawn`, `tokio::spawn`, `async_std::task::spawn` or
+/// `spawn_blocking`, or its future is passed to one of them, like in `tokio::spawn(serve(conn))`.
+fn is_spawned(call: &SyntaxNode) -> bool {
+    for node in call.ancestors() {
+        if ast::Fn::can_cast(node.kind()) {
+            return false;
+        }
+        let is_task = node == *call
+            || match ast::Expr::cast(node.clone()) {
+                Some(ast::Expr::ClosureExpr(_)) => true,
+                Some(ast::Expr::BlockExpr(block)) => block.async_token().is_some(),
+                _ => false,
+            };
+        if !is_task {
+            continue;
+        }
+        let spawner = node.parent().filter(|it| ast::ArgList::can_cast(it.kind()));
+        if callee_name(spawner.and_then(|it| it.parent())).is_some_and(|it| it.starts_with("spawn"))
+        {
+            return true;
+        }
+    }
+    false
+}
+
+/// The name of the function or method called by `call`.
+fn callee_name(call: Option<SyntaxNode>) -> Option<String> {
+    match ast::CallableExpr::cast(call?)? {
+        ast::CallableExpr::Call(call) => match call.expr()? {
+            ast::Expr::PathExpr(path) => {
+                Some(path.path()?.segment()?.name_ref()?.text().to_string())
+            }
+            _ => None,
+        },
+        ast::CallableExpr::MethodCall(call) => Some(call.name_ref()?.text().to_string()),
+    }
+}
+
+/// Whether the result of `call` is awaited, like in `fetch(url).await`.
+fn is_awaited(call: &SyntaxNode) -> bool {
+    call.ancestors()
+        .skip(1)
+        .find(|node| !ast::ParenExpr::can_cast(node.kind()))
+        .is_some_and(|node| ast::AwaitExpr::can_cast(node.kind()))
+}
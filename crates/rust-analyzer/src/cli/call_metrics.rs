@@ -0,0 +1,98 @@
+//! Fan-in, fan-out and transitive reachability of every function of the call graph, to spot
+//! god-functions calling into everything and hot integration points everything calls into.
+
+use std::{collections::VecDeque, io::Write};
+
+use anyhow::Result;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use vfs::AbsPathBuf;
+
+use crate::cli::{
+    flags,
+    function_analyzer::{CallRelation, FunctionInfo, convert_to_relative_path},
+    prune::{FunctionKey, Keys},
+};
+
+#[derive(Debug, Serialize)]
+struct CallMetrics {
+    functions: Vec<FunctionMetrics>,
+}
+
+#[derive(Debug, Serialize)]
+struct FunctionMetrics {
+    name: String,
+    module: String,
+    file: String,
+    line: u32,
+    /// Number of distinct functions calling this one.
+    fan_in: usize,
+    /// Number of distinct functions this one calls.
+    fan_out: usize,
+    /// Number of functions reachable through calls from this one, dependencies included.
+    reachable: usize,
+    /// Number of project functions reaching this one through calls.
+    reached_from: usize,
+}
+
+/// Writes the metrics of the project `functions` as JSON, highest fan-in plus fan-out first.
+pub(super) fn write_metrics(
+    writer: &mut dyn Write,
+    functions: &[FunctionInfo],
+    relations: &[CallRelation],
+    project_root: &AbsPathBuf,
+    redaction: &flags::Redaction,
+) -> Result<()> {
+    let keys = Keys::new(functions);
+    let mut callees: FxHashMap<FunctionKey, FxHashSet<FunctionKey>> = FxHashMap::default();
+    let mut callers: FxHashMap<FunctionKey, FxHashSet<FunctionKey>> = FxHashMap::default();
+    for relation in relations {
+        let caller = keys.key(&relation.caller);
+        let callee = keys.key(&relation.callee);
+        callees.entry(caller.clone()).or_default().insert(callee.clone());
+        callers.entry(callee).or_default().insert(caller);
+    }
+
+    let mut seen = FxHashSet::default();
+    let mut metrics: Vec<FunctionMetrics> = functions
+        .iter()
+        .filter(|function| seen.insert(keys.key(function)))
+        .map(|function| {
+            let key = keys.key(function);
+            FunctionMetrics {
+                name: function.name.clone(),
+                module: function.module.clone(),
+                file: redaction.path(&convert_to_relative_path(&function.file_path, project_root)),
+                line: function.line,
+                fan_in: callers.get(&key).map_or(0, |it| it.len()),
+                fan_out: callees.get(&key).map_or(0, |it| it.len()),
+                reachable: reach(&callees, &key),
+                reached_from: reach(&callers, &key),
+            }
+        })
+        .collect();
+    metrics.sort_by(|a, b| {
+        (b.fan_in + b.fan_out)
+            .cmp(&(a.fan_in + a.fan_out))
+            .then_with(|| (&a.file, a.line).cmp(&(&b.file, b.line)))
+    });
+
+    serde_json::to_writer_pretty(&mut *writer, &CallMetrics { functions: metrics })?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// The number of functions transitively reachable from `start` along `edges`, not counting
+/// `start` itself unless it's recursive.
+fn reach(edges: &FxHashMap<FunctionKey, FxHashSet<FunctionKey>>, start: &FunctionKey) -> usize {
+    let mut visited: FxHashSet<&FunctionKey> = FxHashSet::default();
+    let mut queue: VecDeque<&FunctionKey> = VecDeque::from([start]);
+    while let Some(key) = queue.pop_front() {
+        for next in edges.get(key).into_iter().flatten() {
+            if visited.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    visited.len()
+}
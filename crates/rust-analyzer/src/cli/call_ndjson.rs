@@ -0,0 +1,74 @@
+//! Newline delimited JSON output of `function-analyzer`: one record per call site, written as soon
+//! as the calls of a file are known, and a summary record closing the stream. Unlike the `json`
+//! document, the relations of the whole workspace never have to be in memory at once.
+
+use std::io::Write;
+
+use anyhow::Result;
+use serde::Serialize;
+use vfs::AbsPathBuf;
+
+use crate::cli::{
+    flags,
+    function_analyzer::{CallRelation, relative_relations},
+};
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record<'a> {
+    Call(&'a CallRelation),
+    Summary {
+        direction: &'static str,
+        functions: usize,
+        relations: usize,
+        #[serde(skip_serializing_if = "<[String]>::is_empty")]
+        pruned: &'a [String],
+    },
+}
+
+pub(super) struct NdjsonWriter<'a> {
+    writer: Box<dyn Write>,
+    project_root: &'a AbsPathBuf,
+    redaction: flags::Redaction,
+    relations: usize,
+}
+
+impl<'a> NdjsonWriter<'a> {
+    pub(super) fn new(
+        writer: Box<dyn Write>,
+        project_root: &'a AbsPathBuf,
+        redaction: flags::Redaction,
+    ) -> NdjsonWriter<'a> {
+        NdjsonWriter { writer, project_root, redaction, relations: 0 }
+    }
+
+    /// Writes a record for every relation and flushes them, so that readers can start on them.
+    pub(super) fn write_relations(&mut self, relations: &[CallRelation]) -> Result<()> {
+        for relation in relative_relations(relations, self.project_root, &self.redaction) {
+            self.write(&Record::Call(&relation))?;
+        }
+        self.relations += relations.len();
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Ends the stream with the summary record.
+    pub(super) fn finish(
+        mut self,
+        incoming: bool,
+        functions: usize,
+        pruned: &[String],
+    ) -> Result<()> {
+        let direction = if incoming { "incoming" } else { "outgoing" };
+        let relations = self.relations;
+        self.write(&Record::Summary { direction, functions, relations, pruned })?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn write(&mut self, record: &Record<'_>) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, record)?;
+        writeln!(self.writer)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+//! Shortest call chains between two functions, like from an instruction handler to a sink, with
+//! the call site of every hop.
+
+use std::io::Write;
+
+use anyhow::Result;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use vfs::AbsPathBuf;
+
+use crate::cli::{
+    flags,
+    function_analyzer::{
+        CallRelation, FunctionInfo, convert_to_relative_path, relation_notes, relative_relations,
+    },
+    prune::{FunctionKey, Keys},
+};
+
+/// At most this many chains are reported, there can be exponentially many shortest ones.
+const MAX_CHAINS: usize = 20;
+
+/// The shortest chains of `relations` leading from one of `sources` to one of `sinks`. Chains
+/// through different call sites of the same pair of functions are told apart.
+pub(super) fn shortest_chains<'a>(
+    functions: &[FunctionInfo],
+    relations: &'a [CallRelation],
+    sources: &[&FunctionInfo],
+    sinks: &[&FunctionInfo],
+) -> Vec<Vec<&'a CallRelation>> {
+    let keys = Keys::new(functions);
+    let mut calls_of: FxHashMap<FunctionKey, Vec<&CallRelation>> = FxHashMap::default();
+    for relation in relations {
+        calls_of.entry(keys.key(&relation.caller)).or_default().push(relation);
+    }
+    let sinks: FxHashSet<FunctionKey> = sinks.iter().map(|it| keys.key(it)).collect();
+
+    // Breadth first, remembering every call reaching a function at its shortest distance.
+    let mut distance: FxHashMap<FunctionKey, usize> =
+        sources.iter().map(|it| (keys.key(it), 0)).collect();
+    let mut reached_by: FxHashMap<FunctionKey, Vec<&CallRelation>> = FxHashMap::default();
+    let mut frontier: Vec<FunctionKey> = distance.keys().cloned().collect();
+    let mut level = 0;
+    while !frontier.is_empty() && !frontier.iter().any(|it| sinks.contains(it)) {
+        level += 1;
+        let mut next = Vec::new();
+        for key in &frontier {
+            for &relation in calls_of.get(key).into_iter().flatten() {
+                let callee = keys.key(&relation.callee);
+                match distance.get(&callee) {
+                    Some(&it) if it < level => continue,
+                    Some(_) => {}
+                    None => {
+                        distance.insert(callee.clone(), level);
+                        next.push(callee.clone());
+                    }
+                }
+                reached_by.entry(callee).or_default().push(relation);
+            }
+        }
+        frontier = next;
+    }
+
+    let mut chains = Vec::new();
+    for sink in frontier.iter().filter(|it| sinks.contains(*it)) {
+        let mut chain = Vec::new();
+        collect_chains(&keys, &reached_by, &distance, sink, &mut chain, &mut chains);
+    }
+    chains
+}
+
+/// Walks the calls reaching `key` back to a source, completing `chain` in reverse.
+fn collect_chains<'a>(
+    keys: &Keys,
+    reached_by: &FxHashMap<FunctionKey, Vec<&'a CallRelation>>,
+    distance: &FxHashMap<FunctionKey, usize>,
+    key: &FunctionKey,
+    chain: &mut Vec<&'a CallRelation>,
+    chains: &mut Vec<Vec<&'a CallRelation>>,
+) {
+    if chains.len() == MAX_CHAINS {
+        return;
+    }
+    if distance[key] == 0 {
+        chains.push(chain.iter().rev().copied().collect());
+        return;
+    }
+    for &relation in reached_by.get(key).into_iter().flatten() {
+        let caller = keys.key(&relation.caller);
+        // Calls from functions at the same distance don't lead back to a source any faster.
+        if distance[&caller] + 1 != distance[key] {
+            continue;
+        }
+        chain.push(relation);
+        collect_chains(keys, reached_by, distance, &caller, chain, chains);
+        chain.pop();
+    }
+}
+
+/// The JSON document written for `--path-from` with `--format json`.
+#[derive(Serialize)]
+struct CallChains<'a> {
+    from: &'a str,
+    to: &'a str,
+    chains: Vec<Vec<CallRelation>>,
+}
+
+pub(super) fn write_chains(
+    writer: &mut dyn Write,
+    from: &str,
+    to: &str,
+    chains: &[Vec<&CallRelation>],
+    format: flags::CallFormat,
+    project_root: &AbsPathBuf,
+    redaction: &flags::Redaction,
+) -> Result<()> {
+    if format == flags::CallFormat::Json {
+        let chains = chains
+            .iter()
+            .map(|chain| {
+                let chain: Vec<CallRelation> = chain.iter().map(|&it| it.clone()).collect();
+                relative_relations(&chain, project_root, redaction)
+            })
+            .collect();
+        serde_json::to_writer_pretty(&mut *writer, &CallChains { from, to, chains })?;
+        writeln!(writer)?;
+        return Ok(());
+    }
+
+    let Some(calls) = chains.first().map(|it| it.len()) else {
+        writeln!(writer, "# No call chain from `{from}` to `{to}`")?;
+        return Ok(());
+    };
+    writeln!(writer, "# Shortest call chains from `{from}` to `{to}`, {calls} calls each")?;
+    writeln!(writer, "# Format: the first function, then -> callee_function (call_site) per call")?;
+    if chains.len() == MAX_CHAINS {
+        writeln!(writer, "# Only the first {MAX_CHAINS} chains are listed")?;
+    }
+    let location = |function: &FunctionInfo| {
+        let file = redaction.path(&convert_to_relative_path(&function.file_path, project_root));
+        format!("{file}:{}:{}", function.line, function.path())
+    };
+    for (index, chain) in chains.iter().enumerate() {
+        let Some(first) = chain.first() else { continue };
+        writeln!(writer)?;
+        writeln!(writer, "# Chain {}", index + 1)?;
+        writeln!(writer, "{}", location(&first.caller))?;
+        for relation in chain {
+            writeln!(
+                writer,
+                "  -> {} (call at {}:{}){}",
+                location(&relation.callee),
+                relation.call_site_line,
+                relation.call_site_column,
+                relation_notes(relation)
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Call graph of a single entry function: rather than analyzing every function of the workspace,
+//! the calls are followed breadth first from the function named by `--root`.
+
+use anyhow::Result;
+use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::cli::{
+    function_analyzer::{CallRelation, FunctionInfo},
+    prune::{FunctionKey, Keys},
+};
+
+/// The functions designated by `root`, a path like `my_crate::module::handler` or
+/// `my_crate::Calculator::multiply` that may leave out leading segments as long as it stays
+/// unambiguous.
+pub(super) fn resolve_root<'a>(
+    functions: &'a [FunctionInfo],
+    root: &str,
+) -> Result<Vec<&'a FunctionInfo>> {
+    let exact: Vec<_> = functions.iter().filter(|it| it.path() == root).collect();
+    if !exact.is_empty() {
+        return Ok(exact);
+    }
+
+    let suffix = format!("::{root}");
+    let matches: Vec<_> =
+        functions.iter().filter(|it| it.name == root || it.path().ends_with(&suffix)).collect();
+    let paths: Vec<String> = matches.iter().map(|it| it.path()).unique().sorted().collect();
+    match paths.len() {
+        0 => anyhow::bail!("no function matches `{root}`"),
+        1 => Ok(matches),
+        _ => anyhow::bail!("`{root}` is ambiguous, it matches {}", paths.join(", ")),
+    }
+}
+
+/// Follows the calls from `roots` for up to `depth` levels, `1` giving the direct calls only.
+/// Follows callers instead of callees for `incoming` calls.
+///
+/// `calls_of` analyzes the calls of one level of functions. Only project functions are
+/// followed, calls into dependencies end the traversal.
+pub(super) fn transitive_relations(
+    functions: &[FunctionInfo],
+    roots: Vec<FunctionInfo>,
+    depth: Option<u32>,
+    incoming: bool,
+    mut calls_of: impl FnMut(&[FunctionInfo]) -> Result<Vec<CallRelation>>,
+) -> Result<Vec<CallRelation>> {
+    let keys = Keys::new(functions);
+    let known: FxHashMap<FunctionKey, &FunctionInfo> =
+        functions.iter().map(|it| (keys.key(it), it)).collect();
+    let mut visited: FxHashSet<FunctionKey> = roots.iter().map(|it| keys.key(it)).collect();
+    let mut frontier = roots;
+    let mut relations = Vec::new();
+    let mut level = 0;
+    while !frontier.is_empty() && depth.is_none_or(|depth| level < depth) {
+        level += 1;
+        let calls = calls_of(&frontier)?;
+        frontier = Vec::new();
+        for relation in &calls {
+            let next = if incoming { &relation.caller } else { &relation.callee };
+            let key = keys.key(next);
+            if let Some(&function) = known.get(&key)
+                && visited.insert(key)
+            {
+                frontier.push(function.clone());
+            }
+        }
+        relations.extend(calls);
+    }
+    Ok(relations)
+}
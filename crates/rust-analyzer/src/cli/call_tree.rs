@@ -0,0 +1,440 @@
+//! `call-hierarchy --function`: the callers and callees of a single function as trees, for quick
+//! interactive queries.
+//!
+//! Only the functions on the trees are analyzed, unlike `function-analyzer` which extracts the
+//! whole workspace first. A function already on the branch above ends it, marked as recursive.
+
+use std::{fs, io::Write, path::PathBuf};
+
+use anyhow::Result;
+use ide::{LineCol, RootDatabase};
+use ide_db::{LineIndexDatabase, base_db::salsa, symbol_index::Query};
+use itertools::Itertools;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use syntax::{AstNode, TextRange, TextSize, ast};
+use vfs::{AbsPathBuf, Vfs};
+
+use crate::cli::{
+    analysis_progress::Progress,
+    call_edges::{self, CallSite},
+    call_traversal::resolve_root,
+    code_graph::{LoadOptions, LoadedProject},
+    dispatch,
+    flags::{self, ProgressFormat},
+    function_analyzer::{
+        CallRelation, FunctionInfo, analyze_call_relationships, convert_to_relative_path,
+        function_named_at, is_external_path, relative_relations,
+    },
+    macro_edges::callee_info,
+};
+
+const DEFAULT_DEPTH: u32 = 3;
+
+impl flags::Tree {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("call_hierarchy_tree", function = %self.function).entered();
+        let format = self.format.unwrap_or_default();
+        if !matches!(format, flags::CallFormat::Text | flags::CallFormat::Json) {
+            anyhow::bail!("`call-hierarchy --function` only writes the text and json formats");
+        }
+        eprintln!("Loading workspace...");
+        let project = LoadedProject::load(
+            &self.path.clone().unwrap_or_else(|| PathBuf::from(".")),
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+        let function = resolve_function(&project, &self.function)?;
+        let depth = self.depth.unwrap_or(DEFAULT_DEPTH);
+
+        let mut calls = Calls::default();
+        calls.fill(&project, &function, depth, false)?;
+        calls.fill(&project, &function, depth, true)?;
+
+        // Paths are only made relative once the analysis, which needs them, is done.
+        let redaction = self.redact.unwrap_or_default();
+        let relative = |function: &FunctionInfo| FunctionInfo {
+            file_path: redaction
+                .path(&convert_to_relative_path(&function.file_path, &project.project_root)),
+            ..function.clone()
+        };
+        let function = relative(&function);
+        let calls = Calls {
+            filled: FxHashMap::default(),
+            relations: calls
+                .relations
+                .into_iter()
+                .map(|((key, incoming), relations)| {
+                    let file_path =
+                        redaction.path(&convert_to_relative_path(&key.0, &project.project_root));
+                    let relations =
+                        relative_relations(&relations, &project.project_root, &redaction);
+                    (((file_path, key.1, key.2), incoming), relations)
+                })
+                .collect(),
+        };
+        let tree = CallTree {
+            function: &function,
+            depth,
+            callers: calls.branches(&function, depth, true, &mut vec![key(&function)]),
+            callees: calls.branches(&function, depth, false, &mut vec![key(&function)]),
+        };
+
+        let mut writer: Box<dyn Write> = match &self.output {
+            Some(path) => Box::new(fs::File::create(path)?),
+            None => Box::new(std::io::stdout()),
+        };
+        match format {
+            flags::CallFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, &tree)?;
+                writeln!(writer)?;
+            }
+            _ => tree.write_text(&mut writer)?,
+        }
+        Ok(())
+    }
+}
+
+type Key = (String, u32, u32);
+
+fn key(function: &FunctionInfo) -> Key {
+    (function.file_path.clone(), function.line, function.column)
+}
+
+/// The function designated by `target`, a path like `my_crate::handler` or the `file:line` of a
+/// line of its definition.
+fn resolve_function(project: &LoadedProject, target: &str) -> Result<FunctionInfo> {
+    let db = &project.db;
+    let file_line = target.rsplit_once(':').filter(|(file, _)| file.ends_with(".rs"));
+    // The symbol index is queried through the analysis, outside of the database below.
+    let symbols = match file_line {
+        Some(_) => Vec::new(),
+        None => {
+            let name = target.rsplit("::").next().unwrap_or(target);
+            let mut query = Query::new(name.to_owned());
+            query.exact();
+            project
+                .analysis()
+                .symbol_search(query, usize::MAX)
+                .map_err(|_| anyhow::anyhow!("Symbol search was cancelled"))?
+        }
+    };
+    salsa::attach(db, || {
+        let sema = hir::Semantics::new(db);
+        let candidates: Vec<hir::Function> = match file_line {
+            Some((file, line)) => {
+                let line: u32 = line.parse()?;
+                function_at_line(&sema, &project.vfs, &project.project_root, file, line)
+                    .into_iter()
+                    .collect()
+            }
+            None => functions_named(&sema, symbols),
+        };
+        let functions: Vec<FunctionInfo> = candidates
+            .into_iter()
+            .filter_map(|function| callee_info(&sema, &project.vfs, function))
+            .unique_by(key)
+            .collect();
+        if functions.is_empty() {
+            anyhow::bail!("no function found at `{target}`");
+        }
+        let matches = match file_line {
+            Some(_) => functions.iter().collect(),
+            None => resolve_root(&functions, target)?,
+        };
+        match matches.as_slice() {
+            [function] => Ok((*function).clone()),
+            _ => anyhow::bail!(
+                "`{target}` matches {} functions, pick one by its file and line: {}",
+                matches.len(),
+                matches
+                    .iter()
+                    .map(|it| format!(
+                        "{}:{}",
+                        convert_to_relative_path(&it.file_path, &project.project_root),
+                        it.line
+                    ))
+                    .join(", ")
+            ),
+        }
+    })
+}
+
+/// The innermost function whose definition covers the 1-based `line` of `file`.
+fn function_at_line(
+    sema: &hir::Semantics<'_, RootDatabase>,
+    vfs: &Vfs,
+    project_root: &AbsPathBuf,
+    file: &str,
+    line: u32,
+) -> Option<hir::Function> {
+    let (file_id, _) = vfs.iter().find(|(_, path)| {
+        let path = path.to_string();
+        path == file || convert_to_relative_path(&path, project_root) == file
+    })?;
+    let line_index = sema.db.line_index(file_id);
+    let start = line_index.offset(LineCol { line: line.checked_sub(1)?, col: 0 })?;
+    // The last line has no next line to end at.
+    let end = line_index.offset(LineCol { line, col: 0 }).unwrap_or(TextSize::from(u32::MAX));
+    let line = TextRange::new(start, end);
+    let file = sema.parse_guess_edition(file_id);
+    let fn_ = file
+        .syntax()
+        .descendants()
+        .filter_map(ast::Fn::cast)
+        .filter(|it| it.syntax().text_range().intersect(line).is_some_and(|it| !it.is_empty()))
+        .min_by_key(|it| it.syntax().text_range().len())?;
+    sema.to_def(&fn_)
+}
+
+/// The functions among `symbols`.
+fn functions_named(
+    sema: &hir::Semantics<'_, RootDatabase>,
+    symbols: Vec<ide::NavigationTarget>,
+) -> Vec<hir::Function> {
+    symbols
+        .into_iter()
+        .filter_map(|symbol| {
+            function_named_at(sema, symbol.file_id, symbol.focus_or_full_range().start())
+        })
+        .collect()
+}
+
+/// The calls of the functions on the trees, analyzed once each.
+#[derive(Default)]
+struct Calls {
+    /// The most levels filled below each function, a deeper visit fills it again.
+    filled: FxHashMap<(Key, bool), u32>,
+    relations: FxHashMap<(Key, bool), Vec<CallRelation>>,
+}
+
+impl Calls {
+    /// Analyzes the calls of `function` and of the functions they lead to, `depth` levels down.
+    fn fill(
+        &mut self,
+        project: &LoadedProject,
+        function: &FunctionInfo,
+        depth: u32,
+        incoming: bool,
+    ) -> Result<()> {
+        self.fill_with(function, depth, incoming, &mut |function| {
+            if is_external_path(&function.file_path, &project.project_root) {
+                return Ok(None);
+            }
+            let relations = analyze_call_relationships(
+                &project.analysis(),
+                std::slice::from_ref(function),
+                &project.vfs,
+                &project.db,
+                &project.project_root,
+                incoming,
+                false,
+                &Progress::new(ProgressFormat::None, "functions"),
+            )?;
+            if incoming {
+                return Ok(Some(relations));
+            }
+            Ok(Some(dispatch::resolve_dynamic_dispatch(
+                &project.db,
+                &project.vfs,
+                &project.project_root,
+                relations,
+            )))
+        })
+    }
+
+    /// Fills the calls `analyze` finds, none for the functions not to analyze. The walk is depth
+    /// first, so a function first reached near the bottom is filled again when a shorter branch
+    /// reaches it, its calls being analyzed only once.
+    fn fill_with(
+        &mut self,
+        function: &FunctionInfo,
+        depth: u32,
+        incoming: bool,
+        analyze: &mut dyn FnMut(&FunctionInfo) -> Result<Option<Vec<CallRelation>>>,
+    ) -> Result<()> {
+        let key = (key(function), incoming);
+        let filled = self.filled.entry(key.clone()).or_default();
+        if depth <= *filled {
+            return Ok(());
+        }
+        *filled = depth;
+        if !self.relations.contains_key(&key) {
+            let Some(relations) = analyze(function)? else { return Ok(()) };
+            self.relations.insert(key.clone(), relations);
+        }
+        let next: Vec<FunctionInfo> = self.relations[&key]
+            .iter()
+            .map(|it| if incoming { it.caller.clone() } else { it.callee.clone() })
+            .collect();
+        for function in next {
+            self.fill_with(&function, depth - 1, incoming, analyze)?;
+        }
+        Ok(())
+    }
+
+    /// The callers or callees of `function` with theirs below them, `depth` levels down.
+    /// `branch` holds the functions from the root down to `function`.
+    fn branches<'a>(
+        &'a self,
+        function: &FunctionInfo,
+        depth: u32,
+        incoming: bool,
+        branch: &mut Vec<Key>,
+    ) -> Vec<Node<'a>> {
+        let Some(relations) = self.relations.get(&(key(function), incoming)) else {
+            return Vec::new();
+        };
+        let edges = call_edges::call_edges(relations, false);
+        edges
+            .into_iter()
+            .map(|edge| {
+                let function = if incoming { edge.caller } else { edge.callee };
+                let recursive = branch.contains(&key(function));
+                let children = if recursive || depth <= 1 {
+                    Vec::new()
+                } else {
+                    branch.push(key(function));
+                    let children = self.branches(function, depth - 1, incoming, branch);
+                    branch.pop();
+                    children
+                };
+                Node { function, call_sites: edge.call_sites, recursive, children }
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+struct CallTree<'a> {
+    function: &'a FunctionInfo,
+    depth: u32,
+    callers: Vec<Node<'a>>,
+    callees: Vec<Node<'a>>,
+}
+
+/// A caller or callee, with its own callers or callees below it.
+#[derive(Serialize)]
+struct Node<'a> {
+    function: &'a FunctionInfo,
+    call_sites: Vec<CallSite<'a>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    recursive: bool,
+    children: Vec<Node<'a>>,
+}
+
+impl CallTree<'_> {
+    fn write_text(&self, writer: &mut dyn Write) -> Result<()> {
+        let describe = |function: &FunctionInfo| {
+            format!("{}:{}:{}", function.file_path, function.line, function.path())
+        };
+        writeln!(writer, "{}", describe(self.function))?;
+        for (title, arrow, nodes) in
+            [("Callers", "<-", &self.callers), ("Callees", "->", &self.callees)]
+        {
+            writeln!(writer, "{title}:")?;
+            if nodes.is_empty() {
+                writeln!(writer, "  (none)")?;
+            }
+            let mut stack: Vec<(usize, &Node<'_>)> = nodes.iter().rev().map(|it| (1, it)).collect();
+            while let Some((level, node)) = stack.pop() {
+                let sites = node
+                    .call_sites
+                    .iter()
+                    .map(|site| format!("{}:{}{}", site.line, site.column, site.notes()))
+                    .join(", ");
+                let calls = match node.call_sites.len() {
+                    1 => format!("call at {sites}"),
+                    count => format!("{count} calls at {sites}"),
+                };
+                let recursive = if node.recursive { " (recursive)" } else { "" };
+                let indent = "  ".repeat(level);
+                writeln!(
+                    writer,
+                    "{indent}{arrow} {} ({calls}){recursive}",
+                    describe(node.function)
+                )?;
+                stack.extend(node.children.iter().rev().map(|it| (level + 1, it)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str) -> FunctionInfo {
+        FunctionInfo {
+            name: name.to_owned(),
+            container: None,
+            file_path: "/project/src/lib.rs".to_owned(),
+            line: u32::from(name.as_bytes()[0]),
+            column: 1,
+            module: "project".to_owned(),
+            generated: false,
+        }
+    }
+
+    /// Fills the callees of `from` in the call graph of `edges`, returning the analyzed functions.
+    fn fill(calls: &mut Calls, edges: &[(&str, &str)], from: &str, depth: u32) -> Vec<String> {
+        let mut analyzed = Vec::new();
+        let mut analyze = |caller: &FunctionInfo| {
+            analyzed.push(caller.name.clone());
+            let callees = edges.iter().filter(|(from, _)| *from == caller.name);
+            let relations = callees.map(|(from, to)| CallRelation {
+                caller: function(from),
+                callee: function(to),
+                call_site_line: 1,
+                call_site_column: 1,
+                dispatch: Default::default(),
+                confidence: 1.0,
+                expanded_from: None,
+                call_kind: Default::default(),
+                callee_crate: None,
+                call_snippet: None,
+                instantiated_with: None,
+            });
+            Ok(Some(relations.collect()))
+        };
+        calls.fill_with(&function(from), depth, false, &mut analyze).unwrap();
+        analyzed
+    }
+
+    /// The callees below `from`, one line per node indented by its level.
+    fn tree(calls: &Calls, from: &str, depth: u32) -> Vec<String> {
+        fn walk(nodes: &[Node<'_>], level: usize, lines: &mut Vec<String>) {
+            for node in nodes {
+                lines.push(format!("{}{}", "  ".repeat(level), node.function.name));
+                walk(&node.children, level + 1, lines);
+            }
+        }
+        let root = function(from);
+        let mut lines = Vec::new();
+        walk(&calls.branches(&root, depth, false, &mut vec![key(&root)]), 0, &mut lines);
+        lines
+    }
+
+    #[test]
+    fn diamond_is_filled_as_deep_as_its_shortest_branch() {
+        let check = |edges: &[(&str, &str)], expected: &[&str]| {
+            let mut calls = Calls::default();
+            let analyzed = fill(&mut calls, edges, "a", 3);
+            assert_eq!(tree(&calls, "a", 3), expected);
+            assert_eq!(analyzed.len(), analyzed.iter().unique().count(), "{analyzed:?}");
+        };
+        // `c` is first reached through `b`, a level deeper than directly from `a`.
+        check(
+            &[("a", "b"), ("a", "c"), ("b", "c"), ("c", "d"), ("d", "e")],
+            &["b", "  c", "    d", "c", "  d", "    e"],
+        );
+        check(
+            &[("a", "c"), ("a", "b"), ("b", "c"), ("c", "d"), ("d", "e")],
+            &["c", "  d", "    e", "b", "  c", "    d"],
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! Detects near-duplicate function bodies.
+//!
+//! Every body is reduced to its sequence of token kinds, so renamed identifiers and changed
+//! literals still match, and compared through the Jaccard similarity of its overlapping k-grams.
+//! Copy-pasted handlers that only lost a single check end up with a high but not perfect score.
+
+use std::{fs, hash::BuildHasher};
+
+use anyhow::Result;
+use hir::Semantics;
+use itertools::Itertools;
+use rustc_hash::{FxBuildHasher, FxHashMap, FxHashSet};
+use serde::Serialize;
+use syntax::{
+    AstNode, SyntaxKind,
+    ast::{self, HasName},
+};
+
+use crate::cli::{
+    code_graph::{LoadOptions, LoadedProject, project_files},
+    flags,
+    function_analyzer::convert_to_relative_path,
+};
+
+const DEFAULT_MIN_TOKENS: usize = 40;
+const DEFAULT_SIMILARITY: f64 = 0.8;
+/// Length of the token k-grams bodies are compared by.
+const SHINGLE_LEN: usize = 5;
+/// K-grams shared by more bodies than this are boilerplate and don't indicate a clone.
+const MAX_SHINGLE_BODIES: usize = 64;
+
+impl flags::Clones {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("clones", path = %self.path.display()).entered();
+        let min_tokens = self.min_tokens.unwrap_or(DEFAULT_MIN_TOKENS);
+        let similarity = self.similarity.unwrap_or(DEFAULT_SIMILARITY);
+        if !(0.0..=1.0).contains(&similarity) {
+            anyhow::bail!("--similarity must be between 0.0 and 1.0");
+        }
+
+        eprintln!("Loading workspace...");
+        let project = LoadedProject::load(
+            &self.path,
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+
+        eprintln!("Hashing function bodies...");
+        let bodies = collect_bodies(&project, min_tokens);
+        let groups = clone_groups(&bodies, similarity);
+        eprintln!("Found {} clone groups among {} function bodies", groups.len(), bodies.len());
+
+        let json = serde_json::to_string_pretty(&groups)?;
+        match &self.output {
+            Some(path) => fs::write(path, json)?,
+            None => println!("{json}"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct CloneSpan {
+    name: String,
+    file: String,
+    start_line: u32,
+    end_line: u32,
+    tokens: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct CloneGroup {
+    /// Lowest similarity of the pairs that joined the group, `1.0` for exact clones.
+    similarity: f64,
+    functions: Vec<CloneSpan>,
+}
+
+struct Body {
+    span: CloneSpan,
+    /// Hash of the whole normalized token sequence.
+    hash: u64,
+    shingles: FxHashSet<u64>,
+}
+
+fn collect_bodies(project: &LoadedProject, min_tokens: usize) -> Vec<Body> {
+    let analysis = project.analysis();
+    let sema = Semantics::new(&project.db);
+    let mut bodies = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        for func in file.syntax().descendants().filter_map(ast::Fn::cast) {
+            let (Some(name), Some(body)) = (func.name(), func.body()) else { continue };
+            let tokens = normalized_tokens(body.syntax());
+            if tokens.len() < min_tokens {
+                continue;
+            }
+            let range = func.syntax().text_range();
+            bodies.push(Body {
+                span: CloneSpan {
+                    name: name.text().to_string(),
+                    file: convert_to_relative_path(&file_path, &project.project_root),
+                    start_line: line_index.line_col(range.start()).line + 1,
+                    end_line: line_index.line_col(range.end()).line + 1,
+                    tokens: tokens.len(),
+                },
+                hash: FxBuildHasher.hash_one(&tokens),
+                shingles: tokens
+                    .windows(SHINGLE_LEN)
+                    .map(|it| FxBuildHasher.hash_one(it))
+                    .collect(),
+            });
+        }
+    }
+    bodies
+}
+
+/// The kinds of the non-trivia tokens below `node`, with every identifier and literal mapped to
+/// a single kind so that renamings don't hide a clone.
+fn normalized_tokens(node: &syntax::SyntaxNode) -> Vec<SyntaxKind> {
+    node.descendants_with_tokens()
+        .filter_map(|it| it.into_token())
+        .map(|token| token.kind())
+        .filter(|kind| !kind.is_trivia())
+        .map(|kind| match kind {
+            SyntaxKind::IDENT | SyntaxKind::LIFETIME_IDENT => SyntaxKind::IDENT,
+            kind if kind.is_literal() => SyntaxKind::INT_NUMBER,
+            kind => kind,
+        })
+        .collect()
+}
+
+fn clone_groups(bodies: &[Body], similarity: f64) -> Vec<CloneGroup> {
+    let mut groups = UnionFind::new(bodies.len());
+
+    // Exact clones share their hash, even if their k-grams are too common to be compared below.
+    let by_hash = (0..bodies.len()).into_group_map_by(|&idx| bodies[idx].hash);
+    for members in by_hash.values() {
+        for &idx in &members[1..] {
+            groups.union(members[0], idx, 1.0);
+        }
+    }
+
+    let mut by_shingle: FxHashMap<u64, Vec<usize>> = FxHashMap::default();
+    for (idx, body) in bodies.iter().enumerate() {
+        for &shingle in &body.shingles {
+            by_shingle.entry(shingle).or_default().push(idx);
+        }
+    }
+    let mut shared: FxHashMap<(usize, usize), usize> = FxHashMap::default();
+    for members in by_shingle.values().filter(|it| it.len() <= MAX_SHINGLE_BODIES) {
+        for (&a, &b) in members.iter().tuple_combinations() {
+            *shared.entry((a, b)).or_default() += 1;
+        }
+    }
+    for ((a, b), shared) in shared {
+        let union = bodies[a].shingles.len() + bodies[b].shingles.len() - shared;
+        let jaccard = shared as f64 / union as f64;
+        if jaccard >= similarity {
+            groups.union(a, b, jaccard);
+        }
+    }
+
+    let mut result: Vec<CloneGroup> = (0..bodies.len())
+        .into_group_map_by(|&idx| groups.find(idx))
+        .into_iter()
+        .filter(|(_, members)| members.len() > 1)
+        .map(|(root, members)| CloneGroup {
+            similarity: groups.similarity[root],
+            functions: members
+                .into_iter()
+                .map(|idx| bodies[idx].span.clone())
+                .sorted_by(|a, b| (&a.file, a.start_line).cmp(&(&b.file, b.start_line)))
+                .collect(),
+        })
+        .collect();
+    result.sort_by(|a, b| {
+        let size = |group: &CloneGroup| group.functions.iter().map(|f| f.tokens).sum::<usize>();
+        size(b).cmp(&size(a))
+    });
+    result
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+    /// Lowest similarity of the pairs merged into each root.
+    similarity: Vec<f64>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> UnionFind {
+        UnionFind { parent: (0..len).collect(), similarity: vec![1.0; len] }
+    }
+
+    fn find(&mut self, idx: usize) -> usize {
+        let mut root = idx;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut idx = idx;
+        while self.parent[idx] != root {
+            let next = self.parent[idx];
+            self.parent[idx] = root;
+            idx = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize, similarity: f64) {
+        let (a, b) = (self.find(a), self.find(b));
+        let merged = self.similarity[a].min(self.similarity[b]).min(similarity);
+        self.parent[b] = a;
+        self.similarity[a] = merged;
+    }
+}
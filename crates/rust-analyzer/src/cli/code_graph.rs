@@ -0,0 +1,1095 @@
+//! In-memory model of a project's call graph, Anchor account structs and instructions, and
+//! enums.
+//!
+//! This is the shared representation consumed by the `graph` subcommands: it is built once
+//! from a loaded workspace and then serialized or walked by the individual frontends.
+
+use std::{collections::VecDeque, env, fs, path::Path};
+
+use anyhow::Result;
+use hir::{
+    ChangeWithProcMacros, HasVisibility as _, HirDisplay, ModuleDef, PathResolution, Semantics,
+    SemanticsScope,
+};
+use ide::{Analysis, AnalysisHost, LineIndex, RootDatabase};
+use ide_db::base_db::salsa;
+use load_cargo::{LoadCargoConfig, ProcMacroServerChoice, load_workspace};
+use project_model::{CargoConfig, ProjectManifest, ProjectWorkspace, RustLibSource};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use syntax::{
+    AstNode, Edition, SourceFile, SyntaxKind, SyntaxNode, WalkEvent,
+    ast::{self, HasAttrs, HasGenericArgs, HasModuleItem, HasName, HasVisibility},
+};
+use vfs::{AbsPathBuf, FileId, Vfs, VfsPath};
+
+use crate::cli::{
+    account_constraints::{Constraint, account_constraints, link_field_types},
+    analysis_progress::Progress,
+    anchor_checks,
+    authorities::{Authority, extract_authorities},
+    cpi_calls::{CpiCall, extract_cpi_calls},
+    custom_rules,
+    error_codes::{ErrorCodeEnum, extract_error_codes, link_constraint_errors},
+    events::{GraphEvent, extract_events},
+    findings::Finding,
+    flags::ProgressFormat,
+    function_analyzer::{
+        self, CallRelation, FunctionFilter, FunctionInfo, convert_to_relative_path,
+        is_external_path,
+    },
+    governance::{PrivilegedInstruction, extract_governance},
+    lamport_flows::{InstructionLamportFlows, extract_lamport_flows},
+    native_programs::{NativeProgram, extract_native_programs},
+    pdas::{Pda, extract_pdas, link_pda_programs},
+    programs::{AnchorProgram, extract_programs},
+    state_access::{StateAccess, extract_state_access},
+    state_accounts::{StateAccount, extract_state_accounts},
+    sysvars::{InstructionSysvars, extract_sysvars},
+    token_operations::{InstructionTokenOperations, extract_token_operations},
+    zero_copy::{ZeroCopyType, extract_zero_copy_types},
+};
+
+/// Options shared by every command that needs to load a workspace.
+pub(super) struct LoadOptions<'a> {
+    pub(super) disable_build_scripts: bool,
+    pub(super) disable_proc_macros: bool,
+    pub(super) proc_macro_srv: Option<&'a Path>,
+}
+
+/// A loaded workspace together with the root all reported paths are relative to.
+pub(super) struct LoadedProject {
+    pub(super) db: RootDatabase,
+    pub(super) vfs: Vfs,
+    pub(super) host: AnalysisHost,
+    pub(super) project_root: AbsPathBuf,
+    pub(super) workspace: ProjectWorkspace,
+}
+
+impl LoadedProject {
+    pub(super) fn load(path: &Path, options: LoadOptions<'_>) -> Result<LoadedProject> {
+        let _p = tracing::info_span!("load_workspace", path = %path.display()).entered();
+        let project_root = AbsPathBuf::assert_utf8(env::current_dir()?.join(path));
+        let manifest = ProjectManifest::discover_single(&project_root)?;
+        let cargo_config =
+            CargoConfig { sysroot: Some(RustLibSource::Discover), ..Default::default() };
+
+        let load_cargo_config = LoadCargoConfig {
+            load_out_dirs_from_check: !options.disable_build_scripts,
+            with_proc_macro_server: if options.disable_proc_macros {
+                ProcMacroServerChoice::None
+            } else {
+                match options.proc_macro_srv {
+                    Some(path) => {
+                        ProcMacroServerChoice::Explicit(AbsPathBuf::assert_utf8(path.to_owned()))
+                    }
+                    None => ProcMacroServerChoice::Sysroot,
+                }
+            },
+            prefill_caches: false,
+        };
+
+        let ws = ProjectWorkspace::load(manifest, &cargo_config, &|_| {})?;
+        let (db, vfs, _proc_macro) =
+            load_workspace(ws.clone(), &cargo_config.extra_env, &load_cargo_config)?;
+        let host = AnalysisHost::with_database(db.clone());
+        Ok(LoadedProject { db, vfs, host, project_root, workspace: ws })
+    }
+
+    pub(super) fn analysis(&self) -> Analysis {
+        self.host.analysis()
+    }
+
+    /// Re-reads loaded files after they changed on disk, keeping the analysis of everything
+    /// else.
+    pub(super) fn reload_files(&mut self, paths: &[AbsPathBuf]) {
+        for path in paths {
+            let path = VfsPath::from(path.clone());
+            // Files outside of the loaded crates would need a new source root.
+            if self.vfs.file_id(&path).is_some() {
+                let contents = path.as_path().and_then(|it| fs::read(it).ok());
+                self.vfs.set_file_contents(path, contents);
+            }
+        }
+        let mut change = ChangeWithProcMacros::default();
+        for (_, file) in self.vfs.take_changes() {
+            let text = match file.change {
+                vfs::Change::Create(bytes, _) | vfs::Change::Modify(bytes, _) => {
+                    String::from_utf8(bytes).ok()
+                }
+                vfs::Change::Delete => None,
+            };
+            change.change_file(file.file_id, text);
+        }
+        // Writing to the database waits until every other handle to it is gone, the host's
+        // included.
+        self.host = AnalysisHost::default();
+        self.db.apply_change(change);
+        self.host = AnalysisHost::with_database(self.db.clone());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct GraphFunction {
+    pub(super) id: usize,
+    pub(super) name: String,
+    pub(super) file: String,
+    pub(super) line: u32,
+    pub(super) column: u32,
+    pub(super) module: String,
+    /// Whether the function lives outside of the project (a dependency or the sysroot).
+    pub(super) external: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub(super) struct GraphCall {
+    pub(super) caller: usize,
+    pub(super) callee: usize,
+    pub(super) line: u32,
+    pub(super) column: u32,
+}
+
+/// A `#[derive(Accounts)]` struct.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct AccountStruct {
+    pub(super) name: String,
+    pub(super) file: String,
+    pub(super) line: u32,
+    pub(super) module: String,
+    /// Like `pub` or `pub(crate)`, `private` when only its module sees it.
+    pub(super) visibility: String,
+    pub(super) fields: Vec<AccountField>,
+    /// The paths of the instructions taking the struct as their `Context<T>`.
+    pub(super) instructions: Vec<String>,
+    /// The instruction arguments its constraints can read, declared with `#[instruction(...)]`.
+    /// Typed like the handler parameters of the same name once linked to its instructions.
+    pub(super) instruction_args: Vec<InstructionParam>,
+}
+
+impl AccountStruct {
+    pub(super) fn path(&self) -> String {
+        qualify(&self.module, &self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct AccountField {
+    pub(super) name: String,
+    pub(super) line: u32,
+    pub(super) visibility: String,
+    pub(super) ty: String,
+    /// The state type wrapped by `Account<'info, T>` and friends, if any.
+    pub(super) account_type: Option<String>,
+    /// The constraints of the field's `#[account(...)]` attributes.
+    pub(super) constraints: Vec<Constraint>,
+}
+
+/// A handler of an Anchor `#[program]` module, one instruction of the program.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct Instruction {
+    pub(super) name: String,
+    pub(super) file: String,
+    pub(super) line: u32,
+    /// The line the body of the handler ends on.
+    pub(super) end_line: u32,
+    /// Path of the `#[program]` module.
+    pub(super) module: String,
+    /// The accounts struct `T` of the `Context<T>` parameter.
+    pub(super) accounts: Option<String>,
+    /// The path of the account struct `accounts` resolves to, none when it isn't one.
+    pub(super) accounts_struct: Option<String>,
+    /// The instruction arguments, the parameters besides the context.
+    pub(super) params: Vec<InstructionParam>,
+    /// None when the handler returns `()`.
+    pub(super) return_type: Option<String>,
+    /// The guards of its `#[access_control(...)]` attributes, run before the handler.
+    pub(super) access_control: Vec<AccessGuard>,
+}
+
+impl Instruction {
+    pub(super) fn path(&self) -> String {
+        qualify(&self.module, &self.name)
+    }
+}
+
+/// A guard given to `#[access_control(...)]`, like `Swap::check(&ctx)`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct AccessGuard {
+    /// The call, stripped of whitespace like Anchor reads it.
+    pub(super) call: String,
+    /// The line of the attribute.
+    pub(super) line: u32,
+    #[serde(skip)]
+    column: u32,
+    /// The path of the guard function in the call graph, none when it isn't in it.
+    pub(super) function: Option<String>,
+    /// Where the guard function is defined, none when the call doesn't resolve.
+    pub(super) function_file: Option<String>,
+    pub(super) function_line: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct InstructionParam {
+    pub(super) name: String,
+    pub(super) ty: String,
+}
+
+/// An enum of the project, like the state machine of an account or the instruction set of a
+/// native program.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct GraphEnum {
+    pub(super) name: String,
+    pub(super) file: String,
+    pub(super) line: u32,
+    pub(super) module: String,
+    /// The traits of its `#[derive(...)]` attributes, like `AnchorSerialize`.
+    pub(super) derives: Vec<String>,
+    pub(super) variants: Vec<EnumVariant>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct EnumVariant {
+    pub(super) name: String,
+    /// The value of the variant, explicit or following the previous one. None when it can't be
+    /// evaluated.
+    pub(super) discriminant: Option<i128>,
+    pub(super) fields: Vec<VariantField>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct VariantField {
+    /// None for the fields of tuple variants.
+    pub(super) name: Option<String>,
+    pub(super) ty: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub(super) struct CodeGraph {
+    pub(super) functions: Vec<GraphFunction>,
+    pub(super) calls: Vec<GraphCall>,
+    pub(super) account_structs: Vec<AccountStruct>,
+    pub(super) instructions: Vec<Instruction>,
+    pub(super) enums: Vec<GraphEnum>,
+    pub(super) error_codes: Vec<ErrorCodeEnum>,
+    pub(super) events: Vec<GraphEvent>,
+    pub(super) state_accounts: Vec<StateAccount>,
+    pub(super) zero_copy_types: Vec<ZeroCopyType>,
+    pub(super) cpi_calls: Vec<CpiCall>,
+    pub(super) token_operations: Vec<InstructionTokenOperations>,
+    pub(super) sysvars: Vec<InstructionSysvars>,
+    pub(super) lamport_flows: Vec<InstructionLamportFlows>,
+    pub(super) programs: Vec<AnchorProgram>,
+    pub(super) native_programs: Vec<NativeProgram>,
+    pub(super) pdas: Vec<Pda>,
+    pub(super) authorities: Vec<Authority>,
+    pub(super) state_access: Vec<StateAccess>,
+    pub(super) governance: Vec<PrivilegedInstruction>,
+    /// What the Anchor checks of [`anchor_checks`] report on the program.
+    pub(super) findings: Vec<Finding>,
+}
+
+impl CodeGraph {
+    pub(super) fn build(project: &LoadedProject) -> Result<CodeGraph> {
+        let _p = tracing::info_span!("CodeGraph::build").entered();
+        let analysis = project.analysis();
+
+        eprintln!("Extracting functions...");
+        let functions = function_analyzer::extract_all_functions(
+            &project.db,
+            &project.vfs,
+            &project.project_root,
+            &FunctionFilter::default(),
+        )?;
+        eprintln!("Found {} functions", functions.len());
+
+        eprintln!("Analyzing call relationships...");
+        let relations = function_analyzer::analyze_call_relationships(
+            &analysis,
+            &functions,
+            &project.vfs,
+            &project.db,
+            &project.project_root,
+            false,
+            false,
+            &Progress::new(ProgressFormat::Text, "functions"),
+        )?;
+        eprintln!("Found {} call relationships", relations.len());
+
+        let mut graph = CodeGraph::from_relations(&functions, &relations, &project.project_root);
+
+        eprintln!("Extracting account structs...");
+        graph.account_structs = extract_account_structs(project, &analysis)?;
+        eprintln!("Found {} account structs", graph.account_structs.len());
+
+        eprintln!("Extracting instructions...");
+        graph.instructions = extract_instructions(project, &analysis)?;
+        eprintln!("Found {} instructions", graph.instructions.len());
+        link_instructions(&mut graph.account_structs, &mut graph.instructions);
+        graph.link_access_control();
+
+        eprintln!("Extracting enums...");
+        graph.enums = extract_enums(project, &analysis)?;
+        eprintln!("Found {} enums", graph.enums.len());
+
+        eprintln!("Extracting error codes...");
+        graph.error_codes = extract_error_codes(project, &analysis)?;
+        eprintln!("Found {} error code enums", graph.error_codes.len());
+        link_constraint_errors(&mut graph.account_structs, &graph.error_codes);
+
+        eprintln!("Extracting events...");
+        graph.events = extract_events(project, &analysis, &graph)?;
+        eprintln!("Found {} events", graph.events.len());
+
+        eprintln!("Extracting state accounts...");
+        graph.state_accounts = extract_state_accounts(project, &analysis)?;
+        eprintln!("Found {} state accounts", graph.state_accounts.len());
+
+        eprintln!("Extracting zero-copy types...");
+        graph.zero_copy_types = extract_zero_copy_types(project, &analysis)?;
+        eprintln!("Found {} zero-copy types", graph.zero_copy_types.len());
+
+        eprintln!("Extracting CPI calls...");
+        graph.cpi_calls = extract_cpi_calls(project, &analysis, &graph)?;
+        eprintln!("Found {} CPI calls", graph.cpi_calls.len());
+        graph.token_operations = extract_token_operations(&graph);
+        eprintln!("Found token operations in {} instructions", graph.token_operations.len());
+
+        eprintln!("Extracting sysvar uses...");
+        graph.sysvars = extract_sysvars(project, &analysis, &graph)?;
+        eprintln!("Found sysvar uses in {} instructions", graph.sysvars.len());
+
+        eprintln!("Extracting lamport flows...");
+        graph.lamport_flows = extract_lamport_flows(project, &analysis, &graph)?;
+        eprintln!("Found lamport flows in {} instructions", graph.lamport_flows.len());
+
+        eprintln!("Extracting programs...");
+        graph.programs = extract_programs(project, &graph)?;
+        eprintln!("Found {} programs", graph.programs.len());
+
+        eprintln!("Extracting native programs...");
+        graph.native_programs = extract_native_programs(project, &analysis, &graph)?;
+        eprintln!("Found {} native programs", graph.native_programs.len());
+
+        eprintln!("Extracting PDAs...");
+        graph.pdas = extract_pdas(&graph);
+        link_pda_programs(&mut graph.programs, &graph.pdas);
+        eprintln!("Found {} PDAs", graph.pdas.len());
+
+        eprintln!("Extracting authorities...");
+        graph.authorities = extract_authorities(&graph);
+        eprintln!("Found {} authorities", graph.authorities.len());
+
+        eprintln!("Extracting state access...");
+        graph.state_access = extract_state_access(project, &analysis, &graph)?;
+        eprintln!("Found the access of {} state accounts", graph.state_access.len());
+
+        eprintln!("Extracting governance...");
+        graph.governance = extract_governance(&graph);
+        eprintln!("Found {} privileged instructions", graph.governance.len());
+
+        eprintln!("Running Anchor checks...");
+        graph.findings = anchor_checks::check(project, &analysis, &graph);
+        eprintln!("Anchor checks reported {} findings", graph.findings.len());
+
+        let rules = custom_rules::load(&project.project_root)?;
+        if !rules.is_empty() {
+            eprintln!("Running {} custom rules...", rules.len());
+            let findings = custom_rules::check(&rules, &graph);
+            eprintln!("Custom rules reported {} findings", findings.len());
+            graph.findings.extend(findings);
+        }
+
+        Ok(graph)
+    }
+
+    /// Builds the call graph part of the model from already extracted functions and calls.
+    pub(super) fn from_relations(
+        functions: &[FunctionInfo],
+        relations: &[CallRelation],
+        project_root: &AbsPathBuf,
+    ) -> CodeGraph {
+        let mut graph = CodeGraph::default();
+        let mut ids = FxHashMap::default();
+        for function in functions {
+            graph.intern_function(&mut ids, function, project_root);
+        }
+        for CallRelation { caller, callee, call_site_line, call_site_column, .. } in relations {
+            let caller = graph.intern_function(&mut ids, caller, project_root);
+            let callee = graph.intern_function(&mut ids, callee, project_root);
+            graph.calls.push(GraphCall {
+                caller,
+                callee,
+                line: *call_site_line,
+                column: *call_site_column,
+            });
+        }
+        graph
+    }
+
+    fn intern_function(
+        &mut self,
+        ids: &mut FxHashMap<(String, u32, String), usize>,
+        function: &FunctionInfo,
+        project_root: &AbsPathBuf,
+    ) -> usize {
+        let key = (function.file_path.clone(), function.line, function.name.clone());
+        *ids.entry(key).or_insert_with(|| {
+            let id = self.functions.len();
+            self.functions.push(GraphFunction {
+                id,
+                name: function.name.clone(),
+                file: convert_to_relative_path(&function.file_path, project_root),
+                line: function.line,
+                column: function.column,
+                module: function.module.clone(),
+                external: is_external_path(&function.file_path, project_root),
+            });
+            id
+        })
+    }
+
+    /// The function named `name` whose definition in `file` starts last at or before `line`.
+    pub(super) fn function_at(&self, file: &str, name: &str, line: u32) -> Option<usize> {
+        self.functions
+            .iter()
+            .filter(|it| it.file == file && it.name == name && it.line <= line)
+            .max_by_key(|it| it.line)
+            .map(|it| it.id)
+    }
+
+    /// The project function whose definition in `file` starts last at or before `line`, the one
+    /// holding the line as far as the start lines tell.
+    pub(super) fn function_enclosing(&self, file: &str, line: u32) -> Option<usize> {
+        self.functions
+            .iter()
+            .filter(|it| !it.external && it.file == file && it.line <= line)
+            .max_by_key(|it| it.line)
+            .map(|it| it.id)
+    }
+
+    /// The functions reachable from the handler of every instruction, by instruction path.
+    pub(super) fn instruction_reach(&self) -> Vec<(String, FxHashSet<usize>)> {
+        let mut callees: FxHashMap<usize, Vec<usize>> = FxHashMap::default();
+        for call in &self.calls {
+            callees.entry(call.caller).or_default().push(call.callee);
+        }
+        self.instructions
+            .iter()
+            .filter_map(|instruction| {
+                let handler =
+                    self.function_at(&instruction.file, &instruction.name, instruction.line)?;
+                let mut reached = FxHashSet::from_iter([handler]);
+                let mut queue = VecDeque::from([handler]);
+                while let Some(function) = queue.pop_front() {
+                    for &callee in callees.get(&function).into_iter().flatten() {
+                        if reached.insert(callee) {
+                            queue.push_back(callee);
+                        }
+                    }
+                }
+                Some((instruction.path(), reached))
+            })
+            .collect()
+    }
+
+    /// Resolves the guards of every instruction to their function and puts them in the call
+    /// graph, as calls of its handler.
+    fn link_access_control(&mut self) {
+        let handlers: Vec<_> = self
+            .instructions
+            .iter()
+            .map(|it| self.function_at(&it.file, &it.name, it.line))
+            .collect();
+        for (instruction, handler) in self.instructions.iter_mut().zip(handlers) {
+            for guard in &mut instruction.access_control {
+                let Some(callee) = self.functions.iter().find(|it| {
+                    Some(&it.file) == guard.function_file.as_ref()
+                        && Some(it.line) == guard.function_line
+                }) else {
+                    continue;
+                };
+                guard.function = Some(qualify(&callee.module, &callee.name));
+                let Some(caller) = handler else { continue };
+                // Expanded `access_control` attributes already call their guards.
+                if !self.calls.iter().any(|it| it.caller == caller && it.callee == callee.id) {
+                    self.calls.push(GraphCall {
+                        caller,
+                        callee: callee.id,
+                        line: guard.line,
+                        column: guard.column,
+                    });
+                }
+            }
+        }
+    }
+
+    /// The path of the function `id`, qualified by its module.
+    pub(super) fn function_path(&self, id: usize) -> String {
+        let function = &self.functions[id];
+        qualify(&function.module, &function.name)
+    }
+}
+
+/// Collects every `#[derive(Accounts)]` struct declared in project files.
+fn extract_account_structs(
+    project: &LoadedProject,
+    analysis: &Analysis,
+) -> Result<Vec<AccountStruct>> {
+    let _p = tracing::info_span!("extract_account_structs").entered();
+    let sema = Semantics::new(&project.db);
+    let mut structs = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        // Parsed by `sema` so the visibilities can be resolved.
+        let file = sema.parse_guess_edition(file_id);
+        let module = sema
+            .file_to_module_def(file_id)
+            .map(|module| module_path(&project.db, module))
+            .unwrap_or_default();
+
+        for strukt in file.syntax().descendants().filter_map(ast::Struct::cast) {
+            if !derives(&strukt, "Accounts") {
+                continue;
+            }
+            let Some(name) = strukt.name() else { continue };
+            let visibilities = Visibilities::of(&sema, &strukt);
+            let mut fields = match strukt.field_list() {
+                Some(ast::FieldList::RecordFieldList(fields)) => fields
+                    .fields()
+                    .filter_map(|field| {
+                        let (name, ty) = (field.name()?, field.ty()?);
+                        Some(AccountField {
+                            name: name.text().to_string(),
+                            line: line_index.line_col(name.syntax().text_range().start()).line + 1,
+                            visibility: visibilities.field(&field),
+                            ty: ty.syntax().text().to_string(),
+                            account_type: wrapped_account_type(&ty),
+                            constraints: account_constraints(&field),
+                        })
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            link_field_types(&mut fields);
+            structs.push(AccountStruct {
+                name: name.text().to_string(),
+                file: convert_to_relative_path(&file_path, &project.project_root),
+                line: line_index.line_col(name.syntax().text_range().start()).line + 1,
+                module: module.clone(),
+                visibility: visibilities.item,
+                fields,
+                instructions: Vec::new(),
+                instruction_args: instruction_args(&strukt),
+            });
+        }
+    }
+    structs.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(structs)
+}
+
+/// The visibilities of a struct and its fields, rendered like they are declared.
+pub(super) struct Visibilities {
+    pub(super) item: String,
+    /// The fields by name, resolved through the struct.
+    fields: FxHashMap<String, String>,
+}
+
+impl Visibilities {
+    /// Resolves the visibilities of `strukt`, which must come from a file parsed by `sema`.
+    pub(super) fn of(sema: &Semantics<'_, RootDatabase>, strukt: &ast::Struct) -> Visibilities {
+        let Some(def) = sema.to_def(strukt) else {
+            return Visibilities {
+                item: declared_visibility(strukt),
+                fields: FxHashMap::default(),
+            };
+        };
+        let db = sema.db;
+        let module = def.module(db);
+        let fields = def
+            .fields(db)
+            .into_iter()
+            .map(|field| {
+                let name = field.name(db).display(db, Edition::CURRENT).to_string();
+                (name, visibility_name(db, field.visibility(db), module))
+            })
+            .collect();
+        Visibilities { item: visibility_name(db, def.visibility(db), module), fields }
+    }
+
+    pub(super) fn field(&self, field: &ast::RecordField) -> String {
+        let name = field.name().map(|it| it.text().to_string()).unwrap_or_default();
+        self.fields.get(&name).cloned().unwrap_or_else(|| declared_visibility(field))
+    }
+}
+
+/// The visibility of an item of `module`, like `pub(crate)`, or `private` when only `module`
+/// sees it. Visibilities are the resolved ones: `pub(super)` right below the crate root is
+/// `pub(crate)`.
+fn visibility_name(db: &RootDatabase, visibility: hir::Visibility, module: hir::Module) -> String {
+    let hir::Visibility::Module(to, _) = visibility else {
+        return match visibility {
+            hir::Visibility::PubCrate(_) => "pub(crate)".to_owned(),
+            _ => "pub".to_owned(),
+        };
+    };
+    let to = hir::Module::from(to);
+    if to == module {
+        "private".to_owned()
+    } else if to.is_crate_root() {
+        "pub(crate)".to_owned()
+    } else if module.parent(db) == Some(to) {
+        "pub(super)".to_owned()
+    } else {
+        format!("pub(in {})", module_path(db, to))
+    }
+}
+
+/// The visibility written on `node`, for items `sema` can't resolve.
+fn declared_visibility(node: &impl HasVisibility) -> String {
+    node.visibility().map_or_else(|| "private".to_owned(), |it| it.syntax().text().to_string())
+}
+
+/// Collects every enum declared in project files.
+fn extract_enums(project: &LoadedProject, analysis: &Analysis) -> Result<Vec<GraphEnum>> {
+    let _p = tracing::info_span!("extract_enums").entered();
+    let sema = Semantics::new(&project.db);
+    let mut enums = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        // Parsed by `sema` so the discriminants can be evaluated.
+        let file = sema.parse_guess_edition(file_id);
+        let module = sema
+            .file_to_module_def(file_id)
+            .map(|module| module_path(&project.db, module))
+            .unwrap_or_default();
+
+        for enum_ in file.syntax().descendants().filter_map(ast::Enum::cast) {
+            let Some(name) = enum_.name() else { continue };
+            let discriminants = discriminants(&sema, &enum_);
+            let variants = enum_
+                .variant_list()
+                .into_iter()
+                .flat_map(|it| it.variants())
+                .filter_map(|variant| {
+                    let name = variant.name()?.text().to_string();
+                    Some(EnumVariant {
+                        discriminant: discriminants.get(&name).copied(),
+                        fields: variant_fields(&variant),
+                        name,
+                    })
+                })
+                .collect();
+            enums.push(GraphEnum {
+                name: name.text().to_string(),
+                file: convert_to_relative_path(&file_path, &project.project_root),
+                line: line_index.line_col(name.syntax().text_range().start()).line + 1,
+                module: module.clone(),
+                derives: derive_names(&enum_),
+                variants,
+            });
+        }
+    }
+    enums.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(enums)
+}
+
+/// The values of the variants of `enum_` by name, leaving out those that can't be evaluated.
+pub(super) fn discriminants(
+    sema: &Semantics<'_, RootDatabase>,
+    enum_: &ast::Enum,
+) -> FxHashMap<String, i128> {
+    // Constant evaluation needs the database attached to the thread.
+    salsa::attach(sema.db, || {
+        let variants = sema.to_def(enum_).map(|it| it.variants(sema.db));
+        variants
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|variant| {
+                let value = variant.eval(sema.db).ok()?;
+                Some((variant.name(sema.db).as_str().to_owned(), value))
+            })
+            .collect()
+    })
+}
+
+fn variant_fields(variant: &ast::Variant) -> Vec<VariantField> {
+    match variant.field_list() {
+        Some(ast::FieldList::RecordFieldList(fields)) => fields
+            .fields()
+            .map(|field| VariantField {
+                name: field.name().map(|it| it.text().to_string()),
+                ty: field.ty().map(|it| it.syntax().text().to_string()).unwrap_or_default(),
+            })
+            .collect(),
+        Some(ast::FieldList::TupleFieldList(fields)) => fields
+            .fields()
+            .map(|field| VariantField {
+                name: None,
+                ty: field.ty().map(|it| it.syntax().text().to_string()).unwrap_or_default(),
+            })
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Collects the handlers of every `#[program]` module declared in project files.
+fn extract_instructions(project: &LoadedProject, analysis: &Analysis) -> Result<Vec<Instruction>> {
+    let _p = tracing::info_span!("extract_instructions").entered();
+    let sema = Semantics::new(&project.db);
+    let mut instructions = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        // Parsed by `sema` so the accounts structs can be resolved.
+        let file = sema.parse_guess_edition(file_id);
+        let module = sema
+            .file_to_module_def(file_id)
+            .map(|module| module_path(&project.db, module))
+            .unwrap_or_default();
+
+        for program in file.syntax().descendants().filter_map(ast::Module::cast) {
+            if !program.attrs().any(|attr| attr.simple_name().as_deref() == Some("program")) {
+                continue;
+            }
+            let (Some(program_name), Some(items)) = (program.name(), program.item_list()) else {
+                continue;
+            };
+            let program_module = qualify(&module, program_name.text().as_str());
+            for item in items.items() {
+                let ast::Item::Fn(handler) = item else { continue };
+                let Some(name) = handler.name() else { continue };
+                let line_of = |offset| line_index.line_col(offset).line + 1;
+                let mut accounts = None;
+                let mut params = Vec::new();
+                for param in handler.param_list().into_iter().flat_map(|it| it.params()) {
+                    let Some(ty) = param.ty() else { continue };
+                    if accounts.is_none()
+                        && let Some(context) = context_accounts(&ty)
+                    {
+                        accounts = Some(context);
+                        continue;
+                    }
+                    params.push(InstructionParam {
+                        name: param
+                            .pat()
+                            .map(|it| it.syntax().text().to_string())
+                            .unwrap_or_default(),
+                        ty: resolved_type(&sema, &ty),
+                    });
+                }
+                instructions.push(Instruction {
+                    name: name.text().to_string(),
+                    file: convert_to_relative_path(&file_path, &project.project_root),
+                    line: line_of(name.syntax().text_range().start()),
+                    end_line: line_of(handler.syntax().text_range().end()),
+                    module: program_module.clone(),
+                    accounts: accounts.as_ref().map(type_name),
+                    accounts_struct: accounts.and_then(|ty| resolve_struct(&sema, &ty)),
+                    params,
+                    return_type: handler
+                        .ret_type()
+                        .and_then(|it| it.ty())
+                        .map(|it| it.syntax().text().to_string()),
+                    access_control: access_guards(project, analysis, &sema, &handler, &line_index),
+                });
+            }
+        }
+    }
+    instructions.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(instructions)
+}
+
+/// The guard calls of the `#[access_control(...)]` attributes of `handler`, with the function
+/// each of them resolves to.
+fn access_guards(
+    project: &LoadedProject,
+    analysis: &Analysis,
+    sema: &Semantics<'_, RootDatabase>,
+    handler: &ast::Fn,
+    line_index: &LineIndex,
+) -> Vec<AccessGuard> {
+    let scope = sema.scope(handler.syntax());
+    let mut guards = Vec::new();
+    for attr in handler.attrs() {
+        if attr.simple_name().as_deref() != Some("access_control") {
+            continue;
+        }
+        let Some(args) = attr.token_tree() else { continue };
+        let position = line_index.line_col(attr.syntax().text_range().start());
+        // Anchor juxtaposes the calls without separators and splits them at every `)`.
+        let mut args = args.syntax().text().to_string();
+        args.retain(|c| !c.is_whitespace());
+        let args = args.strip_prefix('(').and_then(|it| it.strip_suffix(')')).unwrap_or_default();
+        for call in args.split(')').filter(|it| !it.is_empty()) {
+            let call = format!("{call})");
+            let function = match parse_expr(&call) {
+                Some(ast::Expr::CallExpr(call)) => match call.expr() {
+                    Some(ast::Expr::PathExpr(callee)) => callee.path(),
+                    _ => None,
+                },
+                _ => None,
+            }
+            .zip(scope.as_ref())
+            .and_then(|(path, scope)| guard_function(sema, scope, &path));
+            let location = function.and_then(|function| {
+                let source = sema.source(function)?;
+                let file_id = source.file_id.file_id()?.file_id(sema.db);
+                let name = source.value.name()?;
+                let line = analysis.file_line_index(file_id).ok()?;
+                let path = project.vfs.file_path(file_id).to_string();
+                Some((
+                    convert_to_relative_path(&path, &project.project_root),
+                    line.line_col(name.syntax().text_range().start()).line + 1,
+                ))
+            });
+            let (function_file, function_line) = location.unzip();
+            guards.push(AccessGuard {
+                call,
+                line: position.line + 1,
+                column: position.col + 1,
+                function: None,
+                function_file,
+                function_line,
+            });
+        }
+    }
+    guards
+}
+
+/// The function a guard call names, either a free one or one of an inherent impl like
+/// `Swap::check`, which scopes don't resolve.
+fn guard_function(
+    sema: &Semantics<'_, RootDatabase>,
+    scope: &SemanticsScope<'_>,
+    path: &ast::Path,
+) -> Option<hir::Function> {
+    if let Some(PathResolution::Def(ModuleDef::Function(function))) =
+        scope.speculative_resolve(path)
+    {
+        return Some(function);
+    }
+    let Some(PathResolution::Def(ModuleDef::Adt(adt))) =
+        scope.speculative_resolve(&path.qualifier()?)
+    else {
+        return None;
+    };
+    let name = path.segment()?.name_ref()?;
+    let db = sema.db;
+    salsa::attach(db, || {
+        hir::Impl::all_for_type(db, adt.ty(db))
+            .into_iter()
+            .filter(|it| it.trait_(db).is_none())
+            .flat_map(|it| it.items(db))
+            .find_map(|item| match item {
+                hir::AssocItem::Function(function) if function.name(db).as_str() == name.text() => {
+                    Some(function)
+                }
+                _ => None,
+            })
+    })
+}
+
+/// The type `ty` resolves to, like `u64` for an alias of it, or its source when it doesn't.
+fn resolved_type(sema: &Semantics<'_, RootDatabase>, ty: &ast::Type) -> String {
+    let krate = sema.scope(ty.syntax()).map(|it| it.krate());
+    match (sema.resolve_type(ty), krate) {
+        (Some(resolved), Some(krate)) if !resolved.contains_unknown() => {
+            salsa::attach(sema.db, || {
+                resolved.display(sema.db, krate.to_display_target(sema.db)).to_string()
+            })
+        }
+        _ => ty.syntax().text().to_string(),
+    }
+}
+
+/// The parameters of the `#[instruction(...)]` attribute of an accounts struct, as written.
+fn instruction_args(strukt: &ast::Struct) -> Vec<InstructionParam> {
+    let Some((_, args)) = strukt
+        .attrs()
+        .filter_map(|attr| attr.as_simple_call())
+        .find(|(name, _)| name == "instruction")
+    else {
+        return Vec::new();
+    };
+    // The arguments are written like the parameters of a function.
+    let file = SourceFile::parse(&format!("fn f{args} {{}}"), Edition::CURRENT).tree();
+    let params = file.syntax().descendants().find_map(ast::ParamList::cast);
+    params
+        .into_iter()
+        .flat_map(|it| it.params())
+        .filter_map(|param| {
+            Some(InstructionParam {
+                name: param.pat()?.syntax().text().to_string(),
+                ty: param.ty()?.syntax().text().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Returns `T` for a `Context<T>` parameter type, like `Swap<'info>` for
+/// `Context<'_, '_, '_, 'info, Swap<'info>>`.
+fn context_accounts(ty: &ast::Type) -> Option<ast::Type> {
+    let ast::Type::PathType(ty) = ty else { return None };
+    let segment = ty.path()?.segment()?;
+    if segment.name_ref()?.text() != "Context" {
+        return None;
+    }
+    let accounts = segment.generic_arg_list()?.generic_args().filter_map(|arg| match arg {
+        ast::GenericArg::TypeArg(arg) => arg.ty(),
+        _ => None,
+    });
+    accounts.last()
+}
+
+/// The name of `ty` without its path and generic arguments, like `Swap` for `crate::Swap<'info>`.
+fn type_name(ty: &ast::Type) -> String {
+    let name = match ty {
+        ast::Type::PathType(ty) => ty.path().and_then(|it| it.segment()?.name_ref()),
+        _ => None,
+    };
+    name.map_or_else(|| ty.syntax().text().to_string(), |it| it.text().to_string())
+}
+
+/// The path of the struct `ty` refers to.
+pub(super) fn resolve_struct(sema: &Semantics<'_, RootDatabase>, ty: &ast::Type) -> Option<String> {
+    let ast::Type::PathType(ty) = ty else { return None };
+    let Some(PathResolution::Def(ModuleDef::Adt(hir::Adt::Struct(strukt)))) =
+        sema.resolve_path(&ty.path()?)
+    else {
+        return None;
+    };
+    let name = strukt.name(sema.db).display(sema.db, Edition::CURRENT).to_string();
+    Some(qualify(&module_path(sema.db, strukt.module(sema.db)), &name))
+}
+
+/// Links every instruction to the account struct of its context, and back. Contexts that
+/// couldn't be resolved fall back to the only account struct of their name, if any.
+fn link_instructions(structs: &mut [AccountStruct], instructions: &mut [Instruction]) {
+    let by_path: FxHashMap<String, usize> =
+        structs.iter().enumerate().map(|(index, it)| (it.path(), index)).collect();
+    let mut by_name: FxHashMap<String, Vec<usize>> = FxHashMap::default();
+    for (index, strukt) in structs.iter().enumerate() {
+        by_name.entry(strukt.name.clone()).or_default().push(index);
+    }
+    for instruction in instructions {
+        let resolved = instruction.accounts_struct.as_ref().and_then(|path| by_path.get(path));
+        let index = resolved.copied().or_else(|| {
+            match by_name.get(instruction.accounts.as_ref()?)?.as_slice() {
+                &[index] => Some(index),
+                _ => None,
+            }
+        });
+        instruction.accounts_struct = index.map(|index| structs[index].path());
+        if let Some(index) = index {
+            structs[index].instructions.push(instruction.path());
+            for arg in &mut structs[index].instruction_args {
+                if let Some(param) = instruction.params.iter().find(|it| it.name == arg.name) {
+                    arg.ty.clone_from(&param.ty);
+                }
+            }
+        }
+    }
+}
+
+/// Joins a module path and the name of an item in it.
+pub(super) fn qualify(module: &str, name: &str) -> String {
+    match module {
+        "" => name.to_owned(),
+        module => format!("{module}::{name}"),
+    }
+}
+
+/// The crate a module path belongs to, its first segment.
+pub(super) fn crate_of(module: &str) -> &str {
+    module.split("::").next().unwrap_or_default()
+}
+
+/// The nodes below `body`, leaving out the functions nested in it, which are visited on their own.
+pub(super) fn body_descendants(body: &SyntaxNode) -> impl Iterator<Item = SyntaxNode> {
+    let mut preorder = body.preorder();
+    std::iter::from_fn(move || {
+        loop {
+            match preorder.next()? {
+                WalkEvent::Enter(node) if ast::Fn::can_cast(node.kind()) => preorder.skip_subtree(),
+                WalkEvent::Enter(node) => return Some(node),
+                WalkEvent::Leave(_) => {}
+            }
+        }
+    })
+}
+
+/// Parses the text of a constraint value as an expression.
+pub(super) fn parse_expr(text: &str) -> Option<ast::Expr> {
+    let file = SourceFile::parse(&format!("const _: usize = {text};"), Edition::CURRENT).tree();
+    file.syntax().descendants().find_map(ast::Const::cast)?.body()
+}
+
+/// Every Rust file of the project itself, with its absolute path.
+pub(super) fn project_files(project: &LoadedProject) -> Vec<(FileId, String)> {
+    project
+        .vfs
+        .iter()
+        .map(|(file_id, path)| (file_id, path.to_string()))
+        .filter(|(_, path)| path.ends_with(".rs") && !is_external_path(path, &project.project_root))
+        .collect()
+}
+
+/// Renders `module` as a `::` separated path starting with the crate name.
+pub(super) fn module_path(db: &RootDatabase, module: hir::Module) -> String {
+    let krate = module.krate().display_name(db).map(|name| name.to_string());
+    krate
+        .into_iter()
+        .chain(
+            module
+                .path_to_root(db)
+                .into_iter()
+                .rev()
+                .filter_map(|it| it.name(db))
+                .map(|it| it.display(db, Edition::CURRENT).to_string()),
+        )
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// The traits listed by the `#[derive(...)]` attributes of `item`, as written.
+pub(super) fn derive_names(item: &impl HasAttrs) -> Vec<String> {
+    item.attrs()
+        .filter_map(|attr| attr.as_simple_call())
+        .filter(|(name, _)| name == "derive")
+        .flat_map(|(_, tt)| {
+            let text: String = tt.syntax().text().to_string().split_whitespace().collect();
+            let inner = text.trim_start_matches('(').trim_end_matches(')').to_owned();
+            inner.split(',').filter(|it| !it.is_empty()).map(str::to_owned).collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Whether `item` carries a `#[derive(...)]` listing `derive_name`.
+pub(super) fn derives(item: &impl HasAttrs, derive_name: &str) -> bool {
+    item.attrs().filter_map(|attr| attr.as_simple_call()).any(|(name, tt)| {
+        name == "derive"
+            && tt.syntax().descendants_with_tokens().any(|it| {
+                it.as_token()
+                    .is_some_and(|t| t.kind() == SyntaxKind::IDENT && t.text() == derive_name)
+            })
+    })
+}
+
+/// Returns `T` for field types like `Account<'info, T>` or `Box<AccountLoader<'info, T>>`.
+pub(super) fn wrapped_account_type(ty: &ast::Type) -> Option<String> {
+    wrapped_account(ty).map(|it| it.syntax().text().to_string())
+}
+
+/// The `T` of [`wrapped_account_type`] as a type node.
+pub(super) fn wrapped_account(ty: &ast::Type) -> Option<ast::Type> {
+    const WRAPPERS: &[&str] = &["Account", "AccountLoader", "InterfaceAccount"];
+    ty.syntax().descendants().filter_map(ast::PathSegment::cast).find_map(|segment| {
+        let name = segment.name_ref()?;
+        if !WRAPPERS.contains(&name.text().as_str()) {
+            return None;
+        }
+        segment.generic_arg_list()?.generic_args().find_map(|arg| match arg {
+            ast::GenericArg::TypeArg(arg) => arg.ty(),
+            _ => None,
+        })
+    })
+}
@@ -0,0 +1,290 @@
+//! The expressions of `constraint = ...` account constraints, parsed into a small tree.
+//!
+//! Constraints are Rust expressions over the fields of the accounts struct, like
+//! `pool.authority == admin.key() && !pool.paused`. Rules reason about what they compare rather
+//! than about their text: the tree keeps comparisons, boolean operators, field reads, `key()` calls
+//! and other method calls, and whatever else as source. Parentheses, references and dereferences
+//! don't change what is checked, so they are dropped.
+
+use std::fmt;
+
+use serde::Serialize;
+use syntax::{
+    AstNode,
+    ast::{self, HasArgList},
+};
+
+use crate::cli::code_graph::parse_expr;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(super) enum ConstraintExpr {
+    /// A comparison like `==`, `!=` or `>=`.
+    Compare {
+        op: String,
+        lhs: Box<ConstraintExpr>,
+        rhs: Box<ConstraintExpr>,
+    },
+    /// `&&` or `||`.
+    Logic {
+        op: String,
+        lhs: Box<ConstraintExpr>,
+        rhs: Box<ConstraintExpr>,
+    },
+    /// Any other binary operator, like `+` or `*`.
+    Arithmetic {
+        op: String,
+        lhs: Box<ConstraintExpr>,
+        rhs: Box<ConstraintExpr>,
+    },
+    Not {
+        expr: Box<ConstraintExpr>,
+    },
+    /// The key of an account, `account.key()`.
+    Key {
+        of: Box<ConstraintExpr>,
+    },
+    Field {
+        base: Box<ConstraintExpr>,
+        field: String,
+    },
+    MethodCall {
+        receiver: Box<ConstraintExpr>,
+        method: String,
+        args: Vec<ConstraintExpr>,
+    },
+    Call {
+        function: String,
+        args: Vec<ConstraintExpr>,
+    },
+    /// A name or path, like `admin` or `crate::ADMIN`.
+    Path {
+        path: String,
+    },
+    Literal {
+        value: String,
+    },
+    /// Anything else, like a block or a macro call, as source.
+    Other {
+        text: String,
+    },
+}
+
+impl ConstraintExpr {
+    /// Parses the value of a constraint, none when it isn't an expression.
+    pub(super) fn parse(text: &str) -> Option<ConstraintExpr> {
+        parse_expr(text).map(|it| ConstraintExpr::of(&it))
+    }
+
+    fn of(expr: &ast::Expr) -> ConstraintExpr {
+        let of = |expr: Option<ast::Expr>| match expr {
+            Some(expr) => Box::new(ConstraintExpr::of(&expr)),
+            None => Box::new(ConstraintExpr::Other { text: String::new() }),
+        };
+        let args = |list: Option<ast::ArgList>| {
+            list.into_iter().flat_map(|it| it.args()).map(|it| ConstraintExpr::of(&it)).collect()
+        };
+        match expr {
+            ast::Expr::ParenExpr(it) => return *of(it.expr()),
+            ast::Expr::RefExpr(it) => return *of(it.expr()),
+            ast::Expr::PrefixExpr(it) => match it.op_kind() {
+                Some(ast::UnaryOp::Not) => return ConstraintExpr::Not { expr: of(it.expr()) },
+                Some(ast::UnaryOp::Deref) => return *of(it.expr()),
+                _ => (),
+            },
+            ast::Expr::BinExpr(it) => {
+                if let Some(op) = it.op_kind() {
+                    let (lhs, rhs) = (of(it.lhs()), of(it.rhs()));
+                    let op_text = op.to_string();
+                    return match op {
+                        ast::BinaryOp::CmpOp(_) => {
+                            ConstraintExpr::Compare { op: op_text, lhs, rhs }
+                        }
+                        ast::BinaryOp::LogicOp(_) => {
+                            ConstraintExpr::Logic { op: op_text, lhs, rhs }
+                        }
+                        ast::BinaryOp::ArithOp(_) => {
+                            ConstraintExpr::Arithmetic { op: op_text, lhs, rhs }
+                        }
+                        ast::BinaryOp::Assignment { .. } => {
+                            ConstraintExpr::Other { text: expr.syntax().text().to_string() }
+                        }
+                    };
+                }
+            }
+            ast::Expr::MethodCallExpr(it) => {
+                if let Some(method) = it.name_ref() {
+                    let receiver = of(it.receiver());
+                    let args: Vec<_> = args(it.arg_list());
+                    return match method.text().as_str() {
+                        "key" if args.is_empty() => ConstraintExpr::Key { of: receiver },
+                        method => {
+                            ConstraintExpr::MethodCall { receiver, method: method.to_owned(), args }
+                        }
+                    };
+                }
+            }
+            ast::Expr::FieldExpr(it) => {
+                if let Some(field) = it.name_ref() {
+                    let field = field.text().to_string();
+                    return ConstraintExpr::Field { base: of(it.expr()), field };
+                }
+            }
+            ast::Expr::CallExpr(it) => {
+                if let Some(ast::Expr::PathExpr(function)) = it.expr() {
+                    let function = function.syntax().text().to_string();
+                    return ConstraintExpr::Call { function, args: args(it.arg_list()) };
+                }
+            }
+            ast::Expr::PathExpr(it) => {
+                return ConstraintExpr::Path { path: it.syntax().text().to_string() };
+            }
+            ast::Expr::Literal(it) => {
+                return ConstraintExpr::Literal { value: it.syntax().text().to_string() };
+            }
+            _ => (),
+        }
+        ConstraintExpr::Other { text: expr.syntax().text().to_string() }
+    }
+
+    /// The comparisons the expression requires to hold, looking through `&&` but not `||` or
+    /// `!`, which don't require either side.
+    pub(super) fn required_comparisons(&self) -> Vec<(&str, &ConstraintExpr, &ConstraintExpr)> {
+        match self {
+            ConstraintExpr::Compare { op, lhs, rhs } => vec![(op.as_str(), &**lhs, &**rhs)],
+            ConstraintExpr::Logic { op, lhs, rhs } if op == "&&" => {
+                let mut comparisons = lhs.required_comparisons();
+                comparisons.extend(rhs.required_comparisons());
+                comparisons
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// The name of a single segment path, like `admin`.
+    pub(super) fn name(&self) -> Option<&str> {
+        match self {
+            ConstraintExpr::Path { path } if !path.contains("::") => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The account and field of a read like `global.authority`.
+    pub(super) fn field_read(&self) -> Option<(&str, &str)> {
+        match self {
+            ConstraintExpr::Field { base, field } => Some((base.name()?, field)),
+            _ => None,
+        }
+    }
+
+    /// The account whose key the expression is, like `admin` in `admin.key()`.
+    pub(super) fn key_of(&self) -> Option<&str> {
+        match self {
+            ConstraintExpr::Key { of } => of.name(),
+            _ => None,
+        }
+    }
+
+    /// The expression and all the expressions it's made of.
+    pub(super) fn walk(&self) -> Vec<&ConstraintExpr> {
+        let children: Vec<&ConstraintExpr> = match self {
+            ConstraintExpr::Compare { lhs, rhs, .. }
+            | ConstraintExpr::Logic { lhs, rhs, .. }
+            | ConstraintExpr::Arithmetic { lhs, rhs, .. } => vec![lhs, rhs],
+            ConstraintExpr::Not { expr } => vec![expr],
+            ConstraintExpr::Key { of } => vec![of],
+            ConstraintExpr::Field { base, .. } => vec![base],
+            ConstraintExpr::MethodCall { receiver, args, .. } => {
+                std::iter::once(&**receiver).chain(args).collect()
+            }
+            ConstraintExpr::Call { args, .. } => args.iter().collect(),
+            ConstraintExpr::Path { .. }
+            | ConstraintExpr::Literal { .. }
+            | ConstraintExpr::Other { .. } => Vec::new(),
+        };
+        std::iter::once(self).chain(children.into_iter().flat_map(|it| it.walk())).collect()
+    }
+}
+
+/// Renders the expression back as source, parenthesizing operands made of an operator.
+impl fmt::Display for ConstraintExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operand = |expr: &ConstraintExpr| match expr {
+            ConstraintExpr::Compare { .. }
+            | ConstraintExpr::Logic { .. }
+            | ConstraintExpr::Arithmetic { .. } => format!("({expr})"),
+            _ => expr.to_string(),
+        };
+        let list = |args: &[ConstraintExpr]| {
+            args.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+        };
+        match self {
+            ConstraintExpr::Compare { op, lhs, rhs }
+            | ConstraintExpr::Logic { op, lhs, rhs }
+            | ConstraintExpr::Arithmetic { op, lhs, rhs } => {
+                write!(f, "{} {op} {}", operand(lhs), operand(rhs))
+            }
+            ConstraintExpr::Not { expr } => write!(f, "!{}", operand(expr)),
+            ConstraintExpr::Key { of } => write!(f, "{}.key()", operand(of)),
+            ConstraintExpr::Field { base, field } => write!(f, "{}.{field}", operand(base)),
+            ConstraintExpr::MethodCall { receiver, method, args } => {
+                write!(f, "{}.{method}({})", operand(receiver), list(args))
+            }
+            ConstraintExpr::Call { function, args } => write!(f, "{function}({})", list(args)),
+            ConstraintExpr::Path { path } => f.write_str(path),
+            ConstraintExpr::Literal { value } => f.write_str(value),
+            ConstraintExpr::Other { text } => f.write_str(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_with_rust_precedence() {
+        let cases = [
+            ("a == b && c", "(a == b) && c"),
+            ("a || b && c", "a || (b && c)"),
+            (
+                "!paused && pool.authority == admin.key()",
+                "!paused && (pool.authority == admin.key())",
+            ),
+            ("a + b * c >= d", "(a + (b * c)) >= d"),
+            ("(a || b) && c", "(a || b) && c"),
+            ("*pool.owner == &crate::ADMIN", "pool.owner == crate::ADMIN"),
+        ];
+        for (source, expected) in cases {
+            let expr = ConstraintExpr::parse(source).unwrap();
+            assert_eq!(expr.to_string(), expected, "`{source}`");
+        }
+    }
+
+    #[test]
+    fn requires_comparisons_through_and_only() {
+        let cases = [
+            ("a.key() == b.owner", vec!["a.key() == b.owner"]),
+            ("x >= 1 && (y != z && w)", vec!["x >= 1", "y != z"]),
+            ("a == b || c == d", vec![]),
+            ("!(a == b)", vec![]),
+        ];
+        for (source, expected) in cases {
+            let expr = ConstraintExpr::parse(source).unwrap();
+            let comparisons: Vec<String> = expr
+                .required_comparisons()
+                .into_iter()
+                .map(|(op, lhs, rhs)| format!("{lhs} {op} {rhs}"))
+                .collect();
+            assert_eq!(comparisons, expected, "`{source}`");
+        }
+    }
+
+    #[test]
+    fn reads_keys_and_fields() {
+        let expr = ConstraintExpr::parse("global.authority == admin.key()").unwrap();
+        let [(_, lhs, rhs)] = expr.required_comparisons()[..] else { panic!("{expr}") };
+        assert_eq!(lhs.field_read(), Some(("global", "authority")));
+        assert_eq!(rhs.key_of(), Some("admin"));
+    }
+}
@@ -0,0 +1,379 @@
+//! The cross-program invocations of a project: its `invoke` and `invoke_signed` calls and the
+//! `CpiContext`s it builds, with the instructions reaching them.
+//!
+//! Call sites are read from the syntax of the function making them. Arguments bound to a local
+//! first, like `let ix = system_instruction::transfer(..); invoke(&ix, ..)`, are followed to the
+//! expression of the binding. Accounts are named by the last field of their path, so
+//! `ctx.accounts.vault.to_account_info()` is `vault`.
+
+use anyhow::Result;
+use hir::Semantics;
+use ide::Analysis;
+use serde::Serialize;
+use syntax::{
+    AstNode, TextSize,
+    ast::{self, HasArgList, HasName},
+};
+
+use crate::cli::{
+    code_graph::{CodeGraph, LoadedProject, project_files},
+    function_analyzer::convert_to_relative_path,
+};
+
+/// The programs of well-known instruction builders, by a segment of the builder's path.
+const BUILDER_PROGRAMS: &[(&str, &str)] = &[
+    ("system_instruction", "system_program"),
+    ("spl_token_2022", "token_2022_program"),
+    ("spl_associated_token_account", "associated_token_program"),
+    ("spl_token", "token_program"),
+];
+
+/// The position of the account authorizing the instruction among the arguments of the token
+/// program instruction builders, like the owner of the source in
+/// `spl_token::instruction::transfer(program, source, destination, owner, signers, amount)`.
+const TOKEN_AUTHORITY_ARGS: &[(&str, usize)] = &[
+    ("transfer", 3),
+    ("transfer_checked", 4),
+    ("mint_to", 3),
+    ("mint_to_checked", 3),
+    ("burn", 3),
+    ("burn_checked", 3),
+    ("approve", 3),
+    ("approve_checked", 4),
+    ("revoke", 2),
+    ("close_account", 3),
+    ("set_authority", 4),
+    ("freeze_account", 3),
+    ("thaw_account", 3),
+];
+
+/// The fields of the `anchor_spl` CPI accounts structs naming the account authorizing the
+/// instruction.
+const AUTHORITY_ROLES: &[&str] = &["authority", "current_authority"];
+
+/// Method calls that only convert an account, skipped when naming it.
+pub(super) const ACCOUNT_CONVERSIONS: &[&str] =
+    &["clone", "to_account_info", "as_ref", "key", "to_owned", "deref"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub(super) struct CpiCall {
+    pub(super) file: String,
+    pub(super) line: u32,
+    /// The function making the call.
+    pub(super) function: Option<String>,
+    pub(super) kind: CpiKind,
+    /// The account of the invoked program, or the program of the instruction builder for
+    /// `invoke`. None when it can't be told from the call site.
+    pub(super) program: Option<String>,
+    pub(super) accounts: Vec<CpiAccount>,
+    /// The seeds the program signs with, none for unsigned calls.
+    pub(super) signer_seeds: Option<String>,
+    /// The account authorizing a token program instruction, like the owner of the source of a
+    /// transfer or the authority of a mint.
+    pub(super) authority: Option<String>,
+    /// The function the context is given to, like `token::transfer`, or the builder of the
+    /// instruction given to `invoke`, like `system_instruction::transfer`.
+    pub(super) target: Option<String>,
+    /// The paths of the instructions reaching the call.
+    pub(super) instructions: Vec<String>,
+    /// The id of `function` in the call graph.
+    #[serde(skip)]
+    function_id: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum CpiKind {
+    Invoke,
+    InvokeSigned,
+    CpiContext,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub(super) struct CpiAccount {
+    /// The field of the accounts struct of a `CpiContext`, like `authority`.
+    pub(super) role: Option<String>,
+    pub(super) account: String,
+}
+
+/// Collects every CPI made by project files, and the instructions of `graph` reaching them.
+pub(super) fn extract_cpi_calls(
+    project: &LoadedProject,
+    analysis: &Analysis,
+    graph: &CodeGraph,
+) -> Result<Vec<CpiCall>> {
+    let _p = tracing::info_span!("extract_cpi_calls").entered();
+    let sema = Semantics::new(&project.db);
+    let mut cpis = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let relative_path = convert_to_relative_path(&file_path, &project.project_root);
+        let line_of = |offset: TextSize| line_index.line_col(offset).line + 1;
+
+        for call in file.syntax().descendants().filter_map(ast::CallExpr::cast) {
+            let Some(ast::Expr::PathExpr(callee)) = call.expr() else { continue };
+            let Some(path) = callee.path() else { continue };
+            let Some(name) = path.segment().and_then(|it| it.name_ref()) else { continue };
+            let qualifier = path.qualifier().and_then(|it| it.segment()?.name_ref());
+            let args: Vec<ast::Expr> =
+                call.arg_list().into_iter().flat_map(|it| it.args()).collect();
+            let function = call.syntax().ancestors().find_map(ast::Fn::cast);
+            let function_id = function.as_ref().and_then(|function| {
+                let name = function.name()?;
+                graph.function_at(
+                    &relative_path,
+                    name.text().as_str(),
+                    line_of(name.syntax().text_range().start()),
+                )
+            });
+            let site = Site {
+                function: function.as_ref(),
+                at: call.syntax().text_range().start(),
+                file: &relative_path,
+                line: line_of(call.syntax().text_range().start()),
+                function_id,
+                function_path: function_id.map(|id| graph.function_path(id)),
+            };
+            let cpi =
+                match (qualifier.as_ref().map(|it| it.text()).as_deref(), name.text().as_str()) {
+                    (Some("CpiContext"), "new" | "new_with_signer") => {
+                        site.cpi_context(&call, &args)
+                    }
+                    (_, "invoke" | "invoke_unchecked") if args.len() == 2 => {
+                        site.invoke(CpiKind::Invoke, &args)
+                    }
+                    (_, "invoke_signed" | "invoke_signed_unchecked") if args.len() == 3 => {
+                        site.invoke(CpiKind::InvokeSigned, &args)
+                    }
+                    _ => continue,
+                };
+            cpis.extend(cpi);
+        }
+    }
+
+    let reachable = graph.instruction_reach();
+    for cpi in &mut cpis {
+        cpi.instructions = reachable
+            .iter()
+            .filter(|(_, functions)| cpi.function_id.is_some_and(|id| functions.contains(&id)))
+            .map(|(instruction, _)| instruction.clone())
+            .collect();
+    }
+    cpis.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(cpis)
+}
+
+/// A call site, in the function whose locals its arguments may name.
+struct Site<'a> {
+    function: Option<&'a ast::Fn>,
+    at: TextSize,
+    file: &'a str,
+    line: u32,
+    function_id: Option<usize>,
+    function_path: Option<String>,
+}
+
+impl Site<'_> {
+    fn cpi(
+        &self,
+        kind: CpiKind,
+        program: Option<String>,
+        accounts: Vec<CpiAccount>,
+        signer_seeds: Option<String>,
+        authority: Option<String>,
+        target: Option<String>,
+    ) -> CpiCall {
+        CpiCall {
+            file: self.file.to_owned(),
+            line: self.line,
+            function: self.function_path.clone(),
+            kind,
+            program,
+            accounts,
+            signer_seeds,
+            authority,
+            target,
+            instructions: Vec::new(),
+            function_id: self.function_id,
+        }
+    }
+
+    /// `invoke(&instruction, &accounts)` or `invoke_signed(.., signer_seeds)`.
+    fn invoke(&self, kind: CpiKind, args: &[ast::Expr]) -> Option<CpiCall> {
+        // Builders of the token programs return a `Result` the call site unwraps with `?`.
+        let instruction = match self.follow(args.first()?) {
+            ast::Expr::TryExpr(it) => it.expr()?,
+            it => it,
+        };
+        let (program, target, authority) = match &instruction {
+            ast::Expr::RecordExpr(record) => {
+                let program_id = record
+                    .record_expr_field_list()?
+                    .fields()
+                    .find(|it| it.field_name().is_some_and(|name| name.text() == "program_id"))
+                    .and_then(|it| it.expr());
+                (program_id.map(|it| account_name(&it)), None, None)
+            }
+            ast::Expr::CallExpr(call) => {
+                let Some(ast::Expr::PathExpr(builder)) = call.expr() else { return None };
+                let builder = builder.path()?;
+                let program = builder_program(&builder);
+                let authority = match program.as_deref() {
+                    Some("token_program" | "token_2022_program") => {
+                        let name = builder.segment()?.name_ref()?;
+                        TOKEN_AUTHORITY_ARGS
+                            .iter()
+                            .find(|(builder, _)| name.text() == *builder)
+                            .and_then(|&(_, arg)| call.arg_list()?.args().nth(arg))
+                            .map(|it| account_name(&it))
+                    }
+                    _ => None,
+                };
+                (program, Some(text(builder.syntax())), authority)
+            }
+            _ => (None, None, None),
+        };
+        let accounts = match self.follow(args.get(1)?) {
+            ast::Expr::ArrayExpr(array) => array
+                .exprs()
+                .map(|it| CpiAccount { role: None, account: account_name(&it) })
+                .collect(),
+            accounts => vec![CpiAccount { role: None, account: account_name(&accounts) }],
+        };
+        let signer_seeds = args.get(2).map(|it| text(it.syntax()));
+        Some(self.cpi(kind, program, accounts, signer_seeds, authority, target))
+    }
+
+    /// `CpiContext::new(program, accounts)`, `new_with_signer(.., signer_seeds)` or either
+    /// followed by `.with_signer(signer_seeds)`.
+    fn cpi_context(&self, call: &ast::CallExpr, args: &[ast::Expr]) -> Option<CpiCall> {
+        let accounts = match self.follow(args.get(1)?) {
+            ast::Expr::RecordExpr(record) => record
+                .record_expr_field_list()?
+                .fields()
+                .filter_map(|field| {
+                    Some(CpiAccount {
+                        role: Some(field.field_name()?.text().to_string()),
+                        account: account_name(&field.expr()?),
+                    })
+                })
+                .collect(),
+            accounts => vec![CpiAccount { role: None, account: account_name(&accounts) }],
+        };
+        // The context as a whole, with the methods chained on it.
+        let mut context = ast::Expr::CallExpr(call.clone());
+        let mut signer_seeds = args.get(2).map(|it| text(it.syntax()));
+        while let Some(method) = context.syntax().parent().and_then(ast::MethodCallExpr::cast) {
+            if method.name_ref().is_some_and(|it| it.text() == "with_signer") {
+                signer_seeds = method.arg_list()?.args().next().map(|it| text(it.syntax()));
+            }
+            context = ast::Expr::MethodCallExpr(method);
+        }
+        let program = Some(account_name(args.first()?));
+        let authority = accounts
+            .iter()
+            .find(|it| it.role.as_deref().is_some_and(|role| AUTHORITY_ROLES.contains(&role)))
+            .map(|it| it.account.clone());
+        Some(self.cpi(
+            CpiKind::CpiContext,
+            program,
+            accounts,
+            signer_seeds,
+            authority,
+            self.consumer(&context),
+        ))
+    }
+
+    /// The path of the function `context` is passed to, directly or through a local.
+    fn consumer(&self, context: &ast::Expr) -> Option<String> {
+        let parent = context.syntax().parent()?;
+        if let Some(call) = ast::ArgList::cast(parent.clone())
+            .and_then(|it| it.syntax().parent().and_then(ast::CallExpr::cast))
+        {
+            return callee(&call);
+        }
+        let ast::Pat::IdentPat(binding) = ast::LetStmt::cast(parent)?.pat()? else { return None };
+        let name = binding.name()?;
+        self.function?
+            .syntax()
+            .descendants()
+            .filter_map(ast::CallExpr::cast)
+            .filter(|it| it.syntax().text_range().start() > self.at)
+            .find(|call| {
+                call.arg_list().into_iter().flat_map(|it| it.args()).any(|arg| {
+                    let ast::Expr::PathExpr(arg) = arg else { return false };
+                    arg.path().is_some_and(|it| it.syntax().text() == name.text().as_str())
+                })
+            })
+            .and_then(|call| callee(&call))
+    }
+
+    /// The expression of the local `expr` names, bound before the call, or `expr` itself.
+    fn follow(&self, expr: &ast::Expr) -> ast::Expr {
+        let expr = match expr {
+            ast::Expr::RefExpr(inner) => inner.expr().unwrap_or_else(|| expr.clone()),
+            _ => expr.clone(),
+        };
+        let ast::Expr::PathExpr(path) = &expr else { return expr };
+        let Some(name) = path.path().filter(|it| it.qualifier().is_none()) else { return expr };
+        let binding = self
+            .function
+            .into_iter()
+            .flat_map(|function| function.syntax().descendants().filter_map(ast::LetStmt::cast));
+        binding
+            .filter(|it| it.syntax().text_range().end() <= self.at)
+            .filter(|it| match it.pat() {
+                Some(ast::Pat::IdentPat(pat)) => {
+                    pat.name().is_some_and(|it| it.syntax().text() == name.syntax().text())
+                }
+                _ => false,
+            })
+            .last()
+            .and_then(|it| it.initializer())
+            .unwrap_or(expr)
+    }
+}
+
+/// The program an instruction builder like `spl_token::instruction::transfer` targets.
+fn builder_program(builder: &ast::Path) -> Option<String> {
+    let segments: Vec<String> =
+        builder.segments().map(|it| it.syntax().text().to_string()).collect();
+    let program = BUILDER_PROGRAMS
+        .iter()
+        .find(|(segment, _)| segments.iter().any(|it| it == segment))
+        .map(|(_, program)| (*program).to_owned());
+    program.or_else(|| Some(text(builder.qualifier()?.syntax())))
+}
+
+/// The account `expr` refers to, e.g. `vault` for `&ctx.accounts.vault.to_account_info()`.
+pub(super) fn account_name(expr: &ast::Expr) -> String {
+    match expr {
+        ast::Expr::RefExpr(inner) => inner.expr().map(|it| account_name(&it)),
+        ast::Expr::ParenExpr(inner) => inner.expr().map(|it| account_name(&it)),
+        ast::Expr::MethodCallExpr(call)
+            if call
+                .name_ref()
+                .is_some_and(|it| ACCOUNT_CONVERSIONS.contains(&it.text().as_str())) =>
+        {
+            call.receiver().map(|it| account_name(&it))
+        }
+        // The `key` field of native `AccountInfo`s, like `owner.key`.
+        ast::Expr::FieldExpr(field) if field.name_ref().is_some_and(|it| it.text() == "key") => {
+            field.expr().map(|it| account_name(&it))
+        }
+        ast::Expr::FieldExpr(field) => field.name_ref().map(|it| it.text().to_string()),
+        _ => None,
+    }
+    .unwrap_or_else(|| text(expr.syntax()))
+}
+
+fn callee(call: &ast::CallExpr) -> Option<String> {
+    let ast::Expr::PathExpr(callee) = call.expr()? else { return None };
+    Some(text(callee.syntax()))
+}
+
+/// The source of `node` on a single line.
+fn text(node: &syntax::SyntaxNode) -> String {
+    node.text().to_string().split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,235 @@
+//! Project specific rules over the fields of account structs, declared in a
+//! `rustgraph-rules.toml` (or `rustgraph-rules.json`) file at the project root and reported
+//! alongside the findings of the built-in checks.
+//!
+//! A rule selects fields with patterns where `*` stands for any run of characters, and lists the
+//! constraints they need:
+//!
+//! ```toml
+//! [[rules]]
+//! id = "vault-owner"
+//! message = "holds funds, but nothing checks who owns it"
+//! severity = "error"
+//! match = { type = "UncheckedAccount", name = "*vault*" }
+//! require = ["owner | address | seeds"]
+//! ```
+//!
+//! - `match` filters on the `struct` name, the field `name`, its `type` without `Box` and
+//!   generic arguments, like `Account`, and the `account_type` it wraps. A left out criterion
+//!   matches every field.
+//! - `require` lists the constraints a selected field must have, each one as alternatives
+//!   separated by `|`. An alternative is a constraint kind like `owner`, or a kind with its value
+//!   like `has_one = authority` or `constraint = !pool.paused || admin.key() == pool.admin`.
+//! - `forbid` lists the constraints it must not have, in the same form.
+//!
+//! Findings are reported under the `rules` plugin, as warnings unless the rule says otherwise.
+
+use std::fs;
+
+use anyhow::{Context, Result};
+use paths::AbsPath;
+use serde::Deserialize;
+
+use crate::cli::{
+    code_graph::{AccountField, AccountStruct, CodeGraph},
+    entry_points::matches_pattern,
+    findings::{Finding, RULES, Severity},
+};
+
+/// The names the rule file is looked up under, in order.
+const RULE_FILES: &[&str] = &["rustgraph-rules.toml", "rustgraph-rules.json"];
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    #[serde(default)]
+    rules: Vec<CustomRule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct CustomRule {
+    pub(super) id: String,
+    message: String,
+    #[serde(default)]
+    severity: Severity,
+    #[serde(default, rename = "match")]
+    selector: Selector,
+    #[serde(default)]
+    require: Vec<String>,
+    #[serde(default)]
+    forbid: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Selector {
+    #[serde(rename = "struct")]
+    strukt: Option<String>,
+    name: Option<String>,
+    #[serde(rename = "type")]
+    ty: Option<String>,
+    account_type: Option<String>,
+}
+
+impl Selector {
+    fn matches(&self, strukt: &AccountStruct, field: &AccountField) -> bool {
+        let matches = |pattern: &Option<String>, value: Option<&str>| match (pattern, value) {
+            (None, _) => true,
+            (Some(pattern), Some(value)) => matches_pattern(pattern, value),
+            (Some(_), None) => false,
+        };
+        let account_type = field.account_type.as_deref().map(|it| last_segment(it.trim()));
+        matches(&self.strukt, Some(&strukt.name))
+            && matches(&self.name, Some(&field.name))
+            && matches(&self.ty, Some(outer_type(&field.ty)))
+            && matches(&self.account_type, account_type)
+    }
+}
+
+/// The rules of the rule file of the project, empty without a file.
+pub(super) fn load(project_root: &AbsPath) -> Result<Vec<CustomRule>> {
+    let Some((path, text)) = RULE_FILES.iter().find_map(|name| {
+        let path = project_root.join(name);
+        fs::read_to_string(&path).ok().map(|text| (path, text))
+    }) else {
+        return Ok(Vec::new());
+    };
+    let file: RuleFile = match path.extension() {
+        Some("json") => {
+            serde_json::from_str(&text).with_context(|| format!("failed to parse {path}"))?
+        }
+        _ => toml::from_str(&text).with_context(|| format!("failed to parse {path}"))?,
+    };
+    for (i, rule) in file.rules.iter().enumerate() {
+        if RULES.iter().any(|it| it.id == rule.id) {
+            anyhow::bail!("{path}: rule `{}` is a built-in rule", rule.id);
+        }
+        if file.rules[..i].iter().any(|it| it.id == rule.id) {
+            anyhow::bail!("{path}: rule `{}` is defined twice", rule.id);
+        }
+        if let Some(empty) =
+            rule.require.iter().chain(&rule.forbid).find(|it| alternatives(it).next().is_none())
+        {
+            anyhow::bail!("{path}: rule `{}` has an empty constraint `{empty}`", rule.id);
+        }
+    }
+    Ok(file.rules)
+}
+
+/// The findings of `rules` over the account structs of `graph`.
+pub(super) fn check(rules: &[CustomRule], graph: &CodeGraph) -> Vec<Finding> {
+    let _p = tracing::info_span!("custom_rules").entered();
+    let mut findings = Vec::new();
+    for rule in rules {
+        for strukt in &graph.account_structs {
+            for field in strukt.fields.iter().filter(|it| rule.selector.matches(strukt, it)) {
+                let missing = rule.require.iter().find(|it| !has_constraint(field, it));
+                let forbidden = rule.forbid.iter().find(|it| has_constraint(field, it));
+                let reason = match (missing, forbidden) {
+                    (Some(missing), _) => format!("without `{missing}`"),
+                    (None, Some(forbidden)) => format!("with `{forbidden}`"),
+                    (None, None) => continue,
+                };
+                findings.push(Finding {
+                    plugin: "rules".to_owned(),
+                    rule: rule.id.clone(),
+                    message: format!("`{}.{}` {}, {reason}", strukt.name, field.name, rule.message),
+                    severity: rule.severity,
+                    file: Some(strukt.file.clone()),
+                    line: Some(field.line),
+                    related: Vec::new(),
+                });
+            }
+        }
+    }
+    findings
+}
+
+/// Whether `field` has one of the constraints of `spec`, like `owner | has_one = authority`.
+fn has_constraint(field: &AccountField, spec: &str) -> bool {
+    alternatives(spec).any(|(kind, value)| {
+        field.constraints.iter().any(|it| {
+            it.kind == kind
+                && value.as_ref().is_none_or(|value| {
+                    it.value.as_deref().is_some_and(|it| without_whitespace(it) == *value)
+                })
+        })
+    })
+}
+
+/// The constraint kinds of `spec`, with the value they need if any.
+fn alternatives(spec: &str) -> impl Iterator<Item = (String, Option<String>)> + '_ {
+    split_standalone(spec, '|').into_iter().filter(|it| !it.trim().is_empty()).map(|it| {
+        match split_standalone(it, '=')[..] {
+            [kind, _, ..] => {
+                let value = &it[kind.len() + 1..];
+                (without_whitespace(kind), Some(without_whitespace(value)))
+            }
+            _ => (without_whitespace(it), None),
+        }
+    })
+}
+
+/// Splits `text` at `separator`, except where it's part of an operator of a value like
+/// `constraint = a == b || c <= d`.
+fn split_standalone(text: &str, separator: char) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, &(at, c)) in chars.iter().enumerate() {
+        let prev = i.checked_sub(1).map(|it| chars[it].1);
+        let next = chars.get(i + 1).map(|it| it.1);
+        let operator = match c {
+            '=' => matches!(prev, Some('=' | '!' | '<' | '>')) || next == Some('='),
+            '|' => prev == Some('|') || next == Some('|'),
+            _ => false,
+        };
+        if c == separator && !operator {
+            parts.push(&text[start..at]);
+            start = at + c.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+fn without_whitespace(text: &str) -> String {
+    text.split_whitespace().collect()
+}
+
+/// The name of the type `ty` without `Box` and generic arguments, like `Account` for
+/// `Box<Account<'info, Pool>>`.
+fn outer_type(ty: &str) -> &str {
+    let mut ty = ty.trim();
+    while let Some(inner) = ty.strip_prefix("Box<").and_then(|it| it.strip_suffix('>')) {
+        ty = inner.trim();
+    }
+    last_segment(ty.split('<').next().unwrap_or(ty).trim())
+}
+
+fn last_segment(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_alternatives_outside_operators() {
+        let cases: [(&str, &[(&str, Option<&str>)]); 4] = [
+            ("owner | address | seeds", &[("owner", None), ("address", None), ("seeds", None)]),
+            ("has_one = authority", &[("has_one", Some("authority"))]),
+            ("constraint = a == b | mut", &[("constraint", Some("a==b")), ("mut", None)]),
+            ("constraint = a.x <= b || c != d", &[("constraint", Some("a.x<=b||c!=d"))]),
+        ];
+        for (spec, expected) in cases {
+            let expected: Vec<(String, Option<String>)> = expected
+                .iter()
+                .map(|(kind, value)| ((*kind).to_owned(), value.map(str::to_owned)))
+                .collect();
+            assert_eq!(alternatives(spec).collect::<Vec<_>>(), expected, "{spec}");
+        }
+    }
+}
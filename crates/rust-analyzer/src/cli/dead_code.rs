@@ -0,0 +1,112 @@
+//! Functions never reached through calls from the entry points of the project, candidates for
+//! removal.
+
+use std::io::Write;
+
+use anyhow::Result;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use vfs::AbsPathBuf;
+
+use crate::cli::{
+    entry_points::EntryKind,
+    flags,
+    function_analyzer::{CallRelation, FunctionInfo, convert_to_relative_path},
+    prune::{FunctionKey, Keys},
+};
+
+/// The functions of `functions` not reached from the ones with an entry kind.
+pub(super) fn unreachable_functions<'a>(
+    functions: &'a [FunctionInfo],
+    relations: &[CallRelation],
+    entries: &[Option<EntryKind>],
+) -> Vec<&'a FunctionInfo> {
+    let keys = Keys::new(functions);
+    let mut callees: FxHashMap<FunctionKey, Vec<FunctionKey>> = FxHashMap::default();
+    for relation in relations {
+        callees.entry(keys.key(&relation.caller)).or_default().push(keys.key(&relation.callee));
+    }
+
+    let mut stack: Vec<FunctionKey> = functions
+        .iter()
+        .zip(entries)
+        .filter(|(_, entry)| entry.is_some())
+        .map(|(function, _)| keys.key(function))
+        .collect();
+    let mut reached: FxHashSet<FunctionKey> = stack.iter().cloned().collect();
+    while let Some(key) = stack.pop() {
+        for callee in callees.get(&key).into_iter().flatten() {
+            if reached.insert(callee.clone()) {
+                stack.push(callee.clone());
+            }
+        }
+    }
+
+    let mut seen = FxHashSet::default();
+    functions
+        .iter()
+        .filter(|function| {
+            let key = keys.key(function);
+            !reached.contains(&key) && seen.insert(key)
+        })
+        .collect()
+}
+
+/// The JSON document written for `--dead-code` with `--format json`.
+#[derive(Serialize)]
+struct DeadCode {
+    entry_points: Vec<DeadCodeFunction>,
+    unreachable: Vec<DeadCodeFunction>,
+}
+
+#[derive(Serialize)]
+struct DeadCodeFunction {
+    name: String,
+    module: String,
+    file: String,
+    line: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<EntryKind>,
+}
+
+pub(super) fn write_dead_code(
+    writer: &mut dyn Write,
+    functions: &[FunctionInfo],
+    entries: &[Option<EntryKind>],
+    unreachable: &[&FunctionInfo],
+    format: flags::CallFormat,
+    project_root: &AbsPathBuf,
+    redaction: &flags::Redaction,
+) -> Result<()> {
+    let describe = |function: &FunctionInfo, kind: Option<EntryKind>| DeadCodeFunction {
+        name: function.name.clone(),
+        module: function.module.clone(),
+        file: redaction.path(&convert_to_relative_path(&function.file_path, project_root)),
+        line: function.line,
+        kind,
+    };
+    let entry_points: Vec<DeadCodeFunction> = functions
+        .iter()
+        .zip(entries)
+        .filter_map(|(function, &kind)| Some(describe(function, Some(kind?))))
+        .collect();
+    let unreachable: Vec<DeadCodeFunction> =
+        unreachable.iter().map(|function| describe(function, None)).collect();
+
+    if format == flags::CallFormat::Json {
+        serde_json::to_writer_pretty(&mut *writer, &DeadCode { entry_points, unreachable })?;
+        writeln!(writer)?;
+        return Ok(());
+    }
+    writeln!(
+        writer,
+        "# Functions unreachable from the {} entry points of the project",
+        entry_points.len()
+    )?;
+    writeln!(writer, "# Format: file:line:function")?;
+    writeln!(writer)?;
+    for function in &unreachable {
+        writeln!(writer, "{}:{}:{}", function.file, function.line, function.name)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,169 @@
+//! Lists the project functions that call into a given dependency, directly or through other
+//! project functions, i.e. the exposure set when an advisory lands against that crate.
+
+use std::{
+    collections::{VecDeque, hash_map::Entry},
+    fs,
+};
+
+use anyhow::Result;
+use hir::Semantics;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+
+use crate::cli::{
+    code_graph::{CodeGraph, LoadOptions, LoadedProject},
+    flags,
+};
+
+impl flags::DepsCallers {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("deps_callers", path = %self.path.display()).entered();
+        eprintln!("Loading workspace...");
+        let project = LoadedProject::load(
+            &self.path,
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+        let graph = CodeGraph::build(&project)?;
+
+        let crate_name = self.crate_name.replace('-', "_");
+        let targets = dependency_functions(&project, &graph, &crate_name);
+        if targets.is_empty() {
+            eprintln!("No calls into `{crate_name}` found");
+        }
+        let callers = callers(&graph, &crate_name, &targets, self.depth);
+        eprintln!(
+            "{} project functions reach `{crate_name}`, {} of them directly",
+            callers.len(),
+            callers.iter().filter(|it| it.depth == 1).count()
+        );
+
+        let report = DepsCallers { krate: crate_name, callers };
+        let json = serde_json::to_string_pretty(&report)?;
+        match &self.output {
+            Some(path) => fs::write(path, json)?,
+            None => println!("{json}"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DepsCallers {
+    #[serde(rename = "crate")]
+    krate: String,
+    callers: Vec<Caller>,
+}
+
+#[derive(Debug, Serialize)]
+struct Caller {
+    name: String,
+    module: String,
+    file: String,
+    line: u32,
+    /// Number of calls between the function and the dependency, `1` for direct callers.
+    depth: u32,
+    /// The shortest call chain from the function into the dependency, ending with the called
+    /// dependency function.
+    path: Vec<String>,
+}
+
+/// The ids of the graph functions defined in the crate named `crate_name`, be it a registry
+/// dependency or a path dependency inside the project.
+fn dependency_functions(
+    project: &LoadedProject,
+    graph: &CodeGraph,
+    crate_name: &str,
+) -> Vec<usize> {
+    let sema = Semantics::new(&project.db);
+    let files: FxHashMap<String, _> =
+        project.vfs.iter().map(|(file_id, path)| (path.to_string(), file_id)).collect();
+    let mut crate_of_file = FxHashMap::default();
+    graph
+        .functions
+        .iter()
+        .filter(|function| {
+            let krate = crate_of_file.entry(function.file.as_str()).or_insert_with(|| {
+                // Paths of project files are relative to the project root.
+                let path = project.project_root.join(&function.file).to_string();
+                let module = sema.file_to_module_defs(*files.get(&path)?).next()?;
+                Some(module.krate().display_name(&project.db)?.to_string())
+            });
+            krate.as_deref() == Some(crate_name)
+        })
+        .map(|function| function.id)
+        .collect()
+}
+
+/// Walks the call graph backwards from `targets`, through project functions outside of the
+/// dependency, for at most `max_depth` calls.
+fn callers(
+    graph: &CodeGraph,
+    crate_name: &str,
+    targets: &[usize],
+    max_depth: Option<u32>,
+) -> Vec<Caller> {
+    let dependency: FxHashSet<usize> = targets.iter().copied().collect();
+    let mut callers_of: FxHashMap<usize, Vec<usize>> = FxHashMap::default();
+    for call in &graph.calls {
+        if !graph.functions[call.caller].external && !dependency.contains(&call.caller) {
+            callers_of.entry(call.callee).or_default().push(call.caller);
+        }
+    }
+
+    // For every reached function, its depth and the next function towards the dependency.
+    let mut reached: FxHashMap<usize, (u32, Option<usize>)> =
+        targets.iter().map(|&id| (id, (0, None))).collect();
+    let mut queue: VecDeque<usize> = targets.iter().copied().collect();
+    while let Some(id) = queue.pop_front() {
+        let depth = reached[&id].0 + 1;
+        if max_depth.is_some_and(|max| depth > max) {
+            continue;
+        }
+        for &caller in callers_of.get(&id).into_iter().flatten() {
+            if let Entry::Vacant(entry) = reached.entry(caller) {
+                entry.insert((depth, Some(id)));
+                queue.push_back(caller);
+            }
+        }
+    }
+
+    let mut callers = reached
+        .iter()
+        .filter(|&(id, _)| !dependency.contains(id))
+        .map(|(&id, &(depth, _))| {
+            let function = &graph.functions[id];
+            let path = std::iter::successors(Some(id), |id| reached[id].1)
+                .skip(1)
+                .map(|id| match reached[&id].1 {
+                    Some(_) => qualified_name(graph, id),
+                    // Functions of dependencies have no module path.
+                    None => format!("{crate_name}::{}", graph.functions[id].name),
+                })
+                .collect();
+            Caller {
+                name: function.name.clone(),
+                module: function.module.clone(),
+                file: function.file.clone(),
+                line: function.line,
+                depth,
+                path,
+            }
+        })
+        .collect::<Vec<_>>();
+    callers.sort_by(|a, b| (a.depth, &a.file, a.line).cmp(&(b.depth, &b.file, b.line)));
+    callers
+}
+
+fn qualified_name(graph: &CodeGraph, id: usize) -> String {
+    let function = &graph.functions[id];
+    if function.module.is_empty() {
+        function.name.clone()
+    } else {
+        format!("{}::{}", function.module, function.name)
+    }
+}
@@ -0,0 +1,136 @@
+//! The discriminators Anchor prefixes the data of accounts and events with.
+//!
+//! A discriminator is the first 8 bytes of the SHA-256 of `<namespace>:<Name>`, where the
+//! namespace is `account` or `event` unless the attribute gives another one, like
+//! `#[account("custom")]`. Anchor 0.31 also accepts explicit bytes, `discriminator = [1, 2]`, of any
+//! length. Only the type name goes in, so two types of the same name in different modules collide.
+
+use syntax::{AstNode, AstToken, ast};
+
+/// Bytes of a discriminator derived from the type name.
+const DISCRIMINATOR_LEN: usize = 8;
+
+/// The discriminator of the type `name` declared with `attr`, like `#[account]` or `#[event]`, as
+/// hex. None when it's given by an expression that isn't a literal byte array.
+pub(super) fn discriminator(attr: &ast::Attr, namespace: &str, name: &str) -> Option<String> {
+    let args: Vec<_> = attr
+        .token_tree()
+        .into_iter()
+        .flat_map(|it| it.syntax().descendants_with_tokens().filter_map(|it| it.into_token()))
+        .filter(|it| !it.kind().is_trivia())
+        .collect();
+    if let Some(start) = args.iter().position(|it| it.text() == "discriminator") {
+        let bytes: Option<Vec<u8>> = args[start..]
+            .iter()
+            .skip_while(|it| it.text() != "[")
+            .take_while(|it| it.text() != "]")
+            .filter(|it| !matches!(it.text(), "[" | ","))
+            .map(|it| ast::IntNumber::cast(it.clone())?.value().ok()?.try_into().ok())
+            .collect();
+        return bytes.filter(|it| !it.is_empty()).map(|it| hex(&it));
+    }
+    let namespace = args
+        .iter()
+        .find_map(|it| Some(ast::String::cast(it.clone())?.value().ok()?.into_owned()))
+        .unwrap_or_else(|| namespace.to_owned());
+    Some(hex(&sha256(format!("{namespace}:{name}").as_bytes())[..DISCRIMINATOR_LEN]))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// SHA-256, which only the discriminators need.
+fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4,
+        0xab1c5ed5, 0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe,
+        0x9bdc06a7, 0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f,
+        0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7,
+        0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+        0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85, 0xa2bfe8a1, 0xa81a664b,
+        0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116,
+        0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7,
+        0xc67178f2,
+    ];
+    let mut state: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab,
+        0x5be0cd19,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for (k, w) in K.into_iter().zip(w) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(k).wrapping_add(w);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            (h, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(t1), c, b, a, t1.wrapping_add(t2));
+        }
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *value = value.wrapping_add(add);
+        }
+    }
+    let mut digest = [0u8; 32];
+    for (bytes, value) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use syntax::{Edition, SourceFile};
+
+    use super::*;
+
+    #[test]
+    fn hashes_test_vectors() {
+        let cases = [
+            ("", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"),
+            ("abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"),
+            (
+                "abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq",
+                "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1",
+            ),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(hex(&sha256(data.as_bytes())), expected, "{data:?}");
+        }
+    }
+
+    #[test]
+    fn matches_anchor_discriminators() {
+        let cases = [
+            ("#[account]", "account", "Global", Some("a7e8e8b1c86c727f")),
+            ("#[account(zero_copy)]", "account", "BondingCurve", Some("17b7f83760d8ac60")),
+            ("#[event]", "event", "TradeEvent", Some("bddb7fd34ee661ee")),
+            ("#[account(\"custom\")]", "account", "Global", Some("66ccc46710d548f7")),
+            ("#[account(discriminator = [1, 0x2a])]", "account", "Global", Some("012a")),
+            ("#[account(discriminator = GLOBAL)]", "account", "Global", None),
+        ];
+        for (attr, namespace, name, expected) in cases {
+            let file = SourceFile::parse(&format!("{attr} struct {name};"), Edition::CURRENT);
+            let attr = file.tree().syntax().descendants().find_map(ast::Attr::cast).unwrap();
+            assert_eq!(discriminator(&attr, namespace, name).as_deref(), expected, "{attr}");
+        }
+    }
+}
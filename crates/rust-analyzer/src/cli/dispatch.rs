@@ -0,0 +1,125 @@
+//! Resolution of calls through trait objects and generic bounds.
+//!
+//! The call hierarchy points such calls at the trait method declaration since the called
+//! implementation is only known at runtime. They're redirected to every implementation of the
+//! method in the project, each marked as dynamic with an even share of the confidence.
+
+use hir::{AsAssocItem, AssocItem, Impl, Semantics};
+use ide::{LineCol, RootDatabase};
+use ide_db::{LineIndexDatabase, base_db::salsa};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use syntax::{AstNode, ast};
+use vfs::{AbsPathBuf, Vfs};
+
+use crate::cli::function_analyzer::{
+    CallRelation, FunctionInfo, extract_function_info, find_file_id_by_path, is_external_path,
+};
+
+/// How the callee of a call is determined.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum Dispatch {
+    /// The callee is known at compile time.
+    #[default]
+    Static,
+    /// The callee is one of the implementations of a trait method.
+    Dynamic,
+}
+
+/// Replaces the calls to project trait methods by calls to their implementations. Traits of
+/// dependencies are left alone, resolving `Clone::clone` would link every generic clone to
+/// every `Clone` implementation of the project.
+pub(super) fn resolve_dynamic_dispatch(
+    db: &RootDatabase,
+    vfs: &Vfs,
+    project_root: &AbsPathBuf,
+    relations: Vec<CallRelation>,
+) -> Vec<CallRelation> {
+    let _p = tracing::info_span!("resolve_dynamic_dispatch").entered();
+    salsa::attach(db, || {
+        let sema = Semantics::new(db);
+        let mut implementations: FxHashMap<(String, u32, u32), Vec<FunctionInfo>> =
+            FxHashMap::default();
+        let mut resolved = Vec::with_capacity(relations.len());
+        for relation in relations {
+            let callee = &relation.callee;
+            if relation.instantiated_with.is_some()
+                || is_external_path(&callee.file_path, project_root)
+            {
+                resolved.push(relation);
+                continue;
+            }
+            let targets = implementations
+                .entry((callee.file_path.clone(), callee.line, callee.column))
+                .or_insert_with(|| trait_method_implementations(&sema, vfs, project_root, callee));
+            if targets.is_empty() {
+                resolved.push(relation);
+                continue;
+            }
+            let confidence = 1.0 / targets.len() as f64;
+            resolved.extend(targets.iter().map(|target| CallRelation {
+                callee: target.clone(),
+                dispatch: Dispatch::Dynamic,
+                confidence,
+                ..relation.clone()
+            }));
+        }
+        resolved
+    })
+}
+
+/// The project functions a call to `callee` may end up in when it's a trait method: the
+/// implementations overriding it, plus its default body if some implementation keeps it.
+fn trait_method_implementations(
+    sema: &Semantics<'_, RootDatabase>,
+    vfs: &Vfs,
+    project_root: &AbsPathBuf,
+    callee: &FunctionInfo,
+) -> Vec<FunctionInfo> {
+    let db = sema.db;
+    let Some(method) = resolve_function(sema, vfs, callee) else { return Vec::new() };
+    let Some(trait_) = method.as_assoc_item(db).and_then(|it| it.container_trait(db)) else {
+        return Vec::new();
+    };
+
+    let name = method.name(db);
+    let mut targets = Vec::new();
+    let mut keeps_default = false;
+    for impl_ in Impl::all_for_trait(db, trait_) {
+        let overriding = impl_.items(db).into_iter().find_map(|item| match item {
+            AssocItem::Function(function) if function.name(db) == name => Some(function),
+            _ => None,
+        });
+        match overriding {
+            Some(function) => targets.push(function),
+            None => keeps_default = true,
+        }
+    }
+    if keeps_default && method.has_body(db) {
+        targets.push(method);
+    }
+    targets
+        .into_iter()
+        .filter_map(|function| extract_function_info(db, function, vfs).ok().flatten())
+        .filter(|function| !is_external_path(&function.file_path, project_root))
+        .collect()
+}
+
+/// The function whose name is at the position of `function`.
+fn resolve_function(
+    sema: &Semantics<'_, RootDatabase>,
+    vfs: &Vfs,
+    function: &FunctionInfo,
+) -> Option<hir::Function> {
+    let file_id = find_file_id_by_path(vfs, &function.file_path)?;
+    let line_index = sema.db.line_index(file_id);
+    let offset = line_index.offset(LineCol {
+        line: function.line.saturating_sub(1),
+        col: function.column.saturating_sub(1),
+    })?;
+    let file = sema.parse_guess_edition(file_id);
+    let token = file.syntax().token_at_offset(offset).right_biased()?;
+    let fn_ = token.parent_ancestors().find_map(ast::Fn::cast)?;
+    sema.to_def(&fn_)
+}
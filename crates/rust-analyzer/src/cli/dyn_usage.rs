@@ -0,0 +1,361 @@
+//! Reports where the project uses trait objects: every `dyn Trait` type, the functions taking
+//! one, and the concrete types coerced into each trait object.
+//!
+//! The call graph resolves calls through a trait object to the trait method only, the concrete
+//! types listed here are the implementations such a call can actually reach.
+
+use std::fs;
+
+use anyhow::Result;
+use hir::{DisplayTarget, HirDisplay, PathResolution, Semantics, Type};
+use ide_db::{RootDatabase, base_db::salsa};
+use itertools::Itertools;
+use serde::Serialize;
+use syntax::{
+    AstNode, SyntaxKind, SyntaxNode,
+    ast::{self, HasName},
+};
+
+use crate::cli::{
+    code_graph::{LoadOptions, LoadedProject, project_files},
+    flags,
+    function_analyzer::convert_to_relative_path,
+};
+
+/// Smart pointers a trait object is commonly stored behind.
+const POINTERS: &[&str] = &["Box", "Rc", "Arc"];
+
+impl flags::DynUsage {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("dyn_usage", path = %self.path.display()).entered();
+        eprintln!("Loading workspace...");
+        let project = LoadedProject::load(
+            &self.path,
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+
+        eprintln!("Collecting trait objects...");
+        let report = collect_report(&project);
+        eprintln!(
+            "Found {} `dyn` types of {} traits, {} functions taking trait objects",
+            report.usages.len(),
+            report.traits.len(),
+            report.apis.len()
+        );
+
+        let json = serde_json::to_string_pretty(&report)?;
+        match &self.output {
+            Some(path) => fs::write(path, json)?,
+            None => println!("{json}"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct DynReport {
+    traits: Vec<TraitSummary>,
+    usages: Vec<DynUsage>,
+    apis: Vec<DynApi>,
+}
+
+#[derive(Debug, Serialize)]
+struct TraitSummary {
+    #[serde(rename = "trait")]
+    trait_name: String,
+    usages: usize,
+    /// Every concrete type observed being coerced into a trait object of this trait.
+    concrete_types: Vec<String>,
+    coercions: Vec<Coercion>,
+}
+
+#[derive(Debug, Serialize)]
+struct DynUsage {
+    #[serde(rename = "trait")]
+    trait_name: String,
+    /// The full trait object type, e.g. `dyn Handler + Send`.
+    ty: String,
+    /// `field`, `param`, `return`, `let`, `cast`, `type_alias`, `impl` or `other`.
+    kind: &'static str,
+    /// The enclosing item, `Struct::field` for fields.
+    item: String,
+    /// What the trait object sits behind, `Box`, `Rc`, `Arc`, `&` or `&mut`.
+    pointer: Option<String>,
+    file: String,
+    line: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct DynApi {
+    function: String,
+    params: Vec<String>,
+    file: String,
+    line: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct Coercion {
+    #[serde(rename = "type")]
+    ty: String,
+    file: String,
+    line: u32,
+}
+
+struct FileCollector<'a, 'db> {
+    sema: &'a Semantics<'db, RootDatabase>,
+    display_target: DisplayTarget,
+    file: String,
+    line_index: &'a ide::LineIndex,
+}
+
+fn collect_report(project: &LoadedProject) -> DynReport {
+    let analysis = project.analysis();
+    let sema = Semantics::new(&project.db);
+    let mut report = DynReport::default();
+    let mut coercions: Vec<(String, Coercion)> = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let Some(krate) = sema.first_crate(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let collector = FileCollector {
+            sema: &sema,
+            display_target: krate.to_display_target(&project.db),
+            file: convert_to_relative_path(&file_path, &project.project_root),
+            line_index: &line_index,
+        };
+        // Types can only be inspected with the database attached to the thread.
+        salsa::attach(&project.db, || {
+            for node in file.syntax().descendants() {
+                if let Some(dyn_ty) = ast::DynTraitType::cast(node.clone()) {
+                    report.usages.extend(collector.usage(&dyn_ty));
+                } else if let Some(func) = ast::Fn::cast(node.clone()) {
+                    report.apis.extend(collector.api(&func));
+                } else if let Some(expr) = ast::Expr::cast(node) {
+                    coercions.extend(collector.coercions(&expr));
+                }
+            }
+        });
+    }
+
+    let mut coercions = coercions.into_iter().into_group_map();
+    let traits = report
+        .usages
+        .iter()
+        .map(|usage| usage.trait_name.clone())
+        .chain(coercions.keys().cloned())
+        .sorted()
+        .dedup()
+        .collect_vec();
+    report.traits = traits
+        .into_iter()
+        .map(|trait_name| {
+            let coercions = coercions.remove(&trait_name).unwrap_or_default();
+            TraitSummary {
+                usages: report.usages.iter().filter(|it| it.trait_name == trait_name).count(),
+                concrete_types: coercions.iter().map(|it| it.ty.clone()).sorted().dedup().collect(),
+                coercions,
+                trait_name,
+            }
+        })
+        .collect();
+    report
+}
+
+impl FileCollector<'_, '_> {
+    fn usage(&self, dyn_ty: &ast::DynTraitType) -> Option<DynUsage> {
+        let trait_name = self.principal_trait(dyn_ty)?;
+        let (kind, item) = usage_context(dyn_ty.syntax());
+        Some(DynUsage {
+            trait_name,
+            ty: dyn_ty.syntax().text().to_string(),
+            kind,
+            item,
+            pointer: pointer(dyn_ty.syntax()),
+            file: self.file.clone(),
+            line: self.line(dyn_ty.syntax()),
+        })
+    }
+
+    /// A function with at least one trait object parameter.
+    fn api(&self, func: &ast::Fn) -> Option<DynApi> {
+        let params = func
+            .param_list()?
+            .params()
+            .filter(|param| {
+                param.syntax().descendants().any(|it| it.kind() == SyntaxKind::DYN_TRAIT_TYPE)
+            })
+            .map(|param| param.syntax().text().to_string())
+            .collect_vec();
+        if params.is_empty() {
+            return None;
+        }
+        Some(DynApi {
+            function: qualified_fn_name(func)?,
+            params,
+            file: self.file.clone(),
+            line: self.line(func.syntax()),
+        })
+    }
+
+    /// The `(trait, coercion)` pairs of a concrete type turned into a trait object by `expr`,
+    /// either through an implicit unsizing coercion or an `as` cast.
+    fn coercions(&self, expr: &ast::Expr) -> Vec<(String, Coercion)> {
+        let db = self.sema.db;
+        let (from, to) = match expr {
+            ast::Expr::CastExpr(cast) => {
+                let Some(from) = cast.expr().and_then(|it| self.sema.type_of_expr(&it)) else {
+                    return Vec::new();
+                };
+                let Some(to) = self.sema.type_of_expr(expr) else { return Vec::new() };
+                (from.original, to.original)
+            }
+            _ => {
+                let Some(info) = self.sema.type_of_expr(expr) else { return Vec::new() };
+                let Some(adjusted) = info.adjusted else { return Vec::new() };
+                (info.original, adjusted)
+            }
+        };
+        let existing = dyn_traits(db, &from);
+        let pointee = pointee(db, &from);
+        if pointee.as_dyn_trait().is_some() || pointee.is_unknown() {
+            return Vec::new();
+        }
+        let ty = pointee.display(db, self.display_target).to_string();
+        dyn_traits(db, &to)
+            .into_iter()
+            .filter(|it| !existing.contains(it))
+            .map(|trait_name| {
+                let coercion = Coercion {
+                    ty: ty.clone(),
+                    file: self.file.clone(),
+                    line: self.line(expr.syntax()),
+                };
+                (trait_name, coercion)
+            })
+            .collect()
+    }
+
+    /// The first non auto trait of a trait object, e.g. `Handler` for `dyn Handler + Send`.
+    fn principal_trait(&self, dyn_ty: &ast::DynTraitType) -> Option<String> {
+        let db = self.sema.db;
+        let paths = dyn_ty
+            .type_bound_list()?
+            .bounds()
+            .filter_map(|bound| match bound.ty()? {
+                ast::Type::PathType(path) => path.path(),
+                _ => None,
+            })
+            .collect_vec();
+        let resolved = paths.iter().find_map(|path| match self.sema.resolve_path(path)? {
+            PathResolution::Def(hir::ModuleDef::Trait(it)) if !it.is_auto(db) => {
+                Some(it.name(db).as_str().to_owned())
+            }
+            _ => None,
+        });
+        // Unresolved traits, e.g. from crates that failed to load, are reported by name.
+        resolved.or_else(|| {
+            let name = paths.first()?.segment()?.name_ref()?;
+            Some(name.text().to_string())
+        })
+    }
+
+    fn line(&self, node: &SyntaxNode) -> u32 {
+        self.line_index.line_col(node.text_range().start()).line + 1
+    }
+}
+
+/// Names of the traits of every trait object within `ty`.
+fn dyn_traits<'db>(db: &'db RootDatabase, ty: &Type<'db>) -> Vec<String> {
+    let mut traits = Vec::new();
+    ty.walk(db, |it| {
+        if let Some(it) = it.as_dyn_trait() {
+            traits.push(it.name(db).as_str().to_owned());
+        }
+    });
+    traits
+}
+
+/// The type behind references and smart pointers, `Foo` for `&Box<Foo>`.
+fn pointee<'db>(db: &'db RootDatabase, ty: &Type<'db>) -> Type<'db> {
+    let ty = ty.strip_references();
+    match ty.as_adt() {
+        Some(adt) if POINTERS.contains(&adt.name(db).as_str()) => {
+            let inner = ty.type_arguments().next();
+            inner.map(|it| it.strip_references()).unwrap_or(ty)
+        }
+        _ => ty,
+    }
+}
+
+fn usage_context(node: &SyntaxNode) -> (&'static str, String) {
+    for ancestor in node.ancestors() {
+        if let Some(field) = ast::RecordField::cast(ancestor.clone()) {
+            let owner = field
+                .syntax()
+                .ancestors()
+                .find_map(ast::Adt::cast)
+                .and_then(|adt| adt.name())
+                .map(|name| name.text().to_string())
+                .unwrap_or_default();
+            let field = field.name().map(|name| name.text().to_string()).unwrap_or_default();
+            return ("field", format!("{owner}::{field}"));
+        }
+        if let Some(field) = ast::TupleField::cast(ancestor.clone()) {
+            let owner = field.syntax().ancestors().find_map(ast::Adt::cast);
+            let name = owner.and_then(|adt| adt.name()).map(|it| it.text().to_string());
+            return ("field", name.unwrap_or_default());
+        }
+        let kind = match ancestor.kind() {
+            SyntaxKind::PARAM | SyntaxKind::SELF_PARAM => "param",
+            SyntaxKind::RET_TYPE => "return",
+            SyntaxKind::LET_STMT => "let",
+            SyntaxKind::CAST_EXPR => "cast",
+            SyntaxKind::TYPE_ALIAS => "type_alias",
+            SyntaxKind::IMPL => "impl",
+            _ => continue,
+        };
+        let item = ancestor
+            .ancestors()
+            .find_map(ast::Fn::cast)
+            .and_then(|func| qualified_fn_name(&func))
+            .or_else(|| {
+                let alias = ast::TypeAlias::cast(ancestor.clone())?;
+                Some(alias.name()?.text().to_string())
+            })
+            .unwrap_or_default();
+        return (kind, item);
+    }
+    let item = node.ancestors().find_map(ast::Fn::cast).and_then(|func| qualified_fn_name(&func));
+    ("other", item.unwrap_or_default())
+}
+
+/// What the trait object at `node` is stored behind, if anything.
+fn pointer(node: &SyntaxNode) -> Option<String> {
+    let parent = node.parent()?;
+    if let Some(reference) = ast::RefType::cast(parent.clone()) {
+        return Some(if reference.mut_token().is_some() { "&mut" } else { "&" }.to_owned());
+    }
+    // `Box<dyn Trait>`: type arg -> generic arg list -> path segment.
+    let segment = parent.ancestors().nth(2).and_then(ast::PathSegment::cast)?;
+    let name = segment.name_ref()?.text().to_string();
+    POINTERS.contains(&name.as_str()).then_some(name)
+}
+
+/// `Type::method` for associated functions, the plain name otherwise.
+fn qualified_fn_name(func: &ast::Fn) -> Option<String> {
+    let name = func.name()?.text().to_string();
+    let owner = func.syntax().parent().and_then(|list| list.parent()).and_then(|owner| {
+        if let Some(imp) = ast::Impl::cast(owner.clone()) {
+            return Some(imp.self_ty()?.syntax().text().to_string());
+        }
+        Some(ast::Trait::cast(owner)?.name()?.text().to_string())
+    });
+    Some(match owner {
+        Some(owner) => format!("{owner}::{name}"),
+        None => name,
+    })
+}
@@ -0,0 +1,197 @@
+//! Detection of the functions called from outside of the project: `main`, the public API, anchor
+//! `#[program]` handlers and native `entrypoint!` functions, tests, and implementations of
+//! dependency traits, which the dependency calls. Anything else is only reached through calls
+//! from these.
+
+use hir::{AsAssocItem, Semantics};
+use ide::{LineCol, RootDatabase};
+use ide_db::{LineIndexDatabase, base_db::salsa, defs::Definition};
+use rustc_hash::FxHashSet;
+use serde::Serialize;
+use syntax::{
+    AstNode, SyntaxKind,
+    ast::{self, HasAttrs},
+};
+use vfs::Vfs;
+
+use crate::cli::{
+    flags::{self, MinVisibility},
+    function_analyzer::{FunctionInfo, find_file_id_by_path, is_visible},
+};
+
+/// Why a function is an entry point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum EntryKind {
+    Main,
+    Public,
+    Program,
+    /// A native program entrypoint, like the function passed to `entrypoint!`.
+    Entrypoint,
+    Test,
+    TraitImpl,
+    /// Matched by an `--entry` pattern.
+    Pattern,
+}
+
+impl EntryKind {
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            EntryKind::Main => "main",
+            EntryKind::Public => "public",
+            EntryKind::Program => "program",
+            EntryKind::Entrypoint => "entrypoint",
+            EntryKind::Test => "test",
+            EntryKind::TraitImpl => "trait-impl",
+            EntryKind::Pattern => "pattern",
+        }
+    }
+}
+
+/// The entry kind of every function of `functions`, `None` for functions only reached through
+/// calls.
+pub(super) fn entry_kinds(
+    db: &RootDatabase,
+    vfs: &Vfs,
+    functions: &[FunctionInfo],
+    kinds: &flags::EntryKinds,
+    patterns: &[String],
+) -> Vec<Option<EntryKind>> {
+    let _p = tracing::info_span!("entry_kinds").entered();
+    salsa::attach(db, || {
+        let sema = Semantics::new(db);
+        let entrypoints = if kinds.program {
+            entrypoint_names(&sema, vfs, functions)
+        } else {
+            FxHashSet::default()
+        };
+        functions
+            .iter()
+            .map(|function| {
+                let path = function.path();
+                if patterns.iter().any(|pattern| matches_pattern(pattern, &path)) {
+                    return Some(EntryKind::Pattern);
+                }
+                let fn_ = fn_at(&sema, vfs, function)?;
+                if kinds.program && in_program_module(&fn_) {
+                    return Some(EntryKind::Program);
+                }
+                if kinds.program && entrypoints.contains(&function.name) {
+                    return Some(EntryKind::Entrypoint);
+                }
+                let def = sema.to_def(&fn_)?;
+                if kinds.main && def.name(db).as_str() == "main" && def.module(db).is_crate_root() {
+                    Some(EntryKind::Main)
+                } else if kinds.tests && (def.is_test(db) || def.is_bench(db)) {
+                    Some(EntryKind::Test)
+                } else if kinds.trait_impls && implements_dependency_trait(db, def) {
+                    Some(EntryKind::TraitImpl)
+                } else if kinds.public
+                    && is_visible(db, Definition::Function(def), MinVisibility::Public)
+                {
+                    Some(EntryKind::Public)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    })
+}
+
+/// The anchor `#[program]` handlers and native entrypoints of `functions`, the roots of their
+/// per-instruction call trees.
+pub(super) fn program_roots(
+    db: &RootDatabase,
+    vfs: &Vfs,
+    functions: &[FunctionInfo],
+) -> Vec<(FunctionInfo, EntryKind)> {
+    let kinds = flags::EntryKinds {
+        main: false,
+        public: false,
+        program: true,
+        tests: false,
+        trait_impls: false,
+    };
+    functions
+        .iter()
+        .zip(entry_kinds(db, vfs, functions, &kinds, &[]))
+        .filter_map(|(function, kind)| Some((function.clone(), kind?)))
+        .collect()
+}
+
+/// The names of the functions passed to `entrypoint!` in the files of `functions`, plus
+/// `process_instruction` which native programs conventionally use.
+fn entrypoint_names(
+    sema: &Semantics<'_, RootDatabase>,
+    vfs: &Vfs,
+    functions: &[FunctionInfo],
+) -> FxHashSet<String> {
+    let mut names: FxHashSet<String> = FxHashSet::from_iter(["process_instruction".to_owned()]);
+    let files: FxHashSet<&str> = functions.iter().map(|it| it.file_path.as_str()).collect();
+    for file_path in files {
+        let Some(file_id) = find_file_id_by_path(vfs, file_path) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        for call in file.syntax().descendants().filter_map(ast::MacroCall::cast) {
+            let Some(segment) = call.path().and_then(|it| it.segment()) else { continue };
+            if !matches!(segment.to_string().as_str(), "entrypoint" | "entrypoint_no_alloc") {
+                continue;
+            }
+            let name = call.token_tree().and_then(|tt| {
+                tt.syntax()
+                    .children_with_tokens()
+                    .filter_map(|it| it.into_token())
+                    .find(|token| token.kind() == SyntaxKind::IDENT)
+            });
+            names.extend(name.map(|token| token.text().to_owned()));
+        }
+    }
+    names
+}
+
+/// Whether `fn_` is an instruction handler of an anchor `#[program]` module.
+fn in_program_module(fn_: &ast::Fn) -> bool {
+    let Some(module) = fn_.syntax().parent().and_then(|it| it.parent()).and_then(ast::Module::cast)
+    else {
+        return false;
+    };
+    module.attrs().any(|attr| attr.simple_name().as_deref() == Some("program"))
+}
+
+fn implements_dependency_trait(db: &RootDatabase, function: hir::Function) -> bool {
+    function
+        .as_assoc_item(db)
+        .and_then(|item| item.implemented_trait(db))
+        .is_some_and(|trait_| !trait_.module(db).krate().origin(db).is_local())
+}
+
+fn fn_at(
+    sema: &Semantics<'_, RootDatabase>,
+    vfs: &Vfs,
+    function: &FunctionInfo,
+) -> Option<ast::Fn> {
+    let file_id = find_file_id_by_path(vfs, &function.file_path)?;
+    let offset = sema.db.line_index(file_id).offset(LineCol {
+        line: function.line.saturating_sub(1),
+        col: function.column.saturating_sub(1),
+    })?;
+    let file = sema.parse_guess_edition(file_id);
+    let token = file.syntax().token_at_offset(offset).right_biased()?;
+    token.parent_ancestors().find_map(ast::Fn::cast)
+}
+
+/// Matches `path` against a pattern where `*` stands for any run of characters, e.g.
+/// `my_program::instructions::*::handler`.
+pub(super) fn matches_pattern(pattern: &str, path: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = path.strip_prefix(first) else { return false };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else { return rest.is_empty() };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
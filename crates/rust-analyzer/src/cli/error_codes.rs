@@ -0,0 +1,178 @@
+//! The Anchor `#[error_code]` enums of a project, with the code and message of every error.
+//!
+//! Anchor numbers the errors of a program from `ERROR_CODE_OFFSET`, or the `offset` given to the
+//! attribute, adding the discriminant of each variant. The errors raised by account constraints
+//! with `@ ErrorCode::Variant` are resolved to the variant, with its code, message and location.
+//! When several programs of a workspace declare an error enum of the same name, the one of the
+//! program declaring the accounts struct is used.
+
+use anyhow::Result;
+use hir::Semantics;
+use ide::Analysis;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use syntax::{
+    AstNode, AstToken, SyntaxKind,
+    ast::{self, HasAttrs, HasName},
+};
+
+use crate::cli::{
+    code_graph::{
+        AccountStruct, LoadedProject, discriminants, module_path, project_files, qualify,
+    },
+    function_analyzer::convert_to_relative_path,
+};
+
+/// The code of the first error of an `#[error_code]` enum without an `offset`.
+const ERROR_CODE_OFFSET: u32 = 6000;
+
+/// An `#[error_code]` enum.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct ErrorCodeEnum {
+    pub(super) name: String,
+    pub(super) file: String,
+    pub(super) line: u32,
+    pub(super) module: String,
+    /// The code of the variant with the discriminant 0.
+    pub(super) offset: u32,
+    pub(super) errors: Vec<ErrorCode>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct ErrorCode {
+    pub(super) name: String,
+    pub(super) line: u32,
+    /// None when the discriminant of the variant can't be evaluated.
+    pub(super) code: Option<u32>,
+    /// The text of its `#[msg(...)]` attribute.
+    pub(super) msg: Option<String>,
+}
+
+/// The variant of an `#[error_code]` enum a constraint raises.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct ErrorVariant {
+    /// Path of the enum, like `pump::errors::ContractError`.
+    pub(super) error_enum: String,
+    pub(super) name: String,
+    pub(super) file: String,
+    pub(super) line: u32,
+    pub(super) msg: Option<String>,
+}
+
+/// Collects every `#[error_code]` enum declared in project files.
+pub(super) fn extract_error_codes(
+    project: &LoadedProject,
+    analysis: &Analysis,
+) -> Result<Vec<ErrorCodeEnum>> {
+    let _p = tracing::info_span!("extract_error_codes").entered();
+    let sema = Semantics::new(&project.db);
+    let mut enums = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let module = sema
+            .file_to_module_def(file_id)
+            .map(|module| module_path(&project.db, module))
+            .unwrap_or_default();
+
+        for enum_ in file.syntax().descendants().filter_map(ast::Enum::cast) {
+            let Some(attr) =
+                enum_.attrs().find(|attr| attr.simple_name().as_deref() == Some("error_code"))
+            else {
+                continue;
+            };
+            let Some(name) = enum_.name() else { continue };
+            let offset = offset(&attr).unwrap_or(ERROR_CODE_OFFSET);
+            let discriminants = discriminants(&sema, &enum_);
+            let line_of =
+                |name: &ast::Name| line_index.line_col(name.syntax().text_range().start()).line + 1;
+            let errors = enum_
+                .variant_list()
+                .into_iter()
+                .flat_map(|it| it.variants())
+                .filter_map(|variant| {
+                    let name = variant.name()?;
+                    let discriminant = discriminants.get(name.text().as_str()).copied();
+                    Some(ErrorCode {
+                        name: name.text().to_string(),
+                        line: line_of(&name),
+                        code: discriminant
+                            .and_then(|it| u32::try_from(it).ok())
+                            .and_then(|it| it.checked_add(offset)),
+                        msg: message(&variant),
+                    })
+                })
+                .collect();
+            enums.push(ErrorCodeEnum {
+                name: name.text().to_string(),
+                file: convert_to_relative_path(&file_path, &project.project_root),
+                line: line_of(&name),
+                module: module.clone(),
+                offset,
+                errors,
+            });
+        }
+    }
+    enums.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(enums)
+}
+
+/// The `N` of `#[error_code(offset = N)]`.
+fn offset(attr: &ast::Attr) -> Option<u32> {
+    let (_, tt) = attr.as_simple_call()?;
+    let text: String = tt.syntax().text().to_string().split_whitespace().collect();
+    let (key, value) = text.trim_start_matches('(').trim_end_matches(')').split_once('=')?;
+    (key == "offset").then(|| value.replace('_', "").parse().ok())?
+}
+
+/// The text of the `#[msg("...")]` attribute of `variant`.
+fn message(variant: &ast::Variant) -> Option<String> {
+    let (_, tt) =
+        variant.attrs().filter_map(|attr| attr.as_simple_call()).find(|(name, _)| name == "msg")?;
+    let token = tt
+        .syntax()
+        .descendants_with_tokens()
+        .find_map(|it| it.into_token().filter(|token| token.kind() == SyntaxKind::STRING))?;
+    Some(ast::String::cast(token)?.value().ok()?.into_owned())
+}
+
+/// Resolves every constraint error naming a variant of `enums`, like `ErrorCode::NotOwner`, to
+/// that variant and its code. Errors of an enum name shared by several `#[error_code]` enums of
+/// the crate of the struct, or of other crates only, stay unresolved.
+pub(super) fn link_constraint_errors(structs: &mut [AccountStruct], enums: &[ErrorCodeEnum]) {
+    let mut by_name: FxHashMap<&str, Vec<&ErrorCodeEnum>> = FxHashMap::default();
+    for enum_ in enums {
+        by_name.entry(&enum_.name).or_default().push(enum_);
+    }
+    let crate_of = |module: &str| module.split("::").next().unwrap_or_default().to_owned();
+    for strukt in structs {
+        let krate = crate_of(&strukt.module);
+        let resolve = |error: &str| {
+            let mut segments = error.rsplit("::").map(str::trim);
+            let variant = segments.next()?;
+            let enum_ = match by_name.get(segments.next()?)?.as_slice() {
+                [enum_] => *enum_,
+                candidates => {
+                    let mut local = candidates.iter().filter(|it| crate_of(&it.module) == krate);
+                    match (local.next(), local.next()) {
+                        (Some(enum_), None) => *enum_,
+                        _ => return None,
+                    }
+                }
+            };
+            let error = enum_.errors.iter().find(|it| it.name == variant)?;
+            Some((enum_, error))
+        };
+        for constraint in strukt.fields.iter_mut().flat_map(|it| &mut it.constraints) {
+            let resolved = constraint.error.as_deref().and_then(resolve);
+            constraint.error_code = resolved.and_then(|(_, error)| error.code);
+            constraint.error_variant = resolved.map(|(enum_, error)| ErrorVariant {
+                error_enum: qualify(&enum_.module, &enum_.name),
+                name: error.name.clone(),
+                file: enum_.file.clone(),
+                line: error.line,
+                msg: error.msg.clone(),
+            });
+        }
+    }
+}
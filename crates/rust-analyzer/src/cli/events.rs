@@ -0,0 +1,179 @@
+//! The Anchor `#[event]` structs of a project, the `emit!` and `emit_cpi!` calls emitting them
+//! and the instructions reaching those calls.
+//!
+//! Events are how programs make their state changes observable off-chain. An instruction emits an
+//! event when its handler, or any function it calls, contains an emit site of the event.
+
+use anyhow::Result;
+use hir::Semantics;
+use ide::Analysis;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use syntax::{
+    AstNode, NodeOrToken, SyntaxKind,
+    ast::{self, HasAttrs, HasName},
+};
+
+use crate::cli::{
+    code_graph::{CodeGraph, LoadedProject, VariantField, module_path, project_files},
+    discriminators::discriminator,
+    function_analyzer::convert_to_relative_path,
+};
+
+/// An `#[event]` struct.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct GraphEvent {
+    pub(super) name: String,
+    pub(super) file: String,
+    pub(super) line: u32,
+    pub(super) module: String,
+    pub(super) fields: Vec<VariantField>,
+    /// The discriminator prefixing its data, as hex. None when it isn't a literal.
+    pub(super) discriminator: Option<String>,
+    pub(super) emit_sites: Vec<EmitSite>,
+    /// The paths of the instructions reaching one of the emit sites.
+    pub(super) instructions: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct EmitSite {
+    pub(super) file: String,
+    pub(super) line: u32,
+    /// The function containing the call, none at the top level of a macro or constant.
+    pub(super) function: Option<String>,
+    /// Whether the event is emitted through a self-CPI with `emit_cpi!`.
+    pub(super) cpi: bool,
+    /// The id of `function` in the call graph.
+    #[serde(skip)]
+    function_id: Option<usize>,
+}
+
+/// Collects every `#[event]` struct declared in project files with the sites emitting it, and
+/// the instructions of `graph` reaching those sites.
+pub(super) fn extract_events(
+    project: &LoadedProject,
+    analysis: &Analysis,
+    graph: &CodeGraph,
+) -> Result<Vec<GraphEvent>> {
+    let _p = tracing::info_span!("extract_events").entered();
+    let sema = Semantics::new(&project.db);
+    let mut events = Vec::new();
+    let mut sites: Vec<(String, EmitSite)> = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let relative_path = convert_to_relative_path(&file_path, &project.project_root);
+        let line_of =
+            |node: &syntax::SyntaxNode| line_index.line_col(node.text_range().start()).line + 1;
+
+        for strukt in file.syntax().descendants().filter_map(ast::Struct::cast) {
+            let Some(attr) =
+                strukt.attrs().find(|attr| attr.simple_name().as_deref() == Some("event"))
+            else {
+                continue;
+            };
+            let Some(name) = strukt.name() else { continue };
+            let module = sema
+                .to_def(&strukt)
+                .map(|it| module_path(&project.db, it.module(&project.db)))
+                .unwrap_or_default();
+            events.push(GraphEvent {
+                name: name.text().to_string(),
+                file: relative_path.clone(),
+                line: line_of(name.syntax()),
+                module,
+                fields: struct_fields(&strukt),
+                discriminator: discriminator(&attr, "event", name.text().as_str()),
+                emit_sites: Vec::new(),
+                instructions: Vec::new(),
+            });
+        }
+
+        for call in file.syntax().descendants().filter_map(ast::MacroCall::cast) {
+            let Some(macro_name) = call.path().and_then(|it| it.segment()?.name_ref()) else {
+                continue;
+            };
+            let cpi = match macro_name.text().as_str() {
+                "emit" => false,
+                "emit_cpi" => true,
+                _ => continue,
+            };
+            let Some(event) = call.token_tree().and_then(|tt| emitted_type(&tt)) else {
+                continue;
+            };
+            let function = call.syntax().ancestors().find_map(ast::Fn::cast);
+            let function_id = function.as_ref().and_then(|function| {
+                let name = function.name()?;
+                graph.function_at(&relative_path, name.text().as_str(), line_of(name.syntax()))
+            });
+            sites.push((
+                event,
+                EmitSite {
+                    file: relative_path.clone(),
+                    line: line_of(call.syntax()),
+                    function: function_id.map(|id| graph.function_path(id)),
+                    cpi,
+                    function_id,
+                },
+            ));
+        }
+    }
+
+    let reachable = graph.instruction_reach();
+    let mut by_name: FxHashMap<String, Vec<usize>> = FxHashMap::default();
+    for (index, event) in events.iter().enumerate() {
+        by_name.entry(event.name.clone()).or_default().push(index);
+    }
+    for (name, site) in sites {
+        // Events of the same name in several modules can't be told apart by the macro call.
+        let Some(&[index]) = by_name.get(&name).map(Vec::as_slice) else { continue };
+        let event = &mut events[index];
+        for (instruction, functions) in &reachable {
+            if site.function_id.is_some_and(|id| functions.contains(&id))
+                && !event.instructions.contains(instruction)
+            {
+                event.instructions.push(instruction.clone());
+            }
+        }
+        event.emit_sites.push(site);
+    }
+    events.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(events)
+}
+
+fn struct_fields(strukt: &ast::Struct) -> Vec<VariantField> {
+    let Some(ast::FieldList::RecordFieldList(fields)) = strukt.field_list() else {
+        return Vec::new();
+    };
+    fields
+        .fields()
+        .map(|field| VariantField {
+            name: field.name().map(|it| it.text().to_string()),
+            ty: field.ty().map(|it| it.syntax().text().to_string()).unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// The name of the struct built by the argument of `emit!(TradeEvent { .. })`.
+fn emitted_type(tt: &ast::TokenTree) -> Option<String> {
+    // The path ends at the braces of the struct literal, the last identifier is the type.
+    let mut name = None;
+    for child in tt.token_trees_and_tokens().skip(1) {
+        match child {
+            NodeOrToken::Token(token) if token.kind() == SyntaxKind::IDENT => {
+                name = Some(token.text().to_owned());
+            }
+            NodeOrToken::Token(token)
+                if matches!(
+                    token.kind(),
+                    SyntaxKind::COLON
+                        | SyntaxKind::WHITESPACE
+                        | SyntaxKind::CRATE_KW
+                        | SyntaxKind::SELF_KW
+                        | SyntaxKind::SUPER_KW
+                ) => {}
+            _ => break,
+        }
+    }
+    name
+}
@@ -0,0 +1,338 @@
+//! Packages the outputs of several analyzers into a single `.tar.zst` archive, so a run can be
+//! archived or handed to another tool as one artifact.
+//!
+//! Next to the outputs the archive holds a `manifest.json` recording the tool version, the
+//! command line, the git revision of the project and the schema version of every output. The
+//! archive itself is reproducible: entries are sorted and carry no timestamps or owners.
+
+use std::{env, fs, path::Path, process::Command};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use tenthash::TentHash;
+
+use crate::cli::{
+    anchor_lang::AnchorLang,
+    audit_report,
+    code_graph::{CodeGraph, GraphCall, GraphFunction, LoadOptions, LoadedProject},
+    exporters::{Exporter, Json, Toml, Yaml},
+    findings::Finding,
+    flags,
+    graph_filter::GraphFilter,
+    lint,
+    metrics::{self, Thresholds},
+};
+
+/// Version of the manifest layout itself.
+const MANIFEST_SCHEMA_VERSION: u32 = 1;
+
+/// Bumped whenever the output of the corresponding analyzer changes incompatibly.
+const CALL_GRAPH_SCHEMA_VERSION: u32 = 1;
+const STRUCTS_SCHEMA_VERSION: u32 = 2;
+const INSTRUCTIONS_SCHEMA_VERSION: u32 = 1;
+const ENUMS_SCHEMA_VERSION: u32 = 1;
+const ERROR_CODES_SCHEMA_VERSION: u32 = 1;
+const EVENTS_SCHEMA_VERSION: u32 = 1;
+const STATE_ACCOUNTS_SCHEMA_VERSION: u32 = 1;
+const ZERO_COPY_SCHEMA_VERSION: u32 = 1;
+const CPI_CALLS_SCHEMA_VERSION: u32 = 1;
+const TOKEN_OPERATIONS_SCHEMA_VERSION: u32 = 1;
+const SYSVARS_SCHEMA_VERSION: u32 = 1;
+const LAMPORT_FLOWS_SCHEMA_VERSION: u32 = 1;
+const PROGRAMS_SCHEMA_VERSION: u32 = 1;
+const NATIVE_PROGRAMS_SCHEMA_VERSION: u32 = 1;
+const PDAS_SCHEMA_VERSION: u32 = 1;
+const AUTHORITIES_SCHEMA_VERSION: u32 = 1;
+const STATE_ACCESS_SCHEMA_VERSION: u32 = 1;
+const GOVERNANCE_SCHEMA_VERSION: u32 = 1;
+const ANCHOR_FINDINGS_SCHEMA_VERSION: u32 = 1;
+const FINDINGS_SCHEMA_VERSION: u32 = 1;
+const METRICS_SCHEMA_VERSION: u32 = 1;
+const REPORT_SCHEMA_VERSION: u32 = 1;
+
+/// zstd level of the archive, the default trades well between size and speed.
+const COMPRESSION_LEVEL: i32 = 3;
+
+#[derive(Debug, Serialize)]
+struct Manifest {
+    schema_version: u32,
+    tool: Tool,
+    /// The command line the bundle was produced with.
+    flags: Vec<String>,
+    project: String,
+    /// `None` when the project isn't part of a git repository.
+    git: Option<GitRevision>,
+    /// The anchor-lang dependency the constraints were checked against, `None` without one.
+    anchor_lang: Option<AnchorLang>,
+    outputs: Vec<Output>,
+}
+
+#[derive(Debug, Serialize)]
+struct Tool {
+    name: &'static str,
+    version: String,
+}
+
+#[derive(Debug, Serialize)]
+struct GitRevision {
+    revision: String,
+    /// Whether the working tree had uncommitted changes.
+    dirty: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Output {
+    file: String,
+    analyzer: &'static str,
+    schema_version: u32,
+    /// Hex encoded TentHash of the file contents.
+    hash: String,
+}
+
+#[derive(Serialize)]
+struct CallGraph<'a> {
+    functions: &'a [GraphFunction],
+    calls: &'a [GraphCall],
+}
+
+impl flags::Bundle {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("export_bundle", path = %self.path.display()).entered();
+        let analyzers = self.analyzers.unwrap_or_default();
+        let redaction = self.redact.unwrap_or_default();
+        let rules = self.rules.unwrap_or_default();
+        eprintln!("Loading workspace...");
+        let project = LoadedProject::load(
+            &self.path,
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+        rules.validate(&project)?;
+        let filter = GraphFilter { structs: &self.struct_name, files: &self.file };
+
+        let files = match self.format.unwrap_or_default() {
+            flags::BundleFormat::Json => {
+                outputs(&project, analyzers, &redaction, &rules, &filter, self.report, &Json)?
+            }
+            flags::BundleFormat::Yaml => {
+                outputs(&project, analyzers, &redaction, &rules, &filter, self.report, &Yaml)?
+            }
+            flags::BundleFormat::Toml => {
+                outputs(&project, analyzers, &redaction, &rules, &filter, self.report, &Toml)?
+            }
+        };
+
+        let project_root = project.project_root.to_string();
+        let manifest = Manifest {
+            schema_version: MANIFEST_SCHEMA_VERSION,
+            tool: Tool { name: "rust-analyzer", version: crate::version().to_string() },
+            flags: env::args().map(|arg| redaction.path(&arg)).collect(),
+            project: redaction.path(&project_root),
+            git: git_revision(&project_root),
+            anchor_lang: AnchorLang::detect(&project.db),
+            outputs: files
+                .iter()
+                .map(|(file, analyzer, schema_version, contents)| Output {
+                    file: file.clone(),
+                    analyzer,
+                    schema_version: *schema_version,
+                    hash: hash(contents),
+                })
+                .collect(),
+        };
+        let mut entries: Vec<(String, String)> =
+            files.into_iter().map(|(file, _, _, contents)| (file, contents)).collect();
+        entries.push(("manifest.json".to_owned(), serde_json::to_string_pretty(&manifest)?));
+        entries.sort();
+
+        write_archive(&self.output, &entries)
+            .with_context(|| format!("failed to write {}", self.output.display()))?;
+        eprintln!("Bundled {} files into {}", entries.len(), self.output.display());
+        Ok(())
+    }
+}
+
+/// Runs the selected analyzers, serializing their outputs with `exporter`, and renders the report
+/// over them.
+fn outputs(
+    project: &LoadedProject,
+    analyzers: flags::Analyzers,
+    redaction: &flags::Redaction,
+    rules: &flags::RuleSelection,
+    filter: &GraphFilter<'_>,
+    report: Option<flags::ReportFormat>,
+    exporter: &impl Exporter,
+) -> Result<Vec<(String, &'static str, u32, String)>> {
+    let mut outputs = Outputs { exporter, files: Vec::new() };
+    // The report covers the code graph and every finding of the bundle.
+    let mut report_graph = None;
+    let mut findings: Vec<Finding> = Vec::new();
+    if analyzers.call_graph || analyzers.structs || report.is_some() {
+        let mut graph = CodeGraph::build(project)?;
+        redaction.graph(&mut graph);
+        rules.retain(&mut graph.findings);
+        filter.retain(&mut graph);
+        if analyzers.call_graph {
+            let call_graph = CallGraph { functions: &graph.functions, calls: &graph.calls };
+            outputs.push("call_graph", "call-graph", CALL_GRAPH_SCHEMA_VERSION, &call_graph)?;
+        }
+        if analyzers.structs {
+            outputs.push("structs", "structs", STRUCTS_SCHEMA_VERSION, &graph.account_structs)?;
+            outputs.push(
+                "instructions",
+                "structs",
+                INSTRUCTIONS_SCHEMA_VERSION,
+                &graph.instructions,
+            )?;
+            outputs.push("enums", "structs", ENUMS_SCHEMA_VERSION, &graph.enums)?;
+            outputs.push(
+                "error_codes",
+                "structs",
+                ERROR_CODES_SCHEMA_VERSION,
+                &graph.error_codes,
+            )?;
+            outputs.push("events", "structs", EVENTS_SCHEMA_VERSION, &graph.events)?;
+            outputs.push(
+                "state_accounts",
+                "structs",
+                STATE_ACCOUNTS_SCHEMA_VERSION,
+                &graph.state_accounts,
+            )?;
+            outputs.push(
+                "zero_copy",
+                "structs",
+                ZERO_COPY_SCHEMA_VERSION,
+                &graph.zero_copy_types,
+            )?;
+            outputs.push("cpi_calls", "structs", CPI_CALLS_SCHEMA_VERSION, &graph.cpi_calls)?;
+            outputs.push(
+                "token_operations",
+                "structs",
+                TOKEN_OPERATIONS_SCHEMA_VERSION,
+                &graph.token_operations,
+            )?;
+            outputs.push("sysvars", "structs", SYSVARS_SCHEMA_VERSION, &graph.sysvars)?;
+            outputs.push(
+                "lamport_flows",
+                "structs",
+                LAMPORT_FLOWS_SCHEMA_VERSION,
+                &graph.lamport_flows,
+            )?;
+            outputs.push("programs", "structs", PROGRAMS_SCHEMA_VERSION, &graph.programs)?;
+            outputs.push(
+                "native_programs",
+                "structs",
+                NATIVE_PROGRAMS_SCHEMA_VERSION,
+                &graph.native_programs,
+            )?;
+            outputs.push("pdas", "structs", PDAS_SCHEMA_VERSION, &graph.pdas)?;
+            outputs.push(
+                "authorities",
+                "structs",
+                AUTHORITIES_SCHEMA_VERSION,
+                &graph.authorities,
+            )?;
+            outputs.push(
+                "state_access",
+                "structs",
+                STATE_ACCESS_SCHEMA_VERSION,
+                &graph.state_access,
+            )?;
+            outputs.push("governance", "structs", GOVERNANCE_SCHEMA_VERSION, &graph.governance)?;
+            outputs.push(
+                "anchor_findings",
+                "structs",
+                ANCHOR_FINDINGS_SCHEMA_VERSION,
+                &graph.findings,
+            )?;
+        }
+        findings.extend(graph.findings.iter().cloned());
+        report_graph = Some(graph);
+    }
+    if analyzers.findings {
+        eprintln!("Running lints...");
+        let mut lint_findings = lint::lint(project);
+        rules.retain(&mut lint_findings);
+        outputs.push("findings", "findings", FINDINGS_SCHEMA_VERSION, &lint_findings)?;
+        findings.extend(lint_findings);
+    }
+    if analyzers.metrics {
+        eprintln!("Computing metrics...");
+        let mut metrics_report = metrics::metrics_report(project, &Thresholds::default())?;
+        rules.retain(&mut metrics_report.findings);
+        outputs.push("metrics", "metrics", METRICS_SCHEMA_VERSION, &metrics_report)?;
+        findings.extend(metrics_report.findings);
+    }
+    if let (Some(report), Some(graph)) = (report, &report_graph) {
+        let (file, contents) = match report {
+            flags::ReportFormat::Markdown => {
+                ("report.md", audit_report::markdown(graph, &findings))
+            }
+            flags::ReportFormat::Html => {
+                ("report.html", audit_report::html(project, graph, &findings, redaction)?)
+            }
+        };
+        outputs.files.push((file.to_owned(), "report", REPORT_SCHEMA_VERSION, contents));
+    }
+    Ok(outputs.files)
+}
+
+/// The files of the bundle, as their name, analyzer, schema version and contents.
+struct Outputs<'a, E> {
+    exporter: &'a E,
+    files: Vec<(String, &'static str, u32, String)>,
+}
+
+impl<E: Exporter> Outputs<'_, E> {
+    /// Adds the output `name` of `analyzer`, in a file named after it.
+    fn push(
+        &mut self,
+        name: &str,
+        analyzer: &'static str,
+        schema_version: u32,
+        value: &impl Serialize,
+    ) -> Result<()> {
+        let contents = self.exporter.export(name, value)?;
+        let file = format!("{name}.{}", self.exporter.extension());
+        self.files.push((file, analyzer, schema_version, contents));
+        Ok(())
+    }
+}
+
+/// The commit checked out in the repository containing `root`, if any.
+fn git_revision(root: &str) -> Option<GitRevision> {
+    let git = |args: &[&str]| {
+        let output = Command::new("git").arg("-C").arg(root).args(args).output().ok()?;
+        output.status.success().then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let revision = git(&["rev-parse", "HEAD"])?.trim().to_owned();
+    let dirty = git(&["status", "--porcelain"]).is_some_and(|status| !status.trim().is_empty());
+    Some(GitRevision { revision, dirty })
+}
+
+pub(super) fn hash(contents: &str) -> String {
+    let mut hasher = TentHash::new();
+    hasher.update(contents.as_bytes());
+    hasher.finalize().iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Writes `entries` as a tarball with fixed metadata, so identical outputs give identical
+/// archives.
+fn write_archive(path: &Path, entries: &[(String, String)]) -> Result<()> {
+    let encoder = zstd::Encoder::new(fs::File::create(path)?, COMPRESSION_LEVEL)?;
+    let mut builder = tar::Builder::new(encoder);
+    for (name, contents) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        header.set_uid(0);
+        header.set_gid(0);
+        builder.append_data(&mut header, name, contents.as_bytes())?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+//! The file formats `export bundle` can write the outputs of the analyzers in.
+//!
+//! Outputs are plain serde values, so a format only has to serialize one and name its files.
+//! TOML documents have to be tables and can't hold nulls: the output goes under a key named after
+//! it, like `[[structs]]`, absent fields are left out and absent items of arrays become empty
+//! tables, keeping the positions of the others.
+
+use anyhow::Result;
+use serde::Serialize;
+
+pub(super) trait Exporter {
+    /// The extension of the files, like `json`.
+    fn extension(&self) -> &'static str;
+
+    /// Serializes `value`, the output of the analyzer named `name` like `structs`.
+    fn export<T: Serialize + ?Sized>(&self, name: &str, value: &T) -> Result<String>;
+}
+
+pub(super) struct Json;
+
+impl Exporter for Json {
+    fn extension(&self) -> &'static str {
+        "json"
+    }
+
+    fn export<T: Serialize + ?Sized>(&self, _name: &str, value: &T) -> Result<String> {
+        Ok(serde_json::to_string_pretty(value)?)
+    }
+}
+
+pub(super) struct Yaml;
+
+impl Exporter for Yaml {
+    fn extension(&self) -> &'static str {
+        "yaml"
+    }
+
+    fn export<T: Serialize + ?Sized>(&self, _name: &str, value: &T) -> Result<String> {
+        Ok(serde_yaml_ng::to_string(value)?)
+    }
+}
+
+pub(super) struct Toml;
+
+impl Exporter for Toml {
+    fn extension(&self) -> &'static str {
+        "toml"
+    }
+
+    fn export<T: Serialize + ?Sized>(&self, name: &str, value: &T) -> Result<String> {
+        // Going through JSON replaces the nulls TOML has no representation for.
+        let mut value = serde_json::to_value(value)?;
+        strip_nulls(&mut value);
+        let document = serde_json::Map::from_iter([(name.to_owned(), value)]);
+        Ok(toml::to_string_pretty(&document)?)
+    }
+}
+
+/// Removes the null fields of the objects in `value` and turns the null items of its arrays into
+/// empty tables.
+fn strip_nulls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Array(items) => {
+            for item in items {
+                match item.is_null() {
+                    true => *item = serde_json::Value::Object(Default::default()),
+                    false => strip_nulls(item),
+                }
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            fields.retain(|_, it| !it.is_null());
+            fields.values_mut().for_each(strip_nulls);
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn toml_keeps_the_positions_of_null_items() {
+        let value = json!([{ "line": 3, "field": null, "amounts": [1, null, 2] }]);
+        let toml = Toml.export("structs", &value).unwrap();
+        assert_eq!(toml, "[[structs]]\nline = 3\namounts = [\n    1,\n    {},\n    2,\n]\n");
+    }
+}
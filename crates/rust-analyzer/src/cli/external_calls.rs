@@ -0,0 +1,61 @@
+//! Calls into the dependencies of the project, attributed to the crate and version cargo
+//! resolved them to.
+//!
+//! Calls into third-party crates are dropped unless asked for, while those into the standard
+//! library are always kept, like the calls of `Ok` or `Vec::push`.
+
+use hir::{Crate, Semantics};
+use ide::RootDatabase;
+use ide_db::base_db::salsa;
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use vfs::{AbsPathBuf, Vfs};
+
+use crate::cli::function_analyzer::{CallRelation, find_file_id_by_path, is_external_path};
+
+/// The dependency crate a callee belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct ExternalCrate {
+    pub(super) name: String,
+    /// The version from `Cargo.toml`, unknown for the standard library.
+    pub(super) version: Option<String>,
+}
+
+/// Drops the calls into third-party crates, or with `include_external` keeps them and fills in
+/// the `callee_crate` of every call leaving the project.
+pub(super) fn attribute_external_calls(
+    db: &RootDatabase,
+    vfs: &Vfs,
+    project_root: &AbsPathBuf,
+    relations: &mut Vec<CallRelation>,
+    include_external: bool,
+) {
+    let _p = tracing::info_span!("attribute_external_calls").entered();
+    salsa::attach(db, || {
+        let sema = Semantics::new(db);
+        let mut crates: FxHashMap<String, Option<Crate>> = FxHashMap::default();
+        relations.retain_mut(|relation| {
+            let path = &relation.callee.file_path;
+            if !is_external_path(path, project_root) {
+                return true;
+            }
+            let krate = *crates.entry(path.clone()).or_insert_with(|| {
+                find_file_id_by_path(vfs, path).and_then(|file_id| sema.first_crate(file_id))
+            });
+            let Some(krate) = krate else { return true };
+            let origin = krate.origin(db);
+            if !include_external {
+                return !origin.is_lib();
+            }
+            relation.callee_crate = Some(ExternalCrate {
+                name: krate
+                    .display_name(db)
+                    .map(|it| it.canonical_name().as_str().to_owned())
+                    .unwrap_or_default(),
+                // The sysroot crates are all versioned `0.0.0`.
+                version: krate.version(db).filter(|_| !origin.is_lang()),
+            });
+            true
+        });
+    })
+}
@@ -0,0 +1,254 @@
+//! Reports the feature set each dependency ends up compiled with once Cargo has unified the
+//! features requested across the workspace, and which members activate each of them.
+//!
+//! Unification means a feature enabled by one member is enabled for every member sharing the
+//! dependency, so code paths like anchor's `init-if-needed` can be switched on for a program that
+//! never asked for them.
+
+use std::{env, fs};
+
+use anyhow::{Context, Result};
+use cargo_metadata::{DependencyKind, Metadata, MetadataCommand, Package, PackageId};
+use itertools::Itertools;
+use project_model::ProjectManifest;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use toolchain::Tool;
+use vfs::AbsPathBuf;
+
+use crate::cli::flags;
+
+impl flags::Features {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("features", path = %self.path.display()).entered();
+        let project_root = AbsPathBuf::assert_utf8(env::current_dir()?.join(&self.path));
+        let ProjectManifest::CargoToml(manifest) = ProjectManifest::discover_single(&project_root)?
+        else {
+            anyhow::bail!("the feature report requires a Cargo workspace");
+        };
+
+        eprintln!("Resolving dependencies...");
+        let metadata = MetadataCommand::new()
+            .cargo_path(Tool::Cargo.path())
+            .manifest_path(AbsPathBuf::from(manifest))
+            .exec()
+            .context("failed to run `cargo metadata`")?;
+        let dependencies = unified_features(&metadata)?;
+        eprintln!(
+            "{} dependencies compiled with {} features",
+            dependencies.len(),
+            dependencies.iter().map(|it| it.features.len()).sum::<usize>()
+        );
+
+        let json = serde_json::to_string_pretty(&dependencies)?;
+        match &self.output {
+            Some(path) => fs::write(path, json)?,
+            None => println!("{json}"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct DependencyFeatures {
+    name: String,
+    version: String,
+    features: Vec<UnifiedFeature>,
+}
+
+#[derive(Debug, Serialize)]
+struct UnifiedFeature {
+    feature: String,
+    /// Packages requesting the feature, either in a dependency declaration or from one of
+    /// their own features.
+    requested_by: Vec<Request>,
+    /// Other features of the same package turning this one on.
+    implied_by: Vec<String>,
+    /// Workspace members whose dependency graph requests the feature.
+    members: Vec<String>,
+    /// Workspace members getting the feature only because another member requested it.
+    unified_into: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+struct Request {
+    package: String,
+    /// `dependency`, `dev-dependency` or `build-dependency` for declarations, `default` when
+    /// the declaration keeps the default features, and ``feature `name` `` for features.
+    via: String,
+}
+
+fn unified_features(metadata: &Metadata) -> Result<Vec<DependencyFeatures>> {
+    let resolve = metadata.resolve.as_ref().context("`cargo metadata` resolved no dependencies")?;
+    let packages: FxHashMap<&PackageId, &Package> =
+        metadata.packages.iter().map(|it| (&it.id, it)).collect();
+    let nodes: FxHashMap<&PackageId, _> = resolve.nodes.iter().map(|it| (&it.id, it)).collect();
+    let members: FxHashSet<&PackageId> = metadata.workspace_members.iter().collect();
+
+    // Who requests which feature of which package.
+    let mut requests: FxHashMap<(&PackageId, String), Vec<(&PackageId, String)>> =
+        FxHashMap::default();
+    for node in &resolve.nodes {
+        let package = packages[&node.id];
+        // The resolved package of every dependency key usable in `dep/feature` entries.
+        let mut resolved_keys = FxHashMap::default();
+        for dependency in &package.dependencies {
+            let Some(target) = node
+                .dependencies
+                .iter()
+                .find(|id| packages[id].name.as_str() == dependency.name.as_str())
+            else {
+                // Optional dependencies that aren't enabled, or another platform's.
+                continue;
+            };
+            resolved_keys
+                .insert(dependency.rename.as_ref().unwrap_or(&dependency.name).as_str(), target);
+            let via = match dependency.kind {
+                DependencyKind::Development => "dev-dependency",
+                DependencyKind::Build => "build-dependency",
+                _ => "dependency",
+            };
+            for feature in &dependency.features {
+                requests
+                    .entry((target, feature.clone()))
+                    .or_default()
+                    .push((&node.id, via.to_owned()));
+            }
+            if dependency.uses_default_features && packages[target].features.contains_key("default")
+            {
+                requests
+                    .entry((target, "default".to_owned()))
+                    .or_default()
+                    .push((&node.id, "default".to_owned()));
+            }
+        }
+        for enabled in &node.features {
+            let Some(entries) = package.features.get(enabled.as_str()) else { continue };
+            for entry in entries {
+                let Some((key, feature)) = entry.split_once('/') else { continue };
+                let Some(target) = resolved_keys.get(key.trim_end_matches('?')) else { continue };
+                requests
+                    .entry((target, feature.to_owned()))
+                    .or_default()
+                    .push((&node.id, format!("feature `{enabled}`")));
+            }
+        }
+    }
+
+    // Every package reachable from each member, the member included.
+    let reachable: FxHashMap<&PackageId, FxHashSet<&PackageId>> = members
+        .iter()
+        .map(|&member| {
+            let mut seen = FxHashSet::from_iter([member]);
+            let mut stack = vec![member];
+            while let Some(id) = stack.pop() {
+                for dependency in nodes.get(id).into_iter().flat_map(|it| &it.dependencies) {
+                    if seen.insert(dependency) {
+                        stack.push(dependency);
+                    }
+                }
+            }
+            (member, seen)
+        })
+        .collect();
+    let member_name = |id: &PackageId| packages[id].name.to_string();
+
+    let mut dependencies = Vec::new();
+    for node in &resolve.nodes {
+        if members.contains(&node.id) || node.features.is_empty() {
+            continue;
+        }
+        let package = packages[&node.id];
+        let enabled: FxHashSet<&str> = node.features.iter().map(|it| it.as_str()).collect();
+        let implied_by = |feature: &str| {
+            enabled
+                .iter()
+                .filter(|&&other| {
+                    package.features.get(other).is_some_and(|entries| {
+                        entries
+                            .iter()
+                            .any(|entry| entry == feature || *entry == format!("dep:{feature}"))
+                    })
+                })
+                .map(|it| it.to_string())
+                .sorted()
+                .collect_vec()
+        };
+
+        // Members activating each feature, following features implied by other features.
+        let mut activating: FxHashMap<&str, FxHashSet<&PackageId>> = enabled
+            .iter()
+            .map(|&feature| {
+                let requesters = requests.get(&(&node.id, feature.to_owned()));
+                let activating = members
+                    .iter()
+                    .filter(|member| {
+                        requesters
+                            .into_iter()
+                            .flatten()
+                            .any(|(id, _)| reachable[*member].contains(id))
+                    })
+                    .copied()
+                    .collect();
+                (feature, activating)
+            })
+            .collect();
+        let implications: FxHashMap<&str, Vec<String>> =
+            enabled.iter().map(|&feature| (feature, implied_by(feature))).collect();
+        loop {
+            let mut changed = false;
+            for (&feature, implying) in &implications {
+                let inherited: Vec<_> = implying
+                    .iter()
+                    .filter_map(|it| activating.get(it.as_str()))
+                    .flatten()
+                    .copied()
+                    .collect();
+                let set = activating.get_mut(feature).unwrap();
+                for member in inherited {
+                    changed |= set.insert(member);
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let depending_members =
+            members.iter().filter(|member| reachable[*member].contains(&node.id)).collect_vec();
+        let features = enabled
+            .iter()
+            .sorted()
+            .map(|&feature| {
+                let requested_by = requests
+                    .get(&(&node.id, feature.to_owned()))
+                    .into_iter()
+                    .flatten()
+                    .map(|(id, via)| Request { package: member_name(id), via: via.clone() })
+                    .sorted()
+                    .dedup()
+                    .collect();
+                let activating = &activating[feature];
+                UnifiedFeature {
+                    feature: feature.to_owned(),
+                    requested_by,
+                    implied_by: implications[feature].clone(),
+                    members: activating.iter().map(|id| member_name(id)).sorted().collect(),
+                    unified_into: depending_members
+                        .iter()
+                        .filter(|member| !activating.contains(**member))
+                        .map(|id| member_name(id))
+                        .sorted()
+                        .collect(),
+                }
+            })
+            .collect();
+        dependencies.push(DependencyFeatures {
+            name: package.name.to_string(),
+            version: package.version.to_string(),
+            features,
+        });
+    }
+    dependencies.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+    Ok(dependencies)
+}
@@ -0,0 +1,177 @@
+//! Findings reported by the analysis commands, the registry of the rules built-in checks report
+//! them under, and their comparison against a baseline so a rule set can be adopted on an
+//! existing codebase by only failing on new findings.
+//!
+//! Most rules are reported by their checks while they walk the sources, like the per field rules
+//! of `anchor_checks`. Rules that only need the extracted model run from the registry, so a new
+//! one is a function over the code graph and an entry in `RULES`.
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::{
+    anchor_checks,
+    code_graph::{CodeGraph, LoadedProject},
+    custom_rules, flags,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(super) struct Finding {
+    /// Name of the plugin file that reported the finding, filled in by the host.
+    #[serde(default)]
+    pub(super) plugin: String,
+    pub(super) rule: String,
+    pub(super) message: String,
+    #[serde(default)]
+    pub(super) severity: Severity,
+    #[serde(default)]
+    pub(super) file: Option<String>,
+    #[serde(default)]
+    pub(super) line: Option<u32>,
+    /// Other places the finding involves, like the other side of a collision.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) related: Vec<Related>,
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum Severity {
+    Info,
+    #[default]
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(super) struct Related {
+    pub(super) file: String,
+    pub(super) line: u32,
+    /// What the place is to the finding, like `the other account`.
+    pub(super) message: String,
+}
+
+/// A rule of the built-in checks.
+pub(super) struct Rule {
+    pub(super) id: &'static str,
+    /// The checks reporting it, like `anchor` or `lint`.
+    pub(super) plugin: &'static str,
+    /// Runs the rule over the code graph, none for the rules its checks report while walking the
+    /// sources.
+    pub(super) check: Option<fn(&CodeGraph) -> Vec<Finding>>,
+}
+
+const fn rule(plugin: &'static str, id: &'static str) -> Rule {
+    Rule { id, plugin, check: None }
+}
+
+/// Every rule of the built-in checks.
+pub(super) const RULES: &[Rule] = &[
+    rule("anchor", "insufficient-space"),
+    rule("anchor", "missing-signer"),
+    rule("anchor", "unchecked-account"),
+    rule("anchor", "non-canonical-bump"),
+    rule("anchor", "realloc-without-zero"),
+    rule("anchor", "unsupported-constraint"),
+    rule("anchor", "unnecessary-mut"),
+    rule("anchor", "write-without-mut"),
+    rule("anchor", "unguarded-init-if-needed"),
+    rule("anchor", "unchecked-arithmetic"),
+    rule("anchor", "truncating-cast"),
+    rule("anchor", "borrow-across-cpi"),
+    Rule {
+        id: "discriminator-collision",
+        plugin: "anchor",
+        check: Some(anchor_checks::discriminator_collisions),
+    },
+    Rule {
+        id: "zero-copy-padding",
+        plugin: "anchor",
+        check: Some(anchor_checks::zero_copy_padding),
+    },
+    rule("lint", "discarded-error"),
+    rule("metrics", "complexity"),
+    rule("metrics", "nesting"),
+    rule("metrics", "branches"),
+    rule("metrics", "lines"),
+    rule("metrics", "statements"),
+    rule("metrics", "params"),
+    rule("idl-diff", "missing-instruction"),
+    rule("idl-diff", "removed-instruction"),
+    rule("idl-diff", "renamed-instruction"),
+    rule("idl-diff", "instruction-args"),
+    rule("idl-diff", "instruction-accounts"),
+    rule("idl-diff", "missing-type"),
+    rule("idl-diff", "removed-type"),
+    rule("idl-diff", "type-fields"),
+    rule("idl-diff", "error-codes"),
+];
+
+/// Runs the rules of `plugin` that only need the code graph.
+pub(super) fn check_graph(plugin: &str, graph: &CodeGraph) -> Vec<Finding> {
+    RULES
+        .iter()
+        .filter(|it| it.plugin == plugin)
+        .filter_map(|it| it.check)
+        .flat_map(|check| check(graph))
+        .collect()
+}
+
+impl flags::RuleSelection {
+    /// Fails on a name that isn't a rule of the built-in checks or of the rule file of `project`.
+    pub(super) fn validate(&self, project: &LoadedProject) -> Result<()> {
+        let custom = custom_rules::load(&project.project_root)?;
+        for name in self.enabled.iter().chain(&self.disabled) {
+            if !RULES.iter().any(|it| it.id == name) && !custom.iter().any(|it| it.id == *name) {
+                anyhow::bail!("unknown rule `{name}`");
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn enabled(&self, rule: &str) -> bool {
+        let selected = self.enabled.is_empty() || self.enabled.iter().any(|it| it == rule);
+        selected && !self.disabled.iter().any(|it| it == rule)
+    }
+
+    /// Drops the findings of the rules that aren't enabled.
+    pub(super) fn retain(&self, findings: &mut Vec<Finding>) {
+        findings.retain(|it| self.enabled(&it.rule));
+    }
+}
+
+impl Finding {
+    /// Identifies a finding across runs. The line is left out so that unrelated edits above a
+    /// known finding don't report it again.
+    fn fingerprint(&self) -> (String, String, String, Option<String>) {
+        (self.plugin.clone(), self.rule.clone(), self.message.clone(), self.file.clone())
+    }
+}
+
+/// Drops every finding that is already part of `baseline`, a findings file written by an earlier
+/// run. Identical findings are matched one to one, so an additional occurrence is still new.
+pub(super) fn new_findings(findings: Vec<Finding>, baseline: &Path) -> Result<Vec<Finding>> {
+    let text = fs::read_to_string(baseline)
+        .with_context(|| format!("failed to read baseline {}", baseline.display()))?;
+    let known: Vec<Finding> = serde_json::from_str(&text)
+        .with_context(|| format!("{} is not a findings file", baseline.display()))?;
+
+    let mut remaining = FxHashMap::default();
+    for finding in &known {
+        *remaining.entry(finding.fingerprint()).or_insert(0usize) += 1;
+    }
+    Ok(findings
+        .into_iter()
+        .filter(|finding| match remaining.get_mut(&finding.fingerprint()) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                false
+            }
+            _ => true,
+        })
+        .collect())
+}
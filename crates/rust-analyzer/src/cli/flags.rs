@@ -1,5 +1,7 @@
 //! Grammar for the command-line arguments.
 #![allow(unreachable_pub)]
+// The commands are parsed once, their size doesn't matter.
+#![allow(clippy::large_enum_variant)]
 use std::{path::PathBuf, str::FromStr};
 
 use ide_ssr::{SsrPattern, SsrRule};
@@ -24,6 +26,9 @@ xflags::xflags! {
         optional --log-file path: PathBuf
         /// Flush log records to the file immediately.
         optional --no-log-buffering
+        /// [Unstable] Export spans of batch commands to this OTLP/HTTP collector
+        /// (requires the `otel` feature).
+        optional --otlp-endpoint url: String
 
         /// [Unstable] Wait until a debugger is attached to (requires debug build).
         optional --wait-dbg
@@ -188,12 +193,36 @@ xflags::xflags! {
 
         /// Generate function call hierarchy analysis.
         cmd function-analyzer {
-            /// Path to the Rust project.
-            required path: PathBuf
+            /// Path to the Rust project. Several projects are analyzed in one run, each written
+            /// to a file of its own in the `--output` directory.
+            repeated path: PathBuf
 
-            /// Output file for call hierarchy data.
+            /// File listing the projects to analyze, one path per line relative to the file.
+            /// Blank lines and lines starting with `#` are skipped.
+            optional --projects-file list: PathBuf
+
+            /// Output file for call hierarchy data, or directory for several projects.
             optional --output path: PathBuf
 
+            /// Format of the call hierarchy data: `text` (default), `json`, `dot` (Graphviz),
+            /// `graphml`, `csv` or `ndjson`, streamed one record per call site.
+            optional --format format: CallFormat
+
+            /// List every call site as a relation of its own in the `text` and `json` output,
+            /// rather than one relation per calling pair with all of its call sites.
+            optional --flat
+
+            /// Include the source of every call site in the `json` and `ndjson` output: its line
+            /// plus this many lines before and after it, `0` giving the line alone.
+            optional --call-context lines: u32
+
+            /// Group the functions of each module into a cluster of the `dot` output.
+            optional --cluster-modules
+
+            /// Collapse the calls into a `module` or `crate` dependency graph, whose edges are
+            /// weighted by the number of calls they stand for. `function` by default.
+            optional --granularity level: Granularity
+
             /// Disable build script running.
             optional --disable-build-scripts
 
@@ -205,16 +234,543 @@ xflags::xflags! {
 
             /// Include dependencies in analysis.
             optional --with-deps
+
+            /// Only analyze the functions of this crate, can be repeated.
+            repeated --include-crate name: String
+
+            /// Skip the functions of this crate, can be repeated.
+            repeated --exclude-crate name: String
+
+            /// Skip tests and benchmarks: `#[test]` functions, `#[cfg(test)]` modules and the
+            /// `tests/` and `benches/` targets.
+            optional --exclude-tests
+
+            /// Skip the calls that only exist in macro expansions, like those of the functions
+            /// generated by `#[program]`.
+            optional --exclude-macro-edges
+
+            /// Keep the functions generated by derives and procedural macros, like the
+            /// `Deserialize` implementations of `#[derive(Deserialize)]`, left out by default.
+            optional --include-generated
+
+            /// Resolve the trait method calls of generic functions to the implementations of the
+            /// types each call of the function instantiates it with, rather than to every
+            /// implementation.
+            optional --instantiations
+
+            /// Keep the calls into third-party dependencies, tagged with the crate name and
+            /// version they resolve to. The calls into the standard library are always kept.
+            optional --include-external
+
+            /// Analyze every function again rather than reusing the calls cached in
+            /// `target/function-analyzer-cache` for unchanged files.
+            optional --no-cache
+
+            /// Only analyze the functions of modules below this path, e.g.
+            /// `my_program::instructions`. Can be repeated.
+            repeated --module-prefix path: String
+
+            /// Only analyze the public API of each crate, same as `--min-visibility public`.
+            optional --only-public
+
+            /// Only analyze the functions visible at least this far: `public` for those other
+            /// crates can call, `crate` for those the whole crate can call, `private` for all.
+            optional --min-visibility level: MinVisibility
+
+            /// How to report the progress of the analysis on stderr: `text` (default), `json`
+            /// events or `none`.
+            optional --progress format: ProgressFormat
+
+            /// List the callers of every function instead of its callees, for impact analysis.
+            optional --incoming
+
+            /// Only follow the calls from this function, e.g. `my_crate::handler`. Leading path
+            /// segments can be left out as long as the function stays unambiguous.
+            optional --root path: String
+
+            /// Follow the calls from the anchor `#[program]` handlers and native entrypoints, the
+            /// roots tagged in the output, giving the call tree of every instruction.
+            optional --entrypoints
+
+            /// Follow the calls from `--root` or `--entrypoints` for this many levels, `1` lists
+            /// their direct calls only. Unlimited by default.
+            optional --depth n: u32
+
+            /// Print the shortest call chains from this function to `--path-to` instead of the
+            /// whole graph, e.g. `my_program::instructions::swap::handler`.
+            optional --path-from path: String
+
+            /// Function the chains of `--path-from` lead to, which may be a dependency function
+            /// like `invoke_signed`.
+            optional --path-to path: String
+
+            /// Write the fan-in, fan-out and transitive reachability counts of every function as
+            /// JSON instead of the calls.
+            optional --metrics
+
+            /// List the functions never reached through calls from the entry points instead of
+            /// the calls.
+            optional --dead-code
+
+            /// Entry points of `--dead-code` (comma separated): `main`, `public`, `program`
+            /// (anchor handlers and native entrypoints), `tests` and/or `trait-impls` (of
+            /// dependency traits). All of them by default.
+            optional --entry-kinds kinds: EntryKinds
+
+            /// Also treat the functions matching this path as entry points, `*` matching
+            /// anything, e.g. `my_crate::handlers::*`. Can be repeated.
+            repeated --entry pattern: String
+
+            /// Browse the call graph interactively in the terminal instead of writing it out.
+            optional --tui
+
+            /// Redact `strings`, `docs` and/or `paths` (comma separated) from the output.
+            optional --redact kinds: Redaction
+
+            /// Shrink the graph of huge workspaces (comma separated): `leaves:N` drops functions
+            /// without calls of their own and fewer than N callers, `hubs:K` keeps the K most
+            /// connected functions and `generated` merges the functions of each macro expansion.
+            optional --prune strategies: Prune
+
+            /// Stop analyzing once this many distinct calling pairs were found, the calls left
+            /// out being summed up by module in the output. Unlimited by default.
+            optional --max-edges count: usize
+
+            /// Stop analyzing once the calls found involve this many functions. Unlimited by
+            /// default.
+            optional --max-nodes count: usize
+
+            /// Analyze one crate at a time, each in a fresh database dropped before the next,
+            /// bounding memory at the cost of reloading the workspace for every crate.
+            optional --low-memory
+        }
+
+        /// Explore the call graph and account structs of a project.
+        cmd graph {
+            /// Serve an interactive web view of the call graph and account structs, answering its
+            /// queries over JSON-RPC.
+            cmd serve {
+                /// Path to the Rust project.
+                required path: PathBuf
+
+                /// Port to listen on. Defaults to 8080.
+                optional --port port: u16
+
+                /// Rebuild the graph when the sources change, viewers receive the changes as
+                /// deltas.
+                optional --watch
+
+                /// Disable build script running.
+                optional --disable-build-scripts
+
+                /// Disable proc-macro expansion.
+                optional --disable-proc-macros
+
+                /// Path to the proc-macro server.
+                optional --proc-macro-srv path: PathBuf
+
+                /// Redact `strings`, `docs` and/or `paths` (comma separated) from the output.
+                optional --redact kinds: Redaction
+            }
+
+            /// Export the graph as a markdown wiki with one page per module, function and struct.
+            cmd wiki {
+                /// Path to the Rust project.
+                required path: PathBuf
+
+                /// Directory the pages are written to.
+                required -o, --output dir: PathBuf
+
+                /// Disable build script running.
+                optional --disable-build-scripts
+
+                /// Disable proc-macro expansion.
+                optional --disable-proc-macros
+
+                /// Path to the proc-macro server.
+                optional --proc-macro-srv path: PathBuf
+
+                /// Redact `strings`, `docs` and/or `paths` (comma separated) from the output.
+                optional --redact kinds: Redaction
+            }
+
+            /// Run WASM plugins over the graph and print the findings they report
+            /// (requires the `wasm-plugins` feature).
+            cmd plugins {
+                /// Path to the Rust project.
+                required path: PathBuf
+
+                /// WASM module implementing the plugin interface, can be repeated.
+                repeated --plugin file: PathBuf
+
+                /// Write the findings to this file instead of stdout.
+                optional -o, --output path: PathBuf
+
+                /// Findings file of an earlier run. Only findings missing from it are reported
+                /// and the command fails if there are any.
+                optional --baseline path: PathBuf
+
+                /// Disable build script running.
+                optional --disable-build-scripts
+
+                /// Disable proc-macro expansion.
+                optional --disable-proc-macros
+
+                /// Path to the proc-macro server.
+                optional --proc-macro-srv path: PathBuf
+
+                /// Redact `strings`, `docs` and/or `paths` (comma separated) from the output.
+                optional --redact kinds: Redaction
+            }
+
+            /// Compare the IDL generated by `anchor build` with the programs of the sources and
+            /// report the instructions, accounts and types that drifted apart, as findings.
+            cmd idl-diff {
+                /// Path to the Rust project.
+                required path: PathBuf
+
+                /// IDL file to compare against, can be repeated. Defaults to the
+                /// `target/idl/*.json` files of the project.
+                repeated --idl file: PathBuf
+
+                /// Write the findings to this file instead of stdout.
+                optional -o, --output path: PathBuf
+
+                /// Disable build script running.
+                optional --disable-build-scripts
+
+                /// Disable proc-macro expansion.
+                optional --disable-proc-macros
+
+                /// Path to the proc-macro server.
+                optional --proc-macro-srv path: PathBuf
+            }
+        }
+
+
+        /// Compute complexity metrics per function and module, and report functions above the
+        /// thresholds.
+        cmd metrics {
+            /// Path to the Rust project.
+            required path: PathBuf
+
+            /// Write the report to this file instead of stdout.
+            optional -o, --output path: PathBuf
+
+            /// Highest allowed cyclomatic complexity. Defaults to 10.
+            optional --max-complexity n: u32
+
+            /// Highest allowed nesting depth. Defaults to 4.
+            optional --max-nesting n: u32
+
+            /// Highest allowed number of branching constructs. Defaults to 20.
+            optional --max-branches n: u32
+
+            /// Highest allowed number of source lines, without blanks and comments. Defaults to 80.
+            optional --max-lines n: u32
+
+            /// Highest allowed number of statements. Defaults to 50.
+            optional --max-statements n: u32
+
+            /// Highest allowed number of parameters. Defaults to 7.
+            optional --max-params n: u32
+
+            /// Disable build script running.
+            optional --disable-build-scripts
+
+            /// Disable proc-macro expansion.
+            optional --disable-proc-macros
+
+            /// Path to the proc-macro server.
+            optional --proc-macro-srv path: PathBuf
+        }
+
+        /// Detect near-duplicate function bodies and report them as clone groups.
+        cmd clones {
+            /// Path to the Rust project.
+            required path: PathBuf
+
+            /// Write the report to this file instead of stdout.
+            optional -o, --output path: PathBuf
+
+            /// Ignore function bodies with fewer tokens than this. Defaults to 40.
+            optional --min-tokens n: usize
+
+            /// Lowest similarity, between 0.0 and 1.0, of two bodies reported as clones.
+            /// Defaults to 0.8.
+            optional --similarity value: f64
+
+            /// Disable build script running.
+            optional --disable-build-scripts
+
+            /// Disable proc-macro expansion.
+            optional --disable-proc-macros
+
+            /// Path to the proc-macro server.
+            optional --proc-macro-srv path: PathBuf
+        }
+
+        /// List the string and byte string literals of the project grouped by enclosing item,
+        /// tagging pubkeys, URLs and PDA seeds.
+        cmd strings {
+            /// Path to the Rust project.
+            required path: PathBuf
+
+            /// Write the inventory to this file instead of stdout.
+            optional -o, --output path: PathBuf
+
+            /// Only list literals tagged as a pubkey, URL or seed.
+            optional --tagged
+
+            /// Disable build script running.
+            optional --disable-build-scripts
+
+            /// Disable proc-macro expansion.
+            optional --disable-proc-macros
+
+            /// Path to the proc-macro server.
+            optional --proc-macro-srv path: PathBuf
+        }
+
+        /// Run the built-in lint rules, such as `discarded-error`, and report their findings.
+        cmd lint {
+            /// Path to the Rust project.
+            required path: PathBuf
+
+            /// Write the findings to this file instead of stdout.
+            optional -o, --output path: PathBuf
+
+            /// Only report findings missing from this findings file, and fail if there are any.
+            optional --baseline path: PathBuf
+
+            /// Rules to report (comma separated), all by default. Rules prefixed with `-` are left
+            /// out, e.g. `-discarded-error`.
+            optional --rules rules: RuleSelection
+
+            /// Disable build script running.
+            optional --disable-build-scripts
+
+            /// Disable proc-macro expansion.
+            optional --disable-proc-macros
+
+            /// Path to the proc-macro server.
+            optional --proc-macro-srv path: PathBuf
+        }
+
+        /// List the `dyn Trait` types and trait object taking functions of the project, with
+        /// the concrete types coerced into each trait object.
+        cmd dyn-usage {
+            /// Path to the Rust project.
+            required path: PathBuf
+
+            /// Write the report to this file instead of stdout.
+            optional -o, --output path: PathBuf
+
+            /// Disable build script running.
+            optional --disable-build-scripts
+
+            /// Disable proc-macro expansion.
+            optional --disable-proc-macros
+
+            /// Path to the proc-macro server.
+            optional --proc-macro-srv path: PathBuf
+        }
+
+        /// List the packages of the dependency graph with a build script or proc-macro, and the
+        /// project items invoking those proc-macros.
+        cmd build-inventory {
+            /// Path to the Rust project.
+            required path: PathBuf
+
+            /// Write the inventory to this file instead of stdout.
+            optional -o, --output path: PathBuf
+
+            /// Disable build script running.
+            optional --disable-build-scripts
+
+            /// Disable proc-macro expansion.
+            optional --disable-proc-macros
+
+            /// Path to the proc-macro server.
+            optional --proc-macro-srv path: PathBuf
         }
 
-        
+        /// List the project functions calling into a dependency, directly or through other
+        /// project functions.
+        cmd deps-callers {
+            /// Path to the Rust project.
+            required path: PathBuf
+
+            /// Name of the dependency, e.g. `spl-token`.
+            required -c, --crate-name name: String
+
+            /// Only follow this many calls from a function into the dependency, `1` lists the
+            /// direct callers only. Unlimited by default.
+            optional --depth n: u32
+
+            /// Write the callers to this file instead of stdout.
+            optional -o, --output path: PathBuf
+
+            /// Disable build script running.
+            optional --disable-build-scripts
+
+            /// Disable proc-macro expansion.
+            optional --disable-proc-macros
+
+            /// Path to the proc-macro server.
+            optional --proc-macro-srv path: PathBuf
+        }
+
+        /// Report the features each dependency is compiled with after Cargo's feature
+        /// unification, and which workspace members activate them.
+        cmd features {
+            /// Path to the Rust project.
+            required path: PathBuf
+
+            /// Write the report to this file instead of stdout.
+            optional -o, --output path: PathBuf
+        }
+
+        cmd export {
+            /// Run the selected analyzers and package their outputs, with a `manifest.json`
+            /// describing the run, into a zstd compressed tarball.
+            cmd bundle {
+                /// Path to the Rust project.
+                required path: PathBuf
+
+                /// Archive to write, e.g. `analysis.tar.zst`.
+                required -o, --output path: PathBuf
+
+                /// Analyzers to run (comma separated): `call-graph`, `structs`, `findings` and/or
+                /// `metrics`. All of them by default.
+                optional --analyzers names: Analyzers
+
+                /// Format of the analyzer outputs: `json` (default), `yaml` or `toml`. The
+                /// manifest is always JSON.
+                optional --format format: BundleFormat
+
+                /// Also render the outputs as a report to prepare an audit with, showing the
+                /// accounts, constraints, PDAs, CPIs and findings of every instruction: `markdown`
+                /// for a `report.md`, `html` for a self-contained `report.html` with the source of
+                /// every location.
+                optional --report format: ReportFormat
+
+                /// Rules whose findings are reported (comma separated), all by default. Rules
+                /// prefixed with `-` are left out, e.g. `-unnecessary-mut,-zero-copy-padding`.
+                optional --rules rules: RuleSelection
+
+                /// Only keep the account structs and other types named like this glob, e.g.
+                /// `CreateBondingCurve` or `*Curve*`, and the instructions using them. Can be
+                /// repeated.
+                repeated --struct-name pattern: Glob
+
+                /// Only keep what is declared in files matching this glob, e.g.
+                /// `src/instructions/curve/*`, matched against the end of the path. Can be
+                /// repeated.
+                repeated --file pattern: Glob
+
+                /// Disable build script running.
+                optional --disable-build-scripts
+
+                /// Disable proc-macro expansion.
+                optional --disable-proc-macros
+
+                /// Path to the proc-macro server.
+                optional --proc-macro-srv path: PathBuf
+
+                /// Redact `strings`, `docs` and/or `paths` (comma separated) from the output.
+                optional --redact kinds: Redaction
+            }
+        }
+
+        cmd call-hierarchy {
+            /// Print the callers and callees of one function as trees, analyzing only the
+            /// functions on them.
+            default cmd tree {
+                /// The function: a path like `my_crate::handler`, whose leading segments can be
+                /// left out as long as it stays unambiguous, or the `file:line` of a line of its
+                /// definition, e.g. `src/lib.rs:42`.
+                required --function function: String
+
+                /// Levels of callers and callees to follow, 3 by default.
+                optional --depth n: u32
+
+                /// Project directory. Defaults to the current directory.
+                optional --path path: PathBuf
+
+                /// Write the trees to this file instead of stdout.
+                optional -o, --output path: PathBuf
+
+                /// Format of the trees: `text` (default) or `json`.
+                optional --format format: CallFormat
+
+                /// Redact `paths` (comma separated with other kinds) from the output.
+                optional --redact kinds: Redaction
+
+                /// Disable build script running.
+                optional --disable-build-scripts
+
+                /// Disable proc-macro expansion.
+                optional --disable-proc-macros
+
+                /// Path to the proc-macro server.
+                optional --proc-macro-srv path: PathBuf
+            }
+
+            /// Compare the call graphs of two versions of the project and report the functions
+            /// and calls added and removed by the head version.
+            cmd diff {
+                /// Base version: a project directory or a git revision, e.g. `main`.
+                required --base version: String
+
+                /// Head version: a project directory or a git revision, e.g. `HEAD`.
+                required --head version: String
+
+                /// Project directory inside the git repository the revisions are taken from.
+                /// Defaults to the current directory.
+                optional --path path: PathBuf
+
+                /// Write the report to this file instead of stdout.
+                optional -o, --output path: PathBuf
+
+                /// Disable build script running.
+                optional --disable-build-scripts
+
+                /// Disable proc-macro expansion.
+                optional --disable-proc-macros
+
+                /// Path to the proc-macro server.
+                optional --proc-macro-srv path: PathBuf
+            }
+        }
 
         cmd source-finder {
-            /// Function or symbol name to search for (supports fuzzy matching).
+            /// Function or symbol name to search for (supports fuzzy matching). A path like
+            /// `my_crate::state::Curve::apply_buy` is resolved exactly instead.
             required symbol_name: String
 
             /// Path to the project root directory.
             required project_path: PathBuf
+
+            /// Redact `strings`, `docs` and/or `paths` (comma separated) from the output.
+            optional --redact kinds: Redaction
+
+            /// Only list the public API of each crate, same as `--min-visibility public`.
+            optional --only-public
+
+            /// Only list the symbols visible at least this far: `public`, `crate` or `private`.
+            optional --min-visibility level: MinVisibility
+
+            /// Only list symbols of this kind: `function`, `struct`, `enum`, `trait` or `const`.
+            optional --kind kind: ItemKind
+
+            /// Also output the source of the project functions a found function calls, following
+            /// calls this many levels deep.
+            optional --expand-calls depth: u32
+
+            /// How to report the progress of the analysis on stderr: `text` (default), `json`
+            /// events or `none`.
+            optional --progress format: ProgressFormat
         }
     }
 }
@@ -228,6 +784,7 @@ pub struct RustAnalyzer {
     pub quiet: bool,
     pub log_file: Option<PathBuf>,
     pub no_log_buffering: bool,
+    pub otlp_endpoint: Option<String>,
     pub wait_dbg: bool,
     pub subcommand: RustAnalyzerCmd,
 }
@@ -249,6 +806,17 @@ pub enum RustAnalyzerCmd {
     Lsif(Lsif),
     Scip(Scip),
     FunctionAnalyzer(FunctionAnalyzer),
+    Graph(Graph),
+    Metrics(Metrics),
+    Clones(Clones),
+    Strings(Strings),
+    Lint(Lint),
+    DynUsage(DynUsage),
+    BuildInventory(BuildInventory),
+    DepsCallers(DepsCallers),
+    Features(Features),
+    Export(Export),
+    CallHierarchy(CallHierarchy),
     SourceFinder(SourceFinder),
 }
 
@@ -365,19 +933,272 @@ pub struct Scip {
 
 #[derive(Debug)]
 pub struct FunctionAnalyzer {
-    pub path: PathBuf,
+    pub path: Vec<PathBuf>,
+
+    pub projects_file: Option<PathBuf>,
     pub output: Option<PathBuf>,
+    pub format: Option<CallFormat>,
+    pub flat: bool,
+    pub call_context: Option<u32>,
+    pub cluster_modules: bool,
+    pub granularity: Option<Granularity>,
     pub disable_build_scripts: bool,
     pub disable_proc_macros: bool,
     pub proc_macro_srv: Option<PathBuf>,
     pub with_deps: bool,
+    pub include_crate: Vec<String>,
+    pub exclude_crate: Vec<String>,
+    pub exclude_tests: bool,
+    pub exclude_macro_edges: bool,
+    pub include_generated: bool,
+    pub instantiations: bool,
+    pub include_external: bool,
+    pub no_cache: bool,
+    pub module_prefix: Vec<String>,
+    pub only_public: bool,
+    pub min_visibility: Option<MinVisibility>,
+    pub progress: Option<ProgressFormat>,
+    pub incoming: bool,
+    pub root: Option<String>,
+    pub entrypoints: bool,
+    pub depth: Option<u32>,
+    pub path_from: Option<String>,
+    pub path_to: Option<String>,
+    pub metrics: bool,
+    pub dead_code: bool,
+    pub entry_kinds: Option<EntryKinds>,
+    pub entry: Vec<String>,
+    pub tui: bool,
+    pub redact: Option<Redaction>,
+    pub prune: Option<Prune>,
+    pub max_edges: Option<usize>,
+    pub max_nodes: Option<usize>,
+    pub low_memory: bool,
+}
+
+#[derive(Debug)]
+pub struct Graph {
+    pub subcommand: GraphCmd,
+}
+
+#[derive(Debug)]
+pub enum GraphCmd {
+    Serve(Serve),
+    Wiki(Wiki),
+    Plugins(Plugins),
+    IdlDiff(IdlDiff),
+}
+
+#[derive(Debug)]
+pub struct Serve {
+    pub path: PathBuf,
+
+    pub port: Option<u16>,
+    pub watch: bool,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+    pub redact: Option<Redaction>,
+}
+
+#[derive(Debug)]
+pub struct Wiki {
+    pub path: PathBuf,
+
+    pub output: PathBuf,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+    pub redact: Option<Redaction>,
+}
+
+#[derive(Debug)]
+pub struct Plugins {
+    pub path: PathBuf,
+
+    pub plugin: Vec<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub baseline: Option<PathBuf>,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+    pub redact: Option<Redaction>,
+}
+
+#[derive(Debug)]
+pub struct IdlDiff {
+    pub path: PathBuf,
+
+    pub idl: Vec<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct Metrics {
+    pub path: PathBuf,
+
+    pub output: Option<PathBuf>,
+    pub max_complexity: Option<u32>,
+    pub max_nesting: Option<u32>,
+    pub max_branches: Option<u32>,
+    pub max_lines: Option<u32>,
+    pub max_statements: Option<u32>,
+    pub max_params: Option<u32>,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct Clones {
+    pub path: PathBuf,
+
+    pub output: Option<PathBuf>,
+    pub min_tokens: Option<usize>,
+    pub similarity: Option<f64>,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct Strings {
+    pub path: PathBuf,
+
+    pub output: Option<PathBuf>,
+    pub tagged: bool,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
 }
 
+#[derive(Debug)]
+pub struct Lint {
+    pub path: PathBuf,
+
+    pub output: Option<PathBuf>,
+    pub baseline: Option<PathBuf>,
+    pub rules: Option<RuleSelection>,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct DynUsage {
+    pub path: PathBuf,
+
+    pub output: Option<PathBuf>,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct BuildInventory {
+    pub path: PathBuf,
+
+    pub output: Option<PathBuf>,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct DepsCallers {
+    pub path: PathBuf,
+
+    pub crate_name: String,
+    pub depth: Option<u32>,
+    pub output: Option<PathBuf>,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct Features {
+    pub path: PathBuf,
+
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct Export {
+    pub subcommand: ExportCmd,
+}
+
+#[derive(Debug)]
+pub enum ExportCmd {
+    Bundle(Bundle),
+}
+
+#[derive(Debug)]
+pub struct Bundle {
+    pub path: PathBuf,
+
+    pub output: PathBuf,
+    pub analyzers: Option<Analyzers>,
+    pub format: Option<BundleFormat>,
+    pub report: Option<ReportFormat>,
+    pub rules: Option<RuleSelection>,
+    pub struct_name: Vec<Glob>,
+    pub file: Vec<Glob>,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+    pub redact: Option<Redaction>,
+}
+
+#[derive(Debug)]
+pub struct CallHierarchy {
+    pub subcommand: CallHierarchyCmd,
+}
+
+#[derive(Debug)]
+pub enum CallHierarchyCmd {
+    Tree(Tree),
+    Diff(Diff),
+}
+
+#[derive(Debug)]
+pub struct Tree {
+    pub function: String,
+    pub depth: Option<u32>,
+    pub path: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub format: Option<CallFormat>,
+    pub redact: Option<Redaction>,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub struct Diff {
+    pub base: String,
+    pub head: String,
+    pub path: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub disable_build_scripts: bool,
+    pub disable_proc_macros: bool,
+    pub proc_macro_srv: Option<PathBuf>,
+}
 
 #[derive(Debug)]
 pub struct SourceFinder {
     pub symbol_name: String,
     pub project_path: PathBuf,
+
+    pub redact: Option<Redaction>,
+    pub only_public: bool,
+    pub min_visibility: Option<MinVisibility>,
+    pub kind: Option<ItemKind>,
+    pub expand_calls: Option<u32>,
+    pub progress: Option<ProgressFormat>,
 }
 
 impl RustAnalyzer {
@@ -403,6 +1224,137 @@ pub enum OutputFormat {
     Csv,
 }
 
+/// How `function-analyzer` writes the call relations.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CallFormat {
+    /// One `caller -> callee (call at line:column)` line per relation.
+    #[default]
+    Text,
+    Json,
+    /// A Graphviz `digraph`.
+    Dot,
+    /// GraphML, for Gephi, yEd or NetworkX.
+    GraphMl,
+    /// A CSV edge list with one row per call site.
+    Csv,
+    /// Newline delimited JSON, one record per call site and a summary record.
+    Ndjson,
+}
+
+impl FromStr for CallFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "dot" => Ok(Self::Dot),
+            "graphml" => Ok(Self::GraphMl),
+            "csv" => Ok(Self::Csv),
+            "ndjson" => Ok(Self::Ndjson),
+            _ => Err(format!(
+                "unknown call hierarchy format `{s}`, expected text, json, dot, graphml, csv or ndjson"
+            )),
+        }
+    }
+}
+
+/// What a node of the `function-analyzer` graph stands for.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    #[default]
+    Function,
+    Module,
+    Crate,
+}
+
+impl FromStr for Granularity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "function" => Ok(Self::Function),
+            "module" => Ok(Self::Module),
+            "crate" => Ok(Self::Crate),
+            _ => Err(format!("unknown granularity `{s}`, expected function, module or crate")),
+        }
+    }
+}
+
+/// How far an item must be visible to be analyzed, from `--min-visibility`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MinVisibility {
+    #[default]
+    Private,
+    Crate,
+    Public,
+}
+
+impl FromStr for MinVisibility {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "private" => Ok(Self::Private),
+            "crate" => Ok(Self::Crate),
+            "public" | "pub" => Ok(Self::Public),
+            _ => Err(format!("unknown visibility `{s}`, expected public, crate or private")),
+        }
+    }
+}
+
+/// The kind of symbols `source-finder` lists, from `--kind`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+    /// Free functions and methods.
+    Function,
+    Struct,
+    Enum,
+    Trait,
+    Const,
+}
+
+impl FromStr for ItemKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "function" | "fn" => Ok(Self::Function),
+            "struct" => Ok(Self::Struct),
+            "enum" => Ok(Self::Enum),
+            "trait" => Ok(Self::Trait),
+            "const" => Ok(Self::Const),
+            _ => Err(format!(
+                "unknown kind `{s}`, expected function, struct, enum, trait or const"
+            )),
+        }
+    }
+}
+
+/// How the analysis commands report their progress, from `--progress`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressFormat {
+    /// `analyzed 1200/5400 functions` lines.
+    #[default]
+    Text,
+    /// One JSON object per event.
+    Json,
+    None,
+}
+
+impl FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "none" => Ok(Self::None),
+            _ => Err(format!("unknown progress format `{s}`, expected text, json or none")),
+        }
+    }
+}
+
 impl RustAnalyzer {
     pub fn verbosity(&self) -> Verbosity {
         if self.quiet {
@@ -426,3 +1378,231 @@ impl FromStr for OutputFormat {
         }
     }
 }
+
+/// Which outputs `export bundle` produces, e.g. `call-graph,findings`.
+#[derive(Debug, Clone, Copy)]
+pub struct Analyzers {
+    pub call_graph: bool,
+    pub structs: bool,
+    pub findings: bool,
+    pub metrics: bool,
+}
+
+impl Default for Analyzers {
+    fn default() -> Self {
+        Analyzers { call_graph: true, structs: true, findings: true, metrics: true }
+    }
+}
+
+impl FromStr for Analyzers {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut analyzers =
+            Analyzers { call_graph: false, structs: false, findings: false, metrics: false };
+        for name in s.split(',').map(str::trim) {
+            match name {
+                "call-graph" => analyzers.call_graph = true,
+                "structs" => analyzers.structs = true,
+                "findings" => analyzers.findings = true,
+                "metrics" => analyzers.metrics = true,
+                _ => {
+                    return Err(format!(
+                        "unknown analyzer `{name}`, expected call-graph, structs, findings or metrics"
+                    ));
+                }
+            }
+        }
+        Ok(analyzers)
+    }
+}
+
+/// How `export bundle` writes the outputs of the analyzers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BundleFormat {
+    #[default]
+    Json,
+    Yaml,
+    /// TOML, with each output under a key named after it since documents have to be tables.
+    Toml,
+}
+
+impl FromStr for BundleFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "yaml" => Ok(Self::Yaml),
+            "toml" => Ok(Self::Toml),
+            _ => Err(format!("unknown bundle format `{s}`, expected json, yaml or toml")),
+        }
+    }
+}
+
+/// A glob pattern, where `*` matches within a path segment, `**` across segments and `?` one
+/// character.
+#[derive(Debug, Clone)]
+pub struct Glob(pub String);
+
+impl FromStr for Glob {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.is_empty() {
+            true => Err("empty glob pattern".to_owned()),
+            false => Ok(Self(s.to_owned())),
+        }
+    }
+}
+
+/// The report `export bundle` renders next to the outputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            _ => Err(format!("unknown report format `{s}`, expected markdown or html")),
+        }
+    }
+}
+
+/// The rules whose findings are reported, e.g. `-unnecessary-mut,-zero-copy-padding`. Rules
+/// prefixed with `-` are left out, the others are the only ones kept.
+#[derive(Debug, Clone, Default)]
+pub struct RuleSelection {
+    pub enabled: Vec<String>,
+    pub disabled: Vec<String>,
+}
+
+impl FromStr for RuleSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut selection = RuleSelection::default();
+        for name in s.split(',').map(str::trim).filter(|it| !it.is_empty()) {
+            match name.strip_prefix('-') {
+                Some(name) => selection.disabled.push(name.to_owned()),
+                None => selection.enabled.push(name.to_owned()),
+            }
+        }
+        Ok(selection)
+    }
+}
+
+/// Which functions `--dead-code` starts from, e.g. `main,program`.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryKinds {
+    pub main: bool,
+    pub public: bool,
+    pub program: bool,
+    pub tests: bool,
+    pub trait_impls: bool,
+}
+
+impl Default for EntryKinds {
+    fn default() -> Self {
+        EntryKinds { main: true, public: true, program: true, tests: true, trait_impls: true }
+    }
+}
+
+impl FromStr for EntryKinds {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut kinds =
+            EntryKinds { main: false, public: false, program: false, tests: false, trait_impls: false };
+        for name in s.split(',').map(str::trim) {
+            match name {
+                "main" => kinds.main = true,
+                "public" => kinds.public = true,
+                "program" => kinds.program = true,
+                "tests" => kinds.tests = true,
+                "trait-impls" => kinds.trait_impls = true,
+                _ => {
+                    return Err(format!(
+                        "unknown entry kind `{name}`, expected main, public, program, tests or trait-impls"
+                    ));
+                }
+            }
+        }
+        Ok(kinds)
+    }
+}
+
+/// How `--prune` shrinks the call graph, e.g. `leaves:2,hubs:500,generated`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Prune {
+    /// Drop functions without outgoing calls that have fewer callers than this.
+    pub min_leaf_fan_in: Option<usize>,
+    /// Keep only the calls between this many of the most connected functions.
+    pub hubs: Option<usize>,
+    /// Merge the generated functions of each macro expansion or build script file.
+    pub generated: bool,
+}
+
+impl FromStr for Prune {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut prune = Prune::default();
+        for strategy in s.split(',').map(str::trim) {
+            let (name, arg) = strategy.split_once(':').unwrap_or((strategy, ""));
+            let count = || {
+                arg.parse::<usize>()
+                    .map_err(|_| format!("`{name}` expects a count, e.g. `{name}:10`"))
+            };
+            match name {
+                "leaves" => prune.min_leaf_fan_in = Some(count()?),
+                "hubs" => prune.hubs = Some(count()?),
+                "generated" => prune.generated = true,
+                _ => {
+                    return Err(format!(
+                        "unknown pruning strategy `{strategy}`, expected leaves:N, hubs:K or generated"
+                    ));
+                }
+            }
+        }
+        Ok(prune)
+    }
+}
+
+/// What `--redact` removes from the output, e.g. `strings,docs,paths`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Redaction {
+    /// Replace string literals by a hash of their contents.
+    pub strings: bool,
+    /// Replace the text of doc comments.
+    pub docs: bool,
+    /// Replace absolute paths, keeping only the file name.
+    pub paths: bool,
+}
+
+impl FromStr for Redaction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut redaction = Redaction::default();
+        for kind in s.split(',').map(str::trim) {
+            match kind {
+                "strings" => redaction.strings = true,
+                "docs" => redaction.docs = true,
+                "paths" => redaction.paths = true,
+                _ => {
+                    return Err(format!(
+                        "unknown redaction `{kind}`, expected strings, docs or paths"
+                    ));
+                }
+            }
+        }
+        Ok(redaction)
+    }
+}
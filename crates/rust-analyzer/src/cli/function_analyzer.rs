@@ -1,41 +1,225 @@
-use std::{env, fs, io::Write, path::PathBuf};
+use crate::cli::{
+    analysis_progress::Progress,
+    batch::{self, Toolchain},
+    budget::Budget,
+    call_cache::CallCache,
+    call_csv, call_dot,
+    call_edges::{self, CallEdge, CallSite},
+    call_graphml,
+    call_kind::{self, CallKind},
+    call_metrics,
+    call_ndjson::NdjsonWriter,
+    call_paths, call_traversal,
+    code_graph::{CodeGraph, module_path},
+    dead_code,
+    dispatch::{self, Dispatch},
+    entry_points::{self, EntryKind},
+    external_calls::{self, ExternalCrate},
+    flags::{self, Granularity, MinVisibility},
+    graph_tui, instantiations, low_memory, macro_edges, module_graph,
+};
 use anyhow::Result;
-use hir::{Crate, ModuleDef, Semantics};
+use cfg::{CfgAtom, CfgExpr};
+use hir::{
+    AsAssocItem, Crate, HasAttrs, HasCrate, HasVisibility, HirDisplay, MacroKind, ModuleDef,
+    Semantics, sym,
+};
 use ide::{Analysis, AnalysisHost, CallHierarchyConfig, CallItem, FilePosition, LineCol};
-use ide_db::{EditionedFileId, LineIndexDatabase};
+use ide_db::{
+    EditionedFileId, LineIndexDatabase,
+    base_db::{SourceDatabase, salsa},
+    defs::{Definition, NameClass},
+};
+use itertools::Itertools;
 use load_cargo::{LoadCargoConfig, ProcMacroServerChoice, load_workspace};
 use project_model::{CargoConfig, ProjectManifest, ProjectWorkspace, RustLibSource};
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize, Serializer, ser::SerializeStruct};
+use std::{
+    env, fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use syntax::{
+    AstNode, TextSize,
+    ast::{self, HasName},
+};
 use vfs::{AbsPathBuf, Vfs};
-use syntax::AstNode;
-use crate::cli::flags;
 
-#[derive(Debug, Clone)]
-struct FunctionInfo {
-    name: String,
-    file_path: String,
-    line: u32,
-    column: u32,
+#[derive(Debug, Clone, Deserialize)]
+pub(super) struct FunctionInfo {
+    pub(super) name: String,
+    /// The type of the impl or the trait the function is defined in, like `Calculator`,
+    /// `Handler` or `<Logger as Handler>`. None for free functions and unknown definitions.
+    #[serde(default)]
+    pub(super) container: Option<String>,
+    pub(super) file_path: String,
+    pub(super) line: u32,
+    pub(super) column: u32,
+    /// Path of the module containing the function, empty when unknown.
+    pub(super) module: String,
+    /// Whether the function is produced by a macro or a build script rather than written in
+    /// the sources.
+    pub(super) generated: bool,
 }
 
-#[derive(Debug, Clone)]
-struct CallRelation {
-    caller: FunctionInfo,
-    callee: FunctionInfo,
-    call_site_line: u32,
-    call_site_column: u32,
+impl FunctionInfo {
+    /// The name behind the impl or trait of the function, like `Calculator::multiply`.
+    pub(super) fn qualified_name(&self) -> String {
+        match &self.container {
+            Some(container) => format!("{container}::{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+
+    /// The full path of the function, like `my_crate::utils::helper_function`.
+    pub(super) fn path(&self) -> String {
+        match self.module.as_str() {
+            "" => self.qualified_name(),
+            module => format!("{module}::{}", self.qualified_name()),
+        }
+    }
+}
+
+/// Functions are written with their full `path`, sparing readers from piecing it together.
+impl Serialize for FunctionInfo {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("FunctionInfo", 8)?;
+        state.serialize_field("name", &self.name)?;
+        match &self.container {
+            Some(container) => state.serialize_field("container", container)?,
+            None => state.skip_field("container")?,
+        }
+        state.serialize_field("path", &self.path())?;
+        state.serialize_field("file_path", &self.file_path)?;
+        state.serialize_field("line", &self.line)?;
+        state.serialize_field("column", &self.column)?;
+        state.serialize_field("module", &self.module)?;
+        state.serialize_field("generated", &self.generated)?;
+        state.end()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct CallRelation {
+    pub(super) caller: FunctionInfo,
+    pub(super) callee: FunctionInfo,
+    pub(super) call_site_line: u32,
+    pub(super) call_site_column: u32,
+    pub(super) dispatch: Dispatch,
+    /// The likelihood of the call reaching this callee, below `1.0` for dynamic dispatch.
+    pub(super) confidence: f64,
+    /// The macro the call only exists in the expansion of, like `require!` or `#[program]`.
+    pub(super) expanded_from: Option<String>,
+    pub(super) call_kind: CallKind,
+    /// The dependency the callee belongs to, with `--include-external`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) callee_crate: Option<ExternalCrate>,
+    /// The source lines around the call site, with `--call-context`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) call_snippet: Option<String>,
+    /// The type of the generic caller the callee was chosen for, like `H = Logger`, with
+    /// `--instantiations`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) instantiated_with: Option<String>,
 }
 
 impl flags::FunctionAnalyzer {
     pub fn run(self) -> Result<()> {
+        let projects = batch::projects(&self.path, self.projects_file.as_deref())?;
+        match projects.as_slice() {
+            [path] => self.analyze(path, &self.output, &mut Toolchain::default()),
+            _ => batch::run(&self, &projects),
+        }
+    }
+
+    /// Analyzes the project at `path`, writing to `output`. The toolchain of the first project
+    /// analyzed is kept in `toolchain` for the others.
+    pub(super) fn analyze(
+        &self,
+        path: &Path,
+        output: &Option<PathBuf>,
+        toolchain: &mut Toolchain,
+    ) -> Result<()> {
+        let _p = tracing::info_span!("function_analyzer", path = %path.display()).entered();
+        if self.depth.is_some() && self.root.is_none() && !self.entrypoints {
+            anyhow::bail!("`--depth` requires `--root` or `--entrypoints`");
+        }
+        if self.entrypoints && (self.root.is_some() || self.path_from.is_some() || self.dead_code) {
+            anyhow::bail!(
+                "`--entrypoints` can't be combined with `--root`, `--path-from` or `--dead-code`"
+            );
+        }
+        if self.path_from.is_some() != self.path_to.is_some() {
+            anyhow::bail!("`--path-from` and `--path-to` go together");
+        }
+        if self.path_from.is_some() && (self.root.is_some() || self.incoming) {
+            anyhow::bail!("`--path-from` can't be combined with `--root` or `--incoming`");
+        }
+        if self.dead_code && (self.root.is_some() || self.incoming || self.path_from.is_some()) {
+            anyhow::bail!(
+                "`--dead-code` can't be combined with `--root`, `--incoming` or `--path-from`"
+            );
+        }
+        if (self.path_from.is_some() || self.dead_code)
+            && !matches!(
+                self.format.unwrap_or_default(),
+                flags::CallFormat::Text | flags::CallFormat::Json
+            )
+        {
+            anyhow::bail!("`--path-from` and `--dead-code` only write the text and json formats");
+        }
+        if self.call_context.is_some()
+            && (!matches!(self.format, Some(flags::CallFormat::Json | flags::CallFormat::Ndjson))
+                || self.metrics
+                || self.dead_code)
+        {
+            anyhow::bail!(
+                "`--call-context` only applies to the calls written by `--format json` or `ndjson`"
+            );
+        }
+        let condensed = self.granularity.filter(|it| *it != Granularity::Function);
+        if condensed.is_some()
+            && (self.metrics
+                || self.dead_code
+                || self.path_from.is_some()
+                || self.tui
+                || self.call_context.is_some())
+        {
+            anyhow::bail!(
+                "`--granularity` can't be combined with `--metrics`, `--dead-code`, `--path-from`, `--tui` or `--call-context`"
+            );
+        }
+        if (self.max_edges.is_some() || self.max_nodes.is_some())
+            && (self.path_from.is_some() || self.dead_code)
+        {
+            anyhow::bail!(
+                "`--max-edges` and `--max-nodes` can't be combined with `--path-from` or `--dead-code`"
+            );
+        }
+        if self.low_memory
+            && (self.root.is_some()
+                || self.entrypoints
+                || self.path_from.is_some()
+                || self.dead_code
+                || self.tui
+                || self.max_edges.is_some()
+                || self.max_nodes.is_some())
+        {
+            anyhow::bail!(
+                "`--low-memory` can't be combined with `--root`, `--entrypoints`, `--path-from`, `--dead-code`, `--tui`, `--max-edges` or `--max-nodes`"
+            );
+        }
         eprintln!("Loading workspace...");
-        
-        let path = AbsPathBuf::assert_utf8(env::current_dir()?.join(&self.path));
-        let manifest = ProjectManifest::discover_single(&path)?;
+        let load_span = tracing::info_span!("load_workspace").entered();
+
+        let project_root = AbsPathBuf::assert_utf8(env::current_dir()?.join(path));
+        let manifest = ProjectManifest::discover_single(&project_root)?;
         let mut cargo_config = CargoConfig::default();
         cargo_config.sysroot = Some(RustLibSource::Discover);
-        
-        let load_cargo_config = LoadCargoConfig {
+        toolchain.configure(&mut cargo_config);
+
+        let mut load_cargo_config = LoadCargoConfig {
             load_out_dirs_from_check: !self.disable_build_scripts,
             with_proc_macro_server: if self.disable_proc_macros {
                 ProcMacroServerChoice::None
@@ -50,38 +234,387 @@ impl flags::FunctionAnalyzer {
             },
             prefill_caches: false,
         };
-        
-        let ws = ProjectWorkspace::load(manifest, &cargo_config, &|_| {})?;
-        let (db, vfs, _proc_macro) = load_workspace(
-            ws,
-            &cargo_config.extra_env,
-            &load_cargo_config,
-        )?;
-        
-        let host = AnalysisHost::with_database(db.clone());
-        let analysis = host.analysis();
-        
-        // Get project root path
-        let project_root = AbsPathBuf::assert_utf8(env::current_dir()?.join(&self.path));
-        
+
+        let progress = Progress::new(self.progress.unwrap_or_default(), "functions");
+        let ws =
+            ProjectWorkspace::load(manifest, &cargo_config, &|message| progress.message(&message))?;
+        toolchain.share(&ws, &mut load_cargo_config.with_proc_macro_server);
+        let filter = FunctionFilter {
+            include_crates: self.include_crate.clone(),
+            exclude_crates: self.exclude_crate.clone(),
+            module_prefixes: self.module_prefix.clone(),
+            exclude_tests: self.exclude_tests,
+            min_visibility: min_visibility(self.only_public, self.min_visibility)?,
+            exclude_generated: !self.include_generated,
+        };
+        if self.low_memory {
+            drop(load_span);
+            let (functions, roots, call_relations) = low_memory::analyze(
+                self,
+                ws,
+                &cargo_config.extra_env,
+                &load_cargo_config,
+                &project_root,
+                &filter,
+                &progress,
+            )?;
+            eprintln!("Found {} call relationships", call_relations.len());
+            return self.write_calls(
+                output,
+                &project_root,
+                &functions,
+                &roots,
+                call_relations,
+                Vec::new(),
+            );
+        }
+        let (db, vfs, _proc_macro) =
+            load_workspace(ws, &cargo_config.extra_env, &load_cargo_config)?;
+        drop(load_span);
+
         eprintln!("Extracting functions...");
-        let functions = extract_all_functions(&db, &vfs, &project_root)?;
+        let functions = extract_all_functions(&db, &vfs, &project_root, &filter)?;
         eprintln!("Found {} functions", functions.len());
-        
+        let roots = entry_points::program_roots(&db, &vfs, &functions);
+        if !roots.is_empty() {
+            eprintln!("Found {} program entry points", roots.len());
+        }
+
         eprintln!("Analyzing call relationships...");
-        let call_relations = analyze_call_relationships(&analysis, &functions, &vfs, &db, &project_root)?;
+        let mut analysis = CallAnalysis::new(self, &db, &vfs, &project_root, &progress);
+        let mut calls_of = |functions: &[FunctionInfo]| analysis.calls_of(functions);
+        if let (Some(from), Some(to)) = (&self.path_from, &self.path_to) {
+            let sources = call_traversal::resolve_root(&functions, from)?;
+            let relations = call_traversal::transitive_relations(
+                &functions,
+                sources.iter().map(|&it| it.clone()).collect(),
+                None,
+                false,
+                &mut calls_of,
+            )?;
+            // Sinks may be dependency functions, only known as callees.
+            let candidates: Vec<FunctionInfo> = functions
+                .iter()
+                .cloned()
+                .chain(relations.iter().map(|relation| relation.callee.clone()))
+                .collect();
+            let sinks = call_traversal::resolve_root(&candidates, to)?;
+            let chains = call_paths::shortest_chains(&functions, &relations, &sources, &sinks);
+            if chains.is_empty() {
+                eprintln!("No call chain from `{from}` to `{to}`");
+            }
+            call_paths::write_chains(
+                &mut open_output(output)?,
+                from,
+                to,
+                &chains,
+                self.format.unwrap_or_default(),
+                &project_root,
+                &self.redact.unwrap_or_default(),
+            )?;
+            return Ok(());
+        }
+        let start: Option<Vec<FunctionInfo>> = match &self.root {
+            Some(root) => {
+                let roots = call_traversal::resolve_root(&functions, root)?;
+                eprintln!("Starting from {} functions matching `{root}`", roots.len());
+                Some(roots.into_iter().cloned().collect())
+            }
+            None if self.entrypoints => {
+                if roots.is_empty() {
+                    anyhow::bail!("no `#[program]` handler or native entrypoint found");
+                }
+                Some(roots.iter().map(|(function, _)| function.clone()).collect())
+            }
+            None => None,
+        };
+        let mut budget = Budget::new(&functions, self.max_edges, self.max_nodes, self.incoming);
+        let mut calls_of = |functions: &[FunctionInfo]| match &mut budget {
+            Some(budget) => budget.calls(functions, &mut calls_of),
+            None => calls_of(functions),
+        };
+        let format = self.format.unwrap_or_default();
+        // Nothing needs the whole graph, write the calls of each file as soon as they are known.
+        if format == flags::CallFormat::Ndjson
+            && start.is_none()
+            && !(self.dead_code
+                || self.metrics
+                || self.tui
+                || self.prune.is_some()
+                || condensed.is_some())
+        {
+            let _p = tracing::info_span!("write_output").entered();
+            let mut writer = NdjsonWriter::new(
+                open_output(output)?,
+                &project_root,
+                self.redact.unwrap_or_default(),
+            );
+            for file_functions in functions.chunk_by(|a, b| a.file_path == b.file_path) {
+                writer.write_relations(&calls_of(file_functions)?)?;
+            }
+            analysis.report_cache();
+            let truncated = budget.map(|it| it.report()).unwrap_or_default();
+            for decision in &truncated {
+                eprintln!("Truncated: {decision}");
+            }
+            writer.finish(self.incoming, functions.len(), &truncated)?;
+            eprintln!("Call hierarchy analysis completed!");
+            return Ok(());
+        }
+        let call_relations = match start {
+            Some(start) => call_traversal::transitive_relations(
+                &functions,
+                start,
+                self.depth,
+                self.incoming,
+                calls_of,
+            )?,
+            None => calls_of(&functions)?,
+        };
+        analysis.report_cache();
         eprintln!("Found {} call relationships", call_relations.len());
-        
+
+        if self.dead_code {
+            let entries = entry_points::entry_kinds(
+                &db,
+                &vfs,
+                &functions,
+                &self.entry_kinds.unwrap_or_default(),
+                &self.entry,
+            );
+            let unreachable =
+                dead_code::unreachable_functions(&functions, &call_relations, &entries);
+            eprintln!("{} of {} functions are unreachable", unreachable.len(), functions.len());
+            dead_code::write_dead_code(
+                &mut open_output(output)?,
+                &functions,
+                &entries,
+                &unreachable,
+                self.format.unwrap_or_default(),
+                &project_root,
+                &self.redact.unwrap_or_default(),
+            )?;
+            return Ok(());
+        }
+
+        let truncated = budget.map(|it| it.report()).unwrap_or_default();
+        for decision in &truncated {
+            eprintln!("Truncated: {decision}");
+        }
+        self.write_calls(output, &project_root, &functions, &roots, call_relations, truncated)
+    }
+
+    /// Writes `call_relations` in the selected format, after pruning them. `pruned` already
+    /// describes what the analysis left out.
+    fn write_calls(
+        &self,
+        output: &Option<PathBuf>,
+        project_root: &AbsPathBuf,
+        functions: &[FunctionInfo],
+        roots: &[(FunctionInfo, EntryKind)],
+        mut call_relations: Vec<CallRelation>,
+        mut pruned: Vec<String>,
+    ) -> Result<()> {
+        let format = self.format.unwrap_or_default();
+        let condensed = self.granularity.filter(|it| *it != Granularity::Function);
+        if let Some(prune) = &self.prune {
+            let decisions;
+            (call_relations, decisions) = prune.relations(functions, call_relations);
+            for decision in &decisions {
+                eprintln!("Pruned: {decision}");
+            }
+            pruned.extend(decisions);
+        }
+
+        if self.metrics {
+            call_metrics::write_metrics(
+                &mut open_output(output)?,
+                functions,
+                &call_relations,
+                project_root,
+                &self.redact.unwrap_or_default(),
+            )?;
+            eprintln!("Call hierarchy analysis completed!");
+            return Ok(());
+        }
+
+        if let Some(granularity) = condensed {
+            let dependencies = module_graph::condense(functions, &call_relations, granularity);
+            module_graph::write_dependencies(
+                &mut open_output(output)?,
+                &dependencies,
+                &pruned,
+                granularity,
+                self.format.unwrap_or_default(),
+            )?;
+            eprintln!("Call hierarchy analysis completed!");
+            return Ok(());
+        }
+
+        if self.tui {
+            let graph = CodeGraph::from_relations(functions, &call_relations, project_root);
+            return graph_tui::run(&graph, project_root.as_ref());
+        }
+
         eprintln!("Writing output...");
-        write_output(&call_relations, &self.output, &project_root)?;
+        let _p = tracing::info_span!("write_output").entered();
+        if format == flags::CallFormat::Ndjson {
+            let mut writer = NdjsonWriter::new(
+                open_output(output)?,
+                project_root,
+                self.redact.unwrap_or_default(),
+            );
+            writer.write_relations(&call_relations)?;
+            writer.finish(self.incoming, functions.len(), &pruned)?;
+            eprintln!("Call hierarchy analysis completed!");
+            return Ok(());
+        }
+        if format == flags::CallFormat::Dot {
+            call_dot::write_dot(
+                &mut open_output(output)?,
+                functions,
+                &call_relations,
+                &pruned,
+                roots,
+                self.cluster_modules,
+                project_root,
+                &self.redact.unwrap_or_default(),
+            )?;
+            eprintln!("Call hierarchy analysis completed!");
+            return Ok(());
+        }
+        if format == flags::CallFormat::Csv {
+            call_csv::write_csv(
+                &mut open_output(output)?,
+                &call_relations,
+                project_root,
+                &self.redact.unwrap_or_default(),
+            )?;
+            eprintln!("Call hierarchy analysis completed!");
+            return Ok(());
+        }
+        if format == flags::CallFormat::GraphMl {
+            call_graphml::write_graphml(
+                &mut open_output(output)?,
+                functions,
+                &call_relations,
+                &pruned,
+                roots,
+                project_root,
+                &self.redact.unwrap_or_default(),
+            )?;
+            eprintln!("Call hierarchy analysis completed!");
+            return Ok(());
+        }
+        write_output(
+            &call_relations,
+            &pruned,
+            roots,
+            output,
+            format,
+            self.incoming,
+            self.flat,
+            project_root,
+            &self.redact.unwrap_or_default(),
+        )?;
         
         eprintln!("Call hierarchy analysis completed!");
         Ok(())
     }
 }
 
+/// The calls of functions, found by the call hierarchy and refined by the steps selected on the
+/// command line, with the calls of unchanged files taken from the cache.
+pub(super) struct CallAnalysis<'a> {
+    cmd: &'a flags::FunctionAnalyzer,
+    db: &'a ide::RootDatabase,
+    vfs: &'a Vfs,
+    analysis: Analysis,
+    project_root: &'a AbsPathBuf,
+    progress: &'a Progress,
+    cache: CallCache<'a>,
+}
+
+impl<'a> CallAnalysis<'a> {
+    pub(super) fn new(
+        cmd: &'a flags::FunctionAnalyzer,
+        db: &'a ide::RootDatabase,
+        vfs: &'a Vfs,
+        project_root: &'a AbsPathBuf,
+        progress: &'a Progress,
+    ) -> Self {
+        CallAnalysis {
+            cmd,
+            db,
+            vfs,
+            analysis: AnalysisHost::with_database(db.clone()).analysis(),
+            project_root,
+            progress,
+            cache: CallCache::new(
+                db,
+                vfs,
+                project_root,
+                cmd.incoming,
+                cmd.exclude_tests,
+                !cmd.no_cache,
+            ),
+        }
+    }
+
+    pub(super) fn calls_of(&mut self, functions: &[FunctionInfo]) -> Result<Vec<CallRelation>> {
+        let CallAnalysis { cmd, db, vfs, analysis, project_root, progress, cache } = self;
+        let (cmd, db, vfs, project_root) = (*cmd, *db, *vfs, *project_root);
+        let analyze = |functions: &[FunctionInfo]| {
+            let mut relations = analyze_call_relationships(
+                analysis,
+                functions,
+                vfs,
+                db,
+                project_root,
+                cmd.incoming,
+                cmd.exclude_tests,
+                progress,
+            )?;
+            if !cmd.incoming {
+                relations.extend(macro_edges::macro_argument_calls(db, vfs, functions));
+            }
+            anyhow::Ok(relations)
+        };
+        let mut relations = cache.relations(functions, analyze)?;
+        if cmd.instantiations {
+            relations = instantiations::resolve_instantiations(db, vfs, project_root, relations);
+        }
+        let mut relations = dispatch::resolve_dynamic_dispatch(db, vfs, project_root, relations);
+        macro_edges::mark_macro_expansions(db, vfs, &mut relations);
+        call_kind::classify_calls(db, vfs, &mut relations);
+        // Chains may lead into dependency functions like `invoke_signed`.
+        let include_external = cmd.include_external || cmd.path_from.is_some();
+        external_calls::attribute_external_calls(
+            db,
+            vfs,
+            project_root,
+            &mut relations,
+            include_external,
+        );
+        if let Some(lines) = cmd.call_context {
+            attach_call_snippets(db, vfs, &mut relations, lines);
+        }
+        if cmd.exclude_macro_edges {
+            relations.retain(|relation| relation.expanded_from.is_none());
+        }
+        Ok(relations)
+    }
+
+    pub(super) fn report_cache(&self) {
+        if !self.cmd.no_cache {
+            let (hits, misses) = self.cache.stats();
+            eprintln!("Reused the cached calls of {hits} functions, analyzed {misses}");
+        }
+    }
+}
+
 /// Check if a file path is external to the project
-fn is_external_path(file_path: &str, project_root: &AbsPathBuf) -> bool {
+pub(super) fn is_external_path(file_path: &str, project_root: &AbsPathBuf) -> bool {
     let project_root_str = project_root.to_string();
     
     // Check if the file is outside the project root
@@ -111,11 +644,136 @@ fn is_external_path(file_path: &str, project_root: &AbsPathBuf) -> bool {
     false
 }
 
-fn extract_all_functions(
-    db: &ide::RootDatabase, 
-    vfs: &Vfs, 
-    project_root: &AbsPathBuf
+/// Check if a file was written by a build script into its `OUT_DIR`
+pub(super) fn is_build_output(file_path: &str) -> bool {
+    file_path.contains("/target/") && file_path.contains("/build/") && file_path.contains("/out/")
+}
+
+/// Whether `func` comes from the expansion of a derive or a procedural macro, possibly through
+/// the declarative macros it expands to, like the `try_accounts` of `#[derive(Accounts)]`.
+fn is_proc_macro_output(db: &ide::RootDatabase, func: hir::Function) -> bool {
+    let Some(source) = Semantics::new(db).source(func) else { return false };
+    let mut file_id = source.file_id;
+    while let Some(call) = file_id.macro_file() {
+        if !matches!(call.kind(db), MacroKind::Declarative | MacroKind::DeclarativeBuiltIn) {
+            return true;
+        }
+        file_id = call.parent(db);
+    }
+    false
+}
+
+/// Narrows `extract_all_functions` down to the parts of a workspace worth analyzing.
+#[derive(Debug, Default, Clone)]
+pub(super) struct FunctionFilter {
+    /// Only keep the functions of these crates, all crates when empty.
+    pub(super) include_crates: Vec<String>,
+    pub(super) exclude_crates: Vec<String>,
+    /// Only keep the functions of modules below one of these paths, all modules when empty.
+    pub(super) module_prefixes: Vec<String>,
+    /// Skip `#[test]` and `#[bench]` functions, `#[cfg(test)]` modules and the crates of the
+    /// `tests/` and `benches/` targets.
+    pub(super) exclude_tests: bool,
+    /// Skip the functions visible less far than this.
+    pub(super) min_visibility: MinVisibility,
+    /// Skip the functions generated by derives and procedural macros.
+    pub(super) exclude_generated: bool,
+}
+
+impl FunctionFilter {
+    pub(super) fn keeps_crate(&self, name: &str) -> bool {
+        // Cargo package names use dashes where crate names use underscores.
+        let matches = |it: &String| it.replace('-', "_") == name.replace('-', "_");
+        (self.include_crates.is_empty() || self.include_crates.iter().any(matches))
+            && !self.exclude_crates.iter().any(matches)
+    }
+
+    pub(super) fn keeps_target(&self, root_file: &str, project_root: &AbsPathBuf) -> bool {
+        !self.exclude_tests
+            || !convert_to_relative_path(root_file, project_root)
+                .split('/')
+                .any(|component| component == "tests" || component == "benches")
+    }
+
+    fn keeps_function(&self, db: &ide::RootDatabase, func: hir::Function) -> bool {
+        (!self.exclude_tests || !(func.is_test(db) || func.is_bench(db)))
+            && is_visible(db, Definition::Function(func), self.min_visibility)
+    }
+
+    fn keeps_generated(
+        &self,
+        db: &ide::RootDatabase,
+        func: hir::Function,
+        info: &FunctionInfo,
+    ) -> bool {
+        !self.exclude_generated || !info.generated || !is_proc_macro_output(db, func)
+    }
+
+    fn keeps_module(&self, module: &str) -> bool {
+        self.module_prefixes.is_empty()
+            || self.module_prefixes.iter().any(|prefix| {
+                module
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+    }
+}
+
+/// The level asked for by `--only-public` and `--min-visibility`.
+pub(super) fn min_visibility(
+    only_public: bool,
+    level: Option<MinVisibility>,
+) -> Result<MinVisibility> {
+    match (only_public, level) {
+        (true, Some(level)) if level != MinVisibility::Public => {
+            anyhow::bail!("`--only-public` can't be combined with a lower `--min-visibility`")
+        }
+        (true, _) => Ok(MinVisibility::Public),
+        (false, level) => Ok(level.unwrap_or_default()),
+    }
+}
+
+/// Whether `def` is visible at least as far as `level`: from other crates for `public`, from
+/// anywhere in its crate for `crate`. The modules containing it must be visible as far too.
+pub(super) fn is_visible(db: &ide::RootDatabase, def: Definition, level: MinVisibility) -> bool {
+    let reaches = |visibility: hir::Visibility| match level {
+        MinVisibility::Private => true,
+        MinVisibility::Crate => match visibility {
+            hir::Visibility::Module(module, _) => hir::Module::from(module).is_crate_root(),
+            hir::Visibility::PubCrate(_) | hir::Visibility::Public => true,
+        },
+        MinVisibility::Public => visibility == hir::Visibility::Public,
+    };
+    let (Some(visibility), Some(module)) = (def.visibility(db), def.module(db)) else {
+        return true;
+    };
+    reaches(visibility)
+        && module
+            .path_to_root(db)
+            .into_iter()
+            .filter(|it| !it.is_crate_root())
+            .all(|it| reaches(it.visibility(db)))
+}
+
+/// Whether the attributes include `#[cfg(test)]`, possibly combined with other predicates.
+fn is_cfg_test(attrs: &hir::AttrsWithOwner) -> bool {
+    fn requires_test(cfg: &CfgExpr) -> bool {
+        match cfg {
+            CfgExpr::Atom(CfgAtom::Flag(flag)) => *flag == sym::test,
+            CfgExpr::All(cfgs) => cfgs.iter().any(requires_test),
+            _ => false,
+        }
+    }
+    attrs.cfgs().any(|cfg| requires_test(&cfg))
+}
+
+pub(super) fn extract_all_functions(
+    db: &ide::RootDatabase,
+    vfs: &Vfs,
+    project_root: &AbsPathBuf,
+    filter: &FunctionFilter,
 ) -> Result<Vec<FunctionInfo>> {
+    let _p = tracing::info_span!("extract_all_functions").entered();
     let mut functions = Vec::new();
     let mut visited_modules = FxHashSet::default();
     let mut visit_queue = Vec::new();
@@ -125,6 +783,11 @@ fn extract_all_functions(
     
     // Initialize the queue with root modules from all crates
     for krate in crates {
+        let name = krate.display_name(db).map(|name| name.to_string()).unwrap_or_default();
+        let root_file = vfs.file_path(krate.root_file(db)).to_string();
+        if !filter.keeps_crate(&name) || !filter.keeps_target(&root_file, project_root) {
+            continue;
+        }
         let root_module = krate.root_module();
         visit_queue.push(root_module);
     }
@@ -132,30 +795,38 @@ fn extract_all_functions(
     // Process all modules
     while let Some(module) = visit_queue.pop() {
         if visited_modules.insert(module) {
+            if filter.exclude_tests && is_cfg_test(&module.attrs(db)) {
+                continue;
+            }
             visit_queue.extend(module.children(db));
+            if !filter.keeps_module(&module_path(db, module)) {
+                continue;
+            }
             
             // Extract functions from this module
             for decl in module.declarations(db) {
-                if let ModuleDef::Function(func) = decl {
-                    if let Some(func_info) = extract_function_info(db, func, vfs)? {
-                        // Filter out external library calls
-                        if !is_external_path(&func_info.file_path, project_root) {
-                            functions.push(func_info);
-                        }
-                    }
+                // Filter out external library calls
+                if let ModuleDef::Function(func) = decl
+                    && filter.keeps_function(db, func)
+                    && let Some(func_info) = extract_function_info(db, func, vfs)?
+                    && !is_external_path(&func_info.file_path, project_root)
+                    && filter.keeps_generated(db, func, &func_info)
+                {
+                    functions.push(func_info);
                 }
             }
             
             // Also check for associated functions in impls
             for impl_def in module.impl_defs(db) {
                 for item in impl_def.items(db) {
-                    if let hir::AssocItem::Function(func) = item {
-                        if let Some(func_info) = extract_function_info(db, func, vfs)? {
-                            // Filter out external library calls
-                            if !is_external_path(&func_info.file_path, project_root) {
-                                functions.push(func_info);
-                            }
-                        }
+                    // Filter out external library calls
+                    if let hir::AssocItem::Function(func) = item
+                        && filter.keeps_function(db, func)
+                        && let Some(func_info) = extract_function_info(db, func, vfs)?
+                        && !is_external_path(&func_info.file_path, project_root)
+                        && filter.keeps_generated(db, func, &func_info)
+                    {
+                        functions.push(func_info);
                     }
                 }
             }
@@ -165,7 +836,7 @@ fn extract_all_functions(
     Ok(functions)
 }
 
-fn extract_function_info(
+pub(super) fn extract_function_info(
     db: &ide::RootDatabase,
     func: hir::Function,
     vfs: &Vfs,
@@ -190,11 +861,25 @@ fn extract_function_info(
         let line_index = db.line_index(original_file_id.file_id(db));
         let line_col = line_index.line_col(text_range.start());
         
+        // Functions of an expansion whose name can't be traced back to the sources are
+        // generated, attribute macros keep the functions they're applied to as written.
+        let generated = is_build_output(&file_path)
+            || source.file_id.is_macro()
+                && source.value.name().is_none_or(|name| {
+                    let range = sema.original_range(name.syntax());
+                    let text = db.file_text(range.file_id.file_id(db)).text(db);
+                    text.get(std::ops::Range::<usize>::from(range.range))
+                        != Some(name.text().as_str())
+                });
+
         let function_info = FunctionInfo {
             name: func.name(db).display(db, syntax::Edition::CURRENT).to_string(),
             file_path,
             line: line_col.line + 1, // Convert to 1-based
             column: line_col.col + 1, // Convert to 1-based
+            module: module_path(db, func.module(db)),
+            container: container_name(db, func),
+            generated,
         };
         
         return Ok(Some(function_info));
@@ -203,14 +888,59 @@ fn extract_function_info(
     Ok(None)
 }
 
-fn analyze_call_relationships(
+/// The type of the impl or the trait `func` is defined in, see [`FunctionInfo::container`].
+pub(super) fn container_name(db: &ide::RootDatabase, func: hir::Function) -> Option<String> {
+    let display_target = func.krate(db).to_display_target(db);
+    // Generic arguments are left out, `Vec<T, A>::new` is just `Vec::new`.
+    let type_name = |ty: hir::Type<'_>| match ty.as_adt() {
+        Some(adt) => adt.name(db).display(db, syntax::Edition::CURRENT).to_string(),
+        None => ty.display(db, display_target).to_string(),
+    };
+    salsa::attach(db, || match func.as_assoc_item(db)?.container(db) {
+        hir::AssocItemContainer::Trait(trait_) => {
+            Some(trait_.name(db).display(db, syntax::Edition::CURRENT).to_string())
+        }
+        hir::AssocItemContainer::Impl(impl_) => {
+            let self_ty = type_name(impl_.self_ty(db));
+            Some(match impl_.trait_(db) {
+                Some(trait_) => format!(
+                    "<{self_ty} as {}>",
+                    trait_.name(db).display(db, syntax::Edition::CURRENT)
+                ),
+                None => self_ty,
+            })
+        }
+    })
+}
+
+/// The function whose name is at `offset` of `file_id`.
+pub(super) fn function_named_at(
+    sema: &Semantics<'_, ide::RootDatabase>,
+    file_id: vfs::FileId,
+    offset: TextSize,
+) -> Option<hir::Function> {
+    let file = sema.parse_guess_edition(file_id);
+    let token = file.syntax().token_at_offset(offset).right_biased()?;
+    match NameClass::classify(sema, &ast::Name::cast(token.parent()?)?)?.defined()? {
+        Definition::Function(function) => Some(function),
+        _ => None,
+    }
+}
+
+pub(super) fn analyze_call_relationships(
     analysis: &Analysis,
     functions: &[FunctionInfo],
     vfs: &Vfs,
     db: &ide::RootDatabase,
     project_root: &AbsPathBuf,
+    incoming: bool,
+    exclude_tests: bool,
+    progress: &Progress,
 ) -> Result<Vec<CallRelation>> {
+    let _p =
+        tracing::info_span!("analyze_call_relationships", functions = functions.len()).entered();
     let mut call_relations = Vec::new();
+    progress.extend(functions.len());
     
     for func in functions {
         // Find the file_id for this function
@@ -232,33 +962,46 @@ fn analyze_call_relationships(
                  if let Some(offset) = offset {
                      let position = FilePosition { file_id: file_id, offset };
                      
-                     let config = CallHierarchyConfig {
-                         exclude_tests: false,
-                     };
+                     let config = CallHierarchyConfig { exclude_tests };
                      
-                     // Get outgoing calls (functions this function calls)
-                     if let Ok(Some(outgoing_calls)) = analysis.outgoing_calls(config, position) {
-                         for call_item in outgoing_calls {
-                             if let Some(call_relation) = create_call_relation_from_item(
+                     // Get outgoing calls (functions this function calls), or incoming calls
+                     // (functions calling this function)
+                     let calls = if incoming {
+                         let position = name_position(analysis, position).unwrap_or(position);
+                         analysis.incoming_calls(config, position)
+                     } else {
+                         analysis.outgoing_calls(config, position)
+                     };
+                     if let Ok(Some(calls)) = calls {
+                         for call_item in calls {
+                             call_relations.extend(create_call_relations_from_item(
                                  func,
                                  &call_item,
+                                 incoming,
                                  vfs,
                                  db,
                                  project_root,
-                             )? {
-                                 call_relations.push(call_relation);
-                             }
+                             )?);
                          }
                      }
                  }
              }
          }
+         progress.advance();
      }
     
     Ok(call_relations)
 }
 
-fn find_file_id_by_path(vfs: &Vfs, file_path: &str) -> Option<vfs::FileId> {
+/// `incoming_calls` only resolves a function from its name, while functions start at their item.
+fn name_position(analysis: &Analysis, position: FilePosition) -> Option<FilePosition> {
+    let file = analysis.parse(position.file_id).ok()?;
+    let token = file.syntax().token_at_offset(position.offset).right_biased()?;
+    let name = token.parent_ancestors().find_map(ast::Fn::cast)?.name()?;
+    Some(FilePosition { file_id: position.file_id, offset: name.syntax().text_range().start() })
+}
+
+pub(super) fn find_file_id_by_path(vfs: &Vfs, file_path: &str) -> Option<vfs::FileId> {
     // Search through all files in VFS to find matching path
     for (file_id, path) in vfs.iter() {
         let path_str = path.to_string();
@@ -269,16 +1012,19 @@ fn find_file_id_by_path(vfs: &Vfs, file_path: &str) -> Option<vfs::FileId> {
     None
 }
 
-fn create_call_relation_from_item(
-    caller_func: &FunctionInfo,
+/// Builds the relations between `func` and the function of `call_item`, which is its callee or,
+/// for `incoming` calls, its caller, one per call site. The call sites are always in the caller.
+fn create_call_relations_from_item(
+    func: &FunctionInfo,
     call_item: &CallItem,
+    incoming: bool,
     vfs: &Vfs,
     db: &ide::RootDatabase,
     project_root: &AbsPathBuf,
-) -> Result<Option<CallRelation>> {
+) -> Result<Vec<CallRelation>> {
     let target = &call_item.target;
     
-    // Get callee information
+    // Get information on the other end of the call
     let file_id = target.file_id;
     let path = vfs.file_path(file_id);
     let file_path = path.to_string();
@@ -290,26 +1036,38 @@ fn create_call_relation_from_item(
     
     // Validate target_range is within file bounds
     if target_range.start() > line_index.len().into() {
-        return Ok(None); // Skip this item if range is invalid
+        return Ok(Vec::new()); // Skip this item if range is invalid
     }
-    
+
     let line_col = line_index.line_col(target_range.start());
-    
-    let callee_info = FunctionInfo {
+    // The call hierarchy only names the function, its module and container come from its
+    // definition.
+    let function =
+        salsa::attach(db, || function_named_at(&Semantics::new(db), file_id, target_range.start()));
+
+    let item_info = FunctionInfo {
         name: target.name.to_string(),
+        generated: is_build_output(&file_path),
         file_path: file_path.clone(),
         line: line_col.line + 1,
         column: line_col.col + 1,
+        module: function.map(|it| module_path(db, it.module(db))).unwrap_or_default(),
+        container: function.and_then(|it| container_name(db, it)),
     };
+
+    let (caller, callee) =
+        if incoming { (item_info, func.clone()) } else { (func.clone(), item_info) };
     
     // Filter out external library calls - only filter if caller is external, not callee
     // We want to keep calls from project functions to standard library (like Ok)
-    if is_external_path(&caller_func.file_path, project_root) {
-        return Ok(None);
+    if is_external_path(&caller.file_path, project_root) {
+        return Ok(Vec::new());
     }
     
-    // Get call site information
-    let (_call_line_col, call_site_line, call_site_column) = if let Some(range_info) = call_item.ranges.first() {
+    // One relation per call site, so calls of the same function spawned, awaited or made
+    // synchronously can be told apart
+    let mut call_sites = Vec::with_capacity(call_item.ranges.len());
+    for range_info in &call_item.ranges {
         let call_file_id = range_info.file_id;
         let call_range = range_info.range;
         
@@ -317,31 +1075,64 @@ fn create_call_relation_from_item(
         let call_editioned_file_id = EditionedFileId::current_edition(db, call_file_id);
         let call_line_index = db.line_index(call_editioned_file_id.file_id(db));
         
-        // Validate call_range is within file bounds
+        // Skip the call sites whose range is invalid
         if call_range.start() > call_line_index.len().into() {
-            return Ok(None); // Skip this item if range is invalid
+            continue;
         }
-        
-        let call_line_col = call_line_index.line_col(call_range.start());
-        
-        (call_line_col, call_line_col.line + 1, call_line_col.col + 1)
-    } else {
+
+        call_sites.push(call_line_index.line_col(call_range.start()));
+    }
+    if call_item.ranges.is_empty() {
         // Fallback to target range if no call ranges available
-        let call_line_col = line_index.line_col(target_range.start());
-        (call_line_col, call_line_col.line + 1, call_line_col.col + 1)
-    };
-    
-    let call_relation = CallRelation {
-        caller: caller_func.clone(),
-        callee: callee_info,
-        call_site_line,
-        call_site_column,
-    };
-    
-    Ok(Some(call_relation))
+        call_sites.push(line_index.line_col(target_range.start()));
+    }
+
+    let call_relations = call_sites
+        .into_iter()
+        .map(|call_line_col| CallRelation {
+            caller: caller.clone(),
+            callee: callee.clone(),
+            call_site_line: call_line_col.line + 1,
+            call_site_column: call_line_col.col + 1,
+            dispatch: Dispatch::Static,
+            confidence: 1.0,
+            expanded_from: None,
+            call_kind: CallKind::Function,
+            callee_crate: None,
+            call_snippet: None,
+            instantiated_with: None,
+        })
+        .collect();
+
+    Ok(call_relations)
 }
 
-fn convert_to_relative_path(file_path: &str, project_root: &AbsPathBuf) -> String {
+/// Fills in the `call_snippet` of every relation with the line of its call site and `context`
+/// lines around it.
+fn attach_call_snippets(
+    db: &ide::RootDatabase,
+    vfs: &Vfs,
+    relations: &mut [CallRelation],
+    context: u32,
+) {
+    let mut texts: FxHashMap<String, Option<String>> = FxHashMap::default();
+    for relation in relations {
+        // The call site is in the caller, whichever the direction.
+        let path = &relation.caller.file_path;
+        let text = texts.entry(path.clone()).or_insert_with(|| {
+            let file_id = find_file_id_by_path(vfs, path)?;
+            Some(db.file_text(file_id).text(db).to_string())
+        });
+        let Some(text) = text else { continue };
+        let line = relation.call_site_line.saturating_sub(1);
+        let (first, last) = (line.saturating_sub(context), line + context);
+        let snippet: Vec<&str> =
+            text.lines().skip(first as usize).take((last - first + 1) as usize).collect();
+        relation.call_snippet = Some(snippet.join("\n"));
+    }
+}
+
+pub(super) fn convert_to_relative_path(file_path: &str, project_root: &AbsPathBuf) -> String {
     let abs_path = std::path::Path::new(file_path);
     let project_root_path = std::path::Path::new(project_root.as_str());
     
@@ -354,40 +1145,187 @@ fn convert_to_relative_path(file_path: &str, project_root: &AbsPathBuf) -> Strin
     }
 }
 
-fn write_output(call_relations: &[CallRelation], output_path: &Option<PathBuf>, project_root: &AbsPathBuf) -> Result<()> {
-    let output = match output_path {
+/// The JSON document written by `--format json`.
+#[derive(Serialize)]
+struct CallHierarchy<'a> {
+    /// `outgoing`, or `incoming` when the relations were collected from the callees.
+    direction: &'static str,
+    /// What `--prune` and the `--max-edges` or `--max-nodes` budget removed, empty for the
+    /// complete graph.
+    pruned: &'a [String],
+    /// The anchor `#[program]` handlers and native entrypoints.
+    roots: Vec<Root>,
+    /// Every call site as a relation of its own with `--flat`, grouped into edges otherwise.
+    relations: Relations<'a>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Relations<'a> {
+    Flat(&'a [CallRelation]),
+    Edges(Vec<CallEdge<'a>>),
+}
+
+#[derive(Serialize)]
+struct Root {
+    #[serde(flatten)]
+    function: FunctionInfo,
+    kind: EntryKind,
+}
+
+fn open_output(output_path: &Option<PathBuf>) -> Result<Box<dyn Write>> {
+    Ok(match output_path {
         Some(path) => {
             let file = fs::File::create(path)?;
             Box::new(file) as Box<dyn Write>
         }
         None => Box::new(std::io::stdout()) as Box<dyn Write>,
+    })
+}
+
+/// `relations` with file paths relative to the project root, and paths and call snippets
+/// redacted, for JSON output.
+pub(super) fn relative_relations(
+    relations: &[CallRelation],
+    project_root: &AbsPathBuf,
+    redaction: &flags::Redaction,
+) -> Vec<CallRelation> {
+    let relative = |function: &FunctionInfo| FunctionInfo {
+        file_path: redaction.path(&convert_to_relative_path(&function.file_path, project_root)),
+        ..function.clone()
     };
-    
-    let mut writer = output;
+    relations
+        .iter()
+        .map(|relation| CallRelation {
+            caller: relative(&relation.caller),
+            callee: relative(&relation.callee),
+            call_snippet: relation.call_snippet.as_deref().map(|it| redaction.source(it)),
+            ..relation.clone()
+        })
+        .collect()
+}
+
+/// The ` [kind]`, ` [with T = Type]`, ` [dynamic, confidence]`, ` [expanded from macro]` and
+/// ` [crate name version]` notes ending a text relation.
+pub(super) fn relation_notes(relation: &CallRelation) -> String {
+    CallSite::of(relation).notes() + &call_edges::crate_note(relation.callee_crate.as_ref())
+}
+
+fn write_output(
+    call_relations: &[CallRelation],
+    pruned: &[String],
+    roots: &[(FunctionInfo, EntryKind)],
+    output_path: &Option<PathBuf>,
+    format: flags::CallFormat,
+    incoming: bool,
+    flat: bool,
+    project_root: &AbsPathBuf,
+    redaction: &flags::Redaction,
+) -> Result<()> {
+    let mut writer = open_output(output_path)?;
+
+    if format == flags::CallFormat::Json {
+        let relations = relative_relations(call_relations, project_root, redaction);
+        let direction = if incoming { "incoming" } else { "outgoing" };
+        let roots = roots
+            .iter()
+            .map(|(function, kind)| Root {
+                function: FunctionInfo {
+                    file_path: redaction
+                        .path(&convert_to_relative_path(&function.file_path, project_root)),
+                    ..function.clone()
+                },
+                kind: *kind,
+            })
+            .collect();
+        let relations = match flat {
+            true => Relations::Flat(&relations),
+            false => Relations::Edges(call_edges::call_edges(&relations, false)),
+        };
+        let document = CallHierarchy { direction, pruned, roots, relations };
+        serde_json::to_writer_pretty(&mut writer, &document)?;
+        writeln!(writer)?;
+        return Ok(());
+    }
     
     // Write header
     writeln!(writer, "# Function Call Hierarchy Analysis")?;
-    writeln!(writer, "# Format: caller_function -> callee_function (call_site)")?;
+    if incoming {
+        writeln!(writer, "# Format: callee_function <- caller_function (call_sites)")?;
+    } else {
+        writeln!(writer, "# Format: caller_function -> callee_function (call_sites)")?;
+    }
+    writeln!(
+        writer,
+        "# Calls of methods, spawned or awaited end with [method], [trait-method], [spawn] or [await]"
+    )?;
+    writeln!(writer, "# Calls resolved to trait implementations end with [dynamic, confidence]")?;
+    writeln!(writer, "# Calls produced by a macro end with [expanded from macro]")?;
+    if call_relations.iter().any(|relation| relation.instantiated_with.is_some()) {
+        writeln!(
+            writer,
+            "# Calls resolved through the callers' type parameters end with [with T = Type]"
+        )?;
+    }
+    if call_relations.iter().any(|relation| relation.callee_crate.is_some()) {
+        writeln!(writer, "# Calls into dependencies end with [crate name version]")?;
+    }
+    for decision in pruned {
+        writeln!(writer, "# Pruned: {decision}")?;
+    }
+    for (function, kind) in roots {
+        let file = redaction.path(&convert_to_relative_path(&function.file_path, project_root));
+        writeln!(
+            writer,
+            "# Root ({}): {file}:{}:{}",
+            kind.as_str(),
+            function.line,
+            function.path()
+        )?;
+    }
     writeln!(writer)?;
     
     // Write call relations
-    for relation in call_relations {
-        let caller_relative_path = convert_to_relative_path(&relation.caller.file_path, project_root);
-        let callee_relative_path = convert_to_relative_path(&relation.callee.file_path, project_root);
-        
+    for edge in call_edges::call_edges(call_relations, flat) {
+        let caller_relative_path =
+            redaction.path(&convert_to_relative_path(&edge.caller.file_path, project_root));
+        let callee_relative_path =
+            redaction.path(&convert_to_relative_path(&edge.callee.file_path, project_root));
+
+        let sites = edge
+            .call_sites
+            .iter()
+            .map(|site| format!("{}:{}{}", site.line, site.column, site.notes()))
+            .join(", ");
+        let calls = match edge.count {
+            1 => format!("call at {sites}"),
+            count => format!("{count} calls at {sites}"),
+        };
+        let notes = call_edges::crate_note(edge.callee_crate);
+        if incoming {
+            writeln!(
+                writer,
+                "{}:{}:{} <- {}:{}:{} ({calls}){notes}",
+                callee_relative_path,
+                edge.callee.line,
+                edge.callee.path(),
+                caller_relative_path,
+                edge.caller.line,
+                edge.caller.path(),
+            )?;
+            continue;
+        }
         writeln!(
             writer,
-            "{}:{}:{} -> {}:{}:{} (call at {}:{})",
+            "{}:{}:{} -> {}:{}:{} ({calls}){notes}",
             caller_relative_path,
-            relation.caller.line,
-            relation.caller.name,
+            edge.caller.line,
+            edge.caller.path(),
             callee_relative_path,
-            relation.callee.line,
-            relation.callee.name,
-            relation.call_site_line,
-            relation.call_site_column
+            edge.callee.line,
+            edge.callee.path(),
         )?;
     }
     
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,173 @@
+//! The privileged instructions of the programs, only some keys can execute, and the keys gating
+//! them.
+//!
+//! A signer of the accounts struct of an instruction is gated when its key has to be one the
+//! program trusts: a field of a state account, like `global.authority` through `has_one`, a fixed
+//! key compared with `address = CREATION_AUTHORITY` or `constraint = admin.key() == ADMIN`, or
+//! the key of a whitelist, an account derived from seeds holding the signer's key which the
+//! instruction reads without creating or changing it. Fixed keys are the expressions reading no
+//! account or instruction argument. Checks of handler bodies aren't looked at.
+
+use serde::Serialize;
+
+use crate::cli::{
+    authorities::{AuthorityKind, is_signer},
+    code_graph::{AccountField, AccountStruct, CodeGraph, Instruction},
+    constraint_expr::ConstraintExpr,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct PrivilegedInstruction {
+    /// The path of the instruction.
+    pub(super) instruction: String,
+    pub(super) file: String,
+    pub(super) line: u32,
+    pub(super) gates: Vec<Gate>,
+}
+
+/// A signer and the key it has to be.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct Gate {
+    pub(super) kind: GateKind,
+    /// The field of the accounts struct signing.
+    pub(super) signer: String,
+    /// The state field like `pump::state::Global.authority`, the fixed key like
+    /// `CREATION_AUTHORITY`, or the whitelist account.
+    pub(super) key: String,
+    /// The seeds of the whitelist account, empty for the other gates.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(super) seeds: Vec<String>,
+    /// The accounts struct field holding the check.
+    pub(super) file: String,
+    pub(super) line: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum GateKind {
+    /// The key stored in a field of a state account.
+    StateField,
+    /// A key fixed in the program.
+    Constant,
+    /// An account derived from the key of the signer.
+    Whitelist,
+}
+
+/// Lists the instructions of `graph` with a gated signer. Its authorities and PDAs have to be
+/// extracted already.
+pub(super) fn extract_governance(graph: &CodeGraph) -> Vec<PrivilegedInstruction> {
+    let _p = tracing::info_span!("extract_governance").entered();
+    let mut privileged = Vec::new();
+    for instruction in &graph.instructions {
+        let Some(strukt) = graph
+            .account_structs
+            .iter()
+            .find(|it| instruction.accounts_struct.as_ref() == Some(&it.path()))
+        else {
+            continue;
+        };
+        let path = instruction.path();
+        let line_of = |name: &str| {
+            strukt.fields.iter().find(|it| it.name == name).map_or(strukt.line, |it| it.line)
+        };
+        let gate = |kind, signer: &str, key: String, line| Gate {
+            kind,
+            signer: signer.to_owned(),
+            key,
+            seeds: Vec::new(),
+            file: strukt.file.clone(),
+            line,
+        };
+        let mut gates = Vec::new();
+
+        let state_fields =
+            graph.authorities.iter().filter(|it| it.kind == AuthorityKind::StateField);
+        for authority in state_fields {
+            for grant in authority.grants.iter().filter(|it| it.instruction == path) {
+                let Some(signer) = &grant.signer else { continue };
+                let key = authority.name.clone();
+                gates.push(gate(GateKind::StateField, signer, key, line_of(signer)));
+            }
+        }
+
+        let signers: Vec<&AccountField> = strukt.fields.iter().filter(|it| is_signer(it)).collect();
+        let signs = |name: &str| signers.iter().any(|it| it.name == name);
+        for field in &strukt.fields {
+            for constraint in &field.constraints {
+                let Some(value) = &constraint.value else { continue };
+                match constraint.kind.as_str() {
+                    "address" if signs(&field.name) => {
+                        let address = ConstraintExpr::parse(value);
+                        if address.is_some_and(|it| is_fixed(&it, strukt, instruction)) {
+                            let key = value.clone();
+                            gates.push(gate(GateKind::Constant, &field.name, key, field.line));
+                        }
+                    }
+                    "constraint" => {
+                        let Some(expr) = &constraint.expr else { continue };
+                        let equalities =
+                            expr.required_comparisons().into_iter().filter(|it| it.0 == "==");
+                        for (_, lhs, rhs) in equalities {
+                            for (signed, key) in [(lhs, rhs), (rhs, lhs)] {
+                                if let Some(signer) = signed.key_of()
+                                    && signs(signer)
+                                    && is_fixed(key, strukt, instruction)
+                                {
+                                    let key = key.to_string();
+                                    gates.push(gate(GateKind::Constant, signer, key, field.line));
+                                }
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let struct_path = strukt.path();
+        for pda in graph.pdas.iter().filter(|it| it.account_struct == struct_path) {
+            let Some(field) = strukt.fields.iter().find(|it| it.name == pda.field) else {
+                continue;
+            };
+            let changed = field
+                .constraints
+                .iter()
+                .any(|it| matches!(it.kind.as_str(), "init" | "init_if_needed" | "mut"));
+            if changed || signs(&field.name) {
+                continue;
+            }
+            let derived_from = pda
+                .seeds
+                .iter()
+                .filter_map(|it| ConstraintExpr::parse(it))
+                .flat_map(|seed| {
+                    let keys = seed.walk().into_iter().filter_map(|it| it.key_of());
+                    keys.map(str::to_owned).collect::<Vec<_>>()
+                })
+                .find(|it| signs(it));
+            if let Some(signer) = derived_from {
+                let whitelist = gate(GateKind::Whitelist, &signer, field.name.clone(), field.line);
+                gates.push(Gate { seeds: pda.seeds.clone(), ..whitelist });
+            }
+        }
+
+        if !gates.is_empty() {
+            let (file, line) = (instruction.file.clone(), instruction.line);
+            privileged.push(PrivilegedInstruction { instruction: path, file, line, gates });
+        }
+    }
+    privileged.sort_by(|a, b| a.instruction.cmp(&b.instruction));
+    privileged
+}
+
+/// Whether `expr` reads no account of `strukt` and no argument of `instruction`.
+fn is_fixed(expr: &ConstraintExpr, strukt: &AccountStruct, instruction: &Instruction) -> bool {
+    let names = (strukt.fields.iter().map(|it| &it.name))
+        .chain(strukt.instruction_args.iter().map(|it| &it.name))
+        .chain(instruction.params.iter().map(|it| &it.name));
+    let names: Vec<&String> = names.collect();
+    expr.walk()
+        .into_iter()
+        .filter_map(|it| it.name())
+        .all(|name| !names.iter().any(|it| *it == name))
+}
@@ -0,0 +1,185 @@
+//! Narrows the code graph down to some types or source files, so a finding can be iterated on
+//! without going through the outputs of the whole program.
+//!
+//! `--struct-name` keeps the account structs and other types named like one of its patterns,
+//! the instructions taking a kept accounts struct and what they reach: their CPIs, token
+//! operations, sysvars, lamport flows, authorities and gates. `--file` keeps what is declared in
+//! a matching file, the instructions whose accounts struct is kept and what they reach, and the
+//! CPIs made in a matching file. Findings are kept on the lines of a kept type, in functions a
+//! kept instruction reaches and, with `--file` alone, in matching files. Programs are kept whole.
+//! The access matrix keeps the kept state accounts, read and written by the kept instructions.
+//!
+//! In patterns `*` matches within a path segment, `**` across segments and `?` one character.
+//! File patterns match the end of the project relative path, so `src/instructions/curve/*` finds
+//! the files of every program of a workspace.
+
+use rustc_hash::FxHashSet;
+
+use crate::cli::{
+    code_graph::{AccountStruct, CodeGraph, Instruction, qualify},
+    flags,
+};
+
+/// The types and files `export bundle` narrows its outputs to.
+pub(super) struct GraphFilter<'a> {
+    pub(super) structs: &'a [flags::Glob],
+    pub(super) files: &'a [flags::Glob],
+}
+
+impl GraphFilter<'_> {
+    /// Drops everything of `graph` the filter doesn't keep.
+    pub(super) fn retain(&self, graph: &mut CodeGraph) {
+        if self.structs.is_empty() && self.files.is_empty() {
+            return;
+        }
+        let _p = tracing::info_span!("GraphFilter::retain").entered();
+        graph.account_structs.retain(|it| self.keeps(&it.name, &it.file));
+        graph.state_accounts.retain(|it| self.keeps(&it.name, &it.file));
+        graph.zero_copy_types.retain(|it| self.keeps(&it.name, &it.file));
+        graph.events.retain(|it| self.keeps(&it.name, &it.file));
+        graph.enums.retain(|it| self.keeps(&it.name, &it.file));
+        graph.error_codes.retain(|it| self.keeps(&it.name, &it.file));
+        graph.native_programs.retain(|it| self.structs.is_empty() && self.keeps_file(&it.file));
+
+        let structs: FxHashSet<String> =
+            graph.account_structs.iter().map(AccountStruct::path).collect();
+        graph.instructions.retain(|it| {
+            it.accounts_struct.as_ref().is_some_and(|it| structs.contains(it))
+                || (self.structs.is_empty() && self.keeps_file(&it.file))
+        });
+        graph.pdas.retain(|it| structs.contains(&it.account_struct));
+
+        let instructions: FxHashSet<String> =
+            graph.instructions.iter().map(Instruction::path).collect();
+        let kept = |instruction: &String| instructions.contains(instruction);
+        graph.cpi_calls.retain(|it| {
+            it.instructions.iter().any(kept)
+                || (self.structs.is_empty() && self.keeps_file(&it.file))
+        });
+        graph.token_operations.retain(|it| kept(&it.instruction));
+        graph.sysvars.retain(|it| kept(&it.instruction));
+        graph.lamport_flows.retain(|it| kept(&it.instruction));
+        for authority in &mut graph.authorities {
+            authority.grants.retain(|it| kept(&it.instruction));
+        }
+        graph.authorities.retain(|it| !it.grants.is_empty());
+        graph.governance.retain(|it| kept(&it.instruction));
+        let states: FxHashSet<String> =
+            graph.state_accounts.iter().map(|it| qualify(&it.module, &it.name)).collect();
+        graph.state_access.retain(|it| states.contains(&it.account));
+        for field in graph.state_access.iter_mut().flat_map(|it| &mut it.fields) {
+            field.read_by.retain(kept);
+            field.written_by.retain(kept);
+        }
+
+        // The lines declaring the kept types and the fields of the accounts structs.
+        let mut lines: FxHashSet<(String, u32)> = FxHashSet::default();
+        for strukt in &graph.account_structs {
+            lines.insert((strukt.file.clone(), strukt.line));
+            lines.extend(strukt.fields.iter().map(|it| (strukt.file.clone(), it.line)));
+        }
+        let items = (graph.state_accounts.iter().map(|it| (&it.file, it.line)))
+            .chain(graph.zero_copy_types.iter().map(|it| (&it.file, it.line)))
+            .chain(graph.events.iter().map(|it| (&it.file, it.line)))
+            .chain(graph.enums.iter().map(|it| (&it.file, it.line)))
+            .chain(graph.error_codes.iter().map(|it| (&it.file, it.line)));
+        lines.extend(items.map(|(file, line)| (file.clone(), line)));
+        let reached: FxHashSet<usize> =
+            graph.instruction_reach().into_iter().flat_map(|(_, it)| it).collect();
+        let mut findings = std::mem::take(&mut graph.findings);
+        findings.retain(|finding| {
+            let (Some(file), Some(line)) = (&finding.file, finding.line) else { return false };
+            lines.contains(&(file.clone(), line))
+                || graph.function_enclosing(file, line).is_some_and(|it| reached.contains(&it))
+                || (self.structs.is_empty() && self.keeps_file(file))
+        });
+        graph.findings = findings;
+    }
+
+    fn keeps(&self, name: &str, file: &str) -> bool {
+        let named = self.structs.is_empty() || self.structs.iter().any(|it| it.matches(name));
+        named && self.keeps_file(file)
+    }
+
+    fn keeps_file(&self, file: &str) -> bool {
+        self.files.is_empty() || self.files.iter().any(|it| it.matches_path(file))
+    }
+}
+
+impl flags::Glob {
+    pub(super) fn matches(&self, text: &str) -> bool {
+        let pattern: Vec<char> = self.0.chars().collect();
+        let text: Vec<char> = text.chars().collect();
+        glob_match(&pattern, &text)
+    }
+
+    /// Whether the pattern matches `path` or one of its trailing segments.
+    pub(super) fn matches_path(&self, path: &str) -> bool {
+        let starts = path.match_indices('/').map(|(index, _)| index + 1);
+        std::iter::once(0).chain(starts).any(|start| self.matches(&path[start..]))
+    }
+}
+
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        // Also matches no directory at all, so `src/**/lib.rs` finds `src/lib.rs`.
+        ['*', '*', '/', rest @ ..] => (0..=text.len())
+            .filter(|&it| it == 0 || text[it - 1] == '/')
+            .any(|it| glob_match(rest, &text[it..])),
+        ['*', '*', rest @ ..] => (0..=text.len()).any(|it| glob_match(rest, &text[it..])),
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|&it| it == 0 || text[it - 1] != '/')
+            .any(|it| glob_match(rest, &text[it..])),
+        ['?', rest @ ..] => {
+            matches!(text, [first, tail @ ..] if *first != '/' && glob_match(rest, tail))
+        }
+        [c, rest @ ..] => {
+            matches!(text, [first, tail @ ..] if first == c && glob_match(rest, tail))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs_match_paths() {
+        let cases = [
+            ("src/**/lib.rs", "src/lib.rs", true),
+            ("src/**/lib.rs", "src/a/b/lib.rs", true),
+            ("src/**/lib.rs", "src/mylib.rs", false),
+            ("src/*.rs", "src/lib.rs", true),
+            ("src/*.rs", "src/a/lib.rs", false),
+            ("src/**", "src/a/lib.rs", true),
+            ("?ib.rs", "lib.rs", true),
+            ("src?lib.rs", "src/lib.rs", false),
+            // File patterns match the end of the path, on segment boundaries.
+            ("instructions/*", "programs/amm/src/instructions/swap.rs", true),
+            ("lib.rs", "programs/amm/src/lib.rs", true),
+            ("ib.rs", "programs/amm/src/lib.rs", false),
+            ("amm/src", "programs/amm/src/lib.rs", false),
+            ("*/lib.rs", "programs/amm/src/lib.rs", true),
+        ];
+        for (pattern, path, expected) in cases {
+            let glob = flags::Glob(pattern.to_owned());
+            assert_eq!(glob.matches_path(path), expected, "`{pattern}` against `{path}`");
+        }
+    }
+
+    #[test]
+    fn globs_match_names() {
+        let cases = [
+            ("Swap*", "SwapBaseIn", true),
+            ("*Pool", "AmmPool", true),
+            ("*Pool", "PoolState", false),
+            ("Pool?", "Pools", true),
+            ("Pool?", "Pool", false),
+        ];
+        for (pattern, name, expected) in cases {
+            let glob = flags::Glob(pattern.to_owned());
+            assert_eq!(glob.matches(name), expected, "`{pattern}` against `{name}`");
+        }
+    }
+}
@@ -0,0 +1,286 @@
+//! Runs user supplied WASM plugins over the code graph so custom audit rules can be written
+//! without forking the analyzer.
+//!
+//! A plugin is a core WASM module (`.wasm`, or `.wat` text) exporting its `memory` and an
+//! `alloc(len: i32) -> i32` function the host uses to hand over JSON encoded items. It may export
+//! any of the following visitor callbacks, each receiving a `(ptr: i32, len: i32)` pair:
+//!
+//! - `on_function`: a function of the graph, including external ones,
+//! - `on_struct`: a `#[derive(Accounts)]` struct with its fields,
+//! - `on_constraint`: one `#[account(...)]` constraint of a struct field, whole and split into
+//!   its `kind`, `value` and `error`,
+//! - `on_edge`: a call, with both the caller and the callee function,
+//!
+//! and `on_finish()`, called once every item has been visited. If it exports
+//! `dealloc(ptr: i32, len: i32)`, every buffer is released after its callback returned.
+//!
+//! Plugins report findings by calling the imported `env.emit_finding(ptr, len)` with a JSON
+//! object `{"rule", "message", "severity"?, "file"?, "line"?, "related"?}`, where the severity is
+//! `info`, `warning` (the default) or `error` and `related` lists other places as
+//! `{"file", "line", "message"}`. They can print debug output with `env.log(ptr, len)`.
+
+use std::{fs, path::Path};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::cli::{
+    account_constraints::Constraint,
+    code_graph::{CodeGraph, GraphFunction, LoadOptions, LoadedProject},
+    findings::{self, Finding},
+    flags,
+};
+
+impl flags::Plugins {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("graph_plugins", path = %self.path.display()).entered();
+        if self.plugin.is_empty() {
+            anyhow::bail!("no plugin given, pass at least one `--plugin <file.wasm>`");
+        }
+
+        eprintln!("Loading workspace...");
+        let project = LoadedProject::load(
+            &self.path,
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+        let mut graph = CodeGraph::build(&project)?;
+        self.redact.unwrap_or_default().graph(&mut graph);
+
+        let mut findings = Vec::new();
+        for plugin in &self.plugin {
+            eprintln!("Running plugin {}...", plugin.display());
+            let _p = tracing::info_span!("run_plugin", plugin = %plugin.display()).entered();
+            findings.extend(run_plugin(plugin, &graph)?);
+        }
+        eprintln!("Plugins reported {} findings", findings.len());
+
+        if let Some(baseline) = &self.baseline {
+            let total = findings.len();
+            findings = findings::new_findings(findings, baseline)?;
+            eprintln!(
+                "{} new findings, {} already in the baseline",
+                findings.len(),
+                total - findings.len()
+            );
+        }
+
+        let json = serde_json::to_string_pretty(&findings)?;
+        match &self.output {
+            Some(path) => fs::write(path, json)?,
+            None => println!("{json}"),
+        }
+        if self.baseline.is_some() && !findings.is_empty() {
+            anyhow::bail!("{} new findings compared to the baseline", findings.len());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct ConstraintItem<'a> {
+    #[serde(rename = "struct")]
+    strukt: &'a str,
+    field: &'a str,
+    /// The whole constraint, like `has_one = owner @ ErrorCode::NotOwner`.
+    constraint: String,
+    #[serde(flatten)]
+    parts: &'a Constraint,
+    file: &'a str,
+    line: u32,
+}
+
+#[derive(Serialize)]
+struct EdgeItem<'a> {
+    caller: &'a GraphFunction,
+    callee: &'a GraphFunction,
+    line: u32,
+    column: u32,
+}
+
+/// Feeds every item of `graph` to the callbacks exported by the plugin at `path`.
+fn run_plugin(path: &Path, graph: &CodeGraph) -> Result<Vec<Finding>> {
+    let name = path.file_name().map(|it| it.to_string_lossy().into_owned()).unwrap_or_default();
+    let mut plugin = imp::Plugin::load(path, name)?;
+
+    for function in &graph.functions {
+        plugin.visit("on_function", function)?;
+    }
+    for strukt in &graph.account_structs {
+        plugin.visit("on_struct", strukt)?;
+    }
+    for strukt in &graph.account_structs {
+        for field in &strukt.fields {
+            for constraint in &field.constraints {
+                let item = ConstraintItem {
+                    strukt: &strukt.name,
+                    field: &field.name,
+                    constraint: constraint.to_string(),
+                    parts: constraint,
+                    file: &strukt.file,
+                    line: field.line,
+                };
+                plugin.visit("on_constraint", &item)?;
+            }
+        }
+    }
+    for call in &graph.calls {
+        let item = EdgeItem {
+            caller: &graph.functions[call.caller],
+            callee: &graph.functions[call.callee],
+            line: call.line,
+            column: call.column,
+        };
+        plugin.visit("on_edge", &item)?;
+    }
+    plugin.finish()
+}
+
+#[cfg(feature = "wasm-plugins")]
+mod imp {
+    use std::path::Path;
+
+    use anyhow::{Context, Result, anyhow};
+    use rustc_hash::FxHashMap;
+    use serde::Serialize;
+    use wasmtime::{Caller, Engine, Instance, Linker, Memory, Module, Store, TypedFunc};
+
+    use crate::cli::findings::Finding;
+
+    struct HostState {
+        plugin: String,
+        findings: Vec<Finding>,
+    }
+
+    pub(super) struct Plugin {
+        store: Store<HostState>,
+        instance: Instance,
+        memory: Memory,
+        alloc: TypedFunc<i32, i32>,
+        dealloc: Option<TypedFunc<(i32, i32), ()>>,
+        /// Exported callbacks by name, `None` if the plugin does not implement it.
+        callbacks: FxHashMap<&'static str, Option<TypedFunc<(i32, i32), ()>>>,
+    }
+
+    impl Plugin {
+        pub(super) fn load(path: &Path, name: String) -> Result<Plugin> {
+            let engine = Engine::default();
+            let module = Module::from_file(&engine, path)
+                .with_context(|| format!("failed to compile plugin {}", path.display()))?;
+
+            let mut linker = Linker::new(&engine);
+            linker.func_wrap(
+                "env",
+                "emit_finding",
+                |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> Result<()> {
+                    let bytes = read_guest(&mut caller, ptr, len)?;
+                    let mut finding: Finding = serde_json::from_slice(&bytes)
+                        .context("plugin emitted a malformed finding")?;
+                    finding.plugin = caller.data().plugin.clone();
+                    caller.data_mut().findings.push(finding);
+                    Ok(())
+                },
+            )?;
+            linker.func_wrap(
+                "env",
+                "log",
+                |mut caller: Caller<'_, HostState>, ptr: i32, len: i32| -> Result<()> {
+                    let bytes = read_guest(&mut caller, ptr, len)?;
+                    eprintln!("[{}] {}", caller.data().plugin, String::from_utf8_lossy(&bytes));
+                    Ok(())
+                },
+            )?;
+
+            let mut store = Store::new(&engine, HostState { plugin: name, findings: Vec::new() });
+            let instance = linker.instantiate(&mut store, &module)?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .ok_or_else(|| anyhow!("plugin does not export `memory`"))?;
+            let alloc = instance.get_typed_func(&mut store, "alloc")?;
+            let dealloc = instance.get_typed_func(&mut store, "dealloc").ok();
+            Ok(Plugin { store, instance, memory, alloc, dealloc, callbacks: FxHashMap::default() })
+        }
+
+        /// Passes `item` as JSON to the `callback` export, if the plugin has one.
+        pub(super) fn visit(
+            &mut self,
+            callback: &'static str,
+            item: &impl Serialize,
+        ) -> Result<()> {
+            let func = match self.callbacks.get(callback) {
+                Some(func) => func.clone(),
+                None => {
+                    let func = self.instance.get_typed_func(&mut self.store, callback).ok();
+                    self.callbacks.insert(callback, func.clone());
+                    func
+                }
+            };
+            let Some(func) = func else { return Ok(()) };
+
+            let bytes = serde_json::to_vec(item)?;
+            let len = i32::try_from(bytes.len())?;
+            let ptr = self.alloc.call(&mut self.store, len)?;
+            self.memory.write(&mut self.store, ptr as u32 as usize, &bytes)?;
+            func.call(&mut self.store, (ptr, len))
+                .with_context(|| format!("`{callback}` failed"))?;
+            if let Some(dealloc) = &self.dealloc {
+                dealloc.call(&mut self.store, (ptr, len))?;
+            }
+            Ok(())
+        }
+
+        pub(super) fn finish(mut self) -> Result<Vec<Finding>> {
+            if let Ok(on_finish) =
+                self.instance.get_typed_func::<(), ()>(&mut self.store, "on_finish")
+            {
+                on_finish.call(&mut self.store, ()).context("`on_finish` failed")?;
+            }
+            Ok(self.store.into_data().findings)
+        }
+    }
+
+    fn read_guest(caller: &mut Caller<'_, HostState>, ptr: i32, len: i32) -> Result<Vec<u8>> {
+        let memory = caller
+            .get_export("memory")
+            .and_then(|export| export.into_memory())
+            .ok_or_else(|| anyhow!("plugin does not export `memory`"))?;
+        let mut bytes = vec![0; len as u32 as usize];
+        memory.read(&caller, ptr as u32 as usize, &mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+#[cfg(not(feature = "wasm-plugins"))]
+mod imp {
+    use std::path::Path;
+
+    use anyhow::Result;
+    use serde::Serialize;
+
+    use crate::cli::findings::Finding;
+
+    pub(super) enum Plugin {}
+
+    impl Plugin {
+        pub(super) fn load(_path: &Path, _name: String) -> Result<Plugin> {
+            anyhow::bail!(
+                "plugins require rust-analyzer to be built with the `wasm-plugins` feature"
+            )
+        }
+
+        pub(super) fn visit(
+            &mut self,
+            _callback: &'static str,
+            _item: &impl Serialize,
+        ) -> Result<()> {
+            match *self {}
+        }
+
+        pub(super) fn finish(self) -> Result<Vec<Finding>> {
+            match self {}
+        }
+    }
+}
@@ -0,0 +1,453 @@
+//! A small HTTP server exposing the code graph to a browser based viewer.
+//!
+//! The viewer renders the graph it fetches from `/api/graph`, its searches, expansions and path
+//! finding are JSON-RPC 2.0 requests posted to `/rpc`, answered from the graph the server keeps:
+//!
+//! - `graph/functions {query}`: the ids of the project functions whose name contains `query`.
+//! - `graph/structs {query}`: the paths of the account structs whose name contains `query`.
+//! - `graph/callees {id}`, `graph/callers {id}`: the ids of the functions the function `id`
+//!   calls, or of those calling it.
+//! - `graph/path {from, to}`: a shortest call path from a function named `from` to one named
+//!   `to`, as `{start, hops}` where the hops are calls, or null.
+//!
+//! With `--watch` the graph is rebuilt whenever the sources change. Every rebuild bumps the
+//! graph version and publishes a [`GraphDelta`], which viewers poll through
+//! `/api/delta?since=<version>` instead of refetching `/api/graph`.
+
+use std::{
+    collections::{VecDeque, hash_map::Entry},
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use lsp_server::{ErrorCode, Request, Response};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::cli::{
+    code_graph::{CodeGraph, GraphCall, LoadOptions, LoadedProject},
+    flags,
+    graph_watch::{GraphDelta, SourceChange, SourceWatcher, StableIds},
+};
+
+const INDEX_HTML: &str = include_str!("graph_serve.html");
+
+/// How often the sources are checked for changes in watch mode.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Number of deltas kept for viewers lagging behind, older ones have to refetch the graph.
+const DELTA_HISTORY: usize = 64;
+
+/// The graph as currently served.
+#[derive(Default)]
+struct Published {
+    version: u64,
+    graph: Arc<CodeGraph>,
+    graph_json: String,
+    /// Serialized deltas of the most recent versions, oldest first.
+    deltas: VecDeque<(u64, String)>,
+}
+
+#[derive(Serialize)]
+struct Snapshot<'a> {
+    version: u64,
+    #[serde(flatten)]
+    graph: &'a CodeGraph,
+}
+
+impl flags::Serve {
+    pub fn run(self) -> Result<()> {
+        let span = tracing::info_span!("graph_serve", path = %self.path.display()).entered();
+        eprintln!("Loading workspace...");
+        let mut project = self.load()?;
+        let mut ids = StableIds::default();
+        let graph = self.build(&project, &mut ids)?;
+        let published = Arc::new(Mutex::new(Published {
+            graph_json: serde_json::to_string(&Snapshot { version: 0, graph: &graph })?,
+            graph: Arc::new(graph),
+            ..Published::default()
+        }));
+        // Serving never finishes, close the span so the load phases get exported.
+        drop(span);
+
+        let port = self.port.unwrap_or(8080);
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        eprintln!("Serving graph at http://127.0.0.1:{port}/");
+        let server = {
+            let published = published.clone();
+            thread::spawn(move || serve(listener, &published))
+        };
+        if !self.watch {
+            server.join().expect("server thread panicked");
+            return Ok(());
+        }
+
+        eprintln!("Watching {} for changes...", project.project_root);
+        let mut watcher = SourceWatcher::new(project.project_root.clone());
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            match watcher.poll() {
+                None => continue,
+                Some(SourceChange::Files(files)) => project.reload_files(&files),
+                Some(SourceChange::Workspace) => match self.load() {
+                    Ok(reloaded) => project = reloaded,
+                    Err(err) => {
+                        eprintln!("Failed to reload workspace: {err}");
+                        continue;
+                    }
+                },
+            }
+            let new_graph = match self.build(&project, &mut ids) {
+                Ok(new_graph) => new_graph,
+                Err(err) => {
+                    eprintln!("Failed to rebuild graph: {err}");
+                    continue;
+                }
+            };
+
+            let mut published = published.lock().unwrap();
+            let delta = GraphDelta::between(&published.graph, &new_graph, published.version + 1);
+            if delta.is_empty() {
+                continue;
+            }
+            // A graph that fails to serialize isn't published, the next rebuild is compared with
+            // the one still served.
+            let json =
+                serde_json::to_string(&Snapshot { version: delta.version, graph: &new_graph })
+                    .and_then(|graph_json| Ok((graph_json, serde_json::to_string(&delta)?)));
+            let (graph_json, delta_json) = match json {
+                Ok(json) => json,
+                Err(err) => {
+                    eprintln!("Failed to serialize graph: {err}");
+                    continue;
+                }
+            };
+            published.version = delta.version;
+            published.graph = Arc::new(new_graph);
+            published.graph_json = graph_json;
+            published.deltas.push_back((delta.version, delta_json));
+            if published.deltas.len() > DELTA_HISTORY {
+                published.deltas.pop_front();
+            }
+            eprintln!("Published graph version {}", delta.version);
+        }
+    }
+
+    fn load(&self) -> Result<LoadedProject> {
+        LoadedProject::load(
+            &self.path,
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )
+    }
+
+    fn build(&self, project: &LoadedProject, ids: &mut StableIds) -> Result<CodeGraph> {
+        let mut graph = CodeGraph::build(project)?;
+        ids.assign(&mut graph);
+        self.redact.unwrap_or_default().graph(&mut graph);
+        Ok(graph)
+    }
+}
+
+fn serve(listener: TcpListener, published: &Mutex<Published>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Failed to accept connection: {err}");
+                continue;
+            }
+        };
+        if let Err(err) = handle_connection(stream, published) {
+            eprintln!("Failed to handle request: {err}");
+        }
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, published: &Mutex<Published>) -> Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Drain the headers, only the JSON-RPC requests of the viewer have a body.
+    let mut content_length = 0;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse()?;
+        }
+        header.clear();
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/" | "/index.html") => {
+            ("200 OK", "text/html; charset=utf-8", INDEX_HTML.to_owned())
+        }
+        ("GET", "/api/graph") => {
+            ("200 OK", "application/json", published.lock().unwrap().graph_json.clone())
+        }
+        ("GET", "/api/delta") => {
+            let since = query
+                .split('&')
+                .find_map(|param| param.strip_prefix("since="))
+                .and_then(|it| it.parse().ok())
+                .unwrap_or(0);
+            match deltas_since(&published.lock().unwrap(), since) {
+                Some(json) => ("200 OK", "application/json", json),
+                // The viewer is too far behind, it refetches the whole graph.
+                None => ("410 Gone", "text/plain; charset=utf-8", "refetch /api/graph".to_owned()),
+            }
+        }
+        ("POST", "/rpc") => match serde_json::from_slice::<Request>(&body) {
+            Ok(request) => {
+                let graph = published.lock().unwrap().graph.clone();
+                let response = JsonRpc { jsonrpc: "2.0", response: rpc(&graph, request) };
+                ("200 OK", "application/json", serde_json::to_string(&response)?)
+            }
+            Err(err) => (
+                "400 Bad Request",
+                "text/plain; charset=utf-8",
+                format!("invalid JSON-RPC request: {err}"),
+            ),
+        },
+        ("GET", _) => ("404 Not Found", "text/plain; charset=utf-8", "not found".to_owned()),
+        _ => {
+            ("405 Method Not Allowed", "text/plain; charset=utf-8", "method not allowed".to_owned())
+        }
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// A JSON array of the deltas leading from version `since` to the published one, `None` if
+/// they're no longer all kept.
+fn deltas_since(published: &Published, since: u64) -> Option<String> {
+    if since > published.version {
+        return None;
+    }
+    let pending: Vec<&str> = published
+        .deltas
+        .iter()
+        .filter(|(version, _)| *version > since)
+        .map(|(_, json)| json.as_str())
+        .collect();
+    if pending.len() as u64 != published.version - since {
+        return None;
+    }
+    Some(format!("[{}]", pending.join(",")))
+}
+
+/// A response as sent over the wire, `lsp_server` leaves out the protocol version.
+#[derive(Serialize)]
+struct JsonRpc {
+    jsonrpc: &'static str,
+    #[serde(flatten)]
+    response: Response,
+}
+
+#[derive(Deserialize)]
+struct Search {
+    query: String,
+}
+
+#[derive(Deserialize)]
+struct Function {
+    id: usize,
+}
+
+#[derive(Deserialize)]
+struct PathRequest {
+    from: String,
+    to: String,
+}
+
+/// The calls leading from the function `start` to the last callee.
+#[derive(Debug, PartialEq, Serialize)]
+struct CallPath<'a> {
+    start: usize,
+    hops: Vec<&'a GraphCall>,
+}
+
+/// Answers a JSON-RPC request of the viewer from `graph`.
+fn rpc(graph: &CodeGraph, request: Request) -> Response {
+    fn respond<P: DeserializeOwned, R: Serialize>(
+        request: Request,
+        answer: impl FnOnce(P) -> R,
+    ) -> Response {
+        match serde_json::from_value(request.params) {
+            Ok(params) => Response::new_ok(request.id, answer(params)),
+            Err(err) => {
+                Response::new_err(request.id, ErrorCode::InvalidParams as i32, err.to_string())
+            }
+        }
+    }
+
+    match request.method.as_str() {
+        "graph/functions" => respond(request, |Search { query }| {
+            let query = query.to_lowercase();
+            graph
+                .functions
+                .iter()
+                .filter(|it| !it.external && it.name.to_lowercase().contains(&query))
+                .map(|it| it.id)
+                .collect::<Vec<_>>()
+        }),
+        "graph/structs" => respond(request, |Search { query }| {
+            let query = query.to_lowercase();
+            graph
+                .account_structs
+                .iter()
+                .filter(|it| it.name.to_lowercase().contains(&query))
+                .map(|it| it.path())
+                .collect::<Vec<_>>()
+        }),
+        "graph/callees" => respond(request, |Function { id }| {
+            neighbors(graph.calls.iter().filter(|it| it.caller == id).map(|it| it.callee))
+        }),
+        "graph/callers" => respond(request, |Function { id }| {
+            neighbors(graph.calls.iter().filter(|it| it.callee == id).map(|it| it.caller))
+        }),
+        "graph/path" => respond(request, |PathRequest { from, to }| call_path(graph, &from, &to)),
+        method => {
+            let message = format!("unknown method `{method}`");
+            Response::new_err(request.id, ErrorCode::MethodNotFound as i32, message)
+        }
+    }
+}
+
+/// The distinct functions of `ids`, in the order they come.
+fn neighbors(ids: impl Iterator<Item = usize>) -> Vec<usize> {
+    let mut seen = FxHashSet::default();
+    ids.filter(|it| seen.insert(*it)).collect()
+}
+
+/// A shortest call path from a function named `from` to one named `to`.
+fn call_path<'a>(graph: &'a CodeGraph, from: &str, to: &str) -> Option<CallPath<'a>> {
+    let named = |name: &str| -> Vec<usize> {
+        graph.functions.iter().filter(|it| it.name == name).map(|it| it.id).collect()
+    };
+    let goals: FxHashSet<usize> = named(to).into_iter().collect();
+    let mut callees: FxHashMap<usize, Vec<&GraphCall>> = FxHashMap::default();
+    for call in &graph.calls {
+        callees.entry(call.caller).or_default().push(call);
+    }
+    // The call each reached function was first reached through, none for the starts.
+    let mut via: FxHashMap<usize, Option<&GraphCall>> = FxHashMap::default();
+    let mut queue = VecDeque::new();
+    for start in named(from) {
+        via.insert(start, None);
+        queue.push_back(start);
+    }
+    while let Some(id) = queue.pop_front() {
+        if goals.contains(&id) {
+            let mut hops = Vec::new();
+            let mut start = id;
+            while let Some(call) = via[&start] {
+                hops.push(call);
+                start = call.caller;
+            }
+            hops.reverse();
+            return Some(CallPath { start, hops });
+        }
+        for &call in callees.get(&id).into_iter().flatten() {
+            if let Entry::Vacant(entry) = via.entry(call.callee) {
+                entry.insert(Some(call));
+                queue.push_back(call.callee);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::cli::code_graph::GraphFunction;
+
+    use super::*;
+
+    /// `main` calls `helper` twice and `log`, `helper` calls `log`.
+    fn graph() -> CodeGraph {
+        let function = |id, name: &str| GraphFunction {
+            id,
+            name: name.to_owned(),
+            file: "src/main.rs".to_owned(),
+            line: id as u32 * 10 + 1,
+            column: 4,
+            module: "app".to_owned(),
+            external: name == "log",
+        };
+        let call = |caller, callee, line| GraphCall { caller, callee, line, column: 5 };
+        CodeGraph {
+            functions: vec![function(3, "main"), function(7, "helper"), function(9, "log")],
+            calls: vec![call(3, 7, 32), call(3, 7, 33), call(3, 9, 34), call(7, 9, 72)],
+            ..CodeGraph::default()
+        }
+    }
+
+    fn request(method: &str, params: serde_json::Value) -> serde_json::Value {
+        let request = Request { id: 1.into(), method: method.to_owned(), params };
+        let response = JsonRpc { jsonrpc: "2.0", response: rpc(&graph(), request) };
+        serde_json::to_value(response).unwrap()
+    }
+
+    #[test]
+    fn answers_requests_from_the_graph() {
+        let cases = [
+            ("graph/functions", json!({ "query": "L" }), json!([7])),
+            ("graph/callees", json!({ "id": 3 }), json!([7, 9])),
+            ("graph/callers", json!({ "id": 9 }), json!([3, 7])),
+            ("graph/path", json!({ "from": "log", "to": "main" }), json!(null)),
+            (
+                "graph/path",
+                json!({ "from": "main", "to": "main" }),
+                json!({ "start": 3, "hops": [] }),
+            ),
+        ];
+        for (method, params, result) in cases {
+            let response = request(method, params.clone());
+            assert_eq!(response["jsonrpc"], "2.0");
+            assert_eq!(response["result"], result, "{method} {params}");
+        }
+    }
+
+    #[test]
+    fn reports_unknown_methods_and_invalid_params() {
+        let response = request("graph/nothing", json!({}));
+        assert_eq!(response["error"]["code"], ErrorCode::MethodNotFound as i32);
+        let response = request("graph/callees", json!({ "name": "main" }));
+        assert_eq!(response["error"]["code"], ErrorCode::InvalidParams as i32);
+    }
+
+    #[test]
+    fn finds_a_shortest_call_path() {
+        let graph = graph();
+        let path = call_path(&graph, "main", "log").unwrap();
+        assert_eq!(path, CallPath { start: 3, hops: vec![&graph.calls[2]] });
+        let path = call_path(&graph, "helper", "log").unwrap();
+        assert_eq!(path, CallPath { start: 7, hops: vec![&graph.calls[3]] });
+    }
+}
@@ -0,0 +1,327 @@
+//! Interactive terminal browser for the call graph.
+//!
+//! Keys: `Tab` cycles focus between the function list, callers and callees, `Enter` jumps to the
+//! highlighted caller/callee, `Backspace` goes back, `b` toggles a bookmark, `B` shows only
+//! bookmarks, `/` starts a search and `q` quits. Bookmarks are printed when the browser exits.
+
+use std::{fs, path::Path};
+
+use anyhow::Result;
+use itertools::Itertools;
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+use rustc_hash::FxHashSet;
+
+use crate::cli::code_graph::{CodeGraph, GraphFunction};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Pane {
+    Functions,
+    Callers,
+    Callees,
+}
+
+struct Browser<'a> {
+    graph: &'a CodeGraph,
+    project_root: &'a Path,
+    /// Indices into `graph.functions` currently shown in the function list.
+    visible: Vec<usize>,
+    functions: ListState,
+    callers: ListState,
+    callees: ListState,
+    focus: Pane,
+    history: Vec<usize>,
+    bookmarks: FxHashSet<usize>,
+    only_bookmarks: bool,
+    search: String,
+    searching: bool,
+}
+
+pub(super) fn run(graph: &CodeGraph, project_root: &Path) -> Result<()> {
+    let mut browser = Browser {
+        graph,
+        project_root,
+        visible: Vec::new(),
+        functions: ListState::default(),
+        callers: ListState::default(),
+        callees: ListState::default(),
+        focus: Pane::Functions,
+        history: Vec::new(),
+        bookmarks: FxHashSet::default(),
+        only_bookmarks: false,
+        search: String::new(),
+        searching: false,
+    };
+    browser.refilter();
+
+    let mut terminal = ratatui::init();
+    let res = browser.event_loop(&mut terminal);
+    ratatui::restore();
+    res?;
+
+    let mut bookmarks: Vec<_> = browser.bookmarks.iter().map(|&id| &graph.functions[id]).collect();
+    bookmarks.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    for function in bookmarks {
+        println!("{}:{}:{}", function.file, function.line, function.name);
+    }
+    Ok(())
+}
+
+impl Browser<'_> {
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else { continue };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if self.searching {
+                match key.code {
+                    KeyCode::Enter | KeyCode::Esc => self.searching = false,
+                    KeyCode::Backspace => {
+                        self.search.pop();
+                        self.refilter();
+                    }
+                    KeyCode::Char(c) => {
+                        self.search.push(c);
+                        self.refilter();
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('/') => {
+                    self.searching = true;
+                    self.focus = Pane::Functions;
+                }
+                KeyCode::Tab => {
+                    self.focus = match self.focus {
+                        Pane::Functions => Pane::Callers,
+                        Pane::Callers => Pane::Callees,
+                        Pane::Callees => Pane::Functions,
+                    }
+                }
+                KeyCode::Down | KeyCode::Char('j') => self.focused_list().select_next(),
+                KeyCode::Up | KeyCode::Char('k') => self.focused_list().select_previous(),
+                KeyCode::Enter => {
+                    let target = match self.focus {
+                        Pane::Functions => None,
+                        Pane::Callers => {
+                            self.callers.selected().and_then(|i| self.callers_of().get(i).copied())
+                        }
+                        Pane::Callees => {
+                            self.callees.selected().and_then(|i| self.callees_of().get(i).copied())
+                        }
+                    };
+                    if let (Some(target), Some(current)) = (target, self.selected()) {
+                        self.history.push(current);
+                        self.jump_to(target);
+                    }
+                }
+                KeyCode::Backspace => {
+                    if let Some(previous) = self.history.pop() {
+                        self.jump_to(previous);
+                    }
+                }
+                KeyCode::Char('b') => {
+                    if let Some(id) = self.selected()
+                        && !self.bookmarks.remove(&id)
+                    {
+                        self.bookmarks.insert(id);
+                    }
+                }
+                KeyCode::Char('B') => {
+                    self.only_bookmarks = !self.only_bookmarks;
+                    self.refilter();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn focused_list(&mut self) -> &mut ListState {
+        match self.focus {
+            Pane::Functions => &mut self.functions,
+            Pane::Callers => &mut self.callers,
+            Pane::Callees => &mut self.callees,
+        }
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.functions.selected().and_then(|i| self.visible.get(i).copied())
+    }
+
+    fn refilter(&mut self) {
+        let search = self.search.to_lowercase();
+        self.visible = self
+            .graph
+            .functions
+            .iter()
+            .filter(|f| !f.external || self.bookmarks.contains(&f.id))
+            .filter(|f| !self.only_bookmarks || self.bookmarks.contains(&f.id))
+            .filter(|f| f.name.to_lowercase().contains(&search))
+            .map(|f| f.id)
+            .collect();
+        self.functions.select(if self.visible.is_empty() { None } else { Some(0) });
+        self.reset_neighbours();
+    }
+
+    /// Selects `id` in the function list, clearing any filter that would hide it.
+    fn jump_to(&mut self, id: usize) {
+        if !self.visible.contains(&id) {
+            self.search.clear();
+            self.only_bookmarks = false;
+            self.refilter();
+        }
+        if !self.visible.contains(&id) {
+            // External functions are hidden from the list, show them anyway.
+            self.visible.push(id);
+        }
+        self.functions.select(self.visible.iter().position(|&it| it == id));
+        self.focus = Pane::Functions;
+        self.reset_neighbours();
+    }
+
+    fn reset_neighbours(&mut self) {
+        self.callers.select(None);
+        self.callees.select(None);
+    }
+
+    fn callers_of(&self) -> Vec<usize> {
+        let Some(id) = self.selected() else { return Vec::new() };
+        self.graph.calls.iter().filter(|c| c.callee == id).map(|c| c.caller).unique().collect()
+    }
+
+    fn callees_of(&self) -> Vec<usize> {
+        let Some(id) = self.selected() else { return Vec::new() };
+        self.graph.calls.iter().filter(|c| c.caller == id).map(|c| c.callee).unique().collect()
+    }
+
+    fn draw(&mut self, frame: &mut Frame<'_>) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [list, neighbours, preview] = Layout::horizontal([
+            Constraint::Percentage(30),
+            Constraint::Percentage(30),
+            Constraint::Percentage(40),
+        ])
+        .areas(main);
+        let [callers, callees] =
+            Layout::vertical([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(neighbours);
+
+        let items: Vec<_> = self.visible.iter().map(|&id| self.item(id)).collect();
+        let title = if self.only_bookmarks { "Bookmarks" } else { "Functions" };
+        frame.render_stateful_widget(
+            self.list(items, title, Pane::Functions),
+            list,
+            &mut self.functions,
+        );
+
+        let items: Vec<_> = self.callers_of().into_iter().map(|id| self.item(id)).collect();
+        frame.render_stateful_widget(
+            self.list(items, "Callers", Pane::Callers),
+            callers,
+            &mut self.callers,
+        );
+        let items: Vec<_> = self.callees_of().into_iter().map(|id| self.item(id)).collect();
+        frame.render_stateful_widget(
+            self.list(items, "Callees", Pane::Callees),
+            callees,
+            &mut self.callees,
+        );
+
+        self.draw_preview(frame, preview);
+
+        let status_line = if self.searching {
+            format!("/{}", self.search)
+        } else {
+            "Tab focus  Enter jump  Backspace back  b bookmark  B bookmarks  / search  q quit"
+                .to_owned()
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+
+    fn item(&self, id: usize) -> ListItem<'static> {
+        let function = &self.graph.functions[id];
+        let marker = if self.bookmarks.contains(&id) { "* " } else { "  " };
+        ListItem::new(Line::from(vec![
+            Span::raw(format!("{marker}{}", function.name)),
+            Span::styled(
+                format!("  {}:{}", function.file, function.line),
+                Style::default().add_modifier(Modifier::DIM),
+            ),
+        ]))
+    }
+
+    fn list(
+        &self,
+        items: Vec<ListItem<'static>>,
+        title: &'static str,
+        pane: Pane,
+    ) -> List<'static> {
+        let mut block = Block::default().borders(Borders::ALL).title(title);
+        if self.focus == pane {
+            block = block.border_style(Style::default().add_modifier(Modifier::BOLD));
+        }
+        List::new(items)
+            .block(block)
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED))
+    }
+
+    /// Shows the source around the focused function, or around the call site of the
+    /// highlighted caller/callee.
+    fn draw_preview(&self, frame: &mut Frame<'_>, area: Rect) {
+        let Some(id) = self.selected() else {
+            frame.render_widget(Block::default().borders(Borders::ALL).title("Source"), area);
+            return;
+        };
+        let call_site = match self.focus {
+            Pane::Functions => None,
+            Pane::Callers => self.callers.selected().and_then(|i| {
+                let caller = *self.callers_of().get(i)?;
+                self.graph.calls.iter().find(|c| c.caller == caller && c.callee == id)
+            }),
+            Pane::Callees => self.callees.selected().and_then(|i| {
+                let callee = *self.callees_of().get(i)?;
+                self.graph.calls.iter().find(|c| c.caller == id && c.callee == callee)
+            }),
+        };
+        let (function, line): (&GraphFunction, u32) = match call_site {
+            Some(call) => (&self.graph.functions[call.caller], call.line),
+            None => (&self.graph.functions[id], self.graph.functions[id].line),
+        };
+
+        let path = self.project_root.join(&function.file);
+        let text = fs::read_to_string(&path).unwrap_or_else(|err| format!("{err}"));
+        let height = area.height.saturating_sub(2) as usize;
+        let first = (line as usize).saturating_sub(height / 3).max(1);
+        let lines: Vec<_> = text
+            .lines()
+            .enumerate()
+            .skip(first - 1)
+            .take(height)
+            .map(|(idx, content)| {
+                let style = if idx + 1 == line as usize {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                Line::styled(format!("{:>5} {content}", idx + 1), style)
+            })
+            .collect();
+        let title = format!("{}:{}", function.file, line);
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(title)),
+            area,
+        );
+    }
+}
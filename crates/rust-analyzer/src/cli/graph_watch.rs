@@ -0,0 +1,359 @@
+//! Watch mode of `graph serve`: keeps the graph in sync with the sources on disk and describes
+//! every rebuild as a delta against the previous graph, so viewers don't refetch the whole
+//! document on each change.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use vfs::AbsPathBuf;
+use walkdir::WalkDir;
+
+use crate::cli::{
+    authorities::{Authority, AuthorityKind},
+    code_graph::{AccountStruct, CodeGraph, GraphCall, GraphEnum, GraphFunction, Instruction},
+    cpi_calls::CpiCall,
+    error_codes::ErrorCodeEnum,
+    events::GraphEvent,
+    findings::Finding,
+    governance::PrivilegedInstruction,
+    lamport_flows::InstructionLamportFlows,
+    native_programs::NativeProgram,
+    pdas::Pda,
+    programs::AnchorProgram,
+    state_access::StateAccess,
+    state_accounts::StateAccount,
+    sysvars::InstructionSysvars,
+    token_operations::InstructionTokenOperations,
+    zero_copy::ZeroCopyType,
+};
+
+/// What changed on disk since the previous poll.
+pub(super) enum SourceChange {
+    /// Only the contents of existing Rust files, which can be reloaded incrementally.
+    Files(Vec<AbsPathBuf>),
+    /// Files were added or removed, or a manifest changed: the workspace has to be reloaded.
+    Workspace,
+}
+
+/// Polls the modification times of the project's Rust files and manifests.
+pub(super) struct SourceWatcher {
+    root: AbsPathBuf,
+    mtimes: FxHashMap<PathBuf, SystemTime>,
+}
+
+impl SourceWatcher {
+    pub(super) fn new(root: AbsPathBuf) -> SourceWatcher {
+        let mtimes = scan(root.as_ref());
+        SourceWatcher { root, mtimes }
+    }
+
+    pub(super) fn poll(&mut self) -> Option<SourceChange> {
+        let mtimes = scan(self.root.as_ref());
+        let old = std::mem::replace(&mut self.mtimes, mtimes);
+        if old.len() != self.mtimes.len() || self.mtimes.keys().any(|path| !old.contains_key(path))
+        {
+            return Some(SourceChange::Workspace);
+        }
+        let modified: Vec<&PathBuf> = self
+            .mtimes
+            .iter()
+            .filter(|&(path, mtime)| old[path] != *mtime)
+            .map(|it| it.0)
+            .collect();
+        if modified.is_empty() {
+            None
+        } else if modified.iter().any(|path| path.extension().is_some_and(|ext| ext == "toml")) {
+            Some(SourceChange::Workspace)
+        } else {
+            Some(SourceChange::Files(
+                modified.into_iter().map(|path| AbsPathBuf::assert_utf8(path.clone())).collect(),
+            ))
+        }
+    }
+}
+
+/// The Rust files and `Cargo.toml` manifests below `root`, skipping build output and hidden
+/// directories.
+fn scan(root: &Path) -> FxHashMap<PathBuf, SystemTime> {
+    WalkDir::new(root)
+        .into_iter()
+        .filter_entry(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            entry.depth() == 0 || !(name.starts_with('.') || name == "target")
+        })
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_type().is_file()
+                && (entry.path().extension().is_some_and(|ext| ext == "rs")
+                    || entry.file_name() == "Cargo.toml")
+        })
+        .filter_map(|entry| {
+            let mtime = fs::metadata(entry.path()).and_then(|it| it.modified()).ok()?;
+            Some((entry.into_path(), mtime))
+        })
+        .collect()
+}
+
+/// Gives functions ids that survive rebuilds of the graph, so deltas can refer to them.
+///
+/// A function is identified by its file, module and name, plus its position among the functions
+/// sharing those, which keeps ids stable when code above it moves.
+#[derive(Default)]
+pub(super) struct StableIds {
+    ids: FxHashMap<(String, String, String, usize), usize>,
+    next: usize,
+}
+
+impl StableIds {
+    /// Replaces the ids of a freshly built graph, which are indices into its functions.
+    pub(super) fn assign(&mut self, graph: &mut CodeGraph) {
+        let mut occurrences: FxHashMap<(&str, &str, &str), usize> = FxHashMap::default();
+        let keys: Vec<_> = graph
+            .functions
+            .iter()
+            .map(|function| {
+                let n = occurrences
+                    .entry((&function.file, &function.module, &function.name))
+                    .or_default();
+                *n += 1;
+                (function.file.clone(), function.module.clone(), function.name.clone(), *n - 1)
+            })
+            .collect();
+        let ids: Vec<usize> = keys
+            .iter()
+            .map(|key| {
+                *self.ids.entry(key.clone()).or_insert_with(|| {
+                    self.next += 1;
+                    self.next - 1
+                })
+            })
+            .collect();
+        // Forget removed functions, their ids are never handed out again.
+        let live: FxHashSet<_> = keys.into_iter().collect();
+        self.ids.retain(|key, _| live.contains(key));
+
+        for function in &mut graph.functions {
+            function.id = ids[function.id];
+        }
+        for call in &mut graph.calls {
+            call.caller = ids[call.caller];
+            call.callee = ids[call.callee];
+        }
+    }
+}
+
+/// The difference between two consecutive versions of the graph.
+#[derive(Debug, Serialize)]
+pub(super) struct GraphDelta {
+    /// The version the delta leads to, it applies on top of `version - 1`.
+    pub(super) version: u64,
+    pub(super) functions: Changes<GraphFunction, usize>,
+    /// Calls have no identity of their own: a moved call is removed and added again.
+    pub(super) calls: Changes<GraphCall, GraphCall>,
+    pub(super) account_structs: Changes<AccountStruct, StructKey>,
+    /// Instructions are identified like structs, by their module and name.
+    pub(super) instructions: Changes<Instruction, StructKey>,
+    pub(super) enums: Changes<GraphEnum, StructKey>,
+    pub(super) error_codes: Changes<ErrorCodeEnum, StructKey>,
+    pub(super) events: Changes<GraphEvent, StructKey>,
+    pub(super) state_accounts: Changes<StateAccount, StructKey>,
+    pub(super) zero_copy_types: Changes<ZeroCopyType, StructKey>,
+    /// CPI calls are identified by their whole contents, like calls.
+    pub(super) cpi_calls: Changes<CpiCall, CpiCall>,
+    /// Token operations are identified by the path of their instruction.
+    pub(super) token_operations: Changes<InstructionTokenOperations, String>,
+    /// Sysvar uses and lamport flows are identified by the path of their instruction, like token
+    /// operations.
+    pub(super) sysvars: Changes<InstructionSysvars, String>,
+    pub(super) lamport_flows: Changes<InstructionLamportFlows, String>,
+    /// Programs are identified by their crate name.
+    pub(super) programs: Changes<AnchorProgram, String>,
+    pub(super) native_programs: Changes<NativeProgram, String>,
+    pub(super) pdas: Changes<Pda, PdaKey>,
+    pub(super) authorities: Changes<Authority, AuthorityKey>,
+    /// The access of state accounts is identified by the path of the account.
+    pub(super) state_access: Changes<StateAccess, String>,
+    /// Privileged instructions are identified by the path of the instruction.
+    pub(super) governance: Changes<PrivilegedInstruction, String>,
+    /// Findings are identified by their whole contents, like calls.
+    pub(super) findings: Changes<Finding, Finding>,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct Changes<T, K> {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(super) added: Vec<T>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(super) removed: Vec<K>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(super) changed: Vec<T>,
+}
+
+impl<T, K> Changes<T, K> {
+    fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub(super) struct StructKey {
+    pub(super) module: String,
+    pub(super) name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub(super) struct AuthorityKey {
+    pub(super) kind: AuthorityKind,
+    pub(super) name: String,
+}
+
+/// PDAs are identified by the struct field their seeds constrain.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub(super) struct PdaKey {
+    pub(super) account_struct: String,
+    pub(super) field: String,
+}
+
+impl GraphDelta {
+    /// Compares two graphs whose function ids were assigned by the same [`StableIds`].
+    pub(super) fn between(old: &CodeGraph, new: &CodeGraph, version: u64) -> GraphDelta {
+        let functions = diff(&old.functions, &new.functions, |it| it.id);
+        let old_calls: FxHashSet<&GraphCall> = old.calls.iter().collect();
+        let new_calls: FxHashSet<&GraphCall> = new.calls.iter().collect();
+        let calls = Changes {
+            added: new.calls.iter().filter(|it| !old_calls.contains(it)).cloned().collect(),
+            removed: old.calls.iter().filter(|it| !new_calls.contains(it)).cloned().collect(),
+            changed: Vec::new(),
+        };
+        let account_structs = diff(&old.account_structs, &new.account_structs, |it| StructKey {
+            module: it.module.clone(),
+            name: it.name.clone(),
+        });
+        let instructions = diff(&old.instructions, &new.instructions, |it| StructKey {
+            module: it.module.clone(),
+            name: it.name.clone(),
+        });
+        let enums = diff(&old.enums, &new.enums, |it| StructKey {
+            module: it.module.clone(),
+            name: it.name.clone(),
+        });
+        let error_codes = diff(&old.error_codes, &new.error_codes, |it| StructKey {
+            module: it.module.clone(),
+            name: it.name.clone(),
+        });
+        let events = diff(&old.events, &new.events, |it| StructKey {
+            module: it.module.clone(),
+            name: it.name.clone(),
+        });
+        let state_accounts = diff(&old.state_accounts, &new.state_accounts, |it| StructKey {
+            module: it.module.clone(),
+            name: it.name.clone(),
+        });
+        let zero_copy_types = diff(&old.zero_copy_types, &new.zero_copy_types, |it| StructKey {
+            module: it.module.clone(),
+            name: it.name.clone(),
+        });
+        let old_cpis: FxHashSet<&CpiCall> = old.cpi_calls.iter().collect();
+        let new_cpis: FxHashSet<&CpiCall> = new.cpi_calls.iter().collect();
+        let cpi_calls = Changes {
+            added: new.cpi_calls.iter().filter(|it| !old_cpis.contains(it)).cloned().collect(),
+            removed: old.cpi_calls.iter().filter(|it| !new_cpis.contains(it)).cloned().collect(),
+            changed: Vec::new(),
+        };
+        let token_operations =
+            diff(&old.token_operations, &new.token_operations, |it| it.instruction.clone());
+        let sysvars = diff(&old.sysvars, &new.sysvars, |it| it.instruction.clone());
+        let lamport_flows =
+            diff(&old.lamport_flows, &new.lamport_flows, |it| it.instruction.clone());
+        let programs = diff(&old.programs, &new.programs, |it| it.name.clone());
+        let native_programs =
+            diff(&old.native_programs, &new.native_programs, |it| it.name.clone());
+        let pdas = diff(&old.pdas, &new.pdas, |it| PdaKey {
+            account_struct: it.account_struct.clone(),
+            field: it.field.clone(),
+        });
+        let authorities = diff(&old.authorities, &new.authorities, |it| AuthorityKey {
+            kind: it.kind,
+            name: it.name.clone(),
+        });
+        let state_access = diff(&old.state_access, &new.state_access, |it| it.account.clone());
+        let governance = diff(&old.governance, &new.governance, |it| it.instruction.clone());
+        let old_findings: FxHashSet<&Finding> = old.findings.iter().collect();
+        let new_findings: FxHashSet<&Finding> = new.findings.iter().collect();
+        let findings = Changes {
+            added: new.findings.iter().filter(|it| !old_findings.contains(it)).cloned().collect(),
+            removed: old.findings.iter().filter(|it| !new_findings.contains(it)).cloned().collect(),
+            changed: Vec::new(),
+        };
+        GraphDelta {
+            version,
+            functions,
+            calls,
+            account_structs,
+            instructions,
+            enums,
+            error_codes,
+            events,
+            state_accounts,
+            zero_copy_types,
+            cpi_calls,
+            token_operations,
+            sysvars,
+            lamport_flows,
+            programs,
+            native_programs,
+            pdas,
+            authorities,
+            state_access,
+            governance,
+            findings,
+        }
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+            && self.calls.is_empty()
+            && self.account_structs.is_empty()
+            && self.instructions.is_empty()
+            && self.enums.is_empty()
+            && self.error_codes.is_empty()
+            && self.events.is_empty()
+            && self.state_accounts.is_empty()
+            && self.zero_copy_types.is_empty()
+            && self.cpi_calls.is_empty()
+            && self.token_operations.is_empty()
+            && self.sysvars.is_empty()
+            && self.lamport_flows.is_empty()
+            && self.programs.is_empty()
+            && self.native_programs.is_empty()
+            && self.pdas.is_empty()
+            && self.authorities.is_empty()
+            && self.state_access.is_empty()
+            && self.governance.is_empty()
+            && self.findings.is_empty()
+    }
+}
+
+fn diff<T, K>(old: &[T], new: &[T], key: impl Fn(&T) -> K) -> Changes<T, K>
+where
+    T: Clone + PartialEq,
+    K: Eq + std::hash::Hash,
+{
+    let old_by_key: FxHashMap<K, &T> = old.iter().map(|it| (key(it), it)).collect();
+    let new_keys: FxHashSet<K> = new.iter().map(&key).collect();
+    let mut changes = Changes { added: Vec::new(), removed: Vec::new(), changed: Vec::new() };
+    for item in new {
+        match old_by_key.get(&key(item)) {
+            None => changes.added.push(item.clone()),
+            Some(&old) if old != item => changes.changed.push(item.clone()),
+            Some(_) => {}
+        }
+    }
+    changes.removed = old.iter().map(&key).filter(|it| !new_keys.contains(it)).collect();
+    changes
+}
@@ -0,0 +1,287 @@
+//! Exports the code graph as an Obsidian compatible markdown wiki.
+//!
+//! Every module, function and struct gets its own page, call edges and constraint references
+//! become `[[wikilinks]]` so the result can be browsed as a knowledge base.
+
+use std::{fmt::Write as _, fs};
+
+use anyhow::Result;
+use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::cli::{
+    code_graph::{CodeGraph, GraphFunction, LoadOptions, LoadedProject},
+    flags,
+};
+
+impl flags::Wiki {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("graph_wiki", path = %self.path.display()).entered();
+        eprintln!("Loading workspace...");
+        let project = LoadedProject::load(
+            &self.path,
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+        let mut graph = CodeGraph::build(&project)?;
+        self.redact.unwrap_or_default().graph(&mut graph);
+
+        eprintln!("Writing wiki...");
+        let _p = tracing::info_span!("write_wiki").entered();
+        let pages = Wiki::new(&graph).pages();
+        for dir in ["modules", "functions", "structs"] {
+            fs::create_dir_all(self.output.join(dir))?;
+        }
+        for (path, content) in &pages {
+            fs::write(self.output.join(path), content)?;
+        }
+        eprintln!("Wrote {} pages to {}", pages.len(), self.output.display());
+        Ok(())
+    }
+}
+
+struct Wiki<'a> {
+    graph: &'a CodeGraph,
+    /// Page name of every project function, indexed by function id.
+    function_pages: FxHashMap<usize, String>,
+    /// Names of the account structs and the state types they reference.
+    struct_names: FxHashSet<String>,
+}
+
+impl<'a> Wiki<'a> {
+    fn new(graph: &'a CodeGraph) -> Wiki<'a> {
+        let project_functions = || graph.functions.iter().filter(|f| !f.external);
+        let counts = project_functions().map(qualified_name).counts();
+        let function_pages = project_functions()
+            .map(|f| {
+                let name = qualified_name(f);
+                let page = if counts[&name] > 1 {
+                    format!("fn-{}-L{}", sanitize(&name), f.line)
+                } else {
+                    format!("fn-{}", sanitize(&name))
+                };
+                (f.id, page)
+            })
+            .collect();
+        let struct_names = graph
+            .account_structs
+            .iter()
+            .flat_map(|s| {
+                std::iter::once(s.name.clone())
+                    .chain(s.fields.iter().filter_map(|f| f.account_type.clone()))
+            })
+            .collect();
+        Wiki { graph, function_pages, struct_names }
+    }
+
+    /// Returns `(relative path, content)` for every page of the wiki.
+    fn pages(&self) -> Vec<(String, String)> {
+        let mut pages = vec![("index.md".to_owned(), self.index_page())];
+        for module in self.modules() {
+            pages.push((format!("modules/{}.md", module_page(&module)), self.module_page(&module)));
+        }
+        for (&id, page) in &self.function_pages {
+            pages.push((format!("functions/{page}.md"), self.function_page(id)));
+        }
+        for name in self.struct_names.iter().sorted() {
+            pages.push((format!("structs/{}.md", struct_page(name)), self.struct_page(name)));
+        }
+        pages.sort();
+        pages
+    }
+
+    /// Every module containing a function or struct, plus all of their ancestors.
+    fn modules(&self) -> Vec<String> {
+        self.graph
+            .functions
+            .iter()
+            .filter(|f| !f.external)
+            .map(|f| f.module.as_str())
+            .chain(self.graph.account_structs.iter().map(|s| s.module.as_str()))
+            .filter(|m| !m.is_empty())
+            .flat_map(|m| {
+                m.match_indices("::").map(|(idx, _)| m[..idx].to_owned()).chain([m.to_owned()])
+            })
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
+    fn index_page(&self) -> String {
+        let mut out = String::from("# Index\n\n## Modules\n\n");
+        for module in self.modules() {
+            let _ = writeln!(out, "- [[{}|{module}]]", module_page(&module));
+        }
+        out.push_str("\n## Structs\n\n");
+        for name in self.struct_names.iter().sorted() {
+            let _ = writeln!(out, "- {}", self.struct_link(name));
+        }
+        out
+    }
+
+    fn module_page(&self, module: &str) -> String {
+        let mut out = format!("# Module `{module}`\n\n");
+        if let Some((parent, _)) = module.rsplit_once("::") {
+            let _ = writeln!(out, "Parent: [[{}|{parent}]]\n", module_page(parent));
+        }
+        let children = self
+            .modules()
+            .into_iter()
+            .filter(|m| m.rsplit_once("::").is_some_and(|(parent, _)| parent == module))
+            .collect_vec();
+        if !children.is_empty() {
+            out.push_str("## Submodules\n\n");
+            for child in children {
+                let _ = writeln!(out, "- [[{}|{child}]]", module_page(&child));
+            }
+            out.push('\n');
+        }
+        out.push_str("## Functions\n\n");
+        for f in self.graph.functions.iter().filter(|f| !f.external && f.module == module) {
+            let _ = writeln!(out, "- {} ({}:{})", self.function_link(f.id), f.file, f.line);
+        }
+        let structs =
+            self.graph.account_structs.iter().filter(|s| s.module == module).collect_vec();
+        if !structs.is_empty() {
+            out.push_str("\n## Account structs\n\n");
+            for s in structs {
+                let _ = writeln!(out, "- {} ({}:{})", self.struct_link(&s.name), s.file, s.line);
+            }
+        }
+        out
+    }
+
+    fn function_page(&self, id: usize) -> String {
+        let f = &self.graph.functions[id];
+        let mut out = format!("# `{}`\n\n", qualified_name(f));
+        let _ = writeln!(out, "- Location: `{}:{}:{}`", f.file, f.line, f.column);
+        if !f.module.is_empty() {
+            let _ = writeln!(out, "- Module: [[{}|{}]]", module_page(&f.module), f.module);
+        }
+
+        out.push_str("\n## Calls\n\n");
+        for call in self.graph.calls.iter().filter(|c| c.caller == id) {
+            let _ = writeln!(out, "- {} at line {}", self.function_link(call.callee), call.line);
+        }
+        out.push_str("\n## Called by\n\n");
+        for call in self.graph.calls.iter().filter(|c| c.callee == id) {
+            let caller = &self.graph.functions[call.caller];
+            let _ = writeln!(
+                out,
+                "- {} at `{}:{}`",
+                self.function_link(call.caller),
+                caller.file,
+                call.line
+            );
+        }
+        out
+    }
+
+    fn struct_page(&self, name: &str) -> String {
+        let mut out = format!("# `{name}`\n\n");
+        if let Some(s) = self.graph.account_structs.iter().find(|s| s.name == name) {
+            let _ = writeln!(out, "- Location: `{}:{}`", s.file, s.line);
+            if !s.module.is_empty() {
+                let _ = writeln!(out, "- Module: [[{}|{}]]", module_page(&s.module), s.module);
+            }
+            out.push_str("\n## Accounts\n\n| Field | Type | Constraints |\n| --- | --- | --- |\n");
+            for field in &s.fields {
+                let ty = match &field.account_type {
+                    Some(account_type) => table_cell(&self.struct_link(account_type)),
+                    None => table_cell(&format!("`{}`", field.ty)),
+                };
+                let constraints = field
+                    .constraints
+                    .iter()
+                    .map(|c| table_cell(&self.link_constraint(&c.to_string())))
+                    .join("<br>");
+                let _ = writeln!(out, "| `{}` | {ty} | {constraints} |", field.name);
+            }
+        }
+
+        let users = self
+            .graph
+            .account_structs
+            .iter()
+            .filter(|s| s.fields.iter().any(|f| f.account_type.as_deref() == Some(name)))
+            .collect_vec();
+        if !users.is_empty() {
+            out.push_str("\n## Used by\n\n");
+            for s in users {
+                let fields = s
+                    .fields
+                    .iter()
+                    .filter(|f| f.account_type.as_deref() == Some(name))
+                    .map(|f| format!("`{}`", f.name))
+                    .join(", ");
+                let _ = writeln!(out, "- {} via {fields}", self.struct_link(&s.name));
+            }
+        }
+        out
+    }
+
+    fn function_link(&self, id: usize) -> String {
+        let f = &self.graph.functions[id];
+        match self.function_pages.get(&id) {
+            Some(page) => format!("[[{page}|{}]]", qualified_name(f)),
+            None => format!("`{}` (external, {}:{})", f.name, f.file, f.line),
+        }
+    }
+
+    fn struct_link(&self, name: &str) -> String {
+        format!("[[{}|{name}]]", struct_page(name))
+    }
+
+    /// Replaces every identifier of a constraint that names a known struct with a link to it.
+    fn link_constraint(&self, constraint: &str) -> String {
+        let mut out = String::from("`");
+        let mut ident = String::new();
+        let flush = |out: &mut String, ident: &mut String| {
+            if self.struct_names.contains(ident.as_str()) {
+                let _ = write!(out, "` {} `", self.struct_link(ident));
+            } else {
+                out.push_str(ident);
+            }
+            ident.clear();
+        };
+        for c in constraint.chars() {
+            if c.is_alphanumeric() || c == '_' {
+                ident.push(c);
+            } else {
+                flush(&mut out, &mut ident);
+                out.push(c);
+            }
+        }
+        flush(&mut out, &mut ident);
+        out.push('`');
+        out.replace("``", "")
+    }
+}
+
+/// Escapes pipes, which would otherwise end the cell (and split wikilink aliases) in a table.
+fn table_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn qualified_name(f: &GraphFunction) -> String {
+    if f.module.is_empty() { f.name.clone() } else { format!("{}::{}", f.module, f.name) }
+}
+
+fn module_page(module: &str) -> String {
+    format!("mod-{}", sanitize(module))
+}
+
+fn struct_page(name: &str) -> String {
+    format!("struct-{}", sanitize(name))
+}
+
+/// Turns a `::` separated path into something usable as a file name on every platform.
+fn sanitize(path: &str) -> String {
+    path.replace("::", ".")
+        .chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, '.' | '_' | '-') { c } else { '_' })
+        .collect()
+}
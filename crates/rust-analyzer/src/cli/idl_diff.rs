@@ -0,0 +1,715 @@
+//! `graph idl-diff`: compares the IDL `anchor build` generated for a program with the program
+//! derived from the sources.
+//!
+//! Clients are built from the IDL, so an IDL that drifted from the code breaks them silently.
+//! Both the IDL layout of Anchor 0.30 and later and the legacy one, with camelCase names and the
+//! fields of accounts inline, are read. Names are compared in snake_case, and types the way the
+//! IDL spells them: by their last path segment, without `Box`.
+//!
+//! - `missing-instruction`, `removed-instruction`: an instruction only in the sources, or only
+//!   in the IDL.
+//! - `renamed-instruction`: an instruction of the IDL matching an instruction of the sources of
+//!   another name by its arguments and accounts.
+//! - `instruction-args`, `instruction-accounts`: the arguments or accounts of an instruction
+//!   differ, in name, type, order, mutability or signing.
+//! - `missing-type`, `removed-type`: a state account or event only in the sources, or only in the
+//!   IDL.
+//! - `type-fields`: the fields of a state account or event, or the variants of an enum, differ.
+//! - `error-codes`: an error only on one side, or with another code.
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use rustc_hash::FxHashMap;
+use serde_json::Value;
+use stdx::to_lower_snake_case;
+use syntax::{
+    AstNode, Edition, SourceFile,
+    ast::{self, HasGenericArgs},
+};
+
+use crate::cli::{
+    code_graph::{AccountStruct, CodeGraph, LoadOptions, LoadedProject, crate_of},
+    findings::{Finding, Severity},
+    flags,
+    function_analyzer::convert_to_relative_path,
+};
+
+impl flags::IdlDiff {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("idl_diff", path = %self.path.display()).entered();
+        eprintln!("Loading workspace...");
+        let project = LoadedProject::load(
+            &self.path,
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+        let idl_files = match self.idl.is_empty() {
+            true => generated_idls(Path::new(project.project_root.as_str()))?,
+            false => self.idl.clone(),
+        };
+        let graph = CodeGraph::build(&project)?;
+
+        let mut findings = Vec::new();
+        for idl_file in &idl_files {
+            eprintln!("Comparing {}...", idl_file.display());
+            let text = fs::read_to_string(idl_file)
+                .with_context(|| format!("failed to read {}", idl_file.display()))?;
+            let value: Value = serde_json::from_str(&text)
+                .with_context(|| format!("{} is not an IDL", idl_file.display()))?;
+            let absolute = fs::canonicalize(idl_file).unwrap_or_else(|_| idl_file.clone());
+            let file = convert_to_relative_path(&absolute.to_string_lossy(), &project.project_root);
+            let idl = Idl::parse(&value);
+            let mut diff = Diff { idl_file: file, findings: Vec::new() };
+            diff.compare(&idl, &Program::of(&graph, idl.name.as_deref()));
+            findings.extend(diff.findings);
+        }
+        eprintln!("IDL diff reported {} findings", findings.len());
+
+        let json = serde_json::to_string_pretty(&findings)?;
+        match &self.output {
+            Some(path) => fs::write(path, json)?,
+            None => println!("{json}"),
+        }
+        Ok(())
+    }
+}
+
+/// The `target/idl/*.json` files of the Anchor workspace at `root`.
+fn generated_idls(root: &Path) -> Result<Vec<std::path::PathBuf>> {
+    let dir = root.join("target").join("idl");
+    let mut files: Vec<_> = fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.extension().is_some_and(|it| it == "json"))
+        .collect();
+    if files.is_empty() {
+        anyhow::bail!("no IDL in {}, run `anchor build` or pass `--idl`", dir.display());
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// An IDL, with its names in snake_case and its types rendered like Rust types.
+#[derive(Default)]
+struct Idl {
+    /// The name of the program, its crate.
+    name: Option<String>,
+    instructions: Vec<IdlInstruction>,
+    /// The names of the state accounts.
+    accounts: Vec<String>,
+    events: Vec<String>,
+    types: FxHashMap<String, TypeDef>,
+    errors: Vec<(String, u32)>,
+}
+
+struct IdlInstruction {
+    name: String,
+    args: Vec<(String, String)>,
+    accounts: Vec<AccountMeta>,
+}
+
+#[derive(PartialEq)]
+struct AccountMeta {
+    name: String,
+    writable: bool,
+    signer: bool,
+}
+
+/// The shape of a struct, its fields, or of an enum, its variants.
+#[derive(PartialEq)]
+enum TypeDef {
+    Struct(Vec<(String, String)>),
+    Enum(Vec<String>),
+}
+
+impl Idl {
+    fn parse(value: &Value) -> Idl {
+        let mut idl = Idl {
+            name: value["metadata"]["name"].as_str().or(value["name"].as_str()).map(str::to_owned),
+            ..Idl::default()
+        };
+        for instruction in array(&value["instructions"]) {
+            let Some(name) = instruction["name"].as_str() else { continue };
+            idl.instructions.push(IdlInstruction {
+                name: to_lower_snake_case(name),
+                args: fields(&instruction["args"]),
+                accounts: array(&instruction["accounts"])
+                    .filter_map(|account| {
+                        Some(AccountMeta {
+                            name: to_lower_snake_case(account["name"].as_str()?),
+                            writable: flag(account, "writable", "isMut"),
+                            signer: flag(account, "signer", "isSigner"),
+                        })
+                    })
+                    .collect(),
+            });
+        }
+        for ty in array(&value["types"]) {
+            if let (Some(name), Some(def)) = (ty["name"].as_str(), type_def(&ty["type"])) {
+                idl.types.insert(defined_name(name), def);
+            }
+        }
+        // The legacy layout declares the fields of accounts and events with them.
+        for account in array(&value["accounts"]) {
+            let Some(name) = account["name"].as_str() else { continue };
+            if let Some(def) = type_def(&account["type"]) {
+                idl.types.insert(name.to_owned(), def);
+            }
+            idl.accounts.push(name.to_owned());
+        }
+        for event in array(&value["events"]) {
+            let Some(name) = event["name"].as_str() else { continue };
+            if event["fields"].is_array() {
+                idl.types.insert(name.to_owned(), TypeDef::Struct(fields(&event["fields"])));
+            }
+            idl.events.push(name.to_owned());
+        }
+        idl.errors = array(&value["errors"])
+            .filter_map(|error| {
+                Some((error["name"].as_str()?.to_owned(), error["code"].as_u64()?.try_into().ok()?))
+            })
+            .collect();
+        idl
+    }
+}
+
+fn array(value: &Value) -> impl Iterator<Item = &Value> {
+    value.as_array().into_iter().flatten()
+}
+
+/// A boolean named `name` in the current layout and `legacy` in the legacy one.
+fn flag(value: &Value, name: &str, legacy: &str) -> bool {
+    value[name].as_bool().or(value[legacy].as_bool()).unwrap_or(false)
+}
+
+/// The named fields of a struct or the arguments of an instruction, or the types of a tuple
+/// struct named by their position.
+fn fields(value: &Value) -> Vec<(String, String)> {
+    array(value)
+        .enumerate()
+        .map(|(index, field)| match field["name"].as_str() {
+            Some(name) => (to_lower_snake_case(name), idl_type(&field["type"])),
+            None => (index.to_string(), idl_type(field)),
+        })
+        .collect()
+}
+
+fn type_def(value: &Value) -> Option<TypeDef> {
+    match value["kind"].as_str()? {
+        "struct" => Some(TypeDef::Struct(fields(&value["fields"]))),
+        "enum" => Some(TypeDef::Enum(
+            array(&value["variants"])
+                .filter_map(|it| Some(it["name"].as_str()?.to_owned()))
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+/// Renders an IDL type like the Rust type it stands for, e.g. `{"vec": "pubkey"}` as
+/// `Vec<Pubkey>`.
+fn idl_type(value: &Value) -> String {
+    if let Some(name) = value.as_str() {
+        return match name {
+            "string" => "String".to_owned(),
+            "pubkey" | "publicKey" => "Pubkey".to_owned(),
+            "bytes" => "Vec<u8>".to_owned(),
+            name => name.to_owned(),
+        };
+    }
+    let Some((kind, inner)) = value.as_object().and_then(|it| it.iter().next()) else {
+        return value.to_string();
+    };
+    match kind.as_str() {
+        "vec" => format!("Vec<{}>", idl_type(inner)),
+        "option" => format!("Option<{}>", idl_type(inner)),
+        "coption" => format!("COption<{}>", idl_type(inner)),
+        "array" => {
+            let len = match &inner[1] {
+                Value::Number(len) => len.to_string(),
+                len => idl_type(len),
+            };
+            format!("[{}; {len}]", idl_type(&inner[0]))
+        }
+        // `"Name"` in the legacy layout, `{"name": "Name", "generics": [...]}` in the current.
+        "defined" => match inner.as_str() {
+            Some(name) => defined_name(name),
+            None => defined_name(inner["name"].as_str().unwrap_or_default()),
+        },
+        "generic" => inner.as_str().unwrap_or_default().to_owned(),
+        _ => value.to_string(),
+    }
+}
+
+/// Types of the same name in several modules are qualified by the IDL, the last segment is
+/// compared.
+fn defined_name(name: &str) -> String {
+    name.rsplit("::").next().unwrap_or(name).to_owned()
+}
+
+/// Renders a type of the sources the way [`idl_type`] renders IDL types.
+fn source_type(text: &str) -> String {
+    let file = SourceFile::parse(&format!("type T = {text};"), Edition::CURRENT).tree();
+    match file.syntax().descendants().find_map(ast::TypeAlias::cast).and_then(|it| it.ty()) {
+        Some(ty) => render(&ty),
+        None => text.split_whitespace().collect(),
+    }
+}
+
+fn render(ty: &ast::Type) -> String {
+    match ty {
+        ast::Type::PathType(path) => {
+            let Some(segment) = path.path().and_then(|it| it.segment()) else {
+                return ty.syntax().text().to_string();
+            };
+            let name = segment.name_ref().map(|it| it.text().to_string()).unwrap_or_default();
+            let args: Vec<String> = segment
+                .generic_arg_list()
+                .into_iter()
+                .flat_map(|it| it.generic_args())
+                .filter_map(|arg| match arg {
+                    ast::GenericArg::TypeArg(arg) => Some(render(&arg.ty()?)),
+                    _ => None,
+                })
+                .collect();
+            match (name.as_str(), args.as_slice()) {
+                ("Box", [inner]) => inner.clone(),
+                (_, []) => name,
+                _ => format!("{name}<{}>", args.join(", ")),
+            }
+        }
+        ast::Type::ArrayType(array) => {
+            let len: String = array
+                .const_arg()
+                .map(|it| it.syntax().text().to_string().split_whitespace().collect())
+                .unwrap_or_default();
+            let item = array.ty().map(|it| render(&it)).unwrap_or_default();
+            format!("[{item}; {len}]")
+        }
+        ast::Type::ParenType(inner) => inner.ty().map(|it| render(&it)).unwrap_or_default(),
+        _ => ty.syntax().text().to_string().split_whitespace().collect(),
+    }
+}
+
+/// The items of the sources an IDL describes, in the shape of an IDL.
+struct Program {
+    instructions: Vec<SourceInstruction>,
+    /// State accounts and events, with where they're declared.
+    types: Vec<SourceType>,
+    enums: Vec<SourceType>,
+    errors: Vec<SourceError>,
+}
+
+struct SourceInstruction {
+    name: String,
+    location: (String, u32),
+    args: Vec<(String, String)>,
+    accounts: Vec<AccountMeta>,
+}
+
+struct SourceType {
+    name: String,
+    location: (String, u32),
+    /// Whether it's an event, an account otherwise.
+    event: bool,
+    def: TypeDef,
+}
+
+struct SourceError {
+    name: String,
+    code: Option<u32>,
+    location: (String, u32),
+}
+
+impl Program {
+    /// The items of `graph` in the crate `name`, or all of them when no module belongs to it.
+    fn of(graph: &CodeGraph, name: Option<&str>) -> Program {
+        let program =
+            name.filter(|name| graph.instructions.iter().any(|it| crate_of(&it.module) == *name));
+        let in_program = |module: &str| program.is_none_or(|name| crate_of(module) == name);
+
+        let instructions = graph
+            .instructions
+            .iter()
+            .filter(|it| in_program(&it.module))
+            .map(|instruction| SourceInstruction {
+                name: instruction.name.clone(),
+                location: (instruction.file.clone(), instruction.line),
+                args: instruction
+                    .params
+                    .iter()
+                    .map(|it| (it.name.clone(), source_type(&it.ty)))
+                    .collect(),
+                accounts: graph
+                    .account_structs
+                    .iter()
+                    .find(|it| instruction.accounts_struct.as_deref() == Some(&it.path()))
+                    .map(account_metas)
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        let accounts =
+            graph.state_accounts.iter().filter(|it| in_program(&it.module)).map(|it| SourceType {
+                name: it.name.clone(),
+                location: (it.file.clone(), it.line),
+                event: false,
+                def: TypeDef::Struct(
+                    it.fields.iter().map(|it| (it.name.clone(), source_type(&it.ty))).collect(),
+                ),
+            });
+        let events = graph.events.iter().filter(|it| in_program(&it.module)).map(|it| SourceType {
+            name: it.name.clone(),
+            location: (it.file.clone(), it.line),
+            event: true,
+            def: TypeDef::Struct(
+                it.fields
+                    .iter()
+                    .enumerate()
+                    .map(|(index, field)| {
+                        let name = field.name.clone().unwrap_or_else(|| index.to_string());
+                        (name, source_type(&field.ty))
+                    })
+                    .collect(),
+            ),
+        });
+        let enums = graph
+            .enums
+            .iter()
+            .filter(|it| in_program(&it.module))
+            .map(|it| SourceType {
+                name: it.name.clone(),
+                location: (it.file.clone(), it.line),
+                event: false,
+                def: TypeDef::Enum(it.variants.iter().map(|it| it.name.clone()).collect()),
+            })
+            .collect();
+        let errors = graph
+            .error_codes
+            .iter()
+            .filter(|it| in_program(&it.module))
+            .flat_map(|enum_| {
+                enum_.errors.iter().map(|error| SourceError {
+                    name: error.name.clone(),
+                    code: error.code,
+                    location: (enum_.file.clone(), error.line),
+                })
+            })
+            .collect();
+        Program { instructions, types: accounts.chain(events).collect(), enums, errors }
+    }
+}
+
+/// The accounts of an instruction as the IDL lists them.
+fn account_metas(strukt: &AccountStruct) -> Vec<AccountMeta> {
+    strukt
+        .fields
+        .iter()
+        .map(|field| {
+            let has = |kind: &str| field.constraints.iter().any(|it| it.kind == kind);
+            let init = has("init") || has("init_if_needed");
+            // Accounts created without seeds are new keypairs, which sign their creation.
+            let keypair = init
+                && !field
+                    .constraints
+                    .iter()
+                    .any(|it| it.kind == "seeds" || it.namespace() == Some("associated_token"));
+            let signer_type =
+                field.ty.split(|c: char| !c.is_alphanumeric() && c != '_').any(|it| it == "Signer");
+            AccountMeta {
+                name: field.name.clone(),
+                writable: init || ["mut", "zero", "close", "realloc"].into_iter().any(has),
+                signer: signer_type || has("signer") || keypair,
+            }
+        })
+        .collect()
+}
+
+struct Diff {
+    idl_file: String,
+    findings: Vec<Finding>,
+}
+
+impl Diff {
+    fn compare(&mut self, idl: &Idl, program: &Program) {
+        self.instructions(idl, program);
+        self.types(idl, program);
+        self.errors(idl, program);
+    }
+
+    fn instructions(&mut self, idl: &Idl, program: &Program) {
+        let mut missing: Vec<&SourceInstruction> = Vec::new();
+        for instruction in &program.instructions {
+            match idl.instructions.iter().find(|it| it.name == instruction.name) {
+                Some(idl_instruction) => self.instruction(idl_instruction, instruction),
+                None => missing.push(instruction),
+            }
+        }
+        let mut removed: Vec<&IdlInstruction> = idl
+            .instructions
+            .iter()
+            .filter(|it| !program.instructions.iter().any(|source| source.name == it.name))
+            .collect();
+
+        // A renamed instruction keeps its arguments and accounts, which no other instruction
+        // on either side shares.
+        let same = |source: &SourceInstruction, idl: &IdlInstruction| {
+            source.args == idl.args && source.accounts == idl.accounts
+        };
+        let renamed: Vec<(&SourceInstruction, &IdlInstruction)> = missing
+            .iter()
+            .filter_map(|&source| {
+                let [old] = removed.iter().filter(|it| same(source, it)).collect::<Vec<_>>()[..]
+                else {
+                    return None;
+                };
+                (missing.iter().filter(|it| same(it, old)).count() == 1).then_some((source, *old))
+            })
+            .collect();
+        for &(source, old) in &renamed {
+            self.report(
+                "renamed-instruction",
+                format!(
+                    "instruction `{}` of the IDL is named `{}` in the sources",
+                    old.name, source.name
+                ),
+                Some(&source.location),
+            );
+        }
+        missing.retain(|it| !renamed.iter().any(|(source, _)| std::ptr::eq(*source, *it)));
+        removed.retain(|it| !renamed.iter().any(|(_, old)| std::ptr::eq(*old, *it)));
+
+        for instruction in missing {
+            self.report(
+                "missing-instruction",
+                format!("instruction `{}` is missing from the IDL", instruction.name),
+                Some(&instruction.location),
+            );
+        }
+        for instruction in removed {
+            self.report(
+                "removed-instruction",
+                format!("instruction `{}` of the IDL no longer exists", instruction.name),
+                None,
+            );
+        }
+    }
+
+    fn instruction(&mut self, idl: &IdlInstruction, source: &SourceInstruction) {
+        let location = Some(&source.location);
+        let name = &source.name;
+        self.members("instruction-args", "argument", name, &source.args, &idl.args, location);
+
+        for account in &source.accounts {
+            let Some(idl_account) = idl.accounts.iter().find(|it| it.name == account.name) else {
+                self.report(
+                    "instruction-accounts",
+                    format!("account `{}` of `{name}` is missing from the IDL", account.name),
+                    location,
+                );
+                continue;
+            };
+            for (what, source_flag, idl_flag) in [
+                ("writable", account.writable, idl_account.writable),
+                ("a signer", account.signer, idl_account.signer),
+            ] {
+                if source_flag != idl_flag {
+                    let (is, isnt) = match source_flag {
+                        true => ("is", "isn't"),
+                        false => ("isn't", "is"),
+                    };
+                    self.report(
+                        "instruction-accounts",
+                        format!(
+                            "account `{}` of `{name}` {is} {what}, but {isnt} in the IDL",
+                            account.name
+                        ),
+                        location,
+                    );
+                }
+            }
+        }
+        for account in &idl.accounts {
+            if !source.accounts.iter().any(|it| it.name == account.name) {
+                self.report(
+                    "instruction-accounts",
+                    format!("account `{}` of `{name}` in the IDL no longer exists", account.name),
+                    location,
+                );
+            }
+        }
+    }
+
+    fn types(&mut self, idl: &Idl, program: &Program) {
+        for ty in &program.types {
+            let declared = match ty.event {
+                true => &idl.events,
+                false => &idl.accounts,
+            };
+            if !declared.contains(&ty.name) {
+                let what = if ty.event { "event" } else { "account" };
+                self.report(
+                    "missing-type",
+                    format!("{what} `{}` is missing from the IDL", ty.name),
+                    Some(&ty.location),
+                );
+                continue;
+            }
+            self.fields(idl, ty);
+        }
+        for (what, event, names) in
+            [("account", false, &idl.accounts), ("event", true, &idl.events)]
+        {
+            for name in names {
+                if !program.types.iter().any(|it| it.event == event && it.name == *name) {
+                    self.report(
+                        "removed-type",
+                        format!("{what} `{name}` of the IDL no longer exists"),
+                        None,
+                    );
+                }
+            }
+        }
+        // Enums are only part of the IDL when an instruction, account or event uses them.
+        for enum_ in &program.enums {
+            if idl.types.contains_key(&enum_.name) {
+                self.fields(idl, enum_);
+            }
+        }
+    }
+
+    /// Reports the differences between the fields or variants of `ty` and those of the IDL.
+    fn fields(&mut self, idl: &Idl, ty: &SourceType) {
+        let Some(idl_def) = idl.types.get(&ty.name) else { return };
+        if *idl_def == ty.def {
+            return;
+        }
+        let location = Some(&ty.location);
+        match (&ty.def, idl_def) {
+            (TypeDef::Struct(fields), TypeDef::Struct(idl_fields)) => {
+                self.members("type-fields", "field", &ty.name, fields, idl_fields, location)
+            }
+            (TypeDef::Enum(variants), TypeDef::Enum(idl_variants)) => self.report(
+                "type-fields",
+                format!(
+                    "variants of `{}` are `{}`, but `{}` in the IDL",
+                    ty.name,
+                    variants.join(", "),
+                    idl_variants.join(", ")
+                ),
+                location,
+            ),
+            _ => self.report(
+                "type-fields",
+                format!("`{}` is a struct on one side and an enum on the other", ty.name),
+                location,
+            ),
+        }
+    }
+
+    /// Reports the arguments or fields, named `what`, of `owner` that differ in name, type or
+    /// order between the sources and the IDL.
+    fn members(
+        &mut self,
+        rule: &str,
+        what: &str,
+        owner: &str,
+        source: &[(String, String)],
+        idl: &[(String, String)],
+        location: Option<&(String, u32)>,
+    ) {
+        for (name, ty) in source {
+            match idl.iter().find(|(it, _)| it == name) {
+                None => self.report(
+                    rule,
+                    format!("{what} `{name}: {ty}` of `{owner}` is missing from the IDL"),
+                    location,
+                ),
+                Some((_, idl_ty)) if idl_ty != ty => self.report(
+                    rule,
+                    format!("{what} `{name}` of `{owner}` is `{ty}`, but `{idl_ty}` in the IDL"),
+                    location,
+                ),
+                Some(_) => {}
+            }
+        }
+        for (name, ty) in idl {
+            if !source.iter().any(|(it, _)| it == name) {
+                self.report(
+                    rule,
+                    format!("{what} `{name}: {ty}` of `{owner}` in the IDL no longer exists"),
+                    location,
+                );
+            }
+        }
+        // Members are serialized in order, the same members in another order break clients.
+        let source_names: Vec<&str> = source.iter().map(|(it, _)| it.as_str()).collect();
+        let idl_names: Vec<&str> = idl.iter().map(|(it, _)| it.as_str()).collect();
+        if source_names != idl_names && sorted(&source_names) == sorted(&idl_names) {
+            self.report(
+                rule,
+                format!("{what}s of `{owner}` are ordered `{}` in the IDL", idl_names.join(", ")),
+                location,
+            );
+        }
+    }
+
+    fn errors(&mut self, idl: &Idl, program: &Program) {
+        for error in &program.errors {
+            match idl.errors.iter().find(|(name, _)| *name == error.name) {
+                None => self.report(
+                    "error-codes",
+                    format!("error `{}` is missing from the IDL", error.name),
+                    Some(&error.location),
+                ),
+                Some((_, code)) if error.code.is_some_and(|it| it != *code) => self.report(
+                    "error-codes",
+                    format!(
+                        "error `{}` has the code {}, but {code} in the IDL",
+                        error.name,
+                        error.code.unwrap_or_default()
+                    ),
+                    Some(&error.location),
+                ),
+                Some(_) => {}
+            }
+        }
+        for (name, code) in &idl.errors {
+            if !program.errors.iter().any(|it| it.name == *name) {
+                self.report(
+                    "error-codes",
+                    format!("error `{name}` ({code}) of the IDL no longer exists"),
+                    None,
+                );
+            }
+        }
+    }
+
+    /// Reports a finding at `location` in the sources, or on the IDL file without one.
+    fn report(&mut self, rule: &str, message: String, location: Option<&(String, u32)>) {
+        let (file, line) = match location {
+            Some((file, line)) => (file.clone(), Some(*line)),
+            None => (self.idl_file.clone(), None),
+        };
+        self.findings.push(Finding {
+            plugin: "idl-diff".to_owned(),
+            rule: rule.to_owned(),
+            message,
+            severity: Severity::Warning,
+            file: Some(file),
+            line,
+            related: Vec::new(),
+        });
+    }
+}
+
+fn sorted<'a>(names: &[&'a str]) -> Vec<&'a str> {
+    let mut names = names.to_vec();
+    names.sort_unstable();
+    names
+}
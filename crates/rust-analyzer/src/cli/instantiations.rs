@@ -0,0 +1,197 @@
+//! Resolution of the trait method calls of generic functions through the types they're
+//! instantiated with.
+//!
+//! In `fn notify<H: Handler>(handler: &H) { handler.handle() }` the call hierarchy only knows
+//! `Handler::handle`, which dynamic dispatch resolution would link to every implementation. The
+//! calls of `notify` tell which `H` it's actually used with, like `notify(&Logger)`, so the call
+//! is redirected to `<Logger as Handler>::handle` and the implementations of the types no call
+//! uses are left out.
+
+use hir::{AsAssocItem, AssocItem, GenericDef, HasCrate, HirDisplay, Impl, Semantics, TypeParam};
+use ide::RootDatabase;
+use ide_db::{base_db::salsa, defs::Definition};
+use rustc_hash::{FxHashMap, FxHashSet};
+use syntax::{
+    AstNode,
+    ast::{self, HasArgList},
+};
+use vfs::{AbsPathBuf, Vfs};
+
+use crate::cli::{
+    call_kind::call_at,
+    dispatch::Dispatch,
+    function_analyzer::{CallRelation, FunctionInfo, extract_function_info, is_external_path},
+    macro_edges::resolve_call,
+};
+
+/// The types a call of a generic function gives to its type parameters.
+type Instantiation<'db> = Vec<(TypeParam, hir::Type<'db>)>;
+
+/// Replaces the calls of trait methods on a type parameter of their generic caller by calls to
+/// the implementations of the types the caller is instantiated with, one relation per type, which
+/// `instantiated_with` names. Calls whose instantiations can't be resolved are left alone.
+pub(super) fn resolve_instantiations(
+    db: &RootDatabase,
+    vfs: &Vfs,
+    project_root: &AbsPathBuf,
+    relations: Vec<CallRelation>,
+) -> Vec<CallRelation> {
+    let _p = tracing::info_span!("resolve_instantiations").entered();
+    salsa::attach(db, || {
+        let sema = Semantics::new(db);
+        let mut instantiations: FxHashMap<hir::Function, Vec<Instantiation<'_>>> =
+            FxHashMap::default();
+        let mut resolved = Vec::with_capacity(relations.len());
+        for relation in relations {
+            let Some((method, param)) = call_on_type_param(&sema, vfs, &relation) else {
+                resolved.push(relation);
+                continue;
+            };
+            let GenericDef::Function(generic) = param.parent(db) else {
+                resolved.push(relation);
+                continue;
+            };
+            let instantiations =
+                instantiations.entry(generic).or_insert_with(|| instantiations_of(&sema, generic));
+            let display_target = generic.krate(db).to_display_target(db);
+            let mut seen = FxHashSet::default();
+            let mut targets = Vec::new();
+            for (_, ty) in instantiations.iter().flatten().filter(|(it, _)| *it == param) {
+                let Some(target) = implementation_for(&sema, vfs, project_root, method, ty) else {
+                    continue;
+                };
+                let ty = ty.display(db, display_target).to_string();
+                if seen.insert((target.file_path.clone(), target.line, target.column, ty.clone())) {
+                    targets.push((target, ty));
+                }
+            }
+            if targets.is_empty() {
+                resolved.push(relation);
+                continue;
+            }
+            // `impl Trait` parameters have no name, they're shown as written.
+            let name = match param.is_implicit(db) {
+                true => param.ty(db).display(db, display_target).to_string(),
+                false => param.name(db).as_str().to_owned(),
+            };
+            resolved.extend(targets.into_iter().map(|(target, ty)| CallRelation {
+                callee: target,
+                dispatch: Dispatch::Static,
+                confidence: 1.0,
+                instantiated_with: Some(format!("{name} = {ty}")),
+                ..relation.clone()
+            }));
+        }
+        resolved
+    })
+}
+
+/// The trait method called by `relation` and the type parameter it's called on, either as the
+/// receiver like `handler.handle()` or as the qualifier like `H::handle(handler)`.
+fn call_on_type_param(
+    sema: &Semantics<'_, RootDatabase>,
+    vfs: &Vfs,
+    relation: &CallRelation,
+) -> Option<(hir::Function, TypeParam)> {
+    let db = sema.db;
+    let call = call_at(sema, vfs, relation)?;
+    let (method, _) = resolve_call(sema, &call)?;
+    method.as_assoc_item(db)?.container_trait(db)?;
+    let param = match ast::CallableExpr::cast(call)? {
+        ast::CallableExpr::MethodCall(call) => {
+            let receiver = sema.type_of_expr(&call.receiver()?)?.original;
+            receiver.strip_references().as_type_param(db)?
+        }
+        ast::CallableExpr::Call(call) => {
+            let ast::Expr::PathExpr(path) = call.expr()? else { return None };
+            match sema.resolve_path(&path.path()?.qualifier()?)? {
+                hir::PathResolution::TypeParam(param) => param,
+                _ => return None,
+            }
+        }
+    };
+    Some((method, param))
+}
+
+/// The type parameters of `generic` at each of its calls: those given or inferred for the call,
+/// and for `impl Trait` parameters the types of the arguments passed for them.
+fn instantiations_of<'db>(
+    sema: &Semantics<'db, RootDatabase>,
+    generic: hir::Function,
+) -> Vec<Instantiation<'db>> {
+    let db = sema.db;
+    let params: Vec<TypeParam> = GenericDef::Function(generic)
+        .type_or_const_params(db)
+        .into_iter()
+        .filter_map(|it| it.as_type_param(db))
+        .collect();
+    let mut instantiations = Vec::new();
+    for (_, references) in Definition::Function(generic).usages(sema).all() {
+        for name_ref in references.iter().filter_map(|it| it.name.as_name_ref()) {
+            let Some(call) = name_ref.syntax().ancestors().find_map(ast::CallableExpr::cast) else {
+                continue;
+            };
+            if resolve_call(sema, call.syntax()).is_none_or(|(it, _)| it != generic) {
+                continue;
+            }
+            let (subst, args, fn_params) = match &call {
+                ast::CallableExpr::Call(call) => {
+                    let Some(ast::Expr::PathExpr(path)) = call.expr() else { continue };
+                    let subst = path.path().and_then(|it| sema.resolve_path_with_subst(&it));
+                    (subst.and_then(|(_, it)| it), call.arg_list(), generic.assoc_fn_params(db))
+                }
+                ast::CallableExpr::MethodCall(call) => {
+                    let subst = sema.resolve_method_call_fallback(call).and_then(|(_, it)| it);
+                    (subst, call.arg_list(), generic.params_without_self(db))
+                }
+            };
+            let named = subst.map(|it| it.types(db)).unwrap_or_default();
+            let args: Vec<ast::Expr> = args.map(|it| it.args().collect()).unwrap_or_default();
+            let instantiation = params
+                .iter()
+                .filter_map(|&param| {
+                    let name = param.name(db);
+                    let ty = named.iter().find(|(it, _)| it == name.symbol()).map(|(_, ty)| ty);
+                    let ty = ty.cloned().or_else(|| {
+                        let index = fn_params.iter().position(|it| {
+                            it.ty().strip_references().as_type_param(db) == Some(param)
+                        })?;
+                        Some(sema.type_of_expr(args.get(index)?)?.original.strip_references())
+                    })?;
+                    (!ty.is_unknown() && ty.as_type_param(db).is_none()).then_some((param, ty))
+                })
+                .collect();
+            instantiations.push(instantiation);
+        }
+    }
+    instantiations
+}
+
+/// The project function `method` of a trait runs for `ty`: the method of the implementation of
+/// the trait for `ty`, or the default body of `method` when that implementation keeps it.
+fn implementation_for(
+    sema: &Semantics<'_, RootDatabase>,
+    vfs: &Vfs,
+    project_root: &AbsPathBuf,
+    method: hir::Function,
+    ty: &hir::Type<'_>,
+) -> Option<FunctionInfo> {
+    let db = sema.db;
+    let trait_ = method.as_assoc_item(db)?.container_trait(db)?;
+    let impl_ = Impl::all_for_trait(db, trait_)
+        .into_iter()
+        .find(|it| it.self_ty(db).could_unify_with(db, ty))?;
+    let name = method.name(db);
+    let function = impl_
+        .items(db)
+        .into_iter()
+        .find_map(|item| match item {
+            AssocItem::Function(function) if function.name(db) == name => Some(function),
+            _ => None,
+        })
+        .or_else(|| method.has_body(db).then_some(method))?;
+    extract_function_info(db, function, vfs)
+        .ok()
+        .flatten()
+        .filter(|function| !is_external_path(&function.file_path, project_root))
+}
@@ -0,0 +1,243 @@
+//! The lamports every instruction moves between accounts, by mutating balances directly or
+//! through transfers of the system program.
+//!
+//! Programs can only debit accounts they own, but credit any account by adding to its balance:
+//! `**account.lamports.borrow_mut() -= amount`, the same through `try_borrow_mut_lamports()?`, or
+//! Anchor's `sub_lamports` and `add_lamports`. A debit and a credit of the same amount in a
+//! function are one flow from the debited to the credited account; the others are flows with an
+//! unknown side. System transfers are `system_program::transfer` calls given a `CpiContext` and
+//! the `system_instruction::transfer` builder.
+
+use anyhow::Result;
+use hir::Semantics;
+use ide::Analysis;
+use serde::Serialize;
+use syntax::{
+    AstNode, SyntaxNode,
+    ast::{self, BinaryOp, HasArgList, HasName},
+};
+
+use crate::cli::{
+    code_graph::{CodeGraph, LoadedProject, body_descendants, project_files},
+    cpi_calls::account_name,
+    function_analyzer::convert_to_relative_path,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct InstructionLamportFlows {
+    /// The path of the instruction.
+    pub(super) instruction: String,
+    pub(super) flows: Vec<LamportFlow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct LamportFlow {
+    /// The debited account, none when the function credits without a matching debit.
+    pub(super) from: Option<String>,
+    /// The credited account, none when the function debits without a matching credit.
+    pub(super) to: Option<String>,
+    /// The expression of the amount, like `amount` or `fee * 2`.
+    pub(super) amount: String,
+    pub(super) mechanism: LamportMechanism,
+    pub(super) file: String,
+    pub(super) line: u32,
+    /// The function moving the lamports.
+    pub(super) function: Option<String>,
+    /// The id of `function` in the call graph.
+    #[serde(skip)]
+    function_id: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum LamportMechanism {
+    /// Balances changed by the program itself.
+    Direct,
+    /// A transfer of the system program.
+    SystemTransfer,
+}
+
+/// A change of the balance of an account by the program.
+struct Mutation {
+    account: String,
+    debit: bool,
+    amount: String,
+    line: u32,
+}
+
+/// Collects the lamport flows of every function of the project, by instruction reaching them,
+/// leaving out instructions moving no lamports.
+pub(super) fn extract_lamport_flows(
+    project: &LoadedProject,
+    analysis: &Analysis,
+    graph: &CodeGraph,
+) -> Result<Vec<InstructionLamportFlows>> {
+    let _p = tracing::info_span!("extract_lamport_flows").entered();
+    let sema = Semantics::new(&project.db);
+    let mut flows = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let relative_path = convert_to_relative_path(&file_path, &project.project_root);
+        let line_of = |node: &SyntaxNode| line_index.line_col(node.text_range().start()).line + 1;
+
+        for function in file.syntax().descendants().filter_map(ast::Fn::cast) {
+            let Some(body) = function.body() else { continue };
+            let function_id = function.name().and_then(|name| {
+                graph.function_at(&relative_path, name.text().as_str(), line_of(name.syntax()))
+            });
+            let flow = |from, to, amount, mechanism, line| LamportFlow {
+                from,
+                to,
+                amount,
+                mechanism,
+                file: relative_path.clone(),
+                line,
+                function: function_id.map(|id| graph.function_path(id)),
+                function_id,
+            };
+
+            let mut mutations: Vec<Mutation> = Vec::new();
+            for node in body_descendants(body.syntax()) {
+                mutations.extend(mutation(&node, line_of));
+                let Some(call) = ast::CallExpr::cast(node) else { continue };
+                let line = line_of(call.syntax());
+                if let Some((from, to, amount)) = system_transfer(graph, function_id, &call, line) {
+                    flows.push(flow(from, to, amount, LamportMechanism::SystemTransfer, line));
+                }
+            }
+            // Pairs every debit with the first credit of the same amount left.
+            let (debits, mut credits): (Vec<_>, Vec<_>) =
+                mutations.into_iter().partition(|it| it.debit);
+            for debit in debits {
+                let credit = credits
+                    .iter()
+                    .position(|it| it.amount == debit.amount)
+                    .map(|index| credits.remove(index).account);
+                let from = Some(debit.account);
+                flows.push(flow(from, credit, debit.amount, LamportMechanism::Direct, debit.line));
+            }
+            for credit in credits {
+                let to = Some(credit.account);
+                flows.push(flow(None, to, credit.amount, LamportMechanism::Direct, credit.line));
+            }
+        }
+    }
+
+    flows.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    let mut instructions = Vec::new();
+    for (instruction, functions) in graph.instruction_reach() {
+        let reached: Vec<LamportFlow> = flows
+            .iter()
+            .filter(|it| it.function_id.is_some_and(|id| functions.contains(&id)))
+            .cloned()
+            .collect();
+        if !reached.is_empty() {
+            instructions.push(InstructionLamportFlows { instruction, flows: reached });
+        }
+    }
+    instructions.sort_by(|a, b| a.instruction.cmp(&b.instruction));
+    Ok(instructions)
+}
+
+/// The balance change `node` makes, if it's `**lamports.borrow_mut() += amount` and the like or a
+/// call of `add_lamports` or `sub_lamports`.
+fn mutation(node: &SyntaxNode, line_of: impl Fn(&SyntaxNode) -> u32) -> Option<Mutation> {
+    if let Some(call) = ast::MethodCallExpr::cast(node.clone()) {
+        let debit = match call.name_ref()?.text().as_str() {
+            "sub_lamports" => true,
+            "add_lamports" => false,
+            _ => return None,
+        };
+        let amount = call.arg_list()?.args().next()?;
+        let account = account_name(&call.receiver()?);
+        let amount = text(amount.syntax());
+        return Some(Mutation { account, debit, amount, line: line_of(node) });
+    }
+    let assignment = ast::BinExpr::cast(node.clone())?;
+    let debit = match assignment.op_kind()? {
+        BinaryOp::Assignment { op: Some(ast::ArithOp::Sub) } => true,
+        BinaryOp::Assignment { op: Some(ast::ArithOp::Add) } => false,
+        _ => return None,
+    };
+    let account = lamports_of(assignment.lhs()?)?;
+    let amount = text(assignment.rhs()?.syntax());
+    Some(Mutation { account, debit, amount, line: line_of(node) })
+}
+
+/// The account whose balance `place` is, like `vault` for `**vault.lamports.borrow_mut()` or
+/// `**vault.try_borrow_mut_lamports()?`.
+fn lamports_of(mut place: ast::Expr) -> Option<String> {
+    loop {
+        place = match place {
+            ast::Expr::PrefixExpr(it) if it.op_kind() == Some(ast::UnaryOp::Deref) => it.expr()?,
+            ast::Expr::ParenExpr(it) => it.expr()?,
+            ast::Expr::TryExpr(it) => it.expr()?,
+            ast::Expr::MethodCallExpr(call) => {
+                let method = call.name_ref()?;
+                let receiver = call.receiver()?;
+                match (method.text().as_str(), receiver) {
+                    ("unwrap" | "expect", receiver) => receiver,
+                    ("try_borrow_mut_lamports", receiver) => return Some(account_name(&receiver)),
+                    ("borrow_mut" | "try_borrow_mut", ast::Expr::FieldExpr(field))
+                        if field.name_ref().is_some_and(|it| it.text() == "lamports") =>
+                    {
+                        return Some(account_name(&field.expr()?));
+                    }
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+    }
+}
+
+/// The accounts and amount of a transfer of the system program: `system_program::transfer(ctx,
+/// amount)`, with the accounts of the context built last before it in the same function, or
+/// `system_instruction::transfer(from, to, amount)`. `line` is the line of the call.
+fn system_transfer(
+    graph: &CodeGraph,
+    function: Option<usize>,
+    call: &ast::CallExpr,
+    line: u32,
+) -> Option<(Option<String>, Option<String>, String)> {
+    let ast::Expr::PathExpr(callee) = call.expr()? else { return None };
+    let path = callee.path()?;
+    if path.segment()?.name_ref()?.text() != "transfer" {
+        return None;
+    }
+    let args: Vec<ast::Expr> = call.arg_list()?.args().collect();
+    match path.qualifier()?.segment()?.name_ref()?.text().as_str() {
+        "system_instruction" => {
+            let [from, to, amount] = args.as_slice() else { return None };
+            Some((Some(account_name(from)), Some(account_name(to)), text(amount.syntax())))
+        }
+        "system_program" => {
+            let target = text(callee.syntax());
+            let function = graph.function_path(function?);
+            let cpi = graph
+                .cpi_calls
+                .iter()
+                .filter(|it| {
+                    it.function.as_ref() == Some(&function)
+                        && it.target.as_ref() == Some(&target)
+                        && it.line <= line
+                })
+                .max_by_key(|it| it.line);
+            let role = |role: &str| {
+                let accounts = cpi.into_iter().flat_map(|it| &it.accounts);
+                accounts
+                    .into_iter()
+                    .find(|it| it.role.as_deref() == Some(role))
+                    .map(|it| it.account.clone())
+            };
+            Some((role("from"), role("to"), text(args.get(1)?.syntax())))
+        }
+        _ => None,
+    }
+}
+
+/// The source of `node` on a single line.
+fn text(node: &SyntaxNode) -> String {
+    node.text().to_string().split_whitespace().collect::<Vec<_>>().join(" ")
+}
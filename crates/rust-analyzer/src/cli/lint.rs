@@ -0,0 +1,214 @@
+//! Built-in lint rules over the project sources, reported as findings.
+//!
+//! - `discarded-error`: a `Result` whose error is silently dropped, through `let _ = ...`, an
+//!   `.ok()` whose value is never used, or a `match` arm like `Err(_) => {}` that neither
+//!   propagates the error nor panics.
+
+use std::fs;
+
+use anyhow::Result;
+use hir::{DisplayTarget, HirDisplay, Semantics, Type};
+use ide_db::{RootDatabase, base_db::salsa};
+use syntax::{
+    AstNode, SyntaxKind, SyntaxNode,
+    ast::{self, HasName},
+};
+
+use crate::cli::{
+    code_graph::{LoadOptions, LoadedProject, project_files},
+    findings::{self, Finding, Severity},
+    flags,
+    function_analyzer::convert_to_relative_path,
+};
+
+/// Macros that abort instead of swallowing the error they're given.
+const DIVERGING_MACROS: &[&str] = &["panic", "unreachable", "todo", "unimplemented", "bail"];
+
+impl flags::Lint {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("lint", path = %self.path.display()).entered();
+        eprintln!("Loading workspace...");
+        let project = LoadedProject::load(
+            &self.path,
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+
+        let rules = self.rules.unwrap_or_default();
+        rules.validate(&project)?;
+        eprintln!("Running lints...");
+        let mut findings = lint(&project);
+        rules.retain(&mut findings);
+        eprintln!("Lints reported {} findings", findings.len());
+
+        if let Some(baseline) = &self.baseline {
+            let total = findings.len();
+            findings = findings::new_findings(findings, baseline)?;
+            eprintln!(
+                "{} new findings, {} already in the baseline",
+                findings.len(),
+                total - findings.len()
+            );
+        }
+
+        let json = serde_json::to_string_pretty(&findings)?;
+        match &self.output {
+            Some(path) => fs::write(path, json)?,
+            None => println!("{json}"),
+        }
+        if self.baseline.is_some() && !findings.is_empty() {
+            anyhow::bail!("{} new findings compared to the baseline", findings.len());
+        }
+        Ok(())
+    }
+}
+
+struct FileLinter<'a, 'db> {
+    sema: &'a Semantics<'db, RootDatabase>,
+    display_target: DisplayTarget,
+    file: String,
+    line_index: &'a ide::LineIndex,
+    findings: Vec<Finding>,
+}
+
+pub(super) fn lint(project: &LoadedProject) -> Vec<Finding> {
+    let analysis = project.analysis();
+    let sema = Semantics::new(&project.db);
+    let mut findings = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let Some(krate) = sema.first_crate(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let mut linter = FileLinter {
+            sema: &sema,
+            display_target: krate.to_display_target(&project.db),
+            file: convert_to_relative_path(&file_path, &project.project_root),
+            line_index: &line_index,
+            findings: Vec::new(),
+        };
+        // Types can only be inspected with the database attached to the thread.
+        salsa::attach(&project.db, || {
+            for node in file.syntax().descendants() {
+                linter.discarded_error(&node);
+            }
+        });
+        findings.extend(linter.findings);
+    }
+    findings
+}
+
+impl FileLinter<'_, '_> {
+    fn discarded_error(&mut self, node: &SyntaxNode) {
+        if let Some(let_stmt) = ast::LetStmt::cast(node.clone()) {
+            let Some(ast::Pat::WildcardPat(_)) = let_stmt.pat() else { return };
+            let Some(init) = let_stmt.initializer() else { return };
+            if let Some(error) = self.result_error(&init) {
+                self.report(node, format!("`let _ =` discards a `Result` with error `{error}`"));
+            } else if let Some(error) = self.ok_call_error(&init) {
+                self.report(node, format!("`.ok()` discards an error `{error}`"));
+            }
+        } else if let Some(stmt) = ast::ExprStmt::cast(node.clone()) {
+            let Some(expr) = stmt.expr() else { return };
+            if let Some(error) = self.ok_call_error(&expr) {
+                self.report(node, format!("`.ok()` discards an error `{error}`"));
+            }
+        } else if let Some(match_expr) = ast::MatchExpr::cast(node.clone()) {
+            let Some(error) = match_expr.expr().and_then(|it| self.result_error(&it)) else {
+                return;
+            };
+            let arms = match_expr.match_arm_list().into_iter().flat_map(|it| it.arms());
+            let has_ok_arm =
+                arms.clone().any(|arm| variant_name(arm.pat()).as_deref() == Some("Ok"));
+            for arm in arms {
+                let swallowing = match arm.pat() {
+                    Some(ast::Pat::WildcardPat(_)) => has_ok_arm,
+                    pat => {
+                        variant_name(pat.clone()).as_deref() == Some("Err")
+                            && pat.is_some_and(|pat| ignores_fields(&pat))
+                    }
+                };
+                if swallowing
+                    && arm.guard().is_none()
+                    && arm.expr().is_some_and(|body| !propagates(body.syntax()))
+                {
+                    self.report(arm.syntax(), format!("`match` arm swallows an error `{error}`"));
+                }
+            }
+        }
+    }
+
+    /// The error type of `expr`, if it's a `Result`.
+    fn result_error(&self, expr: &ast::Expr) -> Option<String> {
+        let ty = self.sema.type_of_expr(expr)?.original;
+        result_error_type(self.sema.db, &ty)
+            .map(|error| error.display(self.sema.db, self.display_target).to_string())
+    }
+
+    /// The error type discarded by `expr`, if it's a `.ok()` call on a `Result`.
+    fn ok_call_error(&self, expr: &ast::Expr) -> Option<String> {
+        let ast::Expr::MethodCallExpr(call) = expr else { return None };
+        if call.name_ref()?.text() != "ok" {
+            return None;
+        }
+        self.result_error(&call.receiver()?)
+    }
+
+    fn report(&mut self, node: &SyntaxNode, message: String) {
+        let function = node
+            .ancestors()
+            .find_map(ast::Fn::cast)
+            .and_then(|func| func.name())
+            .map(|name| format!(" in `{}`", name.text()))
+            .unwrap_or_default();
+        self.findings.push(Finding {
+            plugin: "lint".to_owned(),
+            rule: "discarded-error".to_owned(),
+            message: format!("{message}{function}"),
+            severity: Severity::Warning,
+            file: Some(self.file.clone()),
+            line: Some(self.line_index.line_col(node.text_range().start()).line + 1),
+            related: Vec::new(),
+        });
+    }
+}
+
+fn result_error_type<'db>(db: &'db RootDatabase, ty: &Type<'db>) -> Option<Type<'db>> {
+    let adt = ty.as_adt()?;
+    if adt.name(db).as_str() != "Result" {
+        return None;
+    }
+    ty.type_arguments().nth(1)
+}
+
+/// The last path segment of a tuple struct pattern, e.g. `Err` for `Err(_)`.
+fn variant_name(pat: Option<ast::Pat>) -> Option<String> {
+    let ast::Pat::TupleStructPat(pat) = pat? else { return None };
+    Some(pat.path()?.segment()?.name_ref()?.text().to_string())
+}
+
+/// Whether the tuple struct pattern `pat` binds none of its fields, `_name` bindings included.
+fn ignores_fields(pat: &ast::Pat) -> bool {
+    let ast::Pat::TupleStructPat(pat) = pat else { return false };
+    pat.fields().all(|field| match field {
+        ast::Pat::WildcardPat(_) | ast::Pat::RestPat(_) => true,
+        ast::Pat::IdentPat(ident) => ident.name().is_some_and(|name| name.text().starts_with('_')),
+        _ => false,
+    })
+}
+
+/// Whether an arm body returns, re-raises or panics rather than carrying on.
+fn propagates(body: &SyntaxNode) -> bool {
+    body.descendants().any(|node| match node.kind() {
+        SyntaxKind::RETURN_EXPR | SyntaxKind::TRY_EXPR => true,
+        SyntaxKind::MACRO_CALL => ast::MacroCall::cast(node)
+            .and_then(|call| call.path()?.segment()?.name_ref())
+            .is_some_and(|name| DIVERGING_MACROS.contains(&name.text().as_str())),
+        SyntaxKind::PATH_EXPR => ast::PathExpr::cast(node)
+            .and_then(|path| path.path()?.segment()?.name_ref())
+            .is_some_and(|name| name.text() == "Err"),
+        _ => false,
+    })
+}
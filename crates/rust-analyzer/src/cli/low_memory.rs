@@ -0,0 +1,70 @@
+//! The `--low-memory` mode of `function-analyzer`, for workspaces whose analysis doesn't fit in
+//! memory at once.
+//!
+//! Salsa keeps everything it computed until its database is dropped, and offers no way to empty
+//! its caches. Each crate is therefore analyzed in a database of its own, loaded from the
+//! workspace and dropped before the next crate, and the calls of the crates are merged.
+
+use anyhow::Result;
+use hir::Crate;
+use itertools::Itertools;
+use load_cargo::{LoadCargoConfig, load_workspace};
+use project_model::ProjectWorkspace;
+use rustc_hash::FxHashMap;
+use vfs::AbsPathBuf;
+
+use crate::cli::{
+    analysis_progress::Progress,
+    entry_points::{self, EntryKind},
+    flags,
+    function_analyzer::{
+        CallAnalysis, CallRelation, FunctionFilter, FunctionInfo, extract_all_functions,
+        is_external_path,
+    },
+};
+
+/// The functions, program roots and calls of the crates of `ws` kept by `filter`.
+pub(super) fn analyze(
+    cmd: &flags::FunctionAnalyzer,
+    ws: ProjectWorkspace,
+    extra_env: &FxHashMap<String, Option<String>>,
+    load_config: &LoadCargoConfig,
+    project_root: &AbsPathBuf,
+    filter: &FunctionFilter,
+    progress: &Progress,
+) -> Result<(Vec<FunctionInfo>, Vec<(FunctionInfo, EntryKind)>, Vec<CallRelation>)> {
+    let crates = {
+        let (db, vfs, _proc_macro) = load_workspace(ws.clone(), extra_env, load_config)?;
+        // In the order `extract_all_functions` visits them, so the output stays the same.
+        Crate::all(&db)
+            .into_iter()
+            .rev()
+            .filter_map(|krate| {
+                let name = krate.display_name(&db)?.to_string();
+                let root_file = vfs.file_path(krate.root_file(&db)).to_string();
+                (!is_external_path(&root_file, project_root)
+                    && filter.keeps_crate(&name)
+                    && filter.keeps_target(&root_file, project_root))
+                .then_some(name)
+            })
+            .unique()
+            .collect_vec()
+    };
+
+    let mut functions = Vec::new();
+    let mut roots = Vec::new();
+    let mut relations = Vec::new();
+    for (position, name) in crates.iter().enumerate() {
+        eprintln!("Crate {}/{}: {name}", position + 1, crates.len());
+        let (db, vfs, _proc_macro) = load_workspace(ws.clone(), extra_env, load_config)?;
+        let filter = FunctionFilter { include_crates: vec![name.clone()], ..filter.clone() };
+        let crate_functions = extract_all_functions(&db, &vfs, project_root, &filter)?;
+        eprintln!("Found {} functions", crate_functions.len());
+        roots.extend(entry_points::program_roots(&db, &vfs, &crate_functions));
+        let mut analysis = CallAnalysis::new(cmd, &db, &vfs, project_root, progress);
+        relations.extend(analysis.calls_of(&crate_functions)?);
+        analysis.report_cache();
+        functions.extend(crate_functions);
+    }
+    Ok((functions, roots, relations))
+}
@@ -0,0 +1,226 @@
+//! Calls produced by macros, and their attribution to the macro producing them.
+//!
+//! A call is expanded from a macro when its call site lies in the arguments of a macro call,
+//! like `require!(check(ctx))`, or when the calling function itself is generated, like the
+//! entrypoints an anchor `#[program]` module expands to. The call hierarchy doesn't look into
+//! macro calls, so the calls in their arguments are collected here.
+
+use hir::Semantics;
+use ide::{LineCol, RootDatabase};
+use ide_db::{LineIndexDatabase, base_db::salsa};
+use syntax::{
+    AstNode, SyntaxKind, SyntaxNode,
+    ast::{self, HasName},
+};
+use vfs::Vfs;
+
+use crate::cli::{
+    call_kind::CallKind,
+    code_graph::module_path,
+    dispatch::Dispatch,
+    function_analyzer::{
+        CallRelation, FunctionInfo, container_name, find_file_id_by_path, is_build_output,
+    },
+};
+
+/// The calls written in the arguments of the macro calls of `functions`, which only exist once
+/// the macros are expanded.
+pub(super) fn macro_argument_calls(
+    db: &RootDatabase,
+    vfs: &Vfs,
+    functions: &[FunctionInfo],
+) -> Vec<CallRelation> {
+    let _p = tracing::info_span!("macro_argument_calls").entered();
+    salsa::attach(db, || {
+        let sema = Semantics::new(db);
+        let mut relations = Vec::new();
+        for function in functions {
+            let Some(fn_) = function_at(&sema, vfs, &function.file_path, function)
+                .and_then(|node| node.ancestors().find_map(ast::Fn::cast))
+            else {
+                continue;
+            };
+            let Some(body) = fn_.body() else { continue };
+            for call in body.syntax().descendants().filter_map(ast::MacroCall::cast) {
+                let Some(arguments) = call.token_tree() else { continue };
+                let arguments = arguments.syntax().text_range();
+                let mut expansions: Vec<SyntaxNode> =
+                    sema.expand_macro_call(&call).map(|it| it.value).into_iter().collect();
+                while let Some(expansion) = expansions.pop() {
+                    for node in expansion.descendants() {
+                        if let Some(nested) = ast::MacroCall::cast(node.clone()) {
+                            expansions.extend(sema.expand_macro_call(&nested).map(|it| it.value));
+                            continue;
+                        }
+                        let Some((callee, site)) = resolve_call(&sema, &node) else { continue };
+                        // Calls of the macro's own body don't map back to the arguments.
+                        let Some(site) = sema.original_range_opt(&site) else { continue };
+                        if !arguments.contains_range(site.range) {
+                            continue;
+                        }
+                        let Some(callee) = callee_info(&sema, vfs, callee) else { continue };
+                        let line_col =
+                            db.line_index(site.file_id.file_id(db)).line_col(site.range.start());
+                        relations.push(CallRelation {
+                            caller: function.clone(),
+                            callee,
+                            call_site_line: line_col.line + 1,
+                            call_site_column: line_col.col + 1,
+                            dispatch: Dispatch::Static,
+                            confidence: 1.0,
+                            expanded_from: None,
+                            call_kind: CallKind::Macro,
+                            callee_crate: None,
+                            call_snippet: None,
+                            instantiated_with: None,
+                        });
+                    }
+                }
+            }
+        }
+        relations
+    })
+}
+
+/// The function called by a call expression, and the node standing for the call site.
+pub(super) fn resolve_call(
+    sema: &Semantics<'_, RootDatabase>,
+    node: &SyntaxNode,
+) -> Option<(hir::Function, SyntaxNode)> {
+    match ast::CallableExpr::cast(node.clone())? {
+        ast::CallableExpr::Call(call) => {
+            let expr = call.expr()?;
+            match sema.type_of_expr(&expr)?.original.as_callable(sema.db)?.kind() {
+                hir::CallableKind::Function(function) => Some((function, expr.syntax().clone())),
+                _ => None,
+            }
+        }
+        ast::CallableExpr::MethodCall(call) => {
+            let function = sema.resolve_method_call(&call)?;
+            Some((function, call.name_ref()?.syntax().clone()))
+        }
+    }
+}
+
+/// Describes a callee by the position of its name, like the call hierarchy does.
+pub(super) fn callee_info(
+    sema: &Semantics<'_, RootDatabase>,
+    vfs: &Vfs,
+    function: hir::Function,
+) -> Option<FunctionInfo> {
+    let db = sema.db;
+    let name = sema.source(function)?.value.name()?;
+    let range = sema.original_range(name.syntax());
+    let file_id = range.file_id.file_id(db);
+    let file_path = vfs.file_path(file_id).to_string();
+    let line_col = db.line_index(file_id).line_col(range.range.start());
+    Some(FunctionInfo {
+        name: function.name(db).as_str().to_owned(),
+        generated: is_build_output(&file_path),
+        file_path,
+        line: line_col.line + 1,
+        column: line_col.col + 1,
+        module: module_path(db, function.module(db)),
+        container: container_name(db, function),
+    })
+}
+
+/// The token at the 1-based position of `function` in `file_path`.
+fn function_at(
+    sema: &Semantics<'_, RootDatabase>,
+    vfs: &Vfs,
+    file_path: &str,
+    function: &FunctionInfo,
+) -> Option<SyntaxNode> {
+    token_at(sema, vfs, file_path, LineCol { line: function.line, col: function.column })?.parent()
+}
+
+fn token_at(
+    sema: &Semantics<'_, RootDatabase>,
+    vfs: &Vfs,
+    file_path: &str,
+    position: LineCol,
+) -> Option<syntax::SyntaxToken> {
+    let file_id = find_file_id_by_path(vfs, file_path)?;
+    let offset = sema.db.line_index(file_id).offset(LineCol {
+        line: position.line.saturating_sub(1),
+        col: position.col.saturating_sub(1),
+    })?;
+    let file = sema.parse_guess_edition(file_id);
+    file.syntax().token_at_offset(offset).right_biased()
+}
+
+/// Fills in the `expanded_from` field of every relation.
+pub(super) fn mark_macro_expansions(db: &RootDatabase, vfs: &Vfs, relations: &mut [CallRelation]) {
+    let _p = tracing::info_span!("mark_macro_expansions").entered();
+    salsa::attach(db, || {
+        let sema = Semantics::new(db);
+        for relation in relations {
+            let caller = &relation.caller;
+            // Attribute macros keep the functions they're applied to, their calls are written
+            // in the sources unless the caller is generated.
+            relation.expanded_from = enclosing_macro(
+                &sema,
+                vfs,
+                &caller.file_path,
+                LineCol { line: relation.call_site_line, col: relation.call_site_column },
+                caller.generated,
+            )
+            .or_else(|| {
+                let position = LineCol { line: caller.line, col: caller.column };
+                caller
+                    .generated
+                    .then(|| enclosing_macro(&sema, vfs, &caller.file_path, position, true))
+                    .flatten()
+            });
+        }
+    })
+}
+
+/// The innermost macro call around a 1-based position: `name!` for function-like macros and,
+/// with `with_attributes`, `#[name]` for attribute macros and `derive(Name)` for derives.
+fn enclosing_macro(
+    sema: &Semantics<'_, RootDatabase>,
+    vfs: &Vfs,
+    file_path: &str,
+    position: LineCol,
+    with_attributes: bool,
+) -> Option<String> {
+    let token = token_at(sema, vfs, file_path, position)?;
+    for node in token.parent_ancestors() {
+        if let Some(call) = ast::MacroCall::cast(node.clone()) {
+            let name = match sema.resolve_macro_call(&call) {
+                Some(mac) => mac.name(sema.db).as_str().to_owned(),
+                None => call.path()?.syntax().text().to_string(),
+            };
+            return Some(format!("{name}!"));
+        }
+        if !with_attributes {
+            continue;
+        }
+        if let Some(attr) = ast::Attr::cast(node.clone())
+            && attr.simple_name().as_deref() == Some("derive")
+        {
+            let derives: Vec<String> = sema
+                .resolve_derive_macro(&attr)?
+                .into_iter()
+                .flatten()
+                .map(|mac| mac.name(sema.db).as_str().to_owned())
+                .collect();
+            // Expansions of a single derive map back to its name in the attribute.
+            let name = match token.kind() {
+                SyntaxKind::IDENT if derives.iter().any(|it| it == token.text()) => {
+                    token.text().to_owned()
+                }
+                _ => derives.join(", "),
+            };
+            return Some(format!("derive({name})"));
+        }
+        if let Some(item) = ast::Item::cast(node)
+            && let Some(mac) = sema.resolve_attr_macro_call(&item)
+        {
+            return Some(format!("#[{}]", mac.name(sema.db).as_str()));
+        }
+    }
+    None
+}
@@ -0,0 +1,344 @@
+//! Per function size and complexity metrics computed from the syntax tree, aggregated per
+//! module and checked against thresholds.
+//!
+//! - `complexity`: McCabe's cyclomatic complexity, one plus every `if`, loop condition,
+//!   additional match arm, match guard, `let ... else`, `&&`/`||` and `require!`/`assert!` style
+//!   macro, the latter being how Anchor programs spell most of their checks.
+//! - `max_nesting`: the deepest nesting of `if`, `match`, loops and closures, `else if` chains
+//!   count as a single level.
+//! - `branches`: the number of branching constructs, `if`, `match`, `let ... else` and loops.
+//! - `lines`: source lines of the function, without blank and comment-only lines.
+//! - `statements`: `let` and expression statements, including those of closures.
+//! - `params`: parameters including `self`.
+
+use std::fs;
+
+use anyhow::Result;
+use hir::Semantics;
+use itertools::Itertools;
+use serde::Serialize;
+use syntax::{
+    AstNode, SyntaxKind, SyntaxNode,
+    ast::{self, BinaryOp, HasName},
+};
+
+use crate::cli::{
+    code_graph::{LoadOptions, LoadedProject, module_path, project_files},
+    findings::{Finding, Severity},
+    flags,
+    function_analyzer::convert_to_relative_path,
+};
+
+const DEFAULT_MAX_COMPLEXITY: u32 = 10;
+const DEFAULT_MAX_NESTING: u32 = 4;
+const DEFAULT_MAX_BRANCHES: u32 = 20;
+const DEFAULT_MAX_LINES: u32 = 80;
+const DEFAULT_MAX_STATEMENTS: u32 = 50;
+const DEFAULT_MAX_PARAMS: u32 = 7;
+
+impl flags::Metrics {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("metrics", path = %self.path.display()).entered();
+        eprintln!("Loading workspace...");
+        let project = LoadedProject::load(
+            &self.path,
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+
+        eprintln!("Computing metrics...");
+        let thresholds = Thresholds {
+            complexity: self.max_complexity,
+            nesting: self.max_nesting,
+            branches: self.max_branches,
+            lines: self.max_lines,
+            statements: self.max_statements,
+            params: self.max_params,
+        };
+        let report = metrics_report(&project, &thresholds)?;
+        eprintln!(
+            "Measured {} functions in {} modules, {} above thresholds",
+            report.functions.len(),
+            report.modules.len(),
+            report.findings.len()
+        );
+
+        let json = serde_json::to_string_pretty(&report)?;
+        match &self.output {
+            Some(path) => fs::write(path, json)?,
+            None => println!("{json}"),
+        }
+        Ok(())
+    }
+}
+
+/// Per function limits above which a finding is reported, `None` uses the default.
+#[derive(Debug, Default)]
+pub(super) struct Thresholds {
+    pub(super) complexity: Option<u32>,
+    pub(super) nesting: Option<u32>,
+    pub(super) branches: Option<u32>,
+    pub(super) lines: Option<u32>,
+    pub(super) statements: Option<u32>,
+    pub(super) params: Option<u32>,
+}
+
+impl Thresholds {
+    fn check(&self, functions: &[FunctionMetrics]) -> Vec<Finding> {
+        let limits = [
+            ("complexity", "a cyclomatic complexity", self.complexity, DEFAULT_MAX_COMPLEXITY),
+            ("nesting", "a nesting depth", self.nesting, DEFAULT_MAX_NESTING),
+            ("branches", "a branch count", self.branches, DEFAULT_MAX_BRANCHES),
+            ("lines", "a length in lines", self.lines, DEFAULT_MAX_LINES),
+            ("statements", "a statement count", self.statements, DEFAULT_MAX_STATEMENTS),
+            ("params", "a parameter count", self.params, DEFAULT_MAX_PARAMS),
+        ];
+        let mut findings = Vec::new();
+        for function in functions {
+            let values = [
+                function.complexity,
+                function.max_nesting,
+                function.branches,
+                function.lines,
+                function.statements,
+                function.params,
+            ];
+            for ((rule, what, limit, default), value) in limits.iter().zip(values) {
+                let limit = limit.unwrap_or(*default);
+                if value > limit {
+                    findings.push(Finding {
+                        plugin: "metrics".to_owned(),
+                        rule: (*rule).to_owned(),
+                        message: format!(
+                            "`{}` has {what} of {value}, above the threshold of {limit}",
+                            function.name
+                        ),
+                        severity: Severity::Warning,
+                        file: Some(function.file.clone()),
+                        line: Some(function.line),
+                        related: Vec::new(),
+                    });
+                }
+            }
+        }
+        findings
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct FunctionMetrics {
+    name: String,
+    module: String,
+    file: String,
+    line: u32,
+    complexity: u32,
+    max_nesting: u32,
+    branches: u32,
+    lines: u32,
+    statements: u32,
+    params: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct ModuleMetrics {
+    module: String,
+    functions: usize,
+    lines: u32,
+    total_complexity: u32,
+    max_complexity: u32,
+    average_complexity: f64,
+    max_nesting: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct MetricsReport {
+    functions: Vec<FunctionMetrics>,
+    modules: Vec<ModuleMetrics>,
+    pub(super) findings: Vec<Finding>,
+}
+
+pub(super) fn metrics_report(
+    project: &LoadedProject,
+    thresholds: &Thresholds,
+) -> Result<MetricsReport> {
+    let functions = collect_function_metrics(project)?;
+    let modules = aggregate_modules(&functions);
+    let findings = thresholds.check(&functions);
+    Ok(MetricsReport { functions, modules, findings })
+}
+
+fn collect_function_metrics(project: &LoadedProject) -> Result<Vec<FunctionMetrics>> {
+    let analysis = project.analysis();
+    let sema = Semantics::new(&project.db);
+    let mut functions = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        for func in file.syntax().descendants().filter_map(ast::Fn::cast) {
+            let (Some(name), Some(body)) = (func.name(), func.body()) else { continue };
+            // Functions in cfg'd out modules have no definition, use the module of the file.
+            let module = sema
+                .to_def(&func)
+                .map(|def| def.module(&project.db))
+                .or_else(|| sema.file_to_module_def(file_id))
+                .map(|module| module_path(&project.db, module))
+                .unwrap_or_default();
+            let mut counter = Counter::default();
+            counter.walk(body.syntax(), 0);
+            functions.push(FunctionMetrics {
+                name: name.text().to_string(),
+                module,
+                file: convert_to_relative_path(&file_path, &project.project_root),
+                line: line_index.line_col(name.syntax().text_range().start()).line + 1,
+                complexity: counter.complexity + 1,
+                max_nesting: counter.max_nesting,
+                branches: counter.branches,
+                lines: source_lines(&func.syntax().text().to_string()),
+                statements: counter.statements,
+                params: func.param_list().map_or(0, |params| {
+                    params.params().count() as u32 + u32::from(params.self_param().is_some())
+                }),
+            });
+        }
+    }
+    functions.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(functions)
+}
+
+fn aggregate_modules(functions: &[FunctionMetrics]) -> Vec<ModuleMetrics> {
+    functions
+        .iter()
+        .into_group_map_by(|f| f.module.clone())
+        .into_iter()
+        .map(|(module, functions)| {
+            let total_complexity = functions.iter().map(|f| f.complexity).sum::<u32>();
+            ModuleMetrics {
+                module,
+                functions: functions.len(),
+                lines: functions.iter().map(|f| f.lines).sum(),
+                total_complexity,
+                max_complexity: functions.iter().map(|f| f.complexity).max().unwrap_or_default(),
+                average_complexity: f64::from(total_complexity) / functions.len() as f64,
+                max_nesting: functions.iter().map(|f| f.max_nesting).max().unwrap_or_default(),
+            }
+        })
+        .sorted_by(|a, b| a.module.cmp(&b.module))
+        .collect()
+}
+
+#[derive(Default)]
+struct Counter {
+    /// Decision points, the complexity is one more than this.
+    complexity: u32,
+    max_nesting: u32,
+    branches: u32,
+    statements: u32,
+}
+
+impl Counter {
+    fn walk(&mut self, node: &SyntaxNode, depth: u32) {
+        for child in node.children() {
+            // Nested items are measured on their own.
+            if matches!(
+                child.kind(),
+                SyntaxKind::FN | SyntaxKind::IMPL | SyntaxKind::TRAIT | SyntaxKind::MODULE
+            ) {
+                continue;
+            }
+            let nests = match child.kind() {
+                SyntaxKind::IF_EXPR => {
+                    self.complexity += 1;
+                    self.branches += 1;
+                    // `else if` continues the chain of its parent instead of nesting.
+                    node.kind() != SyntaxKind::IF_EXPR
+                }
+                SyntaxKind::MATCH_EXPR => {
+                    let arms = ast::MatchExpr::cast(child.clone())
+                        .and_then(|it| it.match_arm_list())
+                        .map_or(0, |arms| arms.arms().count() as u32);
+                    self.complexity += arms.saturating_sub(1);
+                    self.branches += 1;
+                    true
+                }
+                SyntaxKind::WHILE_EXPR | SyntaxKind::FOR_EXPR => {
+                    self.complexity += 1;
+                    self.branches += 1;
+                    true
+                }
+                SyntaxKind::LOOP_EXPR => {
+                    self.branches += 1;
+                    true
+                }
+                SyntaxKind::CLOSURE_EXPR => true,
+                SyntaxKind::LET_ELSE => {
+                    self.complexity += 1;
+                    self.branches += 1;
+                    false
+                }
+                SyntaxKind::MATCH_GUARD => {
+                    self.complexity += 1;
+                    false
+                }
+                SyntaxKind::BIN_EXPR => {
+                    if ast::BinExpr::cast(child.clone())
+                        .and_then(|it| it.op_kind())
+                        .is_some_and(|op| matches!(op, BinaryOp::LogicOp(_)))
+                    {
+                        self.complexity += 1;
+                    }
+                    false
+                }
+                SyntaxKind::LET_STMT | SyntaxKind::EXPR_STMT => {
+                    self.statements += 1;
+                    false
+                }
+                SyntaxKind::MACRO_CALL => {
+                    if is_check_macro(&child) {
+                        self.complexity += 1;
+                    }
+                    false
+                }
+                _ => false,
+            };
+            let depth = if nests { depth + 1 } else { depth };
+            self.max_nesting = self.max_nesting.max(depth);
+            self.walk(&child, depth);
+        }
+    }
+}
+
+/// Whether `node` is a `require!`, `assert_eq!` or similar macro, which return early when their
+/// condition does not hold.
+fn is_check_macro(node: &SyntaxNode) -> bool {
+    ast::MacroCall::cast(node.clone())
+        .and_then(|call| call.path()?.segment()?.name_ref())
+        .is_some_and(|name| {
+            let name = name.text();
+            name.starts_with("require") || name.starts_with("assert")
+        })
+}
+
+/// Counts the lines of `text` containing code, skipping blank lines and comments.
+fn source_lines(text: &str) -> u32 {
+    let mut in_block_comment = false;
+    let mut lines = 0;
+    for line in text.lines() {
+        let line = line.trim();
+        if in_block_comment {
+            in_block_comment = !line.contains("*/");
+            continue;
+        }
+        if line.is_empty() || line.starts_with("//") {
+            continue;
+        }
+        if line.starts_with("/*") {
+            in_block_comment = !line.contains("*/");
+            continue;
+        }
+        lines += 1;
+    }
+    lines
+}
@@ -0,0 +1,183 @@
+//! Condensation of the call graph into a module or crate dependency graph for architecture
+//! reviews, every edge weighted by the number of calls it stands for.
+//!
+//! Calls within a module or crate are left out. Functions of unknown modules, like those of
+//! dependencies, count as their crate.
+
+use std::io::Write;
+
+use anyhow::Result;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+
+use crate::cli::{
+    call_csv::write_row,
+    call_dot::quote,
+    call_graphml::{crate_name, escape, write_data},
+    flags::{self, Granularity},
+    function_analyzer::{CallRelation, FunctionInfo},
+    prune::{FunctionKey, Keys},
+};
+
+#[derive(Debug, Serialize)]
+pub(super) struct Dependency {
+    from: String,
+    to: String,
+    /// Number of calls from the functions of `from` to those of `to`.
+    calls: usize,
+}
+
+/// The dependencies between the modules or crates of the functions of `relations`, sorted.
+pub(super) fn condense(
+    functions: &[FunctionInfo],
+    relations: &[CallRelation],
+    granularity: Granularity,
+) -> Vec<Dependency> {
+    // Callees whose definition wasn't found don't know their module, they take it from the
+    // matching project function.
+    let keys = Keys::new(functions);
+    let modules: FxHashMap<FunctionKey, &str> =
+        functions.iter().map(|it| (keys.key(it), it.module.as_str())).collect();
+    let node = |function: &FunctionInfo, external: Option<&str>| {
+        let module = modules.get(&keys.key(function)).copied().unwrap_or(function.module.as_str());
+        let krate = external.unwrap_or_else(|| crate_name(module, &function.file_path));
+        match granularity {
+            Granularity::Module if !module.is_empty() => module.to_owned(),
+            _ => krate.to_owned(),
+        }
+    };
+
+    let mut calls: FxHashMap<(String, String), usize> = FxHashMap::default();
+    for relation in relations {
+        let from = node(&relation.caller, None);
+        let to = node(&relation.callee, relation.callee_crate.as_ref().map(|it| it.name.as_str()));
+        if from != to {
+            *calls.entry((from, to)).or_default() += 1;
+        }
+    }
+    let mut dependencies: Vec<Dependency> =
+        calls.into_iter().map(|((from, to), calls)| Dependency { from, to, calls }).collect();
+    dependencies.sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+    dependencies
+}
+
+/// The JSON document written for `--granularity` with `--format json`.
+#[derive(Serialize)]
+struct DependencyGraph<'a> {
+    granularity: &'static str,
+    pruned: &'a [String],
+    dependencies: &'a [Dependency],
+}
+
+/// A line written for `--granularity` with `--format ndjson`.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record<'a> {
+    Dependency(&'a Dependency),
+    Summary {
+        granularity: &'static str,
+        dependencies: usize,
+        #[serde(skip_serializing_if = "<[String]>::is_empty")]
+        pruned: &'a [String],
+    },
+}
+
+pub(super) fn write_dependencies(
+    writer: &mut dyn Write,
+    dependencies: &[Dependency],
+    pruned: &[String],
+    granularity: Granularity,
+    format: flags::CallFormat,
+) -> Result<()> {
+    let level = match granularity {
+        Granularity::Function => "function",
+        Granularity::Module => "module",
+        Granularity::Crate => "crate",
+    };
+    match format {
+        flags::CallFormat::Text => {
+            writeln!(writer, "# Dependencies between {level}s")?;
+            writeln!(writer, "# Format: {level} -> dependency (number of calls)")?;
+            for decision in pruned {
+                writeln!(writer, "# Pruned: {decision}")?;
+            }
+            writeln!(writer)?;
+            for dependency in dependencies {
+                let Dependency { from, to, calls } = dependency;
+                let plural = if *calls == 1 { "" } else { "s" };
+                writeln!(writer, "{from} -> {to} ({calls} call{plural})")?;
+            }
+        }
+        flags::CallFormat::Json => {
+            let graph = DependencyGraph { granularity: level, pruned, dependencies };
+            serde_json::to_writer_pretty(&mut *writer, &graph)?;
+            writeln!(writer)?;
+        }
+        flags::CallFormat::Ndjson => {
+            let summary =
+                Record::Summary { granularity: level, dependencies: dependencies.len(), pruned };
+            for record in dependencies.iter().map(Record::Dependency).chain([summary]) {
+                serde_json::to_writer(&mut *writer, &record)?;
+                writeln!(writer)?;
+            }
+        }
+        flags::CallFormat::Dot => {
+            for decision in pruned {
+                writeln!(writer, "// Pruned: {decision}")?;
+            }
+            writeln!(writer, "digraph dependencies {{")?;
+            writeln!(writer, "    node [shape=box];")?;
+            for Dependency { from, to, calls } in dependencies {
+                writeln!(
+                    writer,
+                    "    {} -> {} [label=\"{calls}\", weight={calls}];",
+                    quote(from),
+                    quote(to)
+                )?;
+            }
+            writeln!(writer, "}}")?;
+        }
+        flags::CallFormat::Csv => {
+            write_row(writer, &["from", "to", "calls"])?;
+            for Dependency { from, to, calls } in dependencies {
+                write_row(writer, &[from, to, &calls.to_string()])?;
+            }
+        }
+        flags::CallFormat::GraphMl => {
+            writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+            for decision in pruned {
+                writeln!(writer, "<!-- Pruned: {} -->", escape(&decision.replace("--", "- -")))?;
+            }
+            writeln!(writer, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+            writeln!(
+                writer,
+                r#"  <key id="name" for="node" attr.name="name" attr.type="string"/>"#
+            )?;
+            writeln!(
+                writer,
+                r#"  <key id="calls" for="edge" attr.name="calls" attr.type="int"/>"#
+            )?;
+            writeln!(writer, r#"  <graph id="dependencies" edgedefault="directed">"#)?;
+            let mut nodes: FxHashMap<&str, usize> = FxHashMap::default();
+            for name in dependencies.iter().flat_map(|it| [&it.from, &it.to]) {
+                if nodes.contains_key(name.as_str()) {
+                    continue;
+                }
+                let id = nodes.len();
+                nodes.insert(name, id);
+                writeln!(writer, r#"    <node id="n{id}">"#)?;
+                write_data(writer, "name", name)?;
+                writeln!(writer, "    </node>")?;
+            }
+            for (id, dependency) in dependencies.iter().enumerate() {
+                let (from, to) = (nodes[dependency.from.as_str()], nodes[dependency.to.as_str()]);
+                writeln!(writer, r#"    <edge id="e{id}" source="n{from}" target="n{to}">"#)?;
+                write_data(writer, "calls", &dependency.calls.to_string())?;
+                writeln!(writer, "    </edge>")?;
+            }
+            writeln!(writer, "  </graph>")?;
+            writeln!(writer, "</graphml>")?;
+        }
+    }
+    Ok(())
+}
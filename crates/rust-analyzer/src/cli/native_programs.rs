@@ -0,0 +1,368 @@
+//! Native Solana programs, written against `solana-program` without Anchor: the function their
+//! `entrypoint!` hands instructions to, the instruction enum it decodes and, for every
+//! instruction, the handler processing it with the accounts it reads.
+//!
+//! Native programs have no `#[derive(Accounts)]` structs. Handlers take the raw account slice and
+//! read the accounts in order, with `next_account_info(iter)?` or by destructuring the slice like
+//! `let [payer, vault, ..] = accounts`, so the order of those reads is the order clients pass the
+//! accounts in.
+//!
+//! The instruction enum is the enum of the program crate named like `*Instruction` the entrypoint
+//! matches on. A variant is handled by the first project function called in its `match` arm, in
+//! any function the entrypoint reaches. A crate without `entrypoint!` falls back on its
+//! `process_instruction` function, unless it's an Anchor program.
+
+use std::collections::VecDeque;
+
+use anyhow::Result;
+use hir::Semantics;
+use ide::{Analysis, LineIndex};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use syntax::{
+    AstNode, SyntaxKind,
+    ast::{self, HasName},
+};
+use triomphe::Arc;
+
+use crate::cli::{
+    code_graph::{
+        CodeGraph, EnumVariant, GraphEnum, LoadedProject, VariantField, crate_of, module_path,
+        project_files, qualify,
+    },
+    function_analyzer::convert_to_relative_path,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct NativeProgram {
+    /// The crate of the program, like `pump`.
+    pub(super) name: String,
+    /// Path of the function instructions enter the program through.
+    pub(super) entrypoint: String,
+    pub(super) file: String,
+    pub(super) line: u32,
+    /// Path of the enum the instruction data decodes to.
+    pub(super) instruction_enum: Option<String>,
+    /// Whether the instruction enum derives `BorshDeserialize`, which prefixes the data of a
+    /// variant with its index.
+    pub(super) borsh: bool,
+    pub(super) instructions: Vec<NativeInstruction>,
+    /// The accounts the entrypoint reads itself, for programs without an instruction enum.
+    pub(super) accounts: Vec<NativeAccount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct NativeInstruction {
+    /// The variant of the instruction enum, like `Deposit`.
+    pub(super) name: String,
+    /// The position of the variant in the enum.
+    pub(super) index: usize,
+    pub(super) fields: Vec<VariantField>,
+    /// Path of the function processing the instruction. None when no `match` arm of the
+    /// variant calls one.
+    pub(super) handler: Option<String>,
+    /// Where the handler is declared.
+    pub(super) file: Option<String>,
+    pub(super) line: Option<u32>,
+    pub(super) accounts: Vec<NativeAccount>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct NativeAccount {
+    pub(super) name: String,
+    /// The position of the account in the account slice.
+    pub(super) index: usize,
+    /// The line reading the account, in the file of the handler.
+    pub(super) line: u32,
+    /// Whether the handler reads the `is_signer` flag of the account.
+    pub(super) checks_signer: bool,
+    /// Whether the handler reads the `is_writable` flag of the account.
+    pub(super) checks_writable: bool,
+}
+
+/// The body of a function of the graph.
+struct Body {
+    line_index: Arc<LineIndex>,
+    block: ast::BlockExpr,
+}
+
+/// Collects the native programs of the project, one per crate with an entrypoint.
+pub(super) fn extract_native_programs(
+    project: &LoadedProject,
+    analysis: &Analysis,
+    graph: &CodeGraph,
+) -> Result<Vec<NativeProgram>> {
+    let _p = tracing::info_span!("extract_native_programs").entered();
+    let sema = Semantics::new(&project.db);
+    let mut bodies: FxHashMap<usize, Body> = FxHashMap::default();
+    // The functions given to `entrypoint!`, by crate, with the file of the macro call.
+    let mut entrypoints: Vec<(String, String, String)> = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let relative_path = convert_to_relative_path(&file_path, &project.project_root);
+        let module = sema
+            .file_to_module_def(file_id)
+            .map(|module| module_path(&project.db, module))
+            .unwrap_or_default();
+
+        for function in file.syntax().descendants().filter_map(ast::Fn::cast) {
+            let (Some(name), Some(block)) = (function.name(), function.body()) else { continue };
+            let line = line_index.line_col(name.syntax().text_range().start()).line + 1;
+            if let Some(id) = graph.function_at(&relative_path, name.text().as_str(), line) {
+                bodies.insert(id, Body { line_index: line_index.clone(), block });
+            }
+        }
+
+        for call in file.syntax().descendants().filter_map(ast::MacroCall::cast) {
+            let Some(macro_name) = call.path().and_then(|it| it.segment()?.name_ref()) else {
+                continue;
+            };
+            if !matches!(macro_name.text().as_str(), "entrypoint" | "entrypoint_no_alloc") {
+                continue;
+            }
+            let name = call.token_tree().and_then(|tt| {
+                tt.syntax()
+                    .children_with_tokens()
+                    .filter_map(|it| it.into_token())
+                    .find(|token| token.kind() == SyntaxKind::IDENT)
+            });
+            if let Some(name) = name {
+                let krate = crate_of(&module).to_owned();
+                entrypoints.push((krate, name.text().to_owned(), relative_path.clone()));
+            }
+        }
+    }
+
+    let anchor_programs: FxHashSet<&str> =
+        graph.programs.iter().map(|it| it.name.as_str()).collect();
+    let mut roots: Vec<(String, usize)> = Vec::new();
+    for (krate, name, file) in &entrypoints {
+        let candidates = graph
+            .functions
+            .iter()
+            .filter(|it| !it.external && it.name == *name && crate_of(&it.module) == krate);
+        // Prefer the function declared next to the macro call over others of the same name.
+        let Some(function) = candidates.min_by_key(|it| (it.file != *file, &it.file, it.line))
+        else {
+            continue;
+        };
+        if !roots.iter().any(|(it, _)| it == krate) {
+            roots.push((krate.clone(), function.id));
+        }
+    }
+    for function in &graph.functions {
+        let krate = crate_of(&function.module);
+        if function.external
+            || function.name != "process_instruction"
+            || anchor_programs.contains(krate)
+            || roots.iter().any(|(it, _)| it == krate)
+        {
+            continue;
+        }
+        roots.push((krate.to_owned(), function.id));
+    }
+
+    let mut callees: FxHashMap<usize, Vec<usize>> = FxHashMap::default();
+    for call in &graph.calls {
+        callees.entry(call.caller).or_default().push(call.callee);
+    }
+    let mut programs = Vec::new();
+    for (krate, entrypoint) in roots {
+        let mut reached = FxHashSet::from_iter([entrypoint]);
+        let mut queue = VecDeque::from([entrypoint]);
+        while let Some(function) = queue.pop_front() {
+            for &callee in callees.get(&function).into_iter().flatten() {
+                if reached.insert(callee) {
+                    queue.push_back(callee);
+                }
+            }
+        }
+        let mut reached: Vec<usize> = reached.into_iter().collect();
+        reached.sort_unstable();
+
+        let candidates = graph
+            .enums
+            .iter()
+            .filter(|it| crate_of(&it.module) == krate && it.name.ends_with("Instruction"));
+        let Some((enum_, handlers)) = candidates
+            .map(|enum_| (enum_, handlers(graph, &bodies, &reached, enum_)))
+            .enumerate()
+            .max_by_key(|(index, (_, handlers))| (handlers.len(), std::cmp::Reverse(*index)))
+            .map(|(_, it)| it)
+        else {
+            let accounts = bodies.get(&entrypoint).map(accounts_read).unwrap_or_default();
+            programs.push(program(graph, krate, entrypoint, None, Vec::new(), accounts));
+            continue;
+        };
+        let instructions = enum_
+            .variants
+            .iter()
+            .enumerate()
+            .map(|(index, variant)| instruction(graph, &bodies, &handlers, index, variant))
+            .collect();
+        programs.push(program(graph, krate, entrypoint, Some(enum_), instructions, Vec::new()));
+    }
+    programs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(programs)
+}
+
+fn program(
+    graph: &CodeGraph,
+    name: String,
+    entrypoint: usize,
+    enum_: Option<&GraphEnum>,
+    instructions: Vec<NativeInstruction>,
+    accounts: Vec<NativeAccount>,
+) -> NativeProgram {
+    let function = &graph.functions[entrypoint];
+    NativeProgram {
+        name,
+        entrypoint: graph.function_path(entrypoint),
+        file: function.file.clone(),
+        line: function.line,
+        instruction_enum: enum_.map(|it| qualify(&it.module, &it.name)),
+        borsh: enum_.is_some_and(|it| it.derives.iter().any(|it| it == "BorshDeserialize")),
+        instructions,
+        accounts,
+    }
+}
+
+fn instruction(
+    graph: &CodeGraph,
+    bodies: &FxHashMap<usize, Body>,
+    handlers: &FxHashMap<String, usize>,
+    index: usize,
+    variant: &EnumVariant,
+) -> NativeInstruction {
+    let handler = handlers.get(&variant.name).copied();
+    let function = handler.map(|it| &graph.functions[it]);
+    NativeInstruction {
+        name: variant.name.clone(),
+        index,
+        fields: variant.fields.clone(),
+        handler: handler.map(|it| graph.function_path(it)),
+        file: function.map(|it| it.file.clone()),
+        line: function.map(|it| it.line),
+        accounts: handler.and_then(|it| bodies.get(&it)).map(accounts_read).unwrap_or_default(),
+    }
+}
+
+/// The handlers of the variants of `enum_` matched on in the `reached` functions, by variant: the
+/// first project function each `match` arm calls.
+fn handlers(
+    graph: &CodeGraph,
+    bodies: &FxHashMap<usize, Body>,
+    reached: &[usize],
+    enum_: &GraphEnum,
+) -> FxHashMap<String, usize> {
+    let mut handlers = FxHashMap::default();
+    for &function in reached {
+        let Some(body) = bodies.get(&function) else { continue };
+        for arm in body.block.syntax().descendants().filter_map(ast::MatchArm::cast) {
+            let Some(variant) = arm.pat().and_then(|it| matched_variant(&it, enum_)) else {
+                continue;
+            };
+            if handlers.contains_key(&variant) {
+                continue;
+            }
+            let Some(expr) = arm.expr() else { continue };
+            let range = expr.syntax().text_range();
+            let first = body.line_index.line_col(range.start()).line + 1;
+            let last = body.line_index.line_col(range.end()).line + 1;
+            let handler = graph
+                .calls
+                .iter()
+                .filter(|it| {
+                    it.caller == function
+                        && it.callee != function
+                        && (first..=last).contains(&it.line)
+                        && !graph.functions[it.callee].external
+                })
+                .min_by_key(|it| (it.line, it.column));
+            if let Some(handler) = handler {
+                handlers.insert(variant, handler.callee);
+            }
+        }
+    }
+    handlers
+}
+
+/// The variant of `enum_` a `match` arm pattern like `Instruction::Deposit { amount }` matches.
+fn matched_variant(pat: &ast::Pat, enum_: &GraphEnum) -> Option<String> {
+    let path = match pat {
+        ast::Pat::RecordPat(it) => it.path()?,
+        ast::Pat::TupleStructPat(it) => it.path()?,
+        ast::Pat::PathPat(it) => it.path()?,
+        _ => return None,
+    };
+    let qualifier = path.qualifier()?.segment()?.name_ref()?;
+    let variant = path.segment()?.name_ref()?.text().to_string();
+    (qualifier.text() == enum_.name && enum_.variants.iter().any(|it| it.name == variant))
+        .then_some(variant)
+}
+
+/// The accounts `body` reads from the account slice, in order: the bindings of
+/// `next_account_info` calls and of slice patterns like `let [payer, vault] = accounts`.
+fn accounts_read(body: &Body) -> Vec<NativeAccount> {
+    let flag_reads: FxHashSet<(String, String)> = body
+        .block
+        .syntax()
+        .descendants()
+        .filter_map(ast::FieldExpr::cast)
+        .filter_map(|it| Some((it.expr()?.syntax().text().to_string(), it.name_ref()?.to_string())))
+        .collect();
+    let mut names: Vec<(Option<String>, u32)> = Vec::new();
+    for let_ in body.block.syntax().descendants().filter_map(ast::LetStmt::cast) {
+        let (Some(pat), Some(init)) = (let_.pat(), let_.initializer()) else { continue };
+        let line = body.line_index.line_col(let_.syntax().text_range().start()).line + 1;
+        if init
+            .syntax()
+            .descendants()
+            .filter_map(ast::CallExpr::cast)
+            .any(|it| is_next_account_info(&it))
+        {
+            names.push((binding(&pat), line));
+        } else if let ast::Pat::SlicePat(slice) = pat {
+            if !matches!(init, ast::Expr::PathExpr(_) | ast::Expr::RefExpr(_)) {
+                continue;
+            }
+            for pat in slice.pats() {
+                if matches!(pat, ast::Pat::RestPat(_)) {
+                    break;
+                }
+                names.push((binding(&pat), line));
+            }
+        }
+    }
+    names
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, (name, line))| {
+            let name = name?;
+            let reads = |flag: &str| flag_reads.contains(&(name.clone(), flag.to_owned()));
+            Some(NativeAccount {
+                checks_signer: reads("is_signer"),
+                checks_writable: reads("is_writable"),
+                name,
+                index,
+                line,
+            })
+        })
+        .collect()
+}
+
+fn is_next_account_info(call: &ast::CallExpr) -> bool {
+    let Some(ast::Expr::PathExpr(path)) = call.expr() else { return false };
+    path.path()
+        .and_then(|it| it.segment()?.name_ref())
+        .is_some_and(|it| it.text() == "next_account_info")
+}
+
+/// The name a pattern binds, none for `_` and patterns binding several names.
+fn binding(pat: &ast::Pat) -> Option<String> {
+    match pat {
+        ast::Pat::IdentPat(it) => Some(it.name()?.text().to_string()),
+        _ => None,
+    }
+}
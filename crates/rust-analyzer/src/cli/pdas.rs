@@ -0,0 +1,176 @@
+//! The program derived addresses the account structs check with `seeds`, and the program each of
+//! them is derived by.
+//!
+//! Addresses are derived by the program owning the struct unless `seeds::program` names another
+//! one: a program of the workspace, through its `Program<'info, T>` field or its `ID`, or a
+//! well-known program like the token metadata one. Derivations by another program link the two.
+
+use serde::Serialize;
+use syntax::{
+    AstNode, Edition, SourceFile,
+    ast::{self, HasGenericArgs},
+};
+
+use crate::cli::{
+    code_graph::{AccountField, CodeGraph, crate_of, parse_expr},
+    programs::AnchorProgram,
+};
+
+/// Programs outside the workspace, by the names their type, crate or field goes by.
+const KNOWN_PROGRAMS: &[(&[&str], &str, &str)] = &[
+    (&["System", "system_program"], "system_program", "11111111111111111111111111111111"),
+    (
+        &["Token", "token", "spl_token", "token_program"],
+        "token_program",
+        "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
+    ),
+    (
+        &["Token2022", "token_2022", "spl_token_2022", "token_2022_program"],
+        "token_2022_program",
+        "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
+    ),
+    (
+        &[
+            "AssociatedToken",
+            "associated_token",
+            "spl_associated_token_account",
+            "associated_token_program",
+        ],
+        "associated_token_program",
+        "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+    ),
+    (
+        &["Metadata", "mpl_token_metadata", "metadata_program", "token_metadata_program"],
+        "token_metadata_program",
+        "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s",
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct Pda {
+    /// Path of the accounts struct whose field the seeds constrain.
+    pub(super) account_struct: String,
+    pub(super) field: String,
+    /// The elements of the `seeds` array, as written.
+    pub(super) seeds: Vec<String>,
+    /// The `bump` value, none for a bare `bump` Anchor finds itself.
+    pub(super) bump: Option<String>,
+    /// The `seeds::program` expression, none when the program owning the struct derives it.
+    pub(super) seeds_program: Option<String>,
+    /// The program deriving the address, a program of the workspace or a well-known one like
+    /// `token_metadata_program`. None when `seeds_program` doesn't resolve.
+    pub(super) program: Option<String>,
+    pub(super) program_id: Option<String>,
+}
+
+/// Collects the `seeds` constraints of the account structs of `graph`. Its programs have to be
+/// extracted already.
+pub(super) fn extract_pdas(graph: &CodeGraph) -> Vec<Pda> {
+    let _p = tracing::info_span!("extract_pdas").entered();
+    let mut pdas = Vec::new();
+    for strukt in &graph.account_structs {
+        for field in &strukt.fields {
+            let value_of = |kind: &str| {
+                field.constraints.iter().find(|it| it.kind == kind).map(|it| it.value.clone())
+            };
+            let Some(seeds) = value_of("seeds").flatten() else { continue };
+            let seeds_program = value_of("seeds::program").flatten();
+            let program = match &seeds_program {
+                Some(value) => resolve_program(value, &strukt.fields, &graph.programs),
+                None => graph
+                    .programs
+                    .iter()
+                    .find(|it| it.name == crate_of(&strukt.module))
+                    .map(|it| (it.name.clone(), it.declared_id.clone())),
+            };
+            let (program, program_id) = program.unzip();
+            pdas.push(Pda {
+                account_struct: strukt.path(),
+                field: field.name.clone(),
+                seeds: seed_list(&seeds),
+                bump: value_of("bump").flatten(),
+                seeds_program,
+                program,
+                program_id: program_id.flatten(),
+            });
+        }
+    }
+    pdas
+}
+
+/// Records on every program the other programs deriving its PDAs.
+pub(super) fn link_pda_programs(programs: &mut [AnchorProgram], pdas: &[Pda]) {
+    for program in programs.iter_mut() {
+        let mut derived_by: Vec<String> = pdas
+            .iter()
+            .filter(|it| crate_of(&it.account_struct) == program.name)
+            .filter_map(|it| it.program.clone())
+            .filter(|it| *it != program.name)
+            .collect();
+        derived_by.sort();
+        derived_by.dedup();
+        program.pda_programs = derived_by;
+    }
+}
+
+/// The program named by a `seeds::program` expression and its id, if known. `fields` are the
+/// fields of the struct the expression may read, like `other_program.key()`.
+fn resolve_program(
+    value: &str,
+    fields: &[AccountField],
+    programs: &[AnchorProgram],
+) -> Option<(String, Option<String>)> {
+    let expr = parse_expr(value)?;
+    let mut names: Vec<String> = Vec::new();
+    // The root of `other_program.key()` or the segments of `other_program::ID`.
+    let root = expr.syntax().descendants().find_map(ast::PathExpr::cast)?.path()?;
+    let segments: Vec<String> = root
+        .segments()
+        .filter_map(|it| it.name_ref())
+        .map(|it| it.text().to_string())
+        .filter(|it| !matches!(it.as_str(), "crate" | "ID" | "id"))
+        .collect();
+    if let [name] = segments.as_slice()
+        && let Some(field) = fields.iter().find(|it| it.name == *name)
+    {
+        names.extend(program_type(&field.ty));
+    }
+    names.extend(segments);
+
+    names
+        .iter()
+        .find_map(|name| {
+            let snake = stdx::to_lower_snake_case(name);
+            let program = programs.iter().find(|it| it.name == snake)?;
+            Some((program.name.clone(), program.declared_id.clone()))
+        })
+        .or_else(|| {
+            names.iter().find_map(|name| {
+                let (_, program, id) =
+                    KNOWN_PROGRAMS.iter().find(|(aliases, ..)| aliases.contains(&name.as_str()))?;
+                Some(((*program).to_owned(), Some((*id).to_owned())))
+            })
+        })
+}
+
+/// The `T` of a `Program<'info, T>` or `Interface<'info, T>` field type, like `Vault`.
+fn program_type(ty: &str) -> Option<String> {
+    let file = SourceFile::parse(&format!("type T = {ty};"), Edition::CURRENT).tree();
+    let segment = file.syntax().descendants().filter_map(ast::PathSegment::cast).find(|it| {
+        it.name_ref().is_some_and(|it| matches!(it.text().as_str(), "Program" | "Interface"))
+    })?;
+    segment.generic_arg_list()?.generic_args().find_map(|arg| match arg {
+        ast::GenericArg::TypeArg(arg) => Some(arg.ty()?.syntax().text().to_string()),
+        _ => None,
+    })
+}
+
+/// The elements of a `seeds = [..]` array, or the whole value when it isn't a literal array.
+fn seed_list(seeds: &str) -> Vec<String> {
+    match parse_expr(seeds) {
+        Some(ast::Expr::ArrayExpr(array)) => {
+            array.exprs().map(|it| it.syntax().text().to_string()).collect()
+        }
+        _ => vec![seeds.to_owned()],
+    }
+}
@@ -0,0 +1,146 @@
+//! The Anchor programs of a workspace, one per crate with a `#[program]` module, with the ids they
+//! are declared and deployed with and the items of the graph each of them owns.
+//!
+//! Anchor workspaces usually hold several programs under `programs/*`. The graph keeps flat lists
+//! of every item, this splits them by the crate their module belongs to.
+
+use std::{collections::BTreeMap, fs};
+
+use anyhow::{Context, Result};
+use hir::Semantics;
+use serde::Serialize;
+use syntax::{AstNode, AstToken, ast};
+
+use crate::cli::code_graph::{
+    CodeGraph, LoadedProject, crate_of, module_path, project_files, qualify,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct AnchorProgram {
+    /// The crate of the program, like `pump`.
+    pub(super) name: String,
+    /// Path of the `#[program]` module.
+    pub(super) module: String,
+    /// The id given to `declare_id!` in the crate.
+    pub(super) declared_id: Option<String>,
+    /// The ids the `[programs.<cluster>]` tables of `Anchor.toml` deploy it with, by cluster.
+    pub(super) deployed_ids: BTreeMap<String, String>,
+    pub(super) instructions: Vec<String>,
+    pub(super) account_structs: Vec<String>,
+    pub(super) state_accounts: Vec<String>,
+    pub(super) events: Vec<String>,
+    pub(super) error_codes: Vec<String>,
+    /// The other programs deriving the PDAs its accounts check through `seeds::program`, of the
+    /// workspace or well-known ones.
+    pub(super) pda_programs: Vec<String>,
+}
+
+/// Splits the items of `graph` by the program crate declaring them.
+pub(super) fn extract_programs(
+    project: &LoadedProject,
+    graph: &CodeGraph,
+) -> Result<Vec<AnchorProgram>> {
+    let _p = tracing::info_span!("extract_programs").entered();
+    let declared_ids = declared_ids(project);
+    let deployed_ids = deployed_ids(project)?;
+
+    let mut programs: Vec<AnchorProgram> = Vec::new();
+    for instruction in &graph.instructions {
+        let name = crate_of(&instruction.module);
+        if programs.iter().any(|it| it.name == name) {
+            continue;
+        }
+        programs.push(AnchorProgram {
+            name: name.to_owned(),
+            module: instruction.module.clone(),
+            declared_id: declared_ids.get(name).cloned(),
+            deployed_ids: deployed_ids
+                .iter()
+                .filter_map(|(cluster, ids)| Some((cluster.clone(), ids.get(name)?.clone())))
+                .collect(),
+            instructions: paths_in(
+                name,
+                graph.instructions.iter().map(|it| (&it.module, &it.name)),
+            ),
+            account_structs: paths_in(
+                name,
+                graph.account_structs.iter().map(|it| (&it.module, &it.name)),
+            ),
+            state_accounts: paths_in(
+                name,
+                graph.state_accounts.iter().map(|it| (&it.module, &it.name)),
+            ),
+            events: paths_in(name, graph.events.iter().map(|it| (&it.module, &it.name))),
+            error_codes: paths_in(name, graph.error_codes.iter().map(|it| (&it.module, &it.name))),
+            pda_programs: Vec::new(),
+        });
+    }
+    programs.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(programs)
+}
+
+/// The paths of the `items`, given by module and name, declared in the crate `krate`.
+fn paths_in<'a>(krate: &str, items: impl Iterator<Item = (&'a String, &'a String)>) -> Vec<String> {
+    items
+        .filter(|(module, _)| crate_of(module) == krate)
+        .map(|(module, name)| qualify(module, name))
+        .collect()
+}
+
+/// The id of the `declare_id!("...")` of every crate, by crate name.
+fn declared_ids(project: &LoadedProject) -> BTreeMap<String, String> {
+    let sema = Semantics::new(&project.db);
+    let mut ids = BTreeMap::new();
+    for (file_id, _) in project_files(project) {
+        let Some(module) = sema.file_to_module_def(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        for call in file.syntax().descendants().filter_map(ast::MacroCall::cast) {
+            let name = call.path().and_then(|it| it.segment()?.name_ref());
+            if name.is_none_or(|it| it.text() != "declare_id") {
+                continue;
+            }
+            let id = call
+                .token_tree()
+                .into_iter()
+                .flat_map(|it| it.syntax().children_with_tokens().filter_map(|it| it.into_token()));
+            let Some(id) =
+                id.filter_map(ast::String::cast).find_map(|it| Some(it.value().ok()?.into_owned()))
+            else {
+                continue;
+            };
+            let krate = crate_of(&module_path(&project.db, module)).to_owned();
+            ids.insert(krate, id);
+        }
+    }
+    ids
+}
+
+/// The program ids of the `Anchor.toml` of the project, by cluster and program name. Programs are
+/// given either as `name = "<id>"` or as `name = { address = "<id>", .. }`.
+fn deployed_ids(project: &LoadedProject) -> Result<BTreeMap<String, BTreeMap<String, String>>> {
+    let path = project.project_root.join("Anchor.toml");
+    let Ok(text) = fs::read_to_string(&path) else { return Ok(BTreeMap::new()) };
+    let manifest: toml::Table =
+        toml::from_str(&text).with_context(|| format!("failed to parse {path}"))?;
+    let Some(clusters) = manifest.get("programs").and_then(|it| it.as_table()) else {
+        return Ok(BTreeMap::new());
+    };
+    Ok(clusters
+        .iter()
+        .filter_map(|(cluster, programs)| {
+            let programs = programs
+                .as_table()?
+                .iter()
+                .filter_map(|(name, program)| {
+                    let id = match program {
+                        toml::Value::String(id) => id,
+                        program => program.get("address")?.as_str()?,
+                    };
+                    // Program names are crate names, which Cargo accepts with dashes too.
+                    Some((name.replace('-', "_"), id.to_owned()))
+                })
+                .collect();
+            Some((cluster.clone(), programs))
+        })
+        .collect())
+}
@@ -0,0 +1,143 @@
+//! Shrinks the call graph of huge workspaces to something visualizations can still render.
+//!
+//! Every strategy describes what it removed, and those descriptions are written to the output
+//! metadata so a pruned graph is never mistaken for the complete one.
+
+use itertools::Itertools;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use crate::cli::{
+    flags,
+    function_analyzer::{CallRelation, FunctionInfo},
+};
+
+/// Name of the node standing for the generated functions of one expansion.
+const GENERATED: &str = "<generated>";
+
+pub(super) type FunctionKey = (String, u32, String);
+
+/// Identifies functions across calls. Callers start at their item while callees start at their
+/// name, so callees are matched to the closest project function with the same name above them.
+pub(super) struct Keys {
+    lines: FxHashMap<(String, String), Vec<u32>>,
+}
+
+impl Keys {
+    pub(super) fn new(functions: &[FunctionInfo]) -> Keys {
+        let mut lines: FxHashMap<_, Vec<u32>> = FxHashMap::default();
+        for function in functions {
+            lines
+                .entry((function.file_path.clone(), function.name.clone()))
+                .or_default()
+                .push(function.line);
+        }
+        Keys { lines }
+    }
+
+    pub(super) fn key(&self, function: &FunctionInfo) -> FunctionKey {
+        let line = self
+            .lines
+            .get(&(function.file_path.clone(), function.name.clone()))
+            .and_then(|lines| lines.iter().copied().filter(|&line| line <= function.line).max())
+            .unwrap_or(function.line);
+        (function.file_path.clone(), line, function.name.clone())
+    }
+}
+
+impl flags::Prune {
+    /// Applies the selected strategies in turn: generated code is merged first, then leaves are
+    /// dropped and finally the hubs are selected among what remains.
+    pub(super) fn relations(
+        &self,
+        functions: &[FunctionInfo],
+        mut relations: Vec<CallRelation>,
+    ) -> (Vec<CallRelation>, Vec<String>) {
+        let keys = Keys::new(functions);
+        let mut decisions = Vec::new();
+        if self.generated {
+            decisions.push(collapse_generated(&keys, functions, &mut relations));
+        }
+        if let Some(min_fan_in) = self.min_leaf_fan_in {
+            decisions.push(drop_leaves(&keys, &mut relations, min_fan_in));
+        }
+        if let Some(hubs) = self.hubs {
+            decisions.push(keep_hubs(&keys, &mut relations, hubs));
+        }
+        (relations, decisions)
+    }
+}
+
+/// Renames the generated functions to a single node per expansion site, dropping the calls
+/// between functions of the same expansion.
+fn collapse_generated(
+    keys: &Keys,
+    functions: &[FunctionInfo],
+    relations: &mut Vec<CallRelation>,
+) -> String {
+    // Callees only know whether they're generated when they're project functions.
+    let generated: FxHashSet<FunctionKey> =
+        functions.iter().filter(|it| it.generated).map(|it| keys.key(it)).collect();
+    let mut merged = FxHashSet::default();
+    let mut sites = FxHashSet::default();
+    for relation in relations.iter_mut() {
+        for function in [&mut relation.caller, &mut relation.callee] {
+            if function.generated || generated.contains(&keys.key(function)) {
+                merged.insert(keys.key(function));
+                sites.insert((function.file_path.clone(), function.line));
+                function.name = GENERATED.to_owned();
+                function.container = None;
+            }
+        }
+    }
+    relations.retain(|it| keys.key(&it.caller) != keys.key(&it.callee));
+    format!("merged {} generated functions into {} nodes", merged.len(), sites.len())
+}
+
+/// Drops the calls to functions that call nothing themselves and have fewer than
+/// `min_fan_in` distinct callers.
+fn drop_leaves(keys: &Keys, relations: &mut Vec<CallRelation>, min_fan_in: usize) -> String {
+    let callers: FxHashSet<FunctionKey> = relations.iter().map(|it| keys.key(&it.caller)).collect();
+    let mut fan_in: FxHashMap<FunctionKey, FxHashSet<FunctionKey>> = FxHashMap::default();
+    for relation in relations.iter() {
+        fan_in.entry(keys.key(&relation.callee)).or_default().insert(keys.key(&relation.caller));
+    }
+    let leaves: FxHashSet<FunctionKey> = fan_in
+        .into_iter()
+        .filter(|(callee, callers_of)| !callers.contains(callee) && callers_of.len() < min_fan_in)
+        .map(|(callee, _)| callee)
+        .collect();
+
+    let before = relations.len();
+    relations.retain(|it| !leaves.contains(&keys.key(&it.callee)));
+    format!(
+        "dropped {} leaf functions with fewer than {min_fan_in} callers ({} calls)",
+        leaves.len(),
+        before - relations.len()
+    )
+}
+
+/// Keeps the calls between the `count` functions with the most distinct callers and callees.
+fn keep_hubs(keys: &Keys, relations: &mut Vec<CallRelation>, count: usize) -> String {
+    let mut neighbours: FxHashMap<FunctionKey, FxHashSet<FunctionKey>> = FxHashMap::default();
+    for relation in relations.iter() {
+        let (caller, callee) = (keys.key(&relation.caller), keys.key(&relation.callee));
+        neighbours.entry(caller.clone()).or_default().insert(callee.clone());
+        neighbours.entry(callee).or_default().insert(caller);
+    }
+    let total = neighbours.len();
+    let hubs: FxHashSet<FunctionKey> = neighbours
+        .into_iter()
+        .sorted_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)))
+        .take(count)
+        .map(|(function, _)| function)
+        .collect();
+
+    let before = relations.len();
+    relations
+        .retain(|it| hubs.contains(&keys.key(&it.caller)) && hubs.contains(&keys.key(&it.callee)));
+    format!(
+        "kept the {} most connected of {total} functions ({} calls dropped)",
+        hubs.len(),
+        before - relations.len()
+    )
+}
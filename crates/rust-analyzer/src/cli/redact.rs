@@ -0,0 +1,288 @@
+//! Strips string literals, doc comments and absolute paths from command output, so analysis
+//! results of private code can be shared while keeping their structure intact.
+//!
+//! Strings are replaced by a short hash of their contents rather than removed, which keeps equal
+//! literals recognizable as equal.
+
+use std::{fmt::Write as _, path::Path};
+
+use syntax::{
+    AstToken, Edition, SourceFile, SyntaxKind,
+    ast::{self, CommentShape},
+};
+use tenthash::TentHash;
+
+use crate::cli::{
+    authorities::AuthorityKind, code_graph::CodeGraph, constraint_expr::ConstraintExpr, flags,
+    governance::GateKind,
+};
+
+impl flags::Redaction {
+    /// Redacts the string literals and doc comments of a piece of Rust source, which does not
+    /// need to parse cleanly. Line breaks are preserved so line numbers stay valid.
+    pub(super) fn source(&self, text: &str) -> String {
+        if !self.strings && !self.docs {
+            return text.to_owned();
+        }
+        let file = SourceFile::parse(text, Edition::CURRENT);
+        let mut out = String::with_capacity(text.len());
+        for token in file.syntax_node().descendants_with_tokens().filter_map(|it| it.into_token()) {
+            let text = token.text();
+            match token.kind() {
+                SyntaxKind::STRING | SyntaxKind::BYTE_STRING | SyntaxKind::C_STRING
+                    if self.strings =>
+                {
+                    let prefix = match token.kind() {
+                        SyntaxKind::BYTE_STRING => "b",
+                        SyntaxKind::C_STRING => "c",
+                        _ => "",
+                    };
+                    let _ = write!(out, "{prefix}\"<redacted:{}>\"", short_hash(text));
+                    push_newlines(&mut out, text);
+                }
+                SyntaxKind::COMMENT if self.docs => match ast::Comment::cast(token.clone()) {
+                    Some(comment) if comment.is_doc() => {
+                        out.push_str(comment.prefix());
+                        out.push_str(" <redacted>");
+                        push_newlines(&mut out, text);
+                        if comment.kind().shape == CommentShape::Block {
+                            out.push_str(" */");
+                        }
+                    }
+                    _ => out.push_str(text),
+                },
+                _ => out.push_str(text),
+            }
+        }
+        out
+    }
+
+    /// Redacts the value of a string literal.
+    pub(super) fn string(&self, value: &str) -> String {
+        match self.strings {
+            true => format!("<redacted:{}>", short_hash(value)),
+            false => value.to_owned(),
+        }
+    }
+
+    /// Replaces the directories of an absolute path, project relative paths are kept as is.
+    pub(super) fn path(&self, path: &str) -> String {
+        let path_ref = Path::new(path);
+        if !self.paths || !path_ref.is_absolute() {
+            return path.to_owned();
+        }
+        let dir = path_ref.parent().map(|it| it.to_string_lossy()).unwrap_or_default();
+        match path_ref.file_name() {
+            Some(name) => format!("<redacted:{}>/{}", short_hash(&dir), name.to_string_lossy()),
+            None => format!("<redacted:{}>", short_hash(path)),
+        }
+    }
+
+    pub(super) fn graph(&self, graph: &mut CodeGraph) {
+        for function in &mut graph.functions {
+            function.file = self.path(&function.file);
+        }
+        for strukt in &mut graph.account_structs {
+            strukt.file = self.path(&strukt.file);
+            for field in &mut strukt.fields {
+                for constraint in &mut field.constraints {
+                    for part in [&mut constraint.value, &mut constraint.error].into_iter().flatten()
+                    {
+                        *part = self.source(part);
+                    }
+                    if constraint.expr.is_some() {
+                        constraint.expr =
+                            constraint.value.as_deref().and_then(ConstraintExpr::parse);
+                    }
+                    if let Some(variant) = &mut constraint.error_variant {
+                        variant.file = self.path(&variant.file);
+                        variant.msg = variant.msg.as_deref().map(|it| self.string(it));
+                    }
+                }
+            }
+        }
+        for instruction in &mut graph.instructions {
+            instruction.file = self.path(&instruction.file);
+            for guard in &mut instruction.access_control {
+                guard.call = self.source(&guard.call);
+                guard.function_file = guard.function_file.as_deref().map(|it| self.path(it));
+            }
+        }
+        for enum_ in &mut graph.enums {
+            enum_.file = self.path(&enum_.file);
+        }
+        for enum_ in &mut graph.error_codes {
+            enum_.file = self.path(&enum_.file);
+            for msg in enum_.errors.iter_mut().filter_map(|it| it.msg.as_mut()) {
+                *msg = self.string(msg);
+            }
+        }
+        for account in &mut graph.state_accounts {
+            account.file = self.path(&account.file);
+        }
+        for ty in &mut graph.zero_copy_types {
+            ty.file = self.path(&ty.file);
+        }
+        for cpi in &mut graph.cpi_calls {
+            cpi.file = self.path(&cpi.file);
+            let accounts = cpi.accounts.iter_mut().map(|it| &mut it.account);
+            let values = cpi.signer_seeds.iter_mut().chain(&mut cpi.authority);
+            for text in cpi.program.iter_mut().chain(accounts).chain(values) {
+                *text = self.source(text);
+            }
+        }
+        for operation in graph.token_operations.iter_mut().flat_map(|it| &mut it.operations) {
+            operation.file = self.path(&operation.file);
+            for text in operation.authority.iter_mut().chain(&mut operation.signer_seeds) {
+                *text = self.source(text);
+            }
+        }
+        for use_ in graph.sysvars.iter_mut().flat_map(|it| &mut it.sysvars) {
+            use_.file = self.path(&use_.file);
+        }
+        for flow in graph.lamport_flows.iter_mut().flat_map(|it| &mut it.flows) {
+            flow.file = self.path(&flow.file);
+            for text in flow.from.iter_mut().chain(&mut flow.to) {
+                *text = self.source(text);
+            }
+            flow.amount = self.source(&flow.amount);
+        }
+        for program in &mut graph.programs {
+            let deployed = program.deployed_ids.values_mut();
+            for id in program.declared_id.iter_mut().chain(deployed) {
+                *id = self.string(id);
+            }
+        }
+        for program in &mut graph.native_programs {
+            program.file = self.path(&program.file);
+            for instruction in &mut program.instructions {
+                instruction.file = instruction.file.as_deref().map(|it| self.path(it));
+            }
+        }
+        for pda in &mut graph.pdas {
+            let values = pda.bump.iter_mut().chain(&mut pda.seeds_program);
+            for text in pda.seeds.iter_mut().chain(values) {
+                *text = self.source(text);
+            }
+            pda.program_id = pda.program_id.as_deref().map(|it| self.string(it));
+        }
+        for authority in &mut graph.authorities {
+            if authority.kind == AuthorityKind::Address {
+                authority.name = self.source(&authority.name);
+            }
+        }
+        for site in graph
+            .state_access
+            .iter_mut()
+            .flat_map(|it| &mut it.fields)
+            .flat_map(|it| &mut it.writes)
+        {
+            site.file = self.path(&site.file);
+        }
+        for instruction in &mut graph.governance {
+            instruction.file = self.path(&instruction.file);
+            for gate in &mut instruction.gates {
+                gate.file = self.path(&gate.file);
+                if gate.kind != GateKind::StateField {
+                    gate.key = self.source(&gate.key);
+                }
+                for seed in &mut gate.seeds {
+                    *seed = self.source(seed);
+                }
+            }
+        }
+        for finding in &mut graph.findings {
+            finding.message = self.source(&finding.message);
+            finding.file = finding.file.as_deref().map(|it| self.path(it));
+            for related in &mut finding.related {
+                related.file = self.path(&related.file);
+            }
+        }
+        for event in &mut graph.events {
+            event.file = self.path(&event.file);
+            for site in &mut event.emit_sites {
+                site.file = self.path(&site.file);
+            }
+        }
+    }
+}
+
+/// The first 4 bytes of the hash of `text`, enough to tell values apart within one project.
+fn short_hash(text: &str) -> String {
+    let mut hasher = TentHash::new();
+    hasher.update(text.as_bytes());
+    hasher.finalize()[..4].iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn push_newlines(out: &mut String, text: &str) {
+    out.extend(text.chars().filter(|&c| c == '\n'));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redaction(kinds: &str) -> flags::Redaction {
+        kinds.parse().unwrap()
+    }
+
+    #[test]
+    fn redacts_source() {
+        let cases = [
+            (
+                "strings",
+                r#"let a = "x";"#.to_owned(),
+                format!(r#"let a = "<redacted:{}>";"#, short_hash(r#""x""#)),
+            ),
+            (
+                "strings",
+                r#"f(b"x", c"y")"#.to_owned(),
+                format!(
+                    r#"f(b"<redacted:{}>", c"<redacted:{}>")"#,
+                    short_hash(r#"b"x""#),
+                    short_hash(r#"c"y""#)
+                ),
+            ),
+            (
+                "strings",
+                "\"a\nb\" /// doc".to_owned(),
+                format!("\"<redacted:{}>\"\n /// doc", short_hash("\"a\nb\"")),
+            ),
+            (
+                "docs",
+                "/// secret\nfn f() {} // plain \"s\"".to_owned(),
+                "/// <redacted>\nfn f() {} // plain \"s\"".to_owned(),
+            ),
+            (
+                "docs",
+                "/** a\nb */ fn f() {}".to_owned(),
+                "/** <redacted>\n */ fn f() {}".to_owned(),
+            ),
+            ("paths", r#"let a = "x";"#.to_owned(), r#"let a = "x";"#.to_owned()),
+        ];
+        for (kinds, text, expected) in cases {
+            assert_eq!(redaction(kinds).source(&text), expected, "{kinds}: {text}");
+        }
+    }
+
+    #[test]
+    fn redacts_strings_and_paths() {
+        let all = redaction("strings,docs,paths");
+        assert_eq!(all.string("seed"), format!("<redacted:{}>", short_hash("seed")));
+        assert_eq!(all.string("seed"), all.string("seed"));
+        assert_ne!(all.string("seed"), all.string("seeds"));
+        assert_eq!(redaction("docs").string("seed"), "seed");
+
+        let cases = [
+            (
+                "/home/me/pump/src/lib.rs",
+                format!("<redacted:{}>/lib.rs", short_hash("/home/me/pump/src")),
+            ),
+            ("programs/pump/src/lib.rs", "programs/pump/src/lib.rs".to_owned()),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(all.path(path), expected, "{path}");
+            assert_eq!(redaction("strings").path(path), path);
+        }
+    }
+}
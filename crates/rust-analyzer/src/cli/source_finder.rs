@@ -1,18 +1,28 @@
 use std::env;
 use anyhow::{Context, Result};
-use hir::{Crate, ModuleDef, Semantics};
-use ide::{Analysis, AnalysisHost, CallHierarchyConfig, CallItem, FilePosition, LineCol};
+use hir::{Crate, Impl, ModuleDef, ScopeDef, Semantics};
+use ide::{
+    Analysis, AnalysisHost, CallHierarchyConfig, CallItem, FilePosition, LineCol, NavigationTarget,
+    TryToNav,
+};
 use ide_db::{
-    base_db::FileId,
+    base_db::{salsa, FileId},
+    defs::{Definition, NameClass},
     symbol_index::Query,
-    EditionedFileId, LineIndexDatabase,
+    EditionedFileId, LineIndexDatabase, SymbolKind,
 };
+use itertools::Itertools;
 use load_cargo::{load_workspace, LoadCargoConfig, ProcMacroServerChoice};
 use project_model::{CargoConfig, ProjectManifest, ProjectWorkspace, RustLibSource};
+use rustc_hash::FxHashSet;
 use serde::{Deserialize, Serialize};
-use syntax::AstNode;
+use syntax::{ast, AstNode};
 use vfs::{AbsPathBuf, Vfs};
-use crate::cli::flags;
+use crate::cli::{
+    analysis_progress::Progress,
+    flags::{self, MinVisibility},
+    function_analyzer::{is_visible, min_visibility},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Location {
@@ -45,6 +55,21 @@ struct SymbolResult {
     location: Location,
     parameter: Vec<Parameter>,
     calls: Vec<FunctionCall>,
+    /// The project functions reached through `--expand-calls`, level by level
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    expanded: Vec<ExpandedFunction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ExpandedFunction {
+    #[serde(rename = "function")]
+    function_name: String,
+    /// The function calling it, the found function or one expanded before
+    caller: String,
+    /// How many calls away from the found function it is, 1 for its direct callees
+    depth: u32,
+    source: String,
+    location: Location,
 }
 
 #[derive(Debug, Clone)]
@@ -55,8 +80,26 @@ struct FunctionInfo {
     column: u32,
 }
 
+impl flags::ItemKind {
+    /// Whether symbols of `kind` are of this kind, methods counting as functions
+    fn includes(self, kind: Option<SymbolKind>) -> bool {
+        let Some(kind) = kind else {
+            return false;
+        };
+        match self {
+            flags::ItemKind::Function => matches!(kind, SymbolKind::Function | SymbolKind::Method),
+            flags::ItemKind::Struct => kind == SymbolKind::Struct,
+            flags::ItemKind::Enum => kind == SymbolKind::Enum,
+            flags::ItemKind::Trait => kind == SymbolKind::Trait,
+            flags::ItemKind::Const => kind == SymbolKind::Const,
+        }
+    }
+}
+
 impl flags::SourceFinder {
     pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("source_finder", symbol = %self.symbol_name).entered();
+        let load_span = tracing::info_span!("load_workspace").entered();
         let path = AbsPathBuf::assert_utf8(env::current_dir()?.join(&self.project_path));
         
         // Load the project
@@ -71,7 +114,8 @@ impl flags::SourceFinder {
             with_proc_macro_server: ProcMacroServerChoice::Sysroot,
             prefill_caches: false,
         };
-        let ws = ProjectWorkspace::load(manifest, &cargo_config, &|_: String| {})?;
+        let progress = Progress::new(self.progress.unwrap_or_default(), "symbols");
+        let ws = ProjectWorkspace::load(manifest, &cargo_config, &|message| progress.message(&message))?;
         let (db, vfs, _proc_macro) = load_workspace(
             ws,
             &cargo_config.extra_env,
@@ -80,15 +124,26 @@ impl flags::SourceFinder {
         
         let host = AnalysisHost::with_database(db.clone());
         let analysis = host.analysis();
+        drop(load_span);
         
         // Get project root path
         let project_root = AbsPathBuf::assert_utf8(env::current_dir()?.join(&self.project_path));
         
         // Search for symbols and build JSON result
-        let symbols = self.search_symbols_json(&analysis, &vfs, &db, &project_root)?;
+        let symbols = self.search_symbols_json(&analysis, &vfs, &db, &project_root, &progress)?;
         
         // Output JSON - each symbol as a separate JSON object
-        for symbol in symbols {
+        let redaction = self.redact.unwrap_or_default();
+        for mut symbol in symbols {
+            symbol.source = redaction.source(&symbol.source);
+            symbol.location.file = redaction.path(&symbol.location.file);
+            for call in &mut symbol.calls {
+                call.file = redaction.path(&call.file);
+            }
+            for function in &mut symbol.expanded {
+                function.source = redaction.source(&function.source);
+                function.location.file = redaction.path(&function.location.file);
+            }
             let json_output = serde_json::to_string_pretty(&symbol)?;
             println!("{}", json_output);
         }
@@ -101,31 +156,65 @@ impl flags::SourceFinder {
         analysis: &Analysis, 
         vfs: &Vfs, 
         db: &ide::RootDatabase,
-        project_root: &AbsPathBuf
+        project_root: &AbsPathBuf,
+        progress: &Progress,
     ) -> Result<Vec<SymbolResult>> {
-        let mut query = Query::new(self.symbol_name.clone());
-        query.fuzzy(); // Enable fuzzy matching
+        let _p = tracing::info_span!("search_symbols").entered();
+        let level = min_visibility(self.only_public, self.min_visibility)?;
         
-        let search_results = analysis.symbol_search(query, 50)
-            .map_err(|_| anyhow::anyhow!("Symbol search was cancelled"))?;
+        // A path is resolved exactly, so common names like `new` can be told apart
+        let search_results = if self.symbol_name.contains("::") {
+            self.resolve_symbol_path(db, level)
+        } else {
+            let mut query = Query::new(self.symbol_name.clone());
+            query.fuzzy(); // Enable fuzzy matching
+            
+            let search_results = analysis.symbol_search(query, 50)
+                .map_err(|_| anyhow::anyhow!("Symbol search was cancelled"))?;
+            search_results
+                .into_iter()
+                .filter(|nav_target| self.is_visible_enough(db, nav_target, level))
+                .map(|nav_target| (nav_target, None))
+                .collect()
+        };
         
         let mut symbols = Vec::new();
+        progress.extend(search_results.len());
         
-        for nav_target in search_results {
+        for (nav_target, function) in search_results {
+            progress.advance();
+            if self.kind.is_some_and(|kind| !kind.includes(nav_target.kind)) {
+                continue;
+            }
+            
             // Get the source code for this symbol
             if let Ok(source_text) = analysis.file_text(nav_target.file_id) {
                 let (source_code, start_line, end_line) = self.extract_symbol_source(&source_text, &nav_target);
                 let file_path = self.get_file_path(vfs, nav_target.file_id, project_root);
                 
                 // Get function calls if this is a function
-                let function_calls = self.get_function_calls_json(
-                    analysis, 
-                    &nav_target.name.to_string(), 
-                    &file_path, 
-                    vfs, 
-                    db, 
-                    project_root
-                ).unwrap_or_default();
+                let function_calls = match function {
+                    Some(function) => {
+                        self.get_resolved_function_calls_json(analysis, function, vfs, db, project_root)
+                    }
+                    None => self.get_function_calls_json(
+                        analysis, 
+                        nav_target.name.as_str(), 
+                        &file_path, 
+                        vfs, 
+                        db, 
+                        project_root
+                    ),
+                }.unwrap_or_default();
+                
+                // Follow the calls of functions as deep as asked
+                let is_function = matches!(nav_target.kind, Some(SymbolKind::Function | SymbolKind::Method));
+                let expanded = match self.expand_calls {
+                    Some(depth) if is_function => {
+                        self.expand_calls_json(analysis, &nav_target, depth, vfs, project_root)
+                    }
+                    _ => Vec::new(),
+                };
                 
                 // Extract contract name from file path
                 let contract_name = self.extract_file_name(&file_path);
@@ -144,6 +233,7 @@ impl flags::SourceFinder {
                     },
                     parameter: parameters,
                     calls: function_calls,
+                    expanded,
                 };
                 
                 symbols.push(symbol_result);
@@ -153,6 +243,117 @@ impl flags::SourceFinder {
         Ok(symbols)
     }
     
+    /// Resolve a `::` separated path like `my_crate::state::Curve::apply_buy` through hir, starting
+    /// from the crate of its first segment. Every item the path names is returned, with the function
+    /// when it's one.
+    fn resolve_symbol_path(
+        &self,
+        db: &ide::RootDatabase,
+        level: MinVisibility,
+    ) -> Vec<(NavigationTarget, Option<hir::Function>)> {
+        let segments: Vec<&str> = self.symbol_name.split("::").map(str::trim).collect();
+        let Some((crate_name, rest)) = segments.split_first() else {
+            return Vec::new();
+        };
+        salsa::attach(db, || {
+            let mut defs: Vec<Definition> = Crate::all(db)
+                .into_iter()
+                .filter(|krate| {
+                    krate.display_name(db).is_some_and(|name| name.crate_name().as_str() == *crate_name)
+                })
+                .map(|krate| Definition::Module(krate.root_module()))
+                .collect();
+            for segment in rest {
+                defs = defs
+                    .into_iter()
+                    .flat_map(|def| self.resolve_path_segment(db, def, segment))
+                    .unique()
+                    .collect();
+            }
+            defs.into_iter()
+                .filter(|def| is_visible(db, *def, level))
+                .filter_map(|def| {
+                    let nav_target = def.try_to_nav(db)?.call_site;
+                    let function = match def {
+                        Definition::Function(function) => Some(function),
+                        _ => None,
+                    };
+                    Some((nav_target, function))
+                })
+                .collect()
+        })
+    }
+    
+    /// The items named `name` in `def`: the items in scope of a module, the variants and associated
+    /// items of a type, or the items of a trait.
+    fn resolve_path_segment(
+        &self,
+        db: &ide::RootDatabase,
+        def: Definition,
+        name: &str,
+    ) -> Vec<Definition> {
+        let named = |it: Option<hir::Name>| it.is_some_and(|it| it.as_str() == name);
+        match def {
+            Definition::Module(module) => module
+                .scope(db, None)
+                .into_iter()
+                .filter(|(it, _)| it.as_str() == name)
+                .filter_map(|(_, def)| match def {
+                    ScopeDef::ModuleDef(def) => Some(Definition::from(def)),
+                    _ => None,
+                })
+                .collect(),
+            Definition::Adt(adt) => {
+                let variants = match adt {
+                    hir::Adt::Enum(it) => it.variants(db),
+                    _ => Vec::new(),
+                };
+                let variants = variants
+                    .into_iter()
+                    .filter(|it| named(Some(it.name(db))))
+                    .map(Definition::Variant);
+                let items = Impl::all_for_type(db, adt.ty(db))
+                    .into_iter()
+                    .flat_map(|it| it.items(db))
+                    .filter(|it| named(it.name(db)))
+                    .map(Definition::from);
+                variants.chain(items).collect()
+            }
+            Definition::Trait(it) => it
+                .items(db)
+                .into_iter()
+                .filter(|it| named(it.name(db)))
+                .map(Definition::from)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+    
+    /// Whether the symbol of `nav_target` is visible as far as `level`, symbols that don't resolve
+    /// are kept.
+    fn is_visible_enough(
+        &self,
+        db: &ide::RootDatabase,
+        nav_target: &ide::NavigationTarget,
+        level: MinVisibility,
+    ) -> bool {
+        if level == MinVisibility::Private {
+            return true;
+        }
+        salsa::attach(db, || {
+            let sema = Semantics::new(db);
+            let file = sema.parse_guess_edition(nav_target.file_id);
+            let offset = nav_target.focus_or_full_range().start();
+            let def = file
+                .syntax()
+                .token_at_offset(offset)
+                .right_biased()
+                .and_then(|token| ast::Name::cast(token.parent()?))
+                .and_then(|name| NameClass::classify(&sema, &name)?.defined());
+            def.is_none_or(|def| is_visible(db, def, level))
+        })
+    }
+    
     fn extract_symbol_source(&self, source_text: &str, nav_target: &ide::NavigationTarget) -> (String, u32, u32) {
         let full_range = nav_target.full_range;
         let start_offset: usize = full_range.start().into();
@@ -237,6 +438,81 @@ impl flags::SourceFinder {
         Ok(Vec::new())
     }
     
+    /// Get function calls for a function resolved through hir
+    fn get_resolved_function_calls_json(
+        &self,
+        analysis: &Analysis,
+        function: hir::Function,
+        vfs: &Vfs,
+        db: &ide::RootDatabase,
+        project_root: &AbsPathBuf,
+    ) -> Result<Vec<FunctionCall>> {
+        if let Some(func_info) = self.extract_function_info(db, function, vfs)? {
+            return self.analyze_function_calls_json(analysis, &func_info, vfs, db, project_root);
+        }
+        Ok(Vec::new())
+    }
+    
+    /// Follow the outgoing calls of the function of `nav_target` `depth` levels deep, collecting
+    /// the source of every project function reached, once.
+    fn expand_calls_json(
+        &self,
+        analysis: &Analysis,
+        nav_target: &NavigationTarget,
+        depth: u32,
+        vfs: &Vfs,
+        project_root: &AbsPathBuf,
+    ) -> Vec<ExpandedFunction> {
+        let config = CallHierarchyConfig {
+            exclude_tests: false,
+        };
+        let mut seen = FxHashSet::default();
+        seen.insert((nav_target.file_id, nav_target.full_range));
+        let mut frontier = vec![nav_target.clone()];
+        let mut expanded = Vec::new();
+        
+        for level in 1..=depth {
+            let mut next = Vec::new();
+            for caller in frontier {
+                let position = FilePosition {
+                    file_id: caller.file_id,
+                    offset: caller.focus_or_full_range().start(),
+                };
+                let Ok(Some(calls)) = analysis.outgoing_calls(config, position) else {
+                    continue;
+                };
+                for call_item in calls {
+                    let target = call_item.target;
+                    let file_path = vfs.file_path(target.file_id).to_string();
+                    if self.is_external_path(&file_path, project_root)
+                        || !seen.insert((target.file_id, target.full_range))
+                    {
+                        continue;
+                    }
+                    let Ok(source_text) = analysis.file_text(target.file_id) else {
+                        continue;
+                    };
+                    let (source, start_line, end_line) = self.extract_symbol_source(&source_text, &target);
+                    expanded.push(ExpandedFunction {
+                        function_name: target.name.to_string(),
+                        caller: caller.name.to_string(),
+                        depth: level,
+                        source,
+                        location: Location {
+                            file: self.convert_to_relative_path(&file_path, project_root),
+                            start_line,
+                            end_line,
+                        },
+                    });
+                    next.push(target);
+                }
+            }
+            frontier = next;
+        }
+        
+        expanded
+    }
+    
     /// Find file_id by path
     fn find_file_id_by_path(&self, vfs: &Vfs, file_path: &str) -> Option<vfs::FileId> {
         // Convert relative path to absolute path for comparison
@@ -0,0 +1,276 @@
+//! Which instructions read and which write every field of the state accounts, like the
+//! instructions able to change `Global.fee_receiver`.
+//!
+//! Accesses come from two places. The accounts struct of an instruction reads the fields its
+//! constraints name, like `has_one = mint` or `bump = curve.bump`, and writes every field of the
+//! accounts it creates with `init`, `init_if_needed` or `zero`. Function bodies access fields of
+//! the state types through field expressions, resolved by the semantic model: a field assigned to,
+//! borrowed mutably or given as `&mut self` to a method is written, the fields of a struct literal
+//! of the type are written too, any other use reads. Bodies don't tell a value of the type held in
+//! an account from a local copy, both count. Accesses belong to the instructions reaching their
+//! function, accesses no instruction reaches are left out.
+
+use anyhow::Result;
+use hir::Semantics;
+use ide::Analysis;
+use ide_db::RootDatabase;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use syntax::{
+    AstNode, SyntaxNode,
+    ast::{self, BinaryOp, HasName},
+};
+
+use crate::cli::{
+    authorities::state_path,
+    code_graph::{CodeGraph, LoadedProject, body_descendants, module_path, project_files, qualify},
+    constraint_expr::ConstraintExpr,
+    function_analyzer::convert_to_relative_path,
+};
+
+/// The accesses of the fields of an `#[account]` struct.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct StateAccess {
+    /// The path of the state account, like `pump::state::Global`.
+    pub(super) account: String,
+    /// Every field of the account, in declaration order.
+    pub(super) fields: Vec<FieldAccess>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct FieldAccess {
+    pub(super) field: String,
+    /// The paths of the instructions reading the field.
+    pub(super) read_by: Vec<String>,
+    /// The paths of the instructions writing the field.
+    pub(super) written_by: Vec<String>,
+    /// Where the instructions write the field.
+    pub(super) writes: Vec<WriteSite>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub(super) struct WriteSite {
+    pub(super) file: String,
+    pub(super) line: u32,
+    /// The function writing the field, none for the `init` constraint of an accounts struct.
+    pub(super) function: Option<String>,
+}
+
+/// A read or write of a field of a state account.
+struct Access {
+    /// The path of the state account.
+    account: String,
+    field: String,
+    write: bool,
+    file: String,
+    line: u32,
+    function: Option<String>,
+    /// The paths of the instructions making the access.
+    instructions: Vec<String>,
+}
+
+/// Builds the access matrix of every state account of `graph`.
+pub(super) fn extract_state_access(
+    project: &LoadedProject,
+    analysis: &Analysis,
+    graph: &CodeGraph,
+) -> Result<Vec<StateAccess>> {
+    let _p = tracing::info_span!("extract_state_access").entered();
+    let mut accesses = constraint_accesses(graph);
+    accesses.extend(body_accesses(project, analysis, graph)?);
+
+    let mut matrix = Vec::new();
+    for state in &graph.state_accounts {
+        let account = qualify(&state.module, &state.name);
+        let fields = state
+            .fields
+            .iter()
+            .map(|field| {
+                let accesses = accesses
+                    .iter()
+                    .filter(|it| it.account == account && it.field == field.name)
+                    .collect::<Vec<_>>();
+                let by = |write: bool| {
+                    let mut instructions: Vec<String> = accesses
+                        .iter()
+                        .filter(|it| it.write == write)
+                        .flat_map(|it| it.instructions.iter().cloned())
+                        .collect();
+                    instructions.sort();
+                    instructions.dedup();
+                    instructions
+                };
+                let mut writes: Vec<WriteSite> = accesses
+                    .iter()
+                    .filter(|it| it.write)
+                    .map(|it| WriteSite {
+                        file: it.file.clone(),
+                        line: it.line,
+                        function: it.function.clone(),
+                    })
+                    .collect();
+                writes.sort();
+                writes.dedup();
+                FieldAccess {
+                    field: field.name.clone(),
+                    read_by: by(false),
+                    written_by: by(true),
+                    writes,
+                }
+            })
+            .collect();
+        matrix.push(StateAccess { account, fields });
+    }
+    matrix.sort_by(|a, b| a.account.cmp(&b.account));
+    Ok(matrix)
+}
+
+/// The fields the constraints of the accounts structs read, and the fields of the accounts they
+/// create.
+fn constraint_accesses(graph: &CodeGraph) -> Vec<Access> {
+    let states: FxHashSet<String> =
+        graph.state_accounts.iter().map(|it| qualify(&it.module, &it.name)).collect();
+    let mut accesses = Vec::new();
+    for strukt in &graph.account_structs {
+        // The state account stored in a field of the struct.
+        let state_of = |name: &str| {
+            let field = strukt.fields.iter().find(|it| it.name == name)?;
+            let state = state_path(graph, strukt, field.account_type.as_deref()?);
+            states.contains(&state).then_some(state)
+        };
+        let mut access = |account: String, field: String, write: bool, line: u32| {
+            accesses.push(Access {
+                account,
+                field,
+                write,
+                file: strukt.file.clone(),
+                line,
+                function: None,
+                instructions: strukt.instructions.clone(),
+            })
+        };
+        for field in &strukt.fields {
+            let state = state_of(&field.name);
+            for constraint in &field.constraints {
+                if let Some(state) = &state
+                    && matches!(constraint.kind.as_str(), "init" | "init_if_needed" | "zero")
+                {
+                    let account = graph
+                        .state_accounts
+                        .iter()
+                        .find(|it| qualify(&it.module, &it.name) == *state);
+                    for written in account.into_iter().flat_map(|it| &it.fields) {
+                        access(state.clone(), written.name.clone(), true, field.line);
+                    }
+                }
+                let Some(value) = &constraint.value else { continue };
+                if let Some(state) = &state
+                    && constraint.kind == "has_one"
+                {
+                    access(state.clone(), value.clone(), false, field.line);
+                }
+                let expr = constraint.expr.clone().or_else(|| ConstraintExpr::parse(value));
+                let Some(expr) = expr else { continue };
+                for (holder, read) in expr.walk().into_iter().filter_map(|it| it.field_read()) {
+                    if let Some(state) = state_of(holder) {
+                        access(state, read.to_owned(), false, field.line);
+                    }
+                }
+            }
+        }
+    }
+    accesses
+}
+
+/// The accesses of fields of state accounts in the bodies of the functions instructions reach.
+fn body_accesses(
+    project: &LoadedProject,
+    analysis: &Analysis,
+    graph: &CodeGraph,
+) -> Result<Vec<Access>> {
+    let sema = Semantics::new(&project.db);
+    let states: FxHashSet<String> =
+        graph.state_accounts.iter().map(|it| qualify(&it.module, &it.name)).collect();
+    let mut reaching: FxHashMap<usize, Vec<String>> = FxHashMap::default();
+    for (instruction, functions) in graph.instruction_reach() {
+        for function in functions {
+            reaching.entry(function).or_default().push(instruction.clone());
+        }
+    }
+
+    let mut accesses = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let relative_path = convert_to_relative_path(&file_path, &project.project_root);
+        let line_of = |node: &SyntaxNode| line_index.line_col(node.text_range().start()).line + 1;
+
+        for function in file.syntax().descendants().filter_map(ast::Fn::cast) {
+            let Some(body) = function.body() else { continue };
+            let Some(function_id) = function.name().and_then(|name| {
+                graph.function_at(&relative_path, name.text().as_str(), line_of(name.syntax()))
+            }) else {
+                continue;
+            };
+            let Some(instructions) = reaching.get(&function_id) else { continue };
+
+            for node in body_descendants(body.syntax()) {
+                let (field, write) = if let Some(expr) = ast::FieldExpr::cast(node.clone()) {
+                    let Some(field) = sema.resolve_field(&expr).and_then(|it| it.left()) else {
+                        continue;
+                    };
+                    (field, is_written(&sema, &expr))
+                } else if let Some(field) = ast::RecordExprField::cast(node.clone()) {
+                    let Some((field, ..)) = sema.resolve_record_field(&field) else { continue };
+                    (field, true)
+                } else {
+                    continue;
+                };
+                let Some(account) = state_of(sema.db, field).filter(|it| states.contains(it))
+                else {
+                    continue;
+                };
+                accesses.push(Access {
+                    account,
+                    field: field.name(sema.db).as_str().to_owned(),
+                    write,
+                    file: relative_path.clone(),
+                    line: line_of(&node),
+                    function: Some(graph.function_path(function_id)),
+                    instructions: instructions.clone(),
+                });
+            }
+        }
+    }
+    Ok(accesses)
+}
+
+/// The path of the struct declaring `field`.
+fn state_of(db: &RootDatabase, field: hir::Field) -> Option<String> {
+    let hir::VariantDef::Struct(strukt) = field.parent_def(db) else { return None };
+    Some(qualify(&module_path(db, strukt.module(db)), strukt.name(db).as_str()))
+}
+
+/// Whether the place `expr` is part of is assigned to, borrowed mutably or the `&mut self` of a
+/// method call, like `curve.reserves` in `curve.reserves.push(amount)`.
+fn is_written(sema: &Semantics<'_, RootDatabase>, expr: &ast::FieldExpr) -> bool {
+    let mut place = ast::Expr::FieldExpr(expr.clone());
+    while let Some(parent) = place.syntax().parent().and_then(ast::Expr::cast) {
+        place = match parent {
+            ast::Expr::FieldExpr(_) | ast::Expr::ParenExpr(_) => parent,
+            ast::Expr::IndexExpr(ref index) if index.base().as_ref() == Some(&place) => parent,
+            ast::Expr::BinExpr(assignment) => {
+                return matches!(assignment.op_kind(), Some(BinaryOp::Assignment { .. }))
+                    && assignment.lhs().as_ref() == Some(&place);
+            }
+            ast::Expr::RefExpr(reference) => return reference.mut_token().is_some(),
+            ast::Expr::MethodCallExpr(call) if call.receiver().as_ref() == Some(&place) => {
+                let self_param =
+                    sema.resolve_method_call(&call).and_then(|it| it.self_param(sema.db));
+                return self_param.is_some_and(|it| it.access(sema.db) == hir::Access::Exclusive);
+            }
+            _ => return false,
+        };
+    }
+    false
+}
@@ -0,0 +1,382 @@
+//! The Anchor `#[account]` structs of a project, the state its programs store in accounts, with
+//! the space they take.
+//!
+//! Accounts are Borsh serialized after an 8 byte discriminator. Their size is computed the way
+//! `#[derive(InitSpace)]` does, `#[max_len(..)]` bounding strings and vectors, so `space = 8 + X`
+//! constraints can be checked. `zero_copy` accounts are stored as is, their size is the memory
+//! layout of the struct.
+//!
+//! Fields come with their offset in the account data, discriminator included, so indexers and
+//! fuzzers can read them without deserializing the whole account. A string, vector, option or enum
+//! whose variants differ in size shifts the fields after it, they have no fixed offset.
+
+use anyhow::Result;
+use hir::Semantics;
+use ide::Analysis;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use syntax::{
+    AstNode,
+    ast::{self, HasAttrs, HasGenericArgs, HasName},
+};
+
+use crate::cli::{
+    code_graph::{LoadedProject, Visibilities, derive_names, module_path, project_files},
+    discriminators::discriminator,
+    function_analyzer::convert_to_relative_path,
+    zero_copy::Layout,
+};
+
+/// Bytes of the discriminator prepended to the data of every account.
+pub(super) const DISCRIMINATOR_SIZE: u64 = 8;
+
+/// An `#[account]` struct.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct StateAccount {
+    pub(super) name: String,
+    pub(super) file: String,
+    pub(super) line: u32,
+    pub(super) module: String,
+    /// Like `pub` or `pub(crate)`, `private` when only its module sees it.
+    pub(super) visibility: String,
+    /// Declared `#[account(zero_copy)]`, stored with its memory layout rather than serialized.
+    pub(super) zero_copy: bool,
+    /// The discriminator prefixing its data, as hex. None when it isn't a literal.
+    pub(super) discriminator: Option<String>,
+    /// Whether it derives `InitSpace`, providing `INIT_SPACE`.
+    pub(super) init_space: bool,
+    pub(super) fields: Vec<StateField>,
+    /// The bytes of the account, discriminator included. None when a field has no bounded size.
+    pub(super) size: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct StateField {
+    pub(super) name: String,
+    pub(super) ty: String,
+    pub(super) visibility: String,
+    /// The serialized size of the field, none when it isn't bounded or known.
+    pub(super) size: Option<u64>,
+    /// The offset of the field in the account data, counting the discriminator. None when a field
+    /// before it varies in size or has no known size.
+    pub(super) offset: Option<u64>,
+}
+
+/// The structs and enums of the project by name, whose size fields of their type take.
+struct Types {
+    items: FxHashMap<String, ast::Adt>,
+    /// The items whose size is being computed, to stop at recursive types.
+    computing: FxHashSet<String>,
+}
+
+/// Collects every `#[account]` struct declared in project files.
+pub(super) fn extract_state_accounts(
+    project: &LoadedProject,
+    analysis: &Analysis,
+) -> Result<Vec<StateAccount>> {
+    let _p = tracing::info_span!("extract_state_accounts").entered();
+    let sema = Semantics::new(&project.db);
+    let files: Vec<_> = project_files(project)
+        .into_iter()
+        .map(|(file_id, file_path)| (file_id, file_path, sema.parse_guess_edition(file_id)))
+        .collect();
+    let mut types = Types { items: FxHashMap::default(), computing: FxHashSet::default() };
+    for (_, _, file) in &files {
+        for adt in file.syntax().descendants().filter_map(ast::Adt::cast) {
+            if let Some(name) = adt.name() {
+                types.items.entry(name.text().to_string()).or_insert(adt);
+            }
+        }
+    }
+
+    let mut accounts = Vec::new();
+    for (file_id, file_path, file) in &files {
+        let Ok(line_index) = analysis.file_line_index(*file_id) else { continue };
+        for strukt in file.syntax().descendants().filter_map(ast::Struct::cast) {
+            let Some(attr) =
+                strukt.attrs().find(|attr| attr.simple_name().as_deref() == Some("account"))
+            else {
+                continue;
+            };
+            let Some(name) = strukt.name() else { continue };
+            let zero_copy = attr
+                .token_tree()
+                .is_some_and(|tt| tt.syntax().text().to_string().contains("zero_copy"));
+            let visibilities = Visibilities::of(&sema, &strukt);
+            let layout = if zero_copy { Layout::of(&sema, &strukt) } else { None };
+            let mut fields: Vec<StateField> = Vec::new();
+            let mut offset = Some(DISCRIMINATOR_SIZE);
+            for field in record_fields(&strukt) {
+                let name = field.name().map(|it| it.text().to_string()).unwrap_or_default();
+                let size = types.field_size(&field, field.ty());
+                let field_offset = match &layout {
+                    Some(layout) => layout
+                        .fields
+                        .iter()
+                        .find(|it| it.name == name)
+                        .and_then(|it| Some(it.offset? + DISCRIMINATOR_SIZE)),
+                    None => offset,
+                };
+                let varies = field.ty().is_none_or(|it| types.varies(&it));
+                offset = offset.zip(size).filter(|_| !varies).map(|(offset, size)| offset + size);
+                fields.push(StateField {
+                    name,
+                    ty: field.ty().map(|it| it.syntax().text().to_string()).unwrap_or_default(),
+                    visibility: visibilities.field(&field),
+                    size,
+                    offset: field_offset,
+                });
+            }
+            let size = match &layout {
+                Some(layout) => layout.size,
+                None if zero_copy => None,
+                None => fields.iter().map(|it| it.size).sum(),
+            };
+            let def = sema.to_def(&strukt);
+            accounts.push(StateAccount {
+                name: name.text().to_string(),
+                file: convert_to_relative_path(file_path, &project.project_root),
+                line: line_index.line_col(name.syntax().text_range().start()).line + 1,
+                module: def
+                    .map(|it| module_path(&project.db, it.module(&project.db)))
+                    .unwrap_or_default(),
+                visibility: visibilities.item,
+                zero_copy,
+                discriminator: discriminator(&attr, "account", name.text().as_str()),
+                init_space: derive_names(&strukt).iter().any(|it| it.ends_with("InitSpace")),
+                fields,
+                size: size.map(|it| it + DISCRIMINATOR_SIZE),
+            });
+        }
+    }
+    accounts.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(accounts)
+}
+
+fn record_fields(strukt: &ast::Struct) -> impl Iterator<Item = ast::RecordField> {
+    let fields = match strukt.field_list() {
+        Some(ast::FieldList::RecordFieldList(fields)) => Some(fields.fields()),
+        _ => None,
+    };
+    fields.into_iter().flatten()
+}
+
+impl Types {
+    /// The serialized size of `field`, its `#[max_len(..)]` bounding its strings and vectors.
+    fn field_size(&mut self, field: &impl HasAttrs, ty: Option<ast::Type>) -> Option<u64> {
+        let mut max_len = max_len(field).into_iter();
+        self.type_size(&ty?, &mut max_len)
+    }
+
+    fn fields_size(&mut self, fields: Option<ast::FieldList>) -> Option<u64> {
+        match fields {
+            Some(ast::FieldList::RecordFieldList(fields)) => {
+                fields.fields().map(|it| self.field_size(&it, it.ty())).sum()
+            }
+            Some(ast::FieldList::TupleFieldList(fields)) => {
+                fields.fields().map(|it| self.field_size(&it, it.ty())).sum()
+            }
+            None => Some(0),
+        }
+    }
+
+    fn type_size(
+        &mut self,
+        ty: &ast::Type,
+        max_len: &mut impl Iterator<Item = u64>,
+    ) -> Option<u64> {
+        match ty {
+            ast::Type::ArrayType(array) => {
+                let len = array.const_arg()?.syntax().text().to_string().replace('_', "");
+                let len: u64 = len.parse().ok()?;
+                Some(len * self.type_size(&array.ty()?, max_len)?)
+            }
+            ast::Type::TupleType(tuple) => {
+                tuple.fields().map(|it| self.type_size(&it, max_len)).sum()
+            }
+            ast::Type::ParenType(ty) => self.type_size(&ty.ty()?, max_len),
+            ast::Type::PathType(path) => {
+                let (name, args) = name_and_args(path)?;
+                match (name.as_str(), args.as_slice()) {
+                    ("u8" | "i8" | "bool", []) => Some(1),
+                    ("u16" | "i16", []) => Some(2),
+                    ("u32" | "i32" | "f32" | "char", []) => Some(4),
+                    ("u64" | "i64" | "f64" | "usize" | "isize", []) => Some(8),
+                    ("u128" | "i128", []) => Some(16),
+                    ("Pubkey", []) => Some(32),
+                    ("String", []) => Some(4 + max_len.next()?),
+                    ("Vec", [item]) => {
+                        let len = max_len.next()?;
+                        Some(4 + len * self.type_size(item, max_len)?)
+                    }
+                    ("Option", [item]) => Some(1 + self.type_size(item, max_len)?),
+                    ("Box", [item]) => self.type_size(item, max_len),
+                    (_, []) => self.item_size(&name),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the serialized size of `ty` depends on its value, like for strings, vectors,
+    /// options and enums whose variants differ in size.
+    fn varies(&mut self, ty: &ast::Type) -> bool {
+        match ty {
+            ast::Type::ArrayType(array) => array.ty().is_none_or(|it| self.varies(&it)),
+            ast::Type::TupleType(tuple) => tuple.fields().any(|it| self.varies(&it)),
+            ast::Type::ParenType(ty) => ty.ty().is_none_or(|it| self.varies(&it)),
+            ast::Type::PathType(path) => {
+                let Some((name, args)) = name_and_args(path) else { return true };
+                match (name.as_str(), args.as_slice()) {
+                    ("String" | "Vec" | "Option", _) => true,
+                    ("Box", [item]) => self.varies(item),
+                    (_, []) => self.item_varies(&name),
+                    _ => true,
+                }
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether the serialized size of the struct or enum of the project named `name` depends on
+    /// its value. Types outside of the project, like `Pubkey`, have a fixed size.
+    fn item_varies(&mut self, name: &str) -> bool {
+        let Some(item) = self.items.get(name).cloned() else { return false };
+        if !self.computing.insert(name.to_owned()) {
+            return true;
+        }
+        let fields = |list: Option<ast::FieldList>| -> Vec<ast::Type> {
+            match list {
+                Some(ast::FieldList::RecordFieldList(fields)) => {
+                    fields.fields().filter_map(|it| it.ty()).collect()
+                }
+                Some(ast::FieldList::TupleFieldList(fields)) => {
+                    fields.fields().filter_map(|it| it.ty()).collect()
+                }
+                None => Vec::new(),
+            }
+        };
+        let varies = match &item {
+            ast::Adt::Struct(strukt) => {
+                fields(strukt.field_list()).iter().any(|it| self.varies(it))
+            }
+            ast::Adt::Enum(enum_) => {
+                let variants: Vec<ast::Variant> =
+                    enum_.variant_list().into_iter().flat_map(|it| it.variants()).collect();
+                let sizes: FxHashSet<Option<u64>> =
+                    variants.iter().map(|it| self.fields_size(it.field_list())).collect();
+                sizes.len() > 1
+                    || variants
+                        .iter()
+                        .flat_map(|it| fields(it.field_list()))
+                        .any(|it| self.varies(&it))
+            }
+            ast::Adt::Union(_) => true,
+        };
+        self.computing.remove(name);
+        varies
+    }
+
+    /// The serialized size of the struct or enum of the project named `name`.
+    fn item_size(&mut self, name: &str) -> Option<u64> {
+        let item = self.items.get(name)?.clone();
+        if !self.computing.insert(name.to_owned()) {
+            return None;
+        }
+        let size = match &item {
+            ast::Adt::Struct(strukt) => self.fields_size(strukt.field_list()),
+            // The variant index takes a byte, followed by the fields of the largest variant.
+            ast::Adt::Enum(enum_) => enum_
+                .variant_list()
+                .into_iter()
+                .flat_map(|it| it.variants())
+                .map(|variant| self.fields_size(variant.field_list()))
+                .collect::<Option<Vec<u64>>>()
+                .map(|sizes| 1 + sizes.into_iter().max().unwrap_or(0)),
+            ast::Adt::Union(_) => None,
+        };
+        self.computing.remove(name);
+        size
+    }
+}
+
+/// The name of the last segment of `path` and its type arguments, like `Vec` and `u8` for
+/// `Vec<u8>`.
+fn name_and_args(path: &ast::PathType) -> Option<(String, Vec<ast::Type>)> {
+    let segment = path.path()?.segment()?;
+    let name = segment.name_ref()?.text().to_string();
+    let args = segment
+        .generic_arg_list()
+        .into_iter()
+        .flat_map(|it| it.generic_args())
+        .filter_map(|arg| match arg {
+            ast::GenericArg::TypeArg(arg) => arg.ty(),
+            _ => None,
+        })
+        .collect();
+    Some((name, args))
+}
+
+/// The lengths given by the `#[max_len(..)]` attribute of a field, outermost first.
+fn max_len(field: &impl HasAttrs) -> Vec<u64> {
+    let Some((_, tt)) =
+        field.attrs().filter_map(|attr| attr.as_simple_call()).find(|(name, _)| name == "max_len")
+    else {
+        return Vec::new();
+    };
+    let text = tt.syntax().text().to_string();
+    text.trim_start_matches('(')
+        .trim_end_matches(')')
+        .split(',')
+        .map_while(|it| it.trim().replace('_', "").parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use syntax::{Edition, SourceFile};
+
+    use super::*;
+
+    const TYPES: &str = "
+        enum Tier { Free, Paid { until: i64 } }
+        enum Side { Buy, Sell }
+        struct Inner(u16, [Pubkey; 2]);
+        struct Node { next: Option<Box<Node>> }
+    ";
+
+    /// The serialized size of the fields of `State`, and whether one of them varies in size.
+    fn state_size(fields: &str) -> (Option<u64>, bool) {
+        let file =
+            SourceFile::parse(&format!("{TYPES} struct State {{ {fields} }}"), Edition::CURRENT);
+        let mut types = Types { items: FxHashMap::default(), computing: FxHashSet::default() };
+        for adt in file.tree().syntax().descendants().filter_map(ast::Adt::cast) {
+            types.items.insert(adt.name().unwrap().text().to_string(), adt);
+        }
+        let Some(ast::Adt::Struct(state)) = types.items.get("State").cloned() else { panic!() };
+        let size = record_fields(&state).map(|it| types.field_size(&it, it.ty())).sum();
+        let varies = record_fields(&state).any(|it| types.varies(&it.ty().unwrap()));
+        (size, varies)
+    }
+
+    #[test]
+    fn computes_init_space() {
+        let cases = [
+            ("a: u64, b: Pubkey, c: bool", Some(41), false),
+            ("#[max_len(32)] name: String", Some(36), true),
+            ("#[max_len(10)] items: Vec<u16>", Some(24), true),
+            ("#[max_len(5, 8)] names: Vec<String>", Some(64), true),
+            ("owner: Option<Pubkey>", Some(33), true),
+            ("bytes: [u8; 1_000], pair: (u8, u32)", Some(1005), false),
+            ("tier: Tier", Some(9), true),
+            ("side: Side", Some(1), false),
+            ("inner: Box<Inner>", Some(66), false),
+            ("name: String", None, true),
+            ("node: Node", None, true),
+        ];
+        for (fields, size, varies) in cases {
+            assert_eq!(state_size(fields), (size, varies), "{fields}");
+        }
+    }
+}
@@ -0,0 +1,281 @@
+//! Inventories the string and byte string literals of project code, grouped by the item they
+//! appear in.
+//!
+//! Literals are tagged when they look like something that deserves a second look:
+//!
+//! - `pubkey`: a base58 string decoding to 32 bytes, e.g. a hardcoded authority or program id,
+//! - `url`: an `http(s)://` or `ws(s)://` address,
+//! - `seed`: a byte string used as a PDA seed, in a `seeds = [...]` account constraint, a
+//!   `find_program_address`/`create_program_address` call or a `*seeds` binding.
+
+use std::fs;
+
+use anyhow::Result;
+use hir::Semantics;
+use itertools::Itertools;
+use serde::Serialize;
+use syntax::{
+    AstNode, AstToken, SyntaxKind, SyntaxNode, SyntaxToken,
+    ast::{self, HasName},
+};
+
+use crate::cli::{
+    code_graph::{LoadOptions, LoadedProject, project_files},
+    flags,
+    function_analyzer::convert_to_relative_path,
+};
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+impl flags::Strings {
+    pub fn run(self) -> Result<()> {
+        let _p = tracing::info_span!("strings", path = %self.path.display()).entered();
+        eprintln!("Loading workspace...");
+        let project = LoadedProject::load(
+            &self.path,
+            LoadOptions {
+                disable_build_scripts: self.disable_build_scripts,
+                disable_proc_macros: self.disable_proc_macros,
+                proc_macro_srv: self.proc_macro_srv.as_deref(),
+            },
+        )?;
+
+        eprintln!("Collecting string literals...");
+        let mut items = collect_items(&project);
+        if self.tagged {
+            for item in &mut items {
+                item.literals.retain(|literal| !literal.tags.is_empty());
+            }
+            items.retain(|item| !item.literals.is_empty());
+        }
+        let literals = || items.iter().flat_map(|item| &item.literals);
+        let count = |tag| literals().filter(|literal| literal.tags.contains(&tag)).count();
+        let summary = Summary {
+            literals: literals().count(),
+            pubkeys: count("pubkey"),
+            urls: count("url"),
+            seeds: count("seed"),
+        };
+        eprintln!(
+            "Found {} literals in {} items: {} pubkeys, {} URLs, {} seeds",
+            summary.literals,
+            items.len(),
+            summary.pubkeys,
+            summary.urls,
+            summary.seeds
+        );
+
+        let json = serde_json::to_string_pretty(&Inventory { summary, items })?;
+        match &self.output {
+            Some(path) => fs::write(path, json)?,
+            None => println!("{json}"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Inventory {
+    summary: Summary,
+    items: Vec<ItemStrings>,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    literals: usize,
+    pubkeys: usize,
+    urls: usize,
+    seeds: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct ItemStrings {
+    /// Name of the enclosing item, `Type::method` for associated items.
+    item: String,
+    kind: &'static str,
+    file: String,
+    line: u32,
+    literals: Vec<StringLiteral>,
+}
+
+#[derive(Debug, Serialize)]
+struct StringLiteral {
+    /// The unescaped contents, non UTF-8 bytes of byte strings are replaced.
+    value: String,
+    byte_string: bool,
+    line: u32,
+    column: u32,
+    tags: Vec<&'static str>,
+}
+
+fn collect_items(project: &LoadedProject) -> Vec<ItemStrings> {
+    let analysis = project.analysis();
+    let sema = Semantics::new(&project.db);
+    let mut items = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let relative_path = convert_to_relative_path(&file_path, &project.project_root);
+
+        let literals = file
+            .syntax()
+            .descendants_with_tokens()
+            .filter_map(|it| it.into_token())
+            .filter_map(|token| {
+                let (value, byte_string) = literal_value(&token)?;
+                let position = line_index.line_col(token.text_range().start());
+                let literal = StringLiteral {
+                    tags: tags(&token, &value, byte_string),
+                    value,
+                    byte_string,
+                    line: position.line + 1,
+                    column: position.col + 1,
+                };
+                let item = token.parent_ancestors().find_map(ast::Item::cast);
+                Some((item, literal))
+            })
+            .collect_vec();
+        let by_item = literals.into_iter().into_group_map();
+        for (item, literals) in by_item.into_iter().sorted_by_key(|(item, literals)| {
+            (item.as_ref().map(|it| it.syntax().text_range().start()), literals[0].line)
+        }) {
+            let (name, kind, offset) = match &item {
+                Some(item) => {
+                    let (name, kind) = describe_item(item);
+                    (name, kind, item.syntax().text_range().start())
+                }
+                None => ("<file>".to_owned(), "file", 0.into()),
+            };
+            items.push(ItemStrings {
+                item: name,
+                kind,
+                file: relative_path.clone(),
+                line: line_index.line_col(offset).line + 1,
+                literals,
+            });
+        }
+    }
+    items
+}
+
+/// Returns the unescaped value of a string or byte string literal token.
+fn literal_value(token: &SyntaxToken) -> Option<(String, bool)> {
+    if let Some(string) = ast::String::cast(token.clone()) {
+        return Some((string.value().ok()?.into_owned(), false));
+    }
+    let string = ast::ByteString::cast(token.clone())?;
+    Some((String::from_utf8_lossy(&string.value().ok()?).into_owned(), true))
+}
+
+fn describe_item(item: &ast::Item) -> (String, &'static str) {
+    let named = |name: Option<ast::Name>| name.map(|it| it.text().to_string()).unwrap_or_default();
+    let (name, kind) = match item {
+        ast::Item::Fn(it) => (named(it.name()), "fn"),
+        ast::Item::Const(it) => (named(it.name()), "const"),
+        ast::Item::Static(it) => (named(it.name()), "static"),
+        ast::Item::Struct(it) => (named(it.name()), "struct"),
+        ast::Item::Enum(it) => (named(it.name()), "enum"),
+        ast::Item::Union(it) => (named(it.name()), "union"),
+        ast::Item::Trait(it) => (named(it.name()), "trait"),
+        ast::Item::Module(it) => (named(it.name()), "mod"),
+        ast::Item::TypeAlias(it) => (named(it.name()), "type"),
+        ast::Item::Impl(it) => {
+            let ty = it.self_ty().map(|ty| ty.syntax().text().to_string()).unwrap_or_default();
+            return (format!("impl {ty}"), "impl");
+        }
+        ast::Item::MacroCall(it) => {
+            let path = it.path().map(|path| path.syntax().text().to_string()).unwrap_or_default();
+            return (format!("{path}!"), "macro_call");
+        }
+        _ => return (format!("{:?}", item.syntax().kind()).to_lowercase(), "item"),
+    };
+    // Qualify associated items with the type or trait they belong to.
+    let owner = item.syntax().parent().and_then(|list| list.parent());
+    match owner.as_ref().and_then(owner_name) {
+        Some(owner) => (format!("{owner}::{name}"), kind),
+        None => (name, kind),
+    }
+}
+
+fn owner_name(node: &SyntaxNode) -> Option<String> {
+    if let Some(imp) = ast::Impl::cast(node.clone()) {
+        return Some(imp.self_ty()?.syntax().text().to_string());
+    }
+    Some(ast::Trait::cast(node.clone())?.name()?.text().to_string())
+}
+
+fn tags(token: &SyntaxToken, value: &str, byte_string: bool) -> Vec<&'static str> {
+    let mut tags = Vec::new();
+    if is_pubkey(value) {
+        tags.push("pubkey");
+    }
+    if ["http://", "https://", "ws://", "wss://"].iter().any(|scheme| value.starts_with(scheme)) {
+        tags.push("url");
+    }
+    if byte_string && is_seed(token) {
+        tags.push("seed");
+    }
+    tags
+}
+
+/// Whether `value` is the base58 encoding of 32 bytes, the size of a Solana public key.
+fn is_pubkey(value: &str) -> bool {
+    if !(32..=44).contains(&value.len()) {
+        return false;
+    }
+    // Big endian base 256 digits of the decoded number.
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in value.bytes() {
+        let Some(digit) = BASE58_ALPHABET.iter().position(|&it| it == c) else { return false };
+        let mut carry = digit as u32;
+        for byte in bytes.iter_mut().rev() {
+            carry += u32::from(*byte) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.insert(0, carry as u8);
+            carry >>= 8;
+        }
+    }
+    let leading_zeros = value.bytes().take_while(|&c| c == b'1').count();
+    leading_zeros + bytes.len() == 32
+}
+
+/// Whether the byte string `token` is a PDA seed.
+fn is_seed(token: &SyntaxToken) -> bool {
+    // `#[account(seeds = [b"seed", ...])]`, the attribute is only a token tree.
+    if let Some(tree) = token.parent().filter(|it| it.kind() == SyntaxKind::TOKEN_TREE) {
+        let mut preceding =
+            std::iter::successors(tree.prev_sibling_or_token(), |it| it.prev_sibling_or_token())
+                .filter(|it| !it.kind().is_trivia());
+        if preceding.next().is_some_and(|it| it.kind() == SyntaxKind::EQ) {
+            return preceding
+                .next()
+                .and_then(|it| it.into_token())
+                .is_some_and(|it| it.text() == "seeds");
+        }
+    }
+
+    for node in token.parent_ancestors() {
+        if let Some(call) = ast::CallExpr::cast(node.clone()) {
+            let called = call.expr().map(|expr| expr.syntax().text().to_string());
+            return called.is_some_and(|called| {
+                called.ends_with("find_program_address")
+                    || called.ends_with("create_program_address")
+            });
+        }
+        if let Some(call) = ast::MethodCallExpr::cast(node.clone()) {
+            return call.name_ref().is_some_and(|name| name.text().ends_with("program_address"));
+        }
+        if let Some(let_stmt) = ast::LetStmt::cast(node.clone()) {
+            return let_stmt
+                .pat()
+                .is_some_and(|pat| pat.syntax().text().to_string().ends_with("seeds"));
+        }
+        if ast::Item::can_cast(node.kind()) || ast::StmtList::can_cast(node.kind()) {
+            return false;
+        }
+    }
+    false
+}
@@ -0,0 +1,268 @@
+//! The sysvars every instruction reads, like the clock, rent or the instructions sysvar, whether
+//! as accounts of its accounts struct, through `Clock::get()` or from an account passed to it.
+//!
+//! Logic depending on the slot or the time, like a sale opening at a start slot, is security
+//! relevant: slots get skipped and validators have leeway over the clock. Every use lists what
+//! the program reads from the sysvar, the fields and methods used on it like `unix_timestamp`.
+//! Reads of an account are looked for in every function the instruction reaches, reads of a
+//! `get()` call in the function making it.
+
+use anyhow::Result;
+use hir::Semantics;
+use ide::Analysis;
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+use syntax::{
+    AstNode, SyntaxNode,
+    ast::{self, HasName},
+    match_ast,
+};
+
+use crate::cli::{
+    code_graph::{AccountField, CodeGraph, LoadedProject, body_descendants, project_files},
+    cpi_calls::ACCOUNT_CONVERSIONS,
+    function_analyzer::convert_to_relative_path,
+};
+
+/// The sysvars, by the module of `solana_program::sysvar` declaring them.
+const SYSVARS: &[(&str, &str)] = &[
+    ("clock", "Clock"),
+    ("rent", "Rent"),
+    ("instructions", "Instructions"),
+    ("slot_hashes", "SlotHashes"),
+    ("slot_history", "SlotHistory"),
+    ("epoch_schedule", "EpochSchedule"),
+    ("epoch_rewards", "EpochRewards"),
+    ("stake_history", "StakeHistory"),
+    ("recent_blockhashes", "RecentBlockhashes"),
+    ("fees", "Fees"),
+    ("last_restart_slot", "LastRestartSlot"),
+];
+
+/// The functions of `sysvar::instructions` reading the instructions sysvar.
+const INTROSPECTION: &[&str] = &[
+    "load_current_index_checked",
+    "load_instruction_at_checked",
+    "get_instruction_relative",
+    "load_current_index",
+    "load_instruction_at",
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct InstructionSysvars {
+    /// The path of the instruction.
+    pub(super) instruction: String,
+    pub(super) sysvars: Vec<SysvarUse>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct SysvarUse {
+    /// The sysvar, like `Clock`.
+    pub(super) sysvar: String,
+    pub(super) access: SysvarAccess,
+    pub(super) file: String,
+    pub(super) line: u32,
+    /// The field of the accounts struct holding the sysvar, like `Swap.clock`, for accounts.
+    pub(super) field: Option<String>,
+    /// The function of the call, for calls.
+    pub(super) function: Option<String>,
+    /// The fields and methods of the sysvar used, like `slot` or `minimum_balance`.
+    pub(super) reads: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(super) enum SysvarAccess {
+    /// A `Sysvar<'info, T>` field, or one constrained to the address of a sysvar.
+    Account,
+    /// `Clock::get()` and the like.
+    Get,
+    /// `Clock::from_account_info(..)` and the like.
+    FromAccountInfo,
+    /// A function of `sysvar::instructions`, like `load_instruction_at_checked`.
+    Introspection,
+}
+
+/// Collects the sysvars used by every instruction of `graph`, leaving out instructions using
+/// none.
+pub(super) fn extract_sysvars(
+    project: &LoadedProject,
+    analysis: &Analysis,
+    graph: &CodeGraph,
+) -> Result<Vec<InstructionSysvars>> {
+    let _p = tracing::info_span!("extract_sysvars").entered();
+    let sema = Semantics::new(&project.db);
+    let mut bodies: FxHashMap<usize, ast::BlockExpr> = FxHashMap::default();
+    let mut calls: Vec<(usize, SysvarUse)> = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        let relative_path = convert_to_relative_path(&file_path, &project.project_root);
+        let line_of = |node: &SyntaxNode| line_index.line_col(node.text_range().start()).line + 1;
+
+        for function in file.syntax().descendants().filter_map(ast::Fn::cast) {
+            let Some(name) = function.name() else { continue };
+            let line = line_of(name.syntax());
+            let Some(id) = graph.function_at(&relative_path, name.text().as_str(), line) else {
+                continue;
+            };
+            let Some(body) = function.body() else { continue };
+            for call in body_descendants(body.syntax()).filter_map(ast::CallExpr::cast) {
+                let Some((sysvar, access)) = sysvar_call(&call) else { continue };
+                let use_ = SysvarUse {
+                    sysvar,
+                    access,
+                    file: relative_path.clone(),
+                    line: line_of(call.syntax()),
+                    field: None,
+                    function: Some(graph.function_path(id)),
+                    reads: call_reads(&body, &call),
+                };
+                calls.push((id, use_));
+            }
+            bodies.insert(id, body);
+        }
+    }
+
+    let mut instructions = Vec::new();
+    for (instruction, functions) in graph.instruction_reach() {
+        let mut sysvars = Vec::new();
+        let structs =
+            graph.account_structs.iter().filter(|it| it.instructions.contains(&instruction));
+        for strukt in structs {
+            for field in &strukt.fields {
+                let Some(sysvar) = field_sysvar(field) else { continue };
+                let mut reads: Vec<String> = functions
+                    .iter()
+                    .filter_map(|id| bodies.get(id))
+                    .flat_map(|it| reads(it.syntax(), &field.name))
+                    .collect();
+                reads.sort();
+                reads.dedup();
+                sysvars.push(SysvarUse {
+                    sysvar: sysvar.to_owned(),
+                    access: SysvarAccess::Account,
+                    file: strukt.file.clone(),
+                    line: field.line,
+                    field: Some(format!("{}.{}", strukt.name, field.name)),
+                    function: None,
+                    reads,
+                });
+            }
+        }
+        let reached = calls.iter().filter(|(id, _)| functions.contains(id));
+        sysvars.extend(reached.map(|(_, it)| it.clone()));
+        if !sysvars.is_empty() {
+            instructions.push(InstructionSysvars { instruction, sysvars });
+        }
+    }
+    instructions.sort_by(|a, b| a.instruction.cmp(&b.instruction));
+    Ok(instructions)
+}
+
+/// The sysvar an accounts struct field holds: the `T` of `Sysvar<'info, T>`, or the sysvar whose
+/// address it's constrained to, like `sysvar::instructions::ID`.
+fn field_sysvar(field: &AccountField) -> Option<&'static str> {
+    let ty: String = field.ty.split_whitespace().collect();
+    let ty = ty.strip_prefix("Box<").and_then(|it| it.strip_suffix('>')).unwrap_or(&ty);
+    if let Some(args) = ty.strip_prefix("Sysvar<").and_then(|it| it.strip_suffix('>')) {
+        let name = args.rsplit(',').next()?.rsplit("::").next()?;
+        return SYSVARS.iter().find(|(_, sysvar)| *sysvar == name).map(|(_, it)| *it);
+    }
+    field.constraints.iter().filter(|it| it.kind == "address").find_map(|constraint| {
+        let value: String = constraint.value.as_deref()?.split_whitespace().collect();
+        let mut segments = value.split("::").skip_while(|it| *it != "sysvar").skip(1);
+        let module = segments.next()?;
+        SYSVARS.iter().find(|(it, _)| *it == module).map(|(_, sysvar)| *sysvar)
+    })
+}
+
+/// The sysvar read by a call like `Clock::get()` or `load_instruction_at_checked(..)`.
+fn sysvar_call(call: &ast::CallExpr) -> Option<(String, SysvarAccess)> {
+    let ast::Expr::PathExpr(callee) = call.expr()? else { return None };
+    let path = callee.path()?;
+    let name = path.segment()?.name_ref()?;
+    if INTROSPECTION.contains(&name.text().as_str()) {
+        return Some(("Instructions".to_owned(), SysvarAccess::Introspection));
+    }
+    let ty = path.qualifier()?.segment()?.name_ref()?;
+    let (_, sysvar) = SYSVARS.iter().find(|(_, it)| ty.text() == *it)?;
+    let access = match name.text().as_str() {
+        "get" => SysvarAccess::Get,
+        "from_account_info" => SysvarAccess::FromAccountInfo,
+        _ => return None,
+    };
+    Some(((*sysvar).to_owned(), access))
+}
+
+/// What is read from the sysvar `call` returns, right away like `Clock::get()?.slot` or through
+/// the local it's bound to in `body`.
+fn call_reads(body: &ast::BlockExpr, call: &ast::CallExpr) -> Vec<String> {
+    // The sysvar itself, after `?`, `unwrap()` and the like.
+    let mut sysvar = call.syntax().clone();
+    while let Some(parent) = sysvar.parent() {
+        let unwraps = match_ast! {
+            match parent {
+                ast::TryExpr(_) => true,
+                ast::RefExpr(_) => true,
+                ast::ParenExpr(_) => true,
+                ast::MethodCallExpr(it) => it
+                    .name_ref()
+                    .is_some_and(|it| matches!(it.text().as_str(), "unwrap" | "expect")),
+                _ => false,
+            }
+        };
+        if !unwraps {
+            break;
+        }
+        sysvar = parent;
+    }
+    let Some(parent) = sysvar.parent() else { return Vec::new() };
+    if let Some(read) = read_name(&parent) {
+        return vec![read];
+    }
+    let binding = ast::LetStmt::cast(parent).and_then(|it| match it.pat()? {
+        ast::Pat::IdentPat(pat) => pat.name(),
+        _ => None,
+    });
+    let Some(binding) = binding else { return Vec::new() };
+    let mut reads = reads(body.syntax(), binding.text().as_str());
+    reads.sort();
+    reads.dedup();
+    reads
+}
+
+/// The fields and methods used on `name` in `body`, whether a local or the field of an accounts
+/// struct like `ctx.accounts.clock`. Nested functions are left to their own reads.
+fn reads(body: &SyntaxNode, name: &str) -> Vec<String> {
+    body_descendants(body)
+        .filter(|node| {
+            let receiver = match_ast! {
+                match node {
+                    ast::FieldExpr(it) => it.expr(),
+                    ast::MethodCallExpr(it) => it.receiver(),
+                    _ => None,
+                }
+            };
+            receiver.is_some_and(|receiver| match receiver {
+                ast::Expr::PathExpr(it) => it.syntax().text() == name,
+                ast::Expr::FieldExpr(it) => it.name_ref().is_some_and(|it| it.text() == name),
+                _ => false,
+            })
+        })
+        .filter_map(|node| read_name(&node))
+        .collect()
+}
+
+/// The field or method a field access or method call uses, skipping account conversions.
+fn read_name(node: &SyntaxNode) -> Option<String> {
+    let name = match_ast! {
+        match node {
+            ast::FieldExpr(it) => it.name_ref()?,
+            ast::MethodCallExpr(it) => it.name_ref()?,
+            _ => return None,
+        }
+    };
+    let name = name.text().to_string();
+    (!ACCOUNT_CONVERSIONS.contains(&name.as_str())).then_some(name)
+}
@@ -0,0 +1,75 @@
+//! The SPL Token operations of every instruction: the transfers, mints, burns and other token
+//! program instructions it makes, with the account authorizing them and the seeds the program
+//! signs for that account with.
+//!
+//! Operations are the CPIs of [`cpi_calls`](super::cpi_calls) into the token programs, through the
+//! `anchor_spl` helpers like `token::transfer` or the `spl_token::instruction` builders given to
+//! `invoke`. An operation with signer seeds moves tokens of a PDA of the program, one without
+//! needs its authority to sign the transaction.
+
+use serde::Serialize;
+
+use crate::cli::code_graph::CodeGraph;
+
+/// The modules of the token program clients, by a segment of the path of the called function.
+const TOKEN_MODULES: &[&str] =
+    &["token", "token_2022", "token_interface", "spl_token", "spl_token_2022"];
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct InstructionTokenOperations {
+    /// The path of the instruction.
+    pub(super) instruction: String,
+    pub(super) operations: Vec<TokenOperation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct TokenOperation {
+    /// The token program instruction, like `transfer` or `mint_to`.
+    pub(super) operation: String,
+    /// The function performing it, like `token::transfer` or `spl_token::instruction::burn`.
+    pub(super) target: String,
+    pub(super) file: String,
+    pub(super) line: u32,
+    /// The function making the CPI.
+    pub(super) function: Option<String>,
+    /// The account authorizing the operation. None for operations without one, like
+    /// `sync_native`, or when it can't be told from the call site.
+    pub(super) authority: Option<String>,
+    /// The seeds the program signs for the authority with, none when the authority signs the
+    /// transaction itself.
+    pub(super) signer_seeds: Option<String>,
+}
+
+/// Groups the token program CPIs of `graph` by the instructions reaching them.
+pub(super) fn extract_token_operations(graph: &CodeGraph) -> Vec<InstructionTokenOperations> {
+    let _p = tracing::info_span!("extract_token_operations").entered();
+    let mut instructions: Vec<InstructionTokenOperations> = Vec::new();
+    for cpi in &graph.cpi_calls {
+        let Some(target) = &cpi.target else { continue };
+        let segments: Vec<&str> = target.split("::").map(str::trim).collect();
+        let Some((operation, modules)) = segments.split_last() else { continue };
+        if !modules.iter().any(|it| TOKEN_MODULES.contains(it)) {
+            continue;
+        }
+        let operation = TokenOperation {
+            operation: (*operation).to_owned(),
+            target: target.clone(),
+            file: cpi.file.clone(),
+            line: cpi.line,
+            function: cpi.function.clone(),
+            authority: cpi.authority.clone(),
+            signer_seeds: cpi.signer_seeds.clone(),
+        };
+        for instruction in &cpi.instructions {
+            match instructions.iter_mut().find(|it| it.instruction == *instruction) {
+                Some(it) => it.operations.push(operation.clone()),
+                None => instructions.push(InstructionTokenOperations {
+                    instruction: instruction.clone(),
+                    operations: vec![operation.clone()],
+                }),
+            }
+        }
+    }
+    instructions.sort_by(|a, b| a.instruction.cmp(&b.instruction));
+    instructions
+}
@@ -0,0 +1,157 @@
+//! The zero-copy types of a project, `#[account(zero_copy)]` accounts and the `#[zero_copy]`
+//! structs they embed, with the memory layout they are stored with.
+//!
+//! Zero-copy data is cast from the account bytes rather than deserialized, so its layout is its
+//! format. Anchor gives these structs `#[repr(C)]`, or `#[repr(packed)]` for `zero_copy(unsafe)`.
+//! The layout is computed field by field for that representation, since the macro may not be
+//! expanded. Padding between fields is easy to miss when computing `space`, and bytemuck's `Pod`
+//! rejects it.
+
+use anyhow::Result;
+use hir::{HasCrate, HirDisplay, Semantics};
+use ide::{Analysis, RootDatabase};
+use ide_db::base_db::salsa;
+use serde::Serialize;
+use syntax::{
+    AstNode,
+    ast::{self, HasAttrs, HasName},
+};
+
+use crate::cli::{
+    code_graph::{LoadedProject, module_path, project_files},
+    function_analyzer::convert_to_relative_path,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct ZeroCopyType {
+    pub(super) name: String,
+    pub(super) file: String,
+    pub(super) line: u32,
+    pub(super) module: String,
+    /// Declared `#[account(zero_copy)]`, stored in an account of its own, rather than
+    /// `#[zero_copy]`.
+    pub(super) account: bool,
+    #[serde(flatten)]
+    pub(super) layout: Layout,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct Layout {
+    /// The `#[repr(..)]` of the struct, or the one the macro gives it, like `C` or `packed`.
+    pub(super) repr: String,
+    /// None when a field has no known layout, like the following offsets.
+    pub(super) size: Option<u64>,
+    pub(super) align: Option<u64>,
+    pub(super) fields: Vec<FieldLayout>,
+    /// The padding after the last field, up to the alignment of the struct.
+    pub(super) trailing_padding: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(super) struct FieldLayout {
+    pub(super) name: String,
+    pub(super) ty: String,
+    pub(super) offset: Option<u64>,
+    pub(super) size: Option<u64>,
+    pub(super) align: Option<u64>,
+    /// The padding inserted before the field to align it.
+    pub(super) padding: Option<u64>,
+}
+
+impl Layout {
+    /// The layout of a zero-copy struct, none when `strukt` isn't one.
+    pub(super) fn of(sema: &Semantics<'_, RootDatabase>, strukt: &ast::Struct) -> Option<Layout> {
+        let zero_copy = strukt.attrs().find_map(|attr| match attr.simple_name()?.as_str() {
+            "zero_copy" => Some(attr.token_tree().map(|it| it.syntax().text().to_string())),
+            "account" => {
+                let args = attr.token_tree()?.syntax().text().to_string();
+                args.contains("zero_copy").then_some(Some(args))
+            }
+            _ => None,
+        })?;
+        let explicit = strukt.attrs().find_map(|attr| match attr.as_simple_call()? {
+            (name, tt) if name == "repr" => {
+                let text = tt.syntax().text().to_string();
+                Some(text.trim_start_matches('(').trim_end_matches(')').replace(' ', ""))
+            }
+            _ => None,
+        });
+        let unsafe_ = zero_copy.is_some_and(|it| it.contains("unsafe"));
+        let repr = explicit.unwrap_or_else(|| if unsafe_ { "packed" } else { "C" }.to_owned());
+        let packed = repr.split(',').any(|it| it.starts_with("packed"));
+
+        let db = sema.db;
+        let def = sema.to_def(strukt)?;
+        let mut fields = Vec::new();
+        // The end of the previous field, while known.
+        let mut end = Some(0u64);
+        let mut max_align = 1u64;
+        for field in def.fields(db) {
+            // Layouts need the database attached to the thread.
+            let layout = salsa::attach(db, || field.layout(db).ok());
+            let size = layout.as_ref().map(|it| it.size());
+            let align = layout.as_ref().map(|it| if packed { 1 } else { it.align() });
+            let offset = end.zip(align).map(|(end, align)| end.next_multiple_of(align));
+            fields.push(FieldLayout {
+                name: field.name(db).as_str().to_owned(),
+                ty: salsa::attach(db, || {
+                    field.ty(db).display(db, def.krate(db).to_display_target(db)).to_string()
+                }),
+                offset,
+                size,
+                align,
+                padding: offset.zip(end).map(|(offset, end)| offset - end),
+            });
+            end = offset.zip(size).map(|(offset, size)| offset + size);
+            max_align = max_align.max(align.unwrap_or(1));
+        }
+        let size = end.map(|end| end.next_multiple_of(max_align));
+        Some(Layout {
+            repr,
+            size,
+            align: end.map(|_| max_align),
+            fields,
+            trailing_padding: size.zip(end).map(|(size, end)| size - end),
+        })
+    }
+
+    /// The padding of the whole struct, none when its layout isn't known.
+    pub(super) fn padding(&self) -> Option<u64> {
+        let between: Option<u64> = self.fields.iter().map(|it| it.padding).sum();
+        Some(between? + self.trailing_padding?)
+    }
+}
+
+/// Collects every zero-copy struct declared in project files.
+pub(super) fn extract_zero_copy_types(
+    project: &LoadedProject,
+    analysis: &Analysis,
+) -> Result<Vec<ZeroCopyType>> {
+    let _p = tracing::info_span!("extract_zero_copy_types").entered();
+    let sema = Semantics::new(&project.db);
+    let mut types = Vec::new();
+    for (file_id, file_path) in project_files(project) {
+        let Ok(line_index) = analysis.file_line_index(file_id) else { continue };
+        let file = sema.parse_guess_edition(file_id);
+        for strukt in file.syntax().descendants().filter_map(ast::Struct::cast) {
+            let (Some(name), Some(layout)) = (strukt.name(), Layout::of(&sema, &strukt)) else {
+                continue;
+            };
+            types.push(ZeroCopyType {
+                name: name.text().to_string(),
+                file: convert_to_relative_path(&file_path, &project.project_root),
+                line: line_index.line_col(name.syntax().text_range().start()).line + 1,
+                module: sema
+                    .to_def(&strukt)
+                    .map(|it| module_path(&project.db, it.module(&project.db)))
+                    .unwrap_or_default(),
+                account: strukt
+                    .attrs()
+                    .any(|attr| attr.simple_name().as_deref() == Some("account")),
+                layout,
+            });
+        }
+    }
+    types.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(types)
+}
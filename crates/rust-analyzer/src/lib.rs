@@ -45,6 +45,7 @@ pub mod tracing {
     pub mod json;
     pub use config::Config;
     pub mod hprof;
+    pub mod otel;
 }
 
 pub mod config;
@@ -15,6 +15,7 @@ use tracing_tree::HierarchicalLayer;
 
 use crate::tracing::hprof;
 use crate::tracing::json;
+use crate::tracing::otel;
 
 #[derive(Debug)]
 pub struct Config<T> {
@@ -43,6 +44,9 @@ pub struct Config<T> {
     /// env RA_PROFILE_JSON=foo|bar|baz
     /// ```
     pub json_profile_filter: Option<String>,
+
+    /// Base URL of an OTLP/HTTP collector the spans of CLI commands are exported to.
+    pub otlp_endpoint: Option<String>,
 }
 
 impl<T> Config<T>
@@ -118,7 +122,22 @@ where
             None => None,
         };
 
+        let otel_layer = match self.otlp_endpoint {
+            Some(endpoint) => match otel::layer(&endpoint) {
+                Ok(layer) => Some(layer),
+                // Logging goes on without exporting the spans. It isn't set up yet, so the warning
+                // goes to stderr.
+                #[allow(clippy::print_stderr)]
+                Err(e) => {
+                    eprintln!("Failed to set up OTLP export: {e:#}");
+                    None
+                }
+            },
+            None => None,
+        };
+
         let subscriber = Registry::default()
+            .with(otel_layer)
             .with(ra_fmt_layer)
             .with(json_profiler_layer)
             .with(profiler_layer)
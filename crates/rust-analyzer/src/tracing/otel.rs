@@ -0,0 +1,75 @@
+//! Exports the spans of the batch analysis commands to an OpenTelemetry collector over
+//! OTLP/HTTP, so slow phases can be spotted when rust-analyzer runs inside CI pipelines.
+//!
+//! Only available when built with the `otel` feature.
+
+use tracing_subscriber::{Layer, Registry};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+#[cfg(feature = "otel")]
+mod imp {
+    use std::sync::OnceLock;
+
+    use anyhow::Context;
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+    use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
+    use tracing::level_filters::LevelFilter;
+    use tracing_subscriber::{Layer, filter::Targets};
+
+    use super::BoxedLayer;
+
+    static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+    pub(super) fn layer(endpoint: &str) -> anyhow::Result<BoxedLayer> {
+        let endpoint = format!("{}/v1/traces", endpoint.trim_end_matches('/'));
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(&endpoint)
+            .build()
+            .with_context(|| format!("failed to create OTLP exporter for `{endpoint}`"))?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name("rust-analyzer").build())
+            .build();
+        let tracer = provider.tracer("rust-analyzer");
+        let _ = PROVIDER.set(provider);
+
+        // The IDE layers below the CLI emit a huge amount of fine grained spans, only the
+        // phases of the commands themselves are interesting in a trace.
+        let filter = Targets::new().with_target("rust_analyzer::cli", LevelFilter::INFO);
+        Ok(tracing_opentelemetry::layer().with_tracer(tracer).with_filter(filter).boxed())
+    }
+
+    pub(super) fn shutdown() -> anyhow::Result<()> {
+        if let Some(provider) = PROVIDER.get() {
+            provider.shutdown()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+mod imp {
+    use super::BoxedLayer;
+
+    pub(super) fn layer(_endpoint: &str) -> anyhow::Result<BoxedLayer> {
+        anyhow::bail!("OTLP export requires rust-analyzer to be built with the `otel` feature")
+    }
+
+    pub(super) fn shutdown() -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Creates a layer sending spans to the OTLP/HTTP collector at `endpoint`, e.g.
+/// `http://localhost:4318`.
+pub(crate) fn layer(endpoint: &str) -> anyhow::Result<BoxedLayer> {
+    imp::layer(endpoint)
+}
+
+/// Flushes all pending spans, must be called before the process exits.
+pub fn shutdown() -> anyhow::Result<()> {
+    imp::shutdown()
+}
@@ -13,6 +13,18 @@ from pathlib import Path
 sys.path.insert(0, str(Path(__file__).parent))
 
 from interface import SolanaAnalyzer
+from messages import LANGUAGES, set_language, t
+
+REDACTION_KINDS = ("strings", "docs", "paths")
+
+
+def parse_redaction(value: str) -> list:
+    """Parse a --redact value like strings,docs,paths"""
+    kinds = [kind.strip() for kind in value.split(",")]
+    for kind in kinds:
+        if kind not in REDACTION_KINDS:
+            raise argparse.ArgumentTypeError(f"unknown redaction '{kind}', expected strings, docs or paths")
+    return kinds
 
 
 def create_parser() -> argparse.ArgumentParser:
@@ -30,23 +42,33 @@ Examples:
   
   # Call graph analyzer
   python cli.py call-graph /path/to/project
+  
+  # Messages in Chinese
+  python cli.py struct-analyzer /path/to/project --lang zh
         """
     )
     
+    # Options shared by every subcommand
+    common = argparse.ArgumentParser(add_help=False)
+    common.add_argument("--lang", choices=LANGUAGES, default="en", help="Language of status and error messages")
+    
     # Subcommands
     subparsers = parser.add_subparsers(dest="analyzer_type", help="Analyzer type", required=True)
     
     # Source finder
-    source_parser = subparsers.add_parser("source-finder", help="Source finder analyzer")
+    source_parser = subparsers.add_parser("source-finder", parents=[common], help="Source finder analyzer")
     source_parser.add_argument("symbol_name", help="Symbol name")
     source_parser.add_argument("project_path", help="Rust project path")
     
     # Struct analyzer
-    struct_parser = subparsers.add_parser("struct-analyzer", help="Struct analyzer")
+    struct_parser = subparsers.add_parser("struct-analyzer", parents=[common], help="Struct analyzer")
     struct_parser.add_argument("project_path", help="Rust project path")
+    struct_parser.add_argument("--baseline", help="Baseline file, report only new items and fail on regressions")
+    struct_parser.add_argument("--redact", type=parse_redaction, default=[],
+                               help="Redact strings, docs and/or paths (comma separated) from the output")
     
     # Call graph analyzer
-    call_graph_parser = subparsers.add_parser("call-graph", help="Call graph analyzer")
+    call_graph_parser = subparsers.add_parser("call-graph", parents=[common], help="Call graph analyzer")
     call_graph_parser.add_argument("project_path", help="Rust project path")
     
     return parser
@@ -56,12 +78,12 @@ def validate_args(args) -> bool:
     """Validate command line arguments"""
     project_path = Path(args.project_path)
     if not project_path.exists():
-        print(f"Error: Project path does not exist: {args.project_path}")
+        print(t("project_path_missing", path=args.project_path))
         return False
     
     cargo_toml = project_path / "Cargo.toml"
     if not cargo_toml.exists():
-        print(f"Error: Not a Rust project (no Cargo.toml found): {args.project_path}")
+        print(t("not_rust_project", path=args.project_path))
         return False
     
     return True
@@ -74,7 +96,7 @@ def run_source_finder(args):
     
     # Check if result is an error dictionary
     if isinstance(result, dict) and "error" in result:
-        print(f"✗ Source finder failed: {result['error']}")
+        print(t("source_finder_failed", error=result['error']))
         return False
     else:
         # result is the raw JSON output from source_finder.rs, print it directly
@@ -85,15 +107,27 @@ def run_source_finder(args):
 def run_struct_analyzer(args):
     """Run struct analyzer"""
     analyzer = SolanaAnalyzer(args.project_path)
-    result = analyzer.analyze_structs()
+    result = analyzer.analyze_structs(args.baseline, args.redact)
     
     if "error" in result:
-        print(f"✗ Struct analysis failed: {result['error']}")
+        print(t("struct_analysis_failed", error=result['error']))
         return False
     else:
-        print("✓ Struct analysis completed")
-        print(f"  Project: {args.project_path}")
-        print(f"  {result.get('summary', 'Analysis completed')}")
+        print(t("struct_analysis_completed"))
+        print(t("project", path=args.project_path))
+        print(f"  {result.get('summary', t('analysis_completed'))}")
+        
+        diff = result.get("baseline_diff")
+        if diff is None:
+            return True
+        for item in diff["new"]:
+            print(f"  + {item}")
+        for item in diff["removed_constraints"]:
+            print(f"  - {item}")
+        if diff["new"] or diff["removed_constraints"]:
+            print(t("baseline_regressions", new=len(diff['new']), removed=len(diff['removed_constraints'])))
+            return False
+        print(t("baseline_unchanged"))
         return True
 
 
@@ -103,11 +137,11 @@ def run_call_graph_analyzer(args):
     result = analyzer.analyze_call_graph()
     
     if "error" in result:
-        print(f"✗ Call graph analysis failed: {result['error']}")
+        print(t("call_graph_failed", error=result['error']))
         return False
     else:
-        print("✓ Call graph analysis completed")
-        print(f"  Project: {args.project_path}")
+        print(t("call_graph_completed"))
+        print(t("project", path=args.project_path))
         
         # Try to find and display output file info
         output_dir = Path(__file__).parent / "output"
@@ -115,7 +149,7 @@ def run_call_graph_analyzer(args):
             json_files = list(output_dir.glob("*_call_graph.json"))
             if json_files:
                 latest_file = max(json_files, key=lambda f: f.stat().st_mtime)
-                print(f"  Output: {latest_file}")
+                print(t("output", path=latest_file))
         
         return True
 
@@ -124,6 +158,7 @@ def main():
     """Main function"""
     parser = create_parser()
     args = parser.parse_args()
+    set_language(args.lang)
     
     if not validate_args(args):
         sys.exit(1)
@@ -137,7 +172,7 @@ def main():
     elif args.analyzer_type == "call-graph":
         success = run_call_graph_analyzer(args)
     else:
-        print(f"Error: Unknown analyzer type: {args.analyzer_type}")
+        print(t("unknown_analyzer", name=args.analyzer_type))
         sys.exit(1)
     
     sys.exit(0 if success else 1)
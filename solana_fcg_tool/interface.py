@@ -9,6 +9,11 @@ import subprocess
 from pathlib import Path
 from typing import Dict, List, Optional, Any
 
+try:
+    from .messages import t
+except ImportError:
+    from messages import t
+
 
 class SolanaAnalyzer:
     
@@ -20,10 +25,10 @@ class SolanaAnalyzer:
     def find_symbols(self, symbol_name: str):
         """Find symbols in the project using source-finder"""
         if not self._validate_project():
-            return {"error": "Invalid Rust project path"}
+            return {"error": t("invalid_project")}
         
         if not symbol_name:
-            return {"error": "Symbol name is required"}
+            return {"error": t("symbol_required")}
         
         try:
             self._ensure_rust_analyzer_built()
@@ -38,24 +43,26 @@ class SolanaAnalyzer:
             result = subprocess.run(cmd, capture_output=True, text=True)
             
             if result.returncode != 0:
-                return {"error": f"Source search failed: {result.stderr}"}
+                return {"error": t("source_search_failed", stderr=result.stderr)}
             
             # Return the raw output directly as source_finder.rs outputs it
             output_text = result.stdout.strip()
             if not output_text:
-                return {"error": "No output received"}
+                return {"error": t("no_output")}
             
             # source_finder.rs outputs each symbol as a separate JSON object, one per line
             # We need to return the raw text output as-is to match exactly
             return output_text
             
         except Exception as e:
-            return {"error": f"Error during source search: {e}"}
+            return {"error": t("source_search_error", error=e)}
     
-    def analyze_structs(self) -> Dict[str, Any]:
-        """Analyze structs in the project"""
+    def analyze_structs(self, baseline: Optional[str] = None,
+                        redact: Optional[List[str]] = None) -> Dict[str, Any]:
+        """Analyze structs in the project, optionally comparing them against a baseline file.
+        The baseline is compared unredacted, `redact` only applies to the reported items"""
         if not self._validate_project():
-            return {"error": "Invalid Rust project path"}
+            return {"error": t("invalid_project")}
         
         try:
             # Import and use struct analyzer
@@ -73,20 +80,29 @@ class SolanaAnalyzer:
             extractor = struct_analyzer_module.SolanaStructExtractor(str(self.project_path))
             extractor.extract_from_project()
             
-            return {
+            result = {
                 "structs_count": len(extractor.structs),
                 "constants_count": len(extractor.constants),
                 "program_ids_count": len(extractor.program_ids),
-                "summary": f"Found {len(extractor.structs)} structs, {len(extractor.constants)} constants, {len(extractor.program_ids)} program IDs"
+                "summary": t(
+                    "struct_summary",
+                    structs=len(extractor.structs),
+                    constants=len(extractor.constants),
+                    program_ids=len(extractor.program_ids),
+                )
             }
+            if baseline:
+                diff = extractor.compare_with_baseline(baseline)
+                result["baseline_diff"] = struct_analyzer_module.redact_baseline_diff(diff, redact or [])
+            return result
             
         except Exception as e:
-            return {"error": f"Error during struct analysis: {e}"}
+            return {"error": t("struct_analysis_error", error=e)}
     
     def analyze_call_graph(self) -> Dict[str, Any]:
         """Analyze call graph in the project"""
         if not self._validate_project():
-            return {"error": "Invalid Rust project path"}
+            return {"error": t("invalid_project")}
         
         try:
             self._ensure_rust_analyzer_built()
@@ -108,18 +124,18 @@ class SolanaAnalyzer:
                 result = subprocess.run(cmd, capture_output=True, text=True)
                 
                 if result.returncode != 0:
-                    return {"error": f"Call graph analysis failed: {result.stderr}"}
+                    return {"error": t("call_graph_run_failed", stderr=result.stderr)}
                 
                 # Run JSON analyzer
                 json_output = self._run_json_analyzer(temp_output_path)
-                return json_output if json_output else {"error": "JSON analysis failed"}
+                return json_output if json_output else {"error": t("json_analysis_failed")}
                 
             finally:
                 if os.path.exists(temp_output_path):
                     os.unlink(temp_output_path)
             
         except Exception as e:
-            return {"error": f"Error during call graph analysis: {e}"}
+            return {"error": t("call_graph_error", error=e)}
     
     def save_results(self, data: Dict[str, Any], output_path: str) -> bool:
         """Save analysis results to JSON file"""
@@ -127,10 +143,10 @@ class SolanaAnalyzer:
             os.makedirs(os.path.dirname(output_path), exist_ok=True)
             with open(output_path, 'w', encoding='utf-8') as f:
                 json.dump(data, f, indent=2, ensure_ascii=False)
-            print(f"Results saved to: {output_path}")
+            print(t("results_saved", path=output_path))
             return True
         except Exception as e:
-            print(f"Failed to save results: {e}")
+            print(t("results_save_failed", error=e))
             return False
     
     def _validate_project(self) -> bool:
@@ -174,7 +190,7 @@ class SolanaAnalyzer:
     def _ensure_rust_analyzer_built(self):
         """Ensure rust-analyzer binary is built"""
         if not self.rust_analyzer_path.exists():
-            print("Building rust-analyzer...")
+            print(t("building_rust_analyzer"))
             # Try to find the project root for building
             project_root = self._find_project_root()
             build_cmd = ["cargo", "build", "--release"]
@@ -186,7 +202,7 @@ class SolanaAnalyzer:
             )
             
             if build_result.returncode != 0:
-                raise Exception(f"Failed to build rust-analyzer: {build_result.stderr}")
+                raise Exception(t("rust_analyzer_build_failed", stderr=build_result.stderr))
     
     def _find_project_root(self) -> Path:
         """Find the project root directory containing Cargo.toml"""
@@ -240,10 +256,11 @@ def find_symbols(project_path: str, symbol_name: str):
     return analyzer.find_symbols(symbol_name)
 
 
-def analyze_structs(project_path: str) -> Dict[str, Any]:
+def analyze_structs(project_path: str, baseline: Optional[str] = None,
+                    redact: Optional[List[str]] = None) -> Dict[str, Any]:
     """Analyze structs in a Rust project"""
     analyzer = SolanaAnalyzer(project_path)
-    return analyzer.analyze_structs()
+    return analyzer.analyze_structs(baseline, redact)
 
 
 def analyze_call_graph(project_path: str) -> Dict[str, Any]:
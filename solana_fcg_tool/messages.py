@@ -0,0 +1,118 @@
+#!/usr/bin/env python3
+"""
+User-facing message catalog
+
+Every status and error string printed by the CLI lives here, keyed by
+language, so output stays in one language across subcommands.
+"""
+
+LANGUAGES = ("en", "zh")
+
+_MESSAGES = {
+    "en": {
+        # cli.py
+        "project_path_missing": "Error: Project path does not exist: {path}",
+        "not_rust_project": "Error: Not a Rust project (no Cargo.toml found): {path}",
+        "unknown_analyzer": "Error: Unknown analyzer type: {name}",
+        "source_finder_failed": "✗ Source finder failed: {error}",
+        "struct_analysis_failed": "✗ Struct analysis failed: {error}",
+        "struct_analysis_completed": "✓ Struct analysis completed",
+        "call_graph_failed": "✗ Call graph analysis failed: {error}",
+        "call_graph_completed": "✓ Call graph analysis completed",
+        "project": "  Project: {path}",
+        "output": "  Output: {path}",
+        "analysis_completed": "Analysis completed",
+        "baseline_regressions": "✗ {new} new items, {removed} removed constraints compared to baseline",
+        "baseline_unchanged": "✓ No changes compared to baseline",
+        # interface.py
+        "invalid_project": "Invalid Rust project path",
+        "symbol_required": "Symbol name is required",
+        "source_search_failed": "Source search failed: {stderr}",
+        "no_output": "No output received",
+        "source_search_error": "Error during source search: {error}",
+        "struct_summary": "Found {structs} structs, {constants} constants, {program_ids} program IDs",
+        "struct_analysis_error": "Error during struct analysis: {error}",
+        "call_graph_run_failed": "Call graph analysis failed: {stderr}",
+        "json_analysis_failed": "JSON analysis failed",
+        "call_graph_error": "Error during call graph analysis: {error}",
+        "results_saved": "Results saved to: {path}",
+        "results_save_failed": "Failed to save results: {error}",
+        "building_rust_analyzer": "Building rust-analyzer...",
+        "rust_analyzer_build_failed": "Failed to build rust-analyzer: {stderr}",
+        # struct-anayzer.py
+        "no_programs_dirs": "No programs/src directories found in {path}",
+        "processing": "Processing: {path}",
+        "file_processing_error": "Error processing {path}: {error}",
+        "file_read_error": "Error reading file {path}: {error}",
+        "struct_extracted": "✓ Extracted struct: {name} with {fields} fields",
+        "structures_exported": "✓ Complete structures exported to: {path}",
+        "structures_extracted": "✓ Extracted {structs} structs, {constants} constants, {program_ids} program IDs",
+        "account_structs_summary": "✓ Account structs: {structs}, Total fields: {fields}",
+        "defi_summary": "✓ DeFi info extracted: {count} (Oracle: {oracles}, Pool: {pools}, Lending: {lending}, Vault: {vaults}, Governance: {governance})",
+        "baseline_saved": "✓ Baseline saved to: {path}",
+        "extracting_structures": "Extracting complete structures from: {path}",
+        "baseline_new_items": "✗ {count} new items compared to baseline:",
+        "baseline_removed_constraints": "✗ {count} constraints removed compared to baseline:",
+    },
+    "zh": {
+        # cli.py
+        "project_path_missing": "错误：项目路径不存在：{path}",
+        "not_rust_project": "错误：不是 Rust 项目（未找到 Cargo.toml）：{path}",
+        "unknown_analyzer": "错误：未知的分析器类型：{name}",
+        "source_finder_failed": "✗ 源码查找失败：{error}",
+        "struct_analysis_failed": "✗ 结构体分析失败：{error}",
+        "struct_analysis_completed": "✓ 结构体分析完成",
+        "call_graph_failed": "✗ 调用图分析失败：{error}",
+        "call_graph_completed": "✓ 调用图分析完成",
+        "project": "  项目：{path}",
+        "output": "  输出：{path}",
+        "analysis_completed": "分析完成",
+        "baseline_regressions": "✗ 与基线相比新增 {new} 项，移除 {removed} 条约束",
+        "baseline_unchanged": "✓ 与基线相比没有变化",
+        # interface.py
+        "invalid_project": "无效的 Rust 项目路径",
+        "symbol_required": "必须提供符号名称",
+        "source_search_failed": "源码查找失败：{stderr}",
+        "no_output": "没有收到输出",
+        "source_search_error": "源码查找时出错：{error}",
+        "struct_summary": "找到 {structs} 个结构体、{constants} 个常量、{program_ids} 个程序 ID",
+        "struct_analysis_error": "结构体分析时出错：{error}",
+        "call_graph_run_failed": "调用图分析失败：{stderr}",
+        "json_analysis_failed": "JSON 分析失败",
+        "call_graph_error": "调用图分析时出错：{error}",
+        "results_saved": "结果已保存到：{path}",
+        "results_save_failed": "保存结果失败：{error}",
+        "building_rust_analyzer": "正在构建 rust-analyzer...",
+        "rust_analyzer_build_failed": "构建 rust-analyzer 失败：{stderr}",
+        # struct-anayzer.py
+        "no_programs_dirs": "在 {path} 中没有找到 programs/src 目录",
+        "processing": "正在处理：{path}",
+        "file_processing_error": "处理 {path} 时出错：{error}",
+        "file_read_error": "读取文件 {path} 时出错：{error}",
+        "struct_extracted": "✓ 已提取结构体：{name}，共 {fields} 个字段",
+        "structures_exported": "✓ 完整结构已导出到：{path}",
+        "structures_extracted": "✓ 已提取 {structs} 个结构体、{constants} 个常量、{program_ids} 个程序 ID",
+        "account_structs_summary": "✓ 账户结构体：{structs} 个，字段总数：{fields}",
+        "defi_summary": "✓ 已提取 DeFi 信息：{count} 条（预言机：{oracles}，资金池：{pools}，借贷：{lending}，金库：{vaults}，治理：{governance}）",
+        "baseline_saved": "✓ 基线已保存到：{path}",
+        "extracting_structures": "正在提取完整结构：{path}",
+        "baseline_new_items": "✗ 与基线相比新增 {count} 项：",
+        "baseline_removed_constraints": "✗ 与基线相比移除 {count} 条约束：",
+    },
+}
+
+_language = "en"
+
+
+def set_language(lang: str):
+    """Select the language of all following messages"""
+    global _language
+    if lang not in _MESSAGES:
+        raise ValueError(f"unknown language '{lang}', expected one of {', '.join(LANGUAGES)}")
+    _language = lang
+
+
+def t(key: str, **kwargs) -> str:
+    """Look up a message in the current language, falling back to English"""
+    template = _MESSAGES[_language].get(key, _MESSAGES["en"][key])
+    return template.format(**kwargs)
@@ -8,10 +8,18 @@ Solana Contract Structure Extractor - Simplified & Accurate Version
 
 import os
 import re
+import json
+import sys
+import hashlib
 from pathlib import Path
 from typing import Dict, List, Optional, Tuple
 from dataclasses import dataclass, field
 
+try:
+    from .messages import LANGUAGES, set_language, t
+except ImportError:
+    from messages import LANGUAGES, set_language, t
+
 @dataclass
 class StructField:
     """结构体字段"""
@@ -148,11 +156,11 @@ class SolanaStructExtractor:
         programs_dirs = list(self.project_root.glob("**/programs/*/src"))
         
         if not programs_dirs:
-            print(f"No programs/src directories found in {self.project_root}")
+            print(t("no_programs_dirs", path=self.project_root))
             return
             
         for programs_dir in programs_dirs:
-            print(f"Processing: {programs_dir}")
+            print(t("processing", path=programs_dir))
             self._process_directory(programs_dir)
     
     def _process_directory(self, directory: Path) -> None:
@@ -161,7 +169,7 @@ class SolanaStructExtractor:
             try:
                 self._process_file(rust_file)
             except Exception as e:
-                print(f"Error processing {rust_file}: {e}")
+                print(t("file_processing_error", path=rust_file, error=e))
     
     def _process_file(self, file_path: Path) -> None:
         """处理单个Rust文件"""
@@ -169,7 +177,7 @@ class SolanaStructExtractor:
             with open(file_path, 'r', encoding='utf-8') as f:
                 content = f.read()
         except Exception as e:
-            print(f"Error reading file {file_path}: {e}")
+            print(t("file_read_error", path=file_path, error=e))
             return
         
         lines = content.split('\n')
@@ -219,7 +227,7 @@ class SolanaStructExtractor:
                     )
                     
                     self.structs.append(struct_def)
-                    print(f"✓ Extracted struct: {struct_name} with {len(fields)} fields")
+                    print(t("struct_extracted", name=struct_name, fields=len(fields)))
             
             i += 1
     
@@ -765,16 +773,20 @@ class SolanaStructExtractor:
                 for governance_info in self.governance_infos:
                     self._write_governance_as_code(f, governance_info)
         
-        print(f"\n✓ Complete structures exported to: {output_path}")
-        print(f"✓ Extracted {len(self.structs)} structs, {len(self.constants)} constants, {len(self.program_ids)} program IDs")
+        print()
+        print(t("structures_exported", path=output_path))
+        print(t("structures_extracted", structs=len(self.structs), constants=len(self.constants),
+                program_ids=len(self.program_ids)))
         
         # 统计信息
         account_structs = [s for s in self.structs if s.is_account_struct]
         total_fields = sum(len(s.fields) for s in self.structs)
         defi_info_count = (len(self.oracle_infos) + len(self.liquidity_pools) + 
                           len(self.lending_pools) + len(self.vaults) + len(self.governance_infos))
-        print(f"✓ Account structs: {len(account_structs)}, Total fields: {total_fields}")
-        print(f"✓ DeFi info extracted: {defi_info_count} (Oracle: {len(self.oracle_infos)}, Pool: {len(self.liquidity_pools)}, Lending: {len(self.lending_pools)}, Vault: {len(self.vaults)}, Governance: {len(self.governance_infos)})")
+        print(t("account_structs_summary", structs=len(account_structs), fields=total_fields))
+        print(t("defi_summary", count=defi_info_count, oracles=len(self.oracle_infos),
+                pools=len(self.liquidity_pools), lending=len(self.lending_pools),
+                vaults=len(self.vaults), governance=len(self.governance_infos)))
     
     def _write_oracle_info_as_code(self, f, oracle_info: OracleInfo) -> None:
         """写入预言机信息作为代码注释"""
@@ -846,6 +858,74 @@ class SolanaStructExtractor:
             f.write(f"// Execution Delay: {governance_info.execution_delay}\n")
         f.write("\n")
     
+    def redact(self, kinds: List[str]) -> None:
+        """就地脱敏已提取的数据：strings 把字符串字面量替换为哈希，paths 去掉绝对路径。
+        文档注释不会被导出，docs 无需处理"""
+        def redact_path(path: str) -> str:
+            relative = self._relative_path(path)
+            if not Path(relative).is_absolute():
+                return relative
+            return f"<redacted:{_short_hash(str(Path(relative).parent))}>/{Path(relative).name}"
+        
+        if "strings" in kinds:
+            for struct in self.structs:
+                struct.attributes = [_redact_strings(attr) for attr in struct.attributes]
+                for struct_field in struct.fields:
+                    struct_field.constraints = [_redact_strings(c) for c in struct_field.constraints]
+            for constant in self.constants:
+                constant.value = _redact_strings(constant.value)
+            for program_id in self.program_ids:
+                program_id.program_id = f"<redacted:{_short_hash(program_id.program_id)}>"
+        
+        if "paths" in kinds:
+            items = (self.structs + self.constants + self.program_ids + self.oracle_infos +
+                     self.liquidity_pools + self.lending_pools + self.vaults + self.governance_infos)
+            for item in items:
+                item.file_path = redact_path(item.file_path)
+    
+    def baseline_items(self) -> List[str]:
+        """生成不含行号的结构快照键，代码移动不会被视为变化"""
+        items = set()
+        for struct in self.structs:
+            struct_key = f"{self._relative_path(struct.file_path)}::{struct.name}"
+            items.add(f"struct {struct_key}")
+            for struct_field in struct.fields:
+                items.add(f"field {struct_key}.{struct_field.name}: {struct_field.field_type}")
+                for constraint in struct_field.constraints:
+                    items.add(f"constraint {struct_key}.{struct_field.name}: {constraint}")
+        for constant in self.constants:
+            items.add(f"const {constant.name}: {constant.const_type} = {constant.value}")
+        for program_id in self.program_ids:
+            items.add(f"program_id {program_id.program_id}")
+        return sorted(items)
+    
+    def save_baseline(self, baseline_path: str) -> None:
+        """把当前结构保存为基线"""
+        baseline_file = Path(baseline_path)
+        baseline_file.parent.mkdir(parents=True, exist_ok=True)
+        with open(baseline_file, 'w', encoding='utf-8') as f:
+            json.dump({"version": 1, "items": self.baseline_items()}, f, indent=2, ensure_ascii=False)
+        print(t("baseline_saved", path=baseline_path))
+    
+    def compare_with_baseline(self, baseline_path: str) -> Dict[str, List[str]]:
+        """与基线比较，只返回新增的条目和被删除的约束（约束被删除视为回归）"""
+        with open(baseline_path, 'r', encoding='utf-8') as f:
+            baseline = set(json.load(f).get("items", []))
+        current = set(self.baseline_items())
+        return {
+            "new": sorted(current - baseline),
+            "removed_constraints": sorted(
+                item for item in baseline - current if item.startswith("constraint ")
+            ),
+        }
+    
+    def _relative_path(self, file_path: str) -> str:
+        """相对于项目根目录的路径"""
+        try:
+            return Path(file_path).relative_to(self.project_root).as_posix()
+        except ValueError:
+            return Path(file_path).as_posix()
+    
     def _write_complete_struct(self, f, struct: StructDefinition) -> None:
         """写入完整的结构体定义"""
         f.write(f"// {struct.file_path}:{struct.line_number}\n")
@@ -871,6 +951,37 @@ class SolanaStructExtractor:
         
         f.write("}\n\n")
 
+_STRING_LITERAL = re.compile(r'(?:\b(b|c))?"(?:\\.|[^"\\])*"')
+_REDACTION_KINDS = ("strings", "docs", "paths")
+
+def _short_hash(text: str) -> str:
+    """内容的短哈希，相同的值脱敏后仍然相同"""
+    return hashlib.sha256(text.encode('utf-8')).hexdigest()[:8]
+
+def _redact_strings(text: str) -> str:
+    """把字符串字面量替换为哈希"""
+    return _STRING_LITERAL.sub(
+        lambda m: f'{m.group(1) or ""}"<redacted:{_short_hash(m.group(0))}>"', text)
+
+def redact_baseline_diff(diff: Dict[str, List[str]], kinds: List[str]) -> Dict[str, List[str]]:
+    """脱敏与基线比较的结果，和 redact 对提取数据的处理一致。条目中的路径已是相对路径"""
+    if "strings" not in kinds:
+        return diff
+    def redact_item(item: str) -> str:
+        if item.startswith("program_id "):
+            return f"program_id <redacted:{_short_hash(item[len('program_id '):])}>"
+        return _redact_strings(item)
+    return {key: [redact_item(item) for item in items] for key, items in diff.items()}
+
+def _parse_redaction(value: str) -> List[str]:
+    """解析 --redact 参数，例如 strings,docs,paths"""
+    import argparse
+    kinds = [kind.strip() for kind in value.split(',')]
+    for kind in kinds:
+        if kind not in _REDACTION_KINDS:
+            raise argparse.ArgumentTypeError(f"unknown redaction '{kind}', expected strings, docs or paths")
+    return kinds
+
 def main():
     """主函数"""
     import argparse
@@ -879,14 +990,52 @@ def main():
     parser.add_argument('project_path', help='Path to the Solana project root')
     parser.add_argument('--output', '-o', default='output/complete_structures.rs',
                        help='Output file path (default: output/complete_structures.rs)')
+    parser.add_argument('--baseline', help='Compare against this baseline, report only new items and exit 1 on regressions')
+    parser.add_argument('--update-baseline', action='store_true',
+                       help='Write the current structure to the --baseline file instead of comparing')
+    parser.add_argument('--redact', type=_parse_redaction, default=[],
+                       help='Redact strings, docs and/or paths (comma separated) from the output')
+    parser.add_argument('--lang', choices=LANGUAGES, default='en',
+                       help='Language of status and error messages')
     
     args = parser.parse_args()
+    set_language(args.lang)
+    if args.update_baseline and not args.baseline:
+        parser.error('--update-baseline requires --baseline')
     
     extractor = SolanaStructExtractor(args.project_path)
-    print(f"Extracting complete structures from: {args.project_path}")
+    print(t("extracting_structures", path=args.project_path))
     
     extractor.extract_from_project()
+    
+    # 基线保存和比较都使用未脱敏的数据，只有导出的内容被脱敏
+    diff = None
+    if args.update_baseline:
+        extractor.save_baseline(args.baseline)
+    elif args.baseline:
+        diff = extractor.compare_with_baseline(args.baseline)
+    
+    extractor.redact(args.redact)
     extractor.export_to_rust_file(args.output)
+    
+    if diff is not None:
+        print_baseline_diff(redact_baseline_diff(diff, args.redact))
+        if diff["new"] or diff["removed_constraints"]:
+            sys.exit(1)
+
+def print_baseline_diff(diff: Dict[str, List[str]]) -> None:
+    """打印与基线相比的变化"""
+    if not diff["new"] and not diff["removed_constraints"]:
+        print(t("baseline_unchanged"))
+        return
+    if diff["new"]:
+        print(t("baseline_new_items", count=len(diff['new'])))
+        for item in diff["new"]:
+            print(f"  + {item}")
+    if diff["removed_constraints"]:
+        print(t("baseline_removed_constraints", count=len(diff['removed_constraints'])))
+        for item in diff["removed_constraints"]:
+            print(f"  - {item}")
 
 if __name__ == '__main__':
     main()
\ No newline at end of file